@@ -0,0 +1,83 @@
+use crate::json::{JsonNode, Number};
+use crate::json::pointer::PointerError;
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Agg {
+    Sum,
+    Min,
+    Max,
+    Avg,
+    Count
+}
+
+/// Summarizes the numbers matching the glob `pattern` (see
+/// `JsonPointer::select_all`). Matched values that are not numbers, and
+/// paths that match nothing, are skipped alike, so `Count` counts numbers
+/// and `Avg` is always `Sum` over `Count`. Over no numbers at all, `Sum` and
+/// `Count` are 0 while `Min`, `Max` and `Avg` are null. Integers are summed
+/// exactly, and `Min` and `Max` return the number as it was; only a sum
+/// involving a fraction, and `Avg`, go through doubles.
+pub fn aggregate(node : &JsonNode, pattern : &str, agg : Agg) -> Result<JsonNode, PointerError> {
+    let numbers : Vec<Number> = node.get_all(pattern)?.into_iter().filter_map(|(_, value)| match value {
+        JsonNode::Number(n) => Some(*n),
+        _ => None
+    }).collect();
+    let result = match agg {
+        Agg::Sum => Some(sum(&numbers)),
+        Agg::Count => Some(Number::from(numbers.len())),
+        Agg::Min => numbers.iter().cloned().reduce(|a, b| if b < a || a.as_f64().is_nan() { b } else { a }),
+        Agg::Max => numbers.iter().cloned().reduce(|a, b| if b > a || a.as_f64().is_nan() { b } else { a }),
+        Agg::Avg if numbers.is_empty() => None,
+        Agg::Avg => Some(Number::from(sum(&numbers).as_f64() / numbers.len() as f64))
+    };
+    Ok(result.map_or(JsonNode::Null, JsonNode::Number))
+}
+
+// exactly while every number is an integer and the total fits in an i128
+fn sum(numbers : &[Number]) -> Number {
+    let exact = numbers.iter().try_fold(0i128, |total, n| total.checked_add(n.as_i128()?));
+    match exact {
+        Some(total) => Number::from(total),
+        None => Number::from(numbers.iter().map(|n| n.as_f64()).sum::<f64>())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_aggregates() {
+        let order = JsonNode::from_str("{\"items\":[{\"price\":4},{\"price\":\"free\"},{\"name\":\"x\"},{\"price\":1.5},{\"price\":6.5}]}").unwrap();
+        let run = |agg| aggregate(&order, "/items/*/price", agg).unwrap();
+        assert_eq!(run(Agg::Sum), JsonNode::Number(12.0.into()));
+        assert_eq!(run(Agg::Count), JsonNode::Number(3.0.into()));
+        assert_eq!(run(Agg::Min), JsonNode::Number(1.5.into()));
+        assert_eq!(run(Agg::Max), JsonNode::Number(6.5.into()));
+        assert_eq!(run(Agg::Avg), JsonNode::Number(4.0.into()));
+    }
+
+    #[test]
+    fn test_nothing_to_aggregate() {
+        let node = JsonNode::from_str("{\"a\":[\"x\"]}").unwrap();
+        assert_eq!(aggregate(&node, "/a/*", Agg::Sum).unwrap(), JsonNode::Number(0.0.into()));
+        assert_eq!(aggregate(&node, "/b", Agg::Count).unwrap(), JsonNode::Number(0.0.into()));
+        assert_eq!(aggregate(&node, "/a/*", Agg::Avg).unwrap(), JsonNode::Null);
+        assert_eq!(aggregate(&node, "/**", Agg::Max).unwrap(), JsonNode::Null);
+        assert!(aggregate(&node, "a", Agg::Sum).is_err());
+    }
+
+    #[test]
+    fn test_integers_stay_exact() {
+        let node = JsonNode::from_str("[9007199254740993,1,18446744073709551615,-9223372036854775808,-5]").unwrap();
+        let run = |agg| aggregate(&node, "/*", agg).unwrap().as_number().unwrap();
+        assert_eq!(run(Agg::Sum).to_string(), "9232379236109516796");
+        assert_eq!(run(Agg::Count).as_u64(), Some(5));
+        assert_eq!(run(Agg::Max).as_u64(), Some(u64::MAX));
+        assert_eq!(run(Agg::Min).as_i64(), Some(i64::MIN));
+        let node = JsonNode::from_str("[9007199254740993,0.5]").unwrap();
+        assert_eq!(aggregate(&node, "/*", Agg::Min).unwrap().as_number().unwrap().to_string(), "0.5");
+        assert!(aggregate(&node, "/*", Agg::Sum).unwrap().as_number().unwrap().is_f64());
+    }
+}
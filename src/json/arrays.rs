@@ -0,0 +1,387 @@
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use crate::json::{maybe_grow, JsonNode, Object};
+use crate::json::pointer::{JsonPointer, PointerError};
+
+#[derive(PartialEq, Debug)]
+pub enum ArrayError {
+    NotAnArray,
+    BadPath(PointerError)
+}
+
+impl fmt::Display for ArrayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ArrayError::NotAnArray => write!(f, "expected an array"),
+            ArrayError::BadPath(reason) => write!(f, "{}", reason)
+        }
+    }
+}
+
+impl From<PointerError> for ArrayError {
+    fn from(reason : PointerError) -> ArrayError {
+        ArrayError::BadPath(reason)
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Order {
+    Asc,
+    Desc
+}
+
+/// How values of the same kind compare. Values of different types always
+/// order null < false < true < numbers < strings < arrays < objects.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Collation {
+    /// Strings compare by code point.
+    Plain,
+    /// Strings that are numbers compare as numbers, and runs of digits inside
+    /// other strings compare by value, so "item2" comes before "item10".
+    NumericAware
+}
+
+/// Stably sorts the elements of an array by the value at `path` inside each
+/// of them; the empty path sorts by the elements themselves. Elements
+/// without a value at `path` go last in either order.
+pub fn sort_by_path(node : &mut JsonNode, path : &str, order : Order, collation : Collation) -> Result<(), ArrayError> {
+    let path = JsonPointer::parse(path)?;
+    let elements = elements_mut(node)?;
+    elements.sort_by(|a, b| match (path.resolve(a), path.resolve(b)) {
+        (Some(a), Some(b)) => {
+            let ordering = compare(a, b, collation);
+            if order == Order::Desc { ordering.reverse() } else { ordering }
+        },
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal
+    });
+    Ok(())
+}
+
+/// Groups the elements of an array by the value at `path` inside each of
+/// them, into an object of arrays that keep the original element order.
+/// String values name their group as they are, other values by their JSON
+/// text; elements without a value at `path` are left out.
+pub fn group_by(node : &JsonNode, path : &str) -> Result<JsonNode, ArrayError> {
+    let path = JsonPointer::parse(path)?;
+    let mut groups : Object = Object::default();
+    for element in elements(node)? {
+        if let Some(key) = path.resolve(element) {
+            if let JsonNode::Array(group) = groups.entry(group_name(key)).or_insert_with(|| JsonNode::Array(Vec::new())) {
+                group.push(element.clone())
+            }
+        }
+    }
+    Ok(JsonNode::Object(groups))
+}
+
+/// Removes the elements whose value at `path` equals that of an earlier
+/// element, keeping the first of each. The empty path compares the whole
+/// elements. Elements without a value at `path` are never duplicates.
+pub fn dedupe_by(node : &mut JsonNode, path : &str) -> Result<(), ArrayError> {
+    let path = JsonPointer::parse(path)?;
+    let elements = elements_mut(node)?;
+    let mut seen = ValueSet::default();
+    elements.retain(|element| match path.resolve(element) {
+        Some(key) => !seen.contains(key) && seen.insert(key.clone()),
+        None => true
+    });
+    Ok(())
+}
+
+/// The elements of `a` and then those of `b`, without duplicates, comparing
+/// the values at `path` as `dedupe_by` does; the empty path compares whole
+/// elements.
+pub fn union(a : &JsonNode, b : &JsonNode, path : &str) -> Result<JsonNode, ArrayError> {
+    let mut out = JsonNode::Array(elements(a)?.iter().chain(elements(b)?).cloned().collect());
+    dedupe_by(&mut out, path)?;
+    Ok(out)
+}
+
+/// The elements of `a`, without duplicates, whose value at `path` some
+/// element of `b` shares. Elements without a value at `path` match nothing.
+pub fn intersection(a : &JsonNode, b : &JsonNode, path : &str) -> Result<JsonNode, ArrayError> {
+    filter_members(a, b, path, true)
+}
+
+/// The elements of `a`, without duplicates, whose value at `path` no element
+/// of `b` shares.
+pub fn difference(a : &JsonNode, b : &JsonNode, path : &str) -> Result<JsonNode, ArrayError> {
+    filter_members(a, b, path, false)
+}
+
+fn filter_members(a : &JsonNode, b : &JsonNode, path : &str, shared : bool) -> Result<JsonNode, ArrayError> {
+    let mut out = a.clone();
+    dedupe_by(&mut out, path)?;
+    let path = JsonPointer::parse(path)?;
+    let mut keys = ValueSet::default();
+    for key in elements(b)?.iter().filter_map(|element| path.resolve(element)) {
+        keys.insert(key);
+    }
+    elements_mut(&mut out)?.retain(|element| path.resolve(element).is_some_and(|key| keys.contains(key)) == shared);
+    Ok(out)
+}
+
+// values bucketed by fingerprint, so that looking one up compares it only
+// with those that hash alike
+struct ValueSet<T> {
+    buckets: HashMap<u64, Vec<T>>
+}
+
+impl<T> Default for ValueSet<T> {
+    fn default() -> ValueSet<T> {
+        ValueSet { buckets: HashMap::new() }
+    }
+}
+
+impl<T : Borrow<JsonNode>> ValueSet<T> {
+    fn contains(&self, value : &JsonNode) -> bool {
+        self.buckets.get(&fingerprint(value)).is_some_and(|bucket| bucket.iter().any(|seen| seen.borrow() == value))
+    }
+
+    // adds `value` unless an equal one is there, returning whether it was added
+    fn insert(&mut self, value : T) -> bool {
+        let bucket = self.buckets.entry(fingerprint(value.borrow())).or_default();
+        if bucket.iter().any(|seen| seen.borrow() == value.borrow()) {
+            return false
+        }
+        bucket.push(value);
+        true
+    }
+}
+
+// a hash that equal values share: equal numbers are the same double, zero
+// whatever its sign, and object members count in any order
+fn fingerprint(node : &JsonNode) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    maybe_grow(|| match node {
+        JsonNode::Null => 0u8.hash(&mut hasher),
+        JsonNode::Boolean(t) => (1u8, t).hash(&mut hasher),
+        JsonNode::Number(n) => (2u8, (n.as_f64() + 0.0).to_bits()).hash(&mut hasher),
+        JsonNode::String(s) => (3u8, s).hash(&mut hasher),
+        JsonNode::Array(a) => {
+            (4u8, a.len()).hash(&mut hasher);
+            for item in a {
+                fingerprint(item).hash(&mut hasher)
+            }
+        },
+        JsonNode::Object(o) => {
+            let members = o.iter().map(|(key, value)| {
+                let mut member = DefaultHasher::new();
+                (key, fingerprint(value)).hash(&mut member);
+                member.finish()
+            });
+            (5u8, o.len(), members.fold(0u64, u64::wrapping_add)).hash(&mut hasher)
+        }
+    });
+    hasher.finish()
+}
+
+fn elements(node : &JsonNode) -> Result<&Vec<JsonNode>, ArrayError> {
+    match node {
+        JsonNode::Array(a) => Ok(a),
+        _ => Err(ArrayError::NotAnArray)
+    }
+}
+
+fn elements_mut(node : &mut JsonNode) -> Result<&mut Vec<JsonNode>, ArrayError> {
+    match node {
+        JsonNode::Array(a) => Ok(a),
+        _ => Err(ArrayError::NotAnArray)
+    }
+}
+
+fn group_name(key : &JsonNode) -> String {
+    match key {
+        JsonNode::String(s) => s.clone(),
+        other => other.to_string()
+    }
+}
+
+/// The total order `sort_by_path` uses.
+pub fn compare(a : &JsonNode, b : &JsonNode, collation : Collation) -> Ordering {
+    let number = |value : &JsonNode| match (value, collation) {
+        (JsonNode::Number(n), _) => Some(n.as_f64()),
+        (JsonNode::String(s), Collation::NumericAware) => s.trim().parse::<f64>().ok().filter(|n| n.is_finite()),
+        _ => None
+    };
+    if let (Some(x), Some(y)) = (number(a), number(b)) {
+        // NaN sorts below every other number, as in jq
+        return x.partial_cmp(&y).unwrap_or_else(|| y.is_nan().cmp(&x.is_nan()))
+    }
+    let rank = |value : &JsonNode| match value {
+        JsonNode::Null => 0,
+        JsonNode::Boolean(false) => 1,
+        JsonNode::Boolean(true) => 2,
+        _ if number(value).is_some() => 3,
+        JsonNode::String(_) => 4,
+        JsonNode::Array(_) => 5,
+        JsonNode::Object(_) => 6,
+        JsonNode::Number(_) => unreachable!("numbers are ranked above")
+    };
+    maybe_grow(|| match (a, b) {
+        (JsonNode::String(x), JsonNode::String(y)) => match collation {
+            Collation::Plain => x.cmp(y),
+            Collation::NumericAware => natural(x, y)
+        },
+        (JsonNode::Array(x), JsonNode::Array(y)) => x.iter().zip(y).map(|(x, y)| compare(x, y, collation))
+            .find(|o| *o != Ordering::Equal).unwrap_or_else(|| x.len().cmp(&y.len())),
+        (JsonNode::Object(x), JsonNode::Object(y)) => {
+            let mut keys_x : Vec<&String> = x.keys().collect();
+            let mut keys_y : Vec<&String> = y.keys().collect();
+            keys_x.sort();
+            keys_y.sort();
+            keys_x.cmp(&keys_y).then_with(|| keys_x.iter().map(|k| compare(&x[*k], &y[*k], collation))
+                .find(|o| *o != Ordering::Equal).unwrap_or(Ordering::Equal))
+        },
+        _ => rank(a).cmp(&rank(b))
+    })
+}
+
+// compares digit runs by value and everything else by code point
+fn natural(a : &str, b : &str) -> Ordering {
+    let (mut a, mut b) = (a.chars().peekable(), b.chars().peekable());
+    loop {
+        match (a.peek().cloned(), b.peek().cloned()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let digits = |chars : &mut std::iter::Peekable<std::str::Chars>| {
+                    let mut run = String::new();
+                    while let Some(c) = chars.peek().filter(|c| c.is_ascii_digit()) {
+                        run.push(*c);
+                        chars.next();
+                    }
+                    run
+                };
+                let (x, y) = (digits(&mut a), digits(&mut b));
+                let (x_value, y_value) = (x.trim_start_matches('0'), y.trim_start_matches('0'));
+                let ordering = x_value.len().cmp(&y_value.len()).then_with(|| x_value.cmp(y_value)).then_with(|| x.len().cmp(&y.len()));
+                if ordering != Ordering::Equal {
+                    return ordering
+                }
+            },
+            (Some(x), Some(y)) => {
+                if x != y {
+                    return x.cmp(&y)
+                }
+                a.next();
+                b.next();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn names(node : &JsonNode) -> Vec<String> {
+        match node {
+            JsonNode::Array(a) => a.iter().map(|e| match e {
+                JsonNode::Object(o) => o["name"].to_string(),
+                other => other.to_string()
+            }).collect(),
+            _ => panic!("not an array")
+        }
+    }
+
+    #[test]
+    fn test_sort_by_path() {
+        let mut people = JsonNode::from_str("[{\"name\":\"a\",\"age\":30},{\"name\":\"b\"},{\"name\":\"c\",\"age\":\"9\"},{\"name\":\"d\",\"age\":30},{\"name\":\"e\",\"age\":null}]").unwrap();
+        sort_by_path(&mut people, "/age", Order::Desc, Collation::NumericAware).unwrap();
+        assert_eq!(names(&people), vec!["\"a\"", "\"d\"", "\"c\"", "\"e\"", "\"b\""]);
+        sort_by_path(&mut people, "/age", Order::Asc, Collation::Plain).unwrap();
+        assert_eq!(names(&people), vec!["\"e\"", "\"a\"", "\"d\"", "\"c\"", "\"b\""]);
+        assert_eq!(sort_by_path(&mut JsonNode::Null, "", Order::Asc, Collation::Plain), Err(ArrayError::NotAnArray));
+        assert!(sort_by_path(&mut people, "age", Order::Asc, Collation::Plain).is_err());
+    }
+
+    #[test]
+    fn test_mixed_types_and_natural_strings() {
+        let mut values = JsonNode::from_str("[\"item10\",{},[1],\"item2\",true,3,null,false,\"Item1\"]").unwrap();
+        sort_by_path(&mut values, "", Order::Asc, Collation::NumericAware).unwrap();
+        assert_eq!(values, JsonNode::from_str("[null,false,true,3,\"Item1\",\"item2\",\"item10\",[1],{}]").unwrap());
+        assert_eq!(natural("a007", "a7"), Ordering::Greater);
+        assert_eq!(natural("a7b", "a7c"), Ordering::Less);
+    }
+
+    #[test]
+    fn test_nan_sorts_below_numbers() {
+        let nan = JsonNode::Number(f64::NAN.into());
+        assert_eq!(compare(&nan, &json!(-1e300), Collation::Plain), Ordering::Less);
+        assert_eq!(compare(&json!(1), &nan, Collation::Plain), Ordering::Greater);
+        assert_eq!(compare(&nan, &nan, Collation::Plain), Ordering::Equal);
+        assert_eq!(compare(&nan, &JsonNode::Boolean(true), Collation::Plain), Ordering::Greater);
+        let mut values = JsonNode::Array(vec![json!(2), nan.clone(), json!(1), nan]);
+        sort_by_path(&mut values, "", Order::Asc, Collation::Plain).unwrap();
+        let sorted : Vec<f64> = (0..4).map(|i| values[i].as_number().unwrap().as_f64()).collect();
+        assert!(sorted[0].is_nan() && sorted[1].is_nan());
+        assert_eq!(sorted[2..], [1.0, 2.0]);
+    }
+
+    #[test]
+    fn test_group_by() {
+        let logs = JsonNode::from_str("[{\"level\":\"warn\",\"n\":1},{\"level\":\"info\",\"n\":2},{\"n\":3},{\"level\":\"warn\",\"n\":4},{\"level\":5,\"n\":5}]").unwrap();
+        let groups = group_by(&logs, "/level").unwrap();
+        assert_eq!(groups, JsonNode::from_str("{\"warn\":[{\"level\":\"warn\",\"n\":1},{\"level\":\"warn\",\"n\":4}],\"info\":[{\"level\":\"info\",\"n\":2}],\"5\":[{\"level\":5,\"n\":5}]}").unwrap());
+        assert_eq!(group_by(&JsonNode::from_str("[1,2,1]").unwrap(), "").unwrap(), JsonNode::from_str("{\"1\":[1,1],\"2\":[2]}").unwrap());
+        assert_eq!(group_by(&JsonNode::Null, ""), Err(ArrayError::NotAnArray));
+    }
+
+    #[test]
+    fn test_dedupe_by() {
+        let mut rows = JsonNode::from_str("[{\"id\":1,\"v\":\"a\"},{\"v\":\"b\"},{\"id\":2,\"v\":\"c\"},{\"id\":1,\"v\":\"d\"},{\"v\":\"e\"}]").unwrap();
+        dedupe_by(&mut rows, "/id").unwrap();
+        assert_eq!(rows, JsonNode::from_str("[{\"id\":1,\"v\":\"a\"},{\"v\":\"b\"},{\"id\":2,\"v\":\"c\"},{\"v\":\"e\"}]").unwrap());
+        let mut values = JsonNode::from_str("[{\"a\":1,\"b\":2},3,{\"b\":2,\"a\":1},3,\"3\"]").unwrap();
+        dedupe_by(&mut values, "").unwrap();
+        assert_eq!(values, JsonNode::from_str("[{\"a\":1,\"b\":2},3,\"3\"]").unwrap());
+    }
+
+    #[test]
+    fn test_set_operations() {
+        let a = JsonNode::from_str("[1,2,2,3,{\"x\":[1]}]").unwrap();
+        let b = JsonNode::from_str("[3,4,{\"x\":[1]},1]").unwrap();
+        assert_eq!(union(&a, &b, "").unwrap(), JsonNode::from_str("[1,2,3,{\"x\":[1]},4]").unwrap());
+        assert_eq!(intersection(&a, &b, "").unwrap(), JsonNode::from_str("[1,3,{\"x\":[1]}]").unwrap());
+        assert_eq!(difference(&a, &b, "").unwrap(), JsonNode::from_str("[2]").unwrap());
+        assert_eq!(union(&a, &JsonNode::Null, ""), Err(ArrayError::NotAnArray));
+    }
+
+    #[test]
+    fn test_set_operations_compare_values() {
+        let a = JsonNode::from_str("[1,-0,{\"x\":[2.0],\"y\":null},\"1\",[1,2]]").unwrap();
+        let b = JsonNode::from_str("[1.0,0,{\"y\":null,\"x\":[2]},[2,1]]").unwrap();
+        assert_eq!(intersection(&a, &b, "").unwrap(), JsonNode::from_str("[1,-0,{\"x\":[2.0],\"y\":null}]").unwrap());
+        assert_eq!(difference(&a, &b, "").unwrap(), JsonNode::from_str("[\"1\",[1,2]]").unwrap());
+    }
+
+    #[test]
+    fn test_large_arrays() {
+        let a = JsonNode::Array((0..100000).map(|i| JsonNode::from(i % 50000)).collect());
+        let b = JsonNode::Array((25000..75000).map(|i| JsonNode::from(i as f64)).collect());
+        let mut deduped = a.clone();
+        dedupe_by(&mut deduped, "").unwrap();
+        assert_eq!(deduped.as_array().unwrap().len(), 50000);
+        assert_eq!(union(&a, &b, "").unwrap().as_array().unwrap().len(), 75000);
+        assert_eq!(intersection(&a, &b, "").unwrap().as_array().unwrap().len(), 25000);
+        assert_eq!(difference(&a, &b, "").unwrap()[24999], JsonNode::from(24999));
+    }
+
+    #[test]
+    fn test_set_operations_by_key() {
+        let old = JsonNode::from_str("[{\"id\":1,\"v\":\"a\"},{\"id\":2,\"v\":\"b\"},{\"v\":\"c\"}]").unwrap();
+        let new = JsonNode::from_str("[{\"id\":2,\"v\":\"B\"},{\"id\":3,\"v\":\"d\"}]").unwrap();
+        assert_eq!(union(&old, &new, "/id").unwrap(), JsonNode::from_str("[{\"id\":1,\"v\":\"a\"},{\"id\":2,\"v\":\"b\"},{\"v\":\"c\"},{\"id\":3,\"v\":\"d\"}]").unwrap());
+        assert_eq!(intersection(&old, &new, "/id").unwrap(), JsonNode::from_str("[{\"id\":2,\"v\":\"b\"}]").unwrap());
+        assert_eq!(difference(&old, &new, "/id").unwrap(), JsonNode::from_str("[{\"id\":1,\"v\":\"a\"},{\"v\":\"c\"}]").unwrap());
+    }
+}
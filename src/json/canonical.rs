@@ -0,0 +1,148 @@
+// The JSON Canonicalization Scheme of RFC 8785, for text that hashes and
+// signs the same however the document was written.
+
+use std::fmt::Write;
+use crate::json::{maybe_grow, JsonNode, Number};
+
+impl JsonNode {
+    /// The document as RFC 8785 canonical JSON: no whitespace, members in
+    /// the order of the UTF-16 code units of their keys, strings with only
+    /// the escapes JSON requires, and every number as ECMAScript prints the
+    /// nearest double, so integers beyond 2^53 lose precision as they would
+    /// in JavaScript. None if a number is not finite, which has no JSON form.
+    pub fn to_canonical_string(&self) -> Option<String> {
+        let mut out = String::new();
+        write_canonical(self, &mut out)?;
+        Some(out)
+    }
+}
+
+fn write_canonical(node : &JsonNode, out : &mut String) -> Option<()> {
+    maybe_grow(|| {
+        match node {
+            JsonNode::Null => out.push_str("null"),
+            JsonNode::Boolean(b) => out.push_str(if *b { "true" } else { "false" }),
+            JsonNode::Number(n) => out.push_str(&ecmascript(*n)?),
+            JsonNode::String(s) => write_string(s, out),
+            JsonNode::Array(items) => {
+                out.push('[');
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        out.push(',')
+                    }
+                    write_canonical(item, out)?
+                }
+                out.push(']')
+            },
+            JsonNode::Object(members) => {
+                let mut members : Vec<(Vec<u16>, &String, &JsonNode)> = members.iter()
+                    .map(|(key, value)| (key.encode_utf16().collect(), key, value))
+                    .collect();
+                members.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+                out.push('{');
+                for (i, (_, key, value)) in members.into_iter().enumerate() {
+                    if i > 0 {
+                        out.push(',')
+                    }
+                    write_string(key, out);
+                    out.push(':');
+                    write_canonical(value, out)?
+                }
+                out.push('}')
+            }
+        }
+        Some(())
+    })
+}
+
+fn write_string(s : &str, out : &mut String) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\u{8}' => out.push_str("\\b"),
+            '\u{c}' => out.push_str("\\f"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c < ' ' => write!(out, "\\u{:04x}", c as u32).expect("writing to a String does not fail"),
+            c => out.push(c)
+        }
+    }
+    out.push('"')
+}
+
+/// `n` as ECMAScript's `Number.prototype.toString` writes the nearest
+/// double: the fewest digits that read back as it, in positional notation
+/// from 1e-6 up to 1e21 and in exponential notation beyond.
+pub(crate) fn ecmascript(n : Number) -> Option<String> {
+    let f = n.as_f64();
+    if !f.is_finite() {
+        return None
+    }
+    if f == 0.0 {
+        return Some("0".to_string())
+    }
+    // Rust's exponential form has the same shortest digits: d.ddde±x
+    let exponential = format!("{:e}", f.abs());
+    let (mantissa, exponent) = exponential.split_once('e').expect("{:e} writes an exponent");
+    let digits = mantissa.replace('.', "");
+    let k = digits.len() as i32;
+    // the point goes after the `n`th digit
+    let n = exponent.parse::<i32>().expect("the exponent is an integer") + 1;
+    let mut out = String::new();
+    if f < 0.0 {
+        out.push('-')
+    }
+    if k <= n && n <= 21 {
+        out.push_str(&digits);
+        out.push_str(&"0".repeat((n - k) as usize))
+    } else if 0 < n && n <= 21 {
+        out.push_str(&digits[..n as usize]);
+        out.push('.');
+        out.push_str(&digits[n as usize..])
+    } else if -6 < n && n <= 0 {
+        out.push_str("0.");
+        out.push_str(&"0".repeat(-n as usize));
+        out.push_str(&digits)
+    } else {
+        out.push_str(&digits[..1]);
+        if k > 1 {
+            out.push('.');
+            out.push_str(&digits[1..])
+        }
+        write!(out, "e{}{}", if n > 0 { "+" } else { "-" }, (n - 1).abs()).expect("writing to a String does not fail")
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_rfc_8785_example() {
+        let text = "{\"numbers\": [333333333.33333329, 1E30, 4.50, 2e-3, 0.000000000000000000000000001],\
+            \"string\": \"\\u20ac$\\u000F\\u000aA'\\u0042\\u0022\\u005c\\\\\\\"\\/\",\"literals\": [null, true, false]}";
+        let node = JsonNode::from_str(text).unwrap();
+        assert_eq!(node.to_canonical_string().unwrap(), "{\"literals\":[null,true,false],\
+            \"numbers\":[333333333.3333333,1e+30,4.5,0.002,1e-27],\"string\":\"\u{20ac}$\\u000f\\nA'B\\\"\\\\\\\\\\\"/\"}");
+    }
+
+    #[test]
+    fn test_sorts_by_utf16_and_formats_numbers_as_ecmascript() {
+        // U+10000 is a surrogate pair, which sorts before U+FFFF in UTF-16
+        let node = JsonNode::from_str("{\"\u{ffff}\":1,\"\u{10000}\":2,\"b\":{\"\\b\\f\":[]},\"a\":-0.0}").unwrap();
+        assert_eq!(node.to_canonical_string().unwrap(), "{\"a\":0,\"b\":{\"\\b\\f\":[]},\"\u{10000}\":2,\"\u{ffff}\":1}");
+        let cases = [(1e21, "1e+21"), (1e20, "100000000000000000000"), (123456789012345680000.0, "123456789012345680000"),
+            (1e-6, "0.000001"), (1e-7, "1e-7"), (-1.5e-7, "-1.5e-7"), (5e-324, "5e-324"), (1.7976931348623157e308, "1.7976931348623157e+308"),
+            (0.1 + 0.2, "0.30000000000000004"), (-12.0, "-12")];
+        for (f, expected) in cases {
+            assert_eq!(ecmascript(Number::from(f)).unwrap(), expected);
+        }
+        assert_eq!(ecmascript(Number::from(u64::MAX)).unwrap(), "18446744073709552000");
+        assert_eq!(JsonNode::from(f64::NAN).to_canonical_string(), None);
+    }
+}
@@ -0,0 +1,123 @@
+use crate::json::{maybe_grow, JsonNode, Object};
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Case {
+    /// `user_id`
+    Snake,
+    /// `userId`
+    Camel,
+    /// `user-id`
+    Kebab
+}
+
+impl Case {
+    /// Respells `key`, splitting it into words at `_`, `-` and spaces and
+    /// where lower case turns upper, so `HTTPServer_id` has the words
+    /// `http`, `server` and `id`.
+    pub fn convert(self, key : &str) -> String {
+        let words = words(key);
+        match self {
+            Case::Snake => words.join("_"),
+            Case::Kebab => words.join("-"),
+            Case::Camel => {
+                let mut out = String::with_capacity(key.len());
+                for (i, word) in words.iter().enumerate() {
+                    let mut chars = word.chars();
+                    match chars.next() {
+                        Some(first) if i > 0 => {
+                            out.extend(first.to_uppercase());
+                            out.push_str(chars.as_str())
+                        },
+                        _ => out.push_str(word)
+                    }
+                }
+                out
+            }
+        }
+    }
+}
+
+fn words(key : &str) -> Vec<String> {
+    let chars : Vec<char> = key.chars().collect();
+    let mut words = Vec::new();
+    let mut word = String::new();
+    for (i, c) in chars.iter().enumerate() {
+        if *c == '_' || *c == '-' || c.is_whitespace() {
+            if !word.is_empty() {
+                words.push(std::mem::take(&mut word))
+            }
+            continue
+        }
+        let previous = if i > 0 { Some(chars[i - 1]) } else { None };
+        let next_lower = chars.get(i + 1).is_some_and(|next| next.is_lowercase());
+        // "aB" starts a word at B, and so does "ABc" at B
+        let boundary = c.is_uppercase() && previous.is_some_and(|p| p.is_lowercase() || p.is_numeric() || (p.is_uppercase() && next_lower));
+        if boundary && !word.is_empty() {
+            words.push(std::mem::take(&mut word))
+        }
+        word.extend(c.to_lowercase());
+    }
+    if !word.is_empty() {
+        words.push(word)
+    }
+    words
+}
+
+/// Respells every object key in the tree in `case`.
+pub fn rename_keys(node : &mut JsonNode, case : Case) {
+    rename_keys_with(node, &mut |key| case.convert(key))
+}
+
+/// Replaces every object key in the tree with what `rename` makes of it.
+/// When several keys of an object get the same name, the value of the key
+/// sorting last wins.
+pub fn rename_keys_with<F>(node : &mut JsonNode, rename : &mut F)
+    where F : FnMut(&str) -> String
+{
+    maybe_grow(|| match node {
+        JsonNode::Array(a) => for item in a {
+            rename_keys_with(item, rename)
+        },
+        JsonNode::Object(o) => {
+            let mut members : Vec<(String, JsonNode)> = std::mem::take(o).into_iter().collect();
+            members.sort_by(|(a, _), (b, _)| a.cmp(b));
+            let mut renamed = Object::with_capacity_and_hasher(members.len(), Default::default());
+            for (key, mut value) in members {
+                rename_keys_with(&mut value, rename);
+                renamed.insert(rename(&key), value);
+            }
+            *o = renamed
+        },
+        _ => ()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_convert() {
+        for key in &["user_id", "userId", "UserId", "user-id", "USER_ID", "user id"] {
+            assert_eq!(Case::Snake.convert(key), "user_id");
+            assert_eq!(Case::Camel.convert(key), "userId");
+            assert_eq!(Case::Kebab.convert(key), "user-id");
+        }
+        assert_eq!(Case::Snake.convert("HTTPServer2Url"), "http_server2_url");
+        assert_eq!(Case::Camel.convert("__x__"), "x");
+    }
+
+    #[test]
+    fn test_rename_keys() {
+        let mut node = JsonNode::from_str("{\"user_name\":\"a\",\"home-address\":{\"zip_code\":1},\"items\":[{\"item_id\":2}]}").unwrap();
+        rename_keys(&mut node, Case::Camel);
+        assert_eq!(node, JsonNode::from_str("{\"userName\":\"a\",\"homeAddress\":{\"zipCode\":1},\"items\":[{\"itemId\":2}]}").unwrap());
+        rename_keys_with(&mut node, &mut |key| key.to_uppercase());
+        assert_eq!(node, JsonNode::from_str("{\"USERNAME\":\"a\",\"HOMEADDRESS\":{\"ZIPCODE\":1},\"ITEMS\":[{\"ITEMID\":2}]}").unwrap());
+
+        let mut node = JsonNode::from_str("{\"a_b\":1,\"aB\":2}").unwrap();
+        rename_keys(&mut node, Case::Snake);
+        assert_eq!(node, JsonNode::from_str("{\"a_b\":1}").unwrap());
+    }
+}
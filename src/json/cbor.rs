@@ -0,0 +1,332 @@
+use std::convert::TryFrom;
+use std::fmt;
+use crate::json::{maybe_grow, JsonNode, Number, Object, DEFAULT_MAX_DEPTH};
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct CborError {
+    /// Where in the input the item that could not be read starts.
+    pub offset: usize,
+    pub message: &'static str
+}
+
+impl fmt::Display for CborError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "byte {}: {}", self.offset, self.message)
+    }
+}
+
+/// `node` as CBOR (RFC 8949) with definite lengths: integers as the
+/// smallest integers that hold them, doubles as single precision floats when
+/// that loses nothing, and members in key order unless the `small_objects`
+/// or `preserve_order` feature keeps their order.
+pub fn encode(node : &JsonNode) -> Vec<u8> {
+    let mut out = Vec::new();
+    encode_into(node, &mut out);
+    out
+}
+
+fn encode_into(node : &JsonNode, out : &mut Vec<u8>) {
+    maybe_grow(|| match node {
+        JsonNode::Null => out.push(0xf6),
+        JsonNode::Boolean(b) => out.push(if *b { 0xf5 } else { 0xf4 }),
+        JsonNode::Number(n) => match (n.as_u64(), n.as_i64()) {
+            (Some(u), _) => head(0, u, out),
+            (None, Some(i)) => head(1, (-1 - i) as u64, out),
+            _ => {
+                let f = n.as_f64();
+                if (f as f32) as f64 == f || f.is_nan() {
+                    out.push(0xfa);
+                    out.extend_from_slice(&(f as f32).to_be_bytes())
+                } else {
+                    out.push(0xfb);
+                    out.extend_from_slice(&f.to_be_bytes())
+                }
+            }
+        },
+        JsonNode::String(s) => {
+            head(3, s.len() as u64, out);
+            out.extend_from_slice(s.as_bytes())
+        },
+        JsonNode::Array(items) => {
+            head(4, items.len() as u64, out);
+            for item in items {
+                encode_into(item, out)
+            }
+        },
+        JsonNode::Object(members) => {
+            let mut members : Vec<(&String, &JsonNode)> = members.iter().collect();
+            if !cfg!(feature = "small_objects") {
+                members.sort_by_key(|&(key, _)| key)
+            }
+            head(5, members.len() as u64, out);
+            for (key, value) in members {
+                head(3, key.len() as u64, out);
+                out.extend_from_slice(key.as_bytes());
+                encode_into(value, out)
+            }
+        }
+    })
+}
+
+// the initial bytes of an item of `major` type with the argument `n`
+fn head(major : u8, n : u64, out : &mut Vec<u8>) {
+    let major = major << 5;
+    match n {
+        0..=23 => out.push(major | n as u8),
+        24..=0xff => out.extend_from_slice(&[major | 24, n as u8]),
+        0x100..=0xffff => {
+            out.push(major | 25);
+            out.extend_from_slice(&(n as u16).to_be_bytes())
+        },
+        0x10000..=0xffff_ffff => {
+            out.push(major | 26);
+            out.extend_from_slice(&(n as u32).to_be_bytes())
+        },
+        _ => {
+            out.push(major | 27);
+            out.extend_from_slice(&n.to_be_bytes())
+        }
+    }
+}
+
+/// Reads one CBOR data item, which must take up all of `input`, mapping
+/// what JSON lacks as RFC 8949 section 6.1 suggests: byte strings become
+/// base64url text, bignums (tags 2 and 3) numbers, other tags their content,
+/// map keys that are not text their JSON text, and non-finite floats,
+/// `undefined` and other simple values null. Indefinite lengths are
+/// accepted; nesting deeper than `DEFAULT_MAX_DEPTH` is not.
+pub fn decode(input : &[u8]) -> Result<JsonNode, CborError> {
+    let mut decoder = Decoder { input, position: 0 };
+    let node = decoder.item(0)?;
+    if decoder.position < input.len() {
+        return Err(CborError { offset: decoder.position, message: "unexpected data after the item" })
+    }
+    Ok(node)
+}
+
+struct Decoder<'a> {
+    input: &'a [u8],
+    position: usize
+}
+
+// what follows the first byte of an item
+enum Argument {
+    Value(u64),
+    // the length of an item is given by a break at its end
+    Indefinite
+}
+
+impl<'a> Decoder<'a> {
+    fn take(&mut self, length : usize, start : usize) -> Result<&'a [u8], CborError> {
+        let end = self.position.checked_add(length).filter(|&end| end <= self.input.len());
+        match end {
+            Some(end) => {
+                let bytes = &self.input[self.position..end];
+                self.position = end;
+                Ok(bytes)
+            },
+            None => Err(CborError { offset: start, message: "the input ends inside the item" })
+        }
+    }
+
+    // the major type and argument of the item at the current position
+    fn head(&mut self, start : usize) -> Result<(u8, Argument), CborError> {
+        let initial = self.take(1, start)?[0];
+        let length = match initial & 0x1f {
+            n @ 0..=23 => return Ok((initial >> 5, Argument::Value(n as u64))),
+            24 => 1,
+            25 => 2,
+            26 => 4,
+            27 => 8,
+            31 if matches!(initial >> 5, 2..=5 | 7) => return Ok((initial >> 5, Argument::Indefinite)),
+            _ => return Err(CborError { offset: start, message: "reserved additional information" })
+        };
+        let bytes = self.take(length, start)?;
+        Ok((initial >> 5, Argument::Value(bytes.iter().fold(0, |n, &b| n << 8 | b as u64))))
+    }
+
+    fn item(&mut self, depth : usize) -> Result<JsonNode, CborError> {
+        let start = self.position;
+        // `depth` containers and tags hold this item
+        if depth == DEFAULT_MAX_DEPTH && self.input.get(start).is_some_and(|b| matches!(b >> 5, 4..=6)) {
+            return Err(CborError { offset: start, message: "items nest too deeply" })
+        }
+        let error = |message| CborError { offset: start, message };
+        maybe_grow(|| match self.head(start)? {
+            (0, Argument::Value(n)) => Ok(JsonNode::from(n)),
+            (1, Argument::Value(n)) => Ok(negative(n as u128)),
+            (2, argument) => self.string(2, argument, start).map(|bytes| JsonNode::String(base64url(&bytes))),
+            (3, argument) => {
+                let bytes = self.string(3, argument, start)?;
+                String::from_utf8(bytes).map(JsonNode::String).map_err(|_| error("text is not UTF-8"))
+            },
+            (4, argument) => {
+                let mut items = Vec::new();
+                while let Some(()) = self.next_entry(&argument, items.len())? {
+                    items.push(self.item(depth + 1)?)
+                }
+                Ok(JsonNode::Array(items))
+            },
+            (5, argument) => {
+                let mut members = Object::default();
+                let mut count = 0;
+                while let Some(()) = self.next_entry(&argument, count)? {
+                    let mut key = self.item(depth + 1)?;
+                    let key = match &mut key {
+                        JsonNode::String(key) => std::mem::take(key),
+                        _ => key.to_string()
+                    };
+                    members.insert(key, self.item(depth + 1)?);
+                    count += 1
+                }
+                Ok(JsonNode::Object(members))
+            },
+            // a bignum, whose magnitude is a byte string
+            (6, Argument::Value(tag)) if (tag == 2 || tag == 3) && self.input.get(self.position).is_some_and(|b| b >> 5 == 2) => {
+                let content = self.position;
+                let (_, argument) = self.head(content)?;
+                let magnitude = self.string(2, argument, content)?;
+                let digits = &magnitude[magnitude.iter().take_while(|&&b| b == 0).count()..];
+                let exact = digits.iter().try_fold(0u128, |n, &b| n.checked_mul(256).map(|n| n | b as u128));
+                let approximate = || digits.iter().fold(0.0, |n, &b| n * 256.0 + b as f64);
+                Ok(match (tag, exact) {
+                    (2, Some(n)) => JsonNode::Number(Number::from(n)),
+                    (_, Some(n)) => negative(n),
+                    (2, None) => JsonNode::from(approximate()),
+                    (_, None) => JsonNode::from(-1.0 - approximate())
+                })
+            },
+            (6, _) => self.item(depth + 1),
+            (7, Argument::Value(n)) => {
+                let f = match (self.input[start] & 0x1f, n) {
+                    (20, _) => return Ok(JsonNode::Boolean(false)),
+                    (21, _) => return Ok(JsonNode::Boolean(true)),
+                    (25, half) => f16_to_f64(half as u16),
+                    (26, single) => f32::from_bits(single as u32) as f64,
+                    (27, double) => f64::from_bits(double),
+                    _ => return Ok(JsonNode::Null)
+                };
+                Ok(if f.is_finite() { JsonNode::from(f) } else { JsonNode::Null })
+            },
+            (7, Argument::Indefinite) => Err(error("unexpected break")),
+            _ => unreachable!("major types are three bits and only strings, arrays and maps are indefinite")
+        })
+    }
+
+    // whether a container with `argument` entries, `count` of them read,
+    // has another, consuming the break after the last of an indefinite one
+    fn next_entry(&mut self, argument : &Argument, count : usize) -> Result<Option<()>, CborError> {
+        match argument {
+            Argument::Value(n) => Ok(Some(()).filter(|_| (count as u64) < *n)),
+            Argument::Indefinite => match self.input.get(self.position) {
+                Some(0xff) => {
+                    self.position += 1;
+                    Ok(None)
+                },
+                Some(_) => Ok(Some(())),
+                None => Err(CborError { offset: self.position, message: "the input ends inside the item" })
+            }
+        }
+    }
+
+    // the bytes of a byte or text string, joining the chunks of an indefinite one
+    fn string(&mut self, major : u8, argument : Argument, start : usize) -> Result<Vec<u8>, CborError> {
+        match argument {
+            Argument::Value(n) => {
+                let length = usize::try_from(n).map_err(|_| CborError { offset: start, message: "the input ends inside the item" })?;
+                Ok(self.take(length, start)?.to_vec())
+            },
+            Argument::Indefinite => {
+                let mut bytes = Vec::new();
+                while let Some(()) = self.next_entry(&Argument::Indefinite, 0)? {
+                    let chunk = self.position;
+                    match self.head(chunk)? {
+                        (m, Argument::Value(n)) if m == major => bytes.extend(self.string(major, Argument::Value(n), chunk)?),
+                        _ => return Err(CborError { offset: chunk, message: "a chunk of a string is not a definite string of its type" })
+                    }
+                }
+                Ok(bytes)
+            }
+        }
+    }
+}
+
+// -1 - n, which is exact down to the smallest i64
+fn negative(n : u128) -> JsonNode {
+    match n.checked_add(1) {
+        Some(magnitude) => JsonNode::Number(-Number::from(magnitude)),
+        None => JsonNode::from(-1.0 - n as f64)
+    }
+}
+
+fn f16_to_f64(half : u16) -> f64 {
+    let exponent = (half >> 10 & 0x1f) as i32;
+    let mantissa = (half & 0x3ff) as f64;
+    let magnitude = match exponent {
+        0 => mantissa * 2f64.powi(-24),
+        31 if mantissa == 0.0 => f64::INFINITY,
+        31 => f64::NAN,
+        _ => (1024.0 + mantissa) * 2f64.powi(exponent - 25)
+    };
+    if half & 0x8000 != 0 { -magnitude } else { magnitude }
+}
+
+// `bytes` in the URL and filename safe base64 alphabet, without padding
+pub(crate) fn base64url(bytes : &[u8]) -> String {
+    const ALPHABET : &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+    let mut out = String::new();
+    for chunk in bytes.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..=chunk.len() {
+            out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char)
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn json(text : &str) -> JsonNode {
+        JsonNode::from_str(text).unwrap()
+    }
+
+    #[test]
+    fn test_encodes_rfc_examples() {
+        assert_eq!(encode(&json("[0,23,24,1000,-1,-1000,1.5,1.1,true,null]")),
+            [0x8a, 0x00, 0x17, 0x18, 0x18, 0x19, 0x03, 0xe8, 0x20, 0x39, 0x03, 0xe7, 0xfa, 0x3f, 0xc0, 0x00, 0x00,
+                0xfb, 0x3f, 0xf1, 0x99, 0x99, 0x99, 0x99, 0x99, 0x9a, 0xf5, 0xf6]);
+        assert_eq!(encode(&json("{\"a\":\"\u{fc}\"}")), [0xa1, 0x61, 0x61, 0x62, 0xc3, 0xbc]);
+        assert_eq!(encode(&JsonNode::from(u64::MAX)), [0x1b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]);
+        let node = json("{\"list\":[1,-2.5,\"x\",{},[]],\"nested\":{\"t\":false,\"n\":null},\"big\":-9223372036854775808}");
+        assert_eq!(decode(&encode(&node)).unwrap(), node);
+    }
+
+    #[test]
+    fn test_decodes_what_json_lacks() {
+        // indefinite text, array and map, a half float, a byte string and a tagged date
+        let input = [0xbf, 0x7f, 0x61, 0x61, 0x61, 0x62, 0xff, 0x9f, 0xf9, 0x3e, 0x00, 0x44, 0x01, 0x02, 0x03, 0xfb,
+            0x01, 0xf9, 0x7c, 0x00, 0xf7, 0xc0, 0x61, 0x64, 0xff, 0xff];
+        assert_eq!(decode(&input).unwrap(), json("{\"ab\":[1.5,\"AQID-w\",1,null,null,\"d\"]}"));
+        // integer keys, a negative beyond an i64 and bignums
+        assert_eq!(decode(&[0xa1, 0x01, 0x3b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]).unwrap(), json("{\"1\":-18446744073709551616}"));
+        assert_eq!(decode(&[0x82, 0xc2, 0x49, 0x01, 0, 0, 0, 0, 0, 0, 0, 0, 0xc3, 0x41, 0x01]).unwrap(), json("[18446744073709551616,-2]"));
+        assert_eq!(decode(&[0xc2, 0x43, 0, 0x01, 0x00]).unwrap().to_string(), "256");
+    }
+
+    #[test]
+    fn test_reports_errors() {
+        let error = |input : &[u8]| decode(input).unwrap_err();
+        assert_eq!(error(&[0x82, 0x01]), CborError { offset: 2, message: "the input ends inside the item" });
+        assert_eq!(error(&[0x01, 0x02]).message, "unexpected data after the item");
+        assert_eq!(error(&[0x62, 0xff, 0xfe]).message, "text is not UTF-8");
+        assert_eq!(error(&[0x1c]).message, "reserved additional information");
+        assert_eq!(error(&[0x5f, 0x61, 0x61, 0xff]).offset, 1);
+        assert_eq!(error(&[0x7b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]).offset, 0);
+        if !cfg!(feature = "grow_stack") {
+            assert_eq!(error(&[0x81; 1000]).message, "items nest too deeply");
+        }
+    }
+}
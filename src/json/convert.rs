@@ -0,0 +1,186 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+use crate::json::{JsonNode, Number, Object};
+
+impl From<bool> for JsonNode {
+    fn from(t : bool) -> JsonNode {
+        JsonNode::Boolean(t)
+    }
+}
+
+impl From<&str> for JsonNode {
+    fn from(s : &str) -> JsonNode {
+        JsonNode::String(s.to_string())
+    }
+}
+
+impl From<String> for JsonNode {
+    fn from(s : String) -> JsonNode {
+        JsonNode::String(s)
+    }
+}
+
+impl<N : Into<Number>> From<N> for JsonNode {
+    fn from(n : N) -> JsonNode {
+        JsonNode::Number(n.into())
+    }
+}
+
+impl<T : Into<JsonNode>> From<Vec<T>> for JsonNode {
+    fn from(items : Vec<T>) -> JsonNode {
+        JsonNode::Array(items.into_iter().map(Into::into).collect())
+    }
+}
+
+impl<T : Into<JsonNode>, S> From<HashMap<String, T, S>> for JsonNode {
+    fn from(members : HashMap<String, T, S>) -> JsonNode {
+        JsonNode::Object(members.into_iter().map(|(k, v)| (k, v.into())).collect())
+    }
+}
+
+/// None becomes null.
+impl<T : Into<JsonNode>> From<Option<T>> for JsonNode {
+    fn from(value : Option<T>) -> JsonNode {
+        value.map_or(JsonNode::Null, Into::into)
+    }
+}
+
+/// A `JsonNode` that is not of the kind it was converted to, handed back.
+#[derive(PartialEq, Debug, Clone)]
+pub struct WrongType {
+    pub expected: &'static str,
+    pub node: JsonNode
+}
+
+impl fmt::Display for WrongType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let found = match self.node {
+            JsonNode::Number(_) => "a number",
+            JsonNode::String(_) => "a string",
+            JsonNode::Array(_) => "an array",
+            JsonNode::Object(_) => "an object",
+            JsonNode::Boolean(_) => "a boolean",
+            JsonNode::Null => "null"
+        };
+        write!(f, "expected {}, found {}", self.expected, found)
+    }
+}
+
+impl TryFrom<JsonNode> for bool {
+    type Error = WrongType;
+
+    fn try_from(node : JsonNode) -> Result<bool, WrongType> {
+        match node {
+            JsonNode::Boolean(t) => Ok(t),
+            node => Err(WrongType { expected: "a boolean", node })
+        }
+    }
+}
+
+impl TryFrom<JsonNode> for String {
+    type Error = WrongType;
+
+    fn try_from(mut node : JsonNode) -> Result<String, WrongType> {
+        match &mut node {
+            JsonNode::String(s) => Ok(std::mem::take(s)),
+            _ => Err(WrongType { expected: "a string", node })
+        }
+    }
+}
+
+impl TryFrom<JsonNode> for Number {
+    type Error = WrongType;
+
+    fn try_from(node : JsonNode) -> Result<Number, WrongType> {
+        match node {
+            JsonNode::Number(n) => Ok(n),
+            node => Err(WrongType { expected: "a number", node })
+        }
+    }
+}
+
+impl TryFrom<JsonNode> for f64 {
+    type Error = WrongType;
+
+    fn try_from(node : JsonNode) -> Result<f64, WrongType> {
+        Number::try_from(node).map(Number::as_f64)
+    }
+}
+
+/// Only integers that fit convert; fractions do not.
+impl TryFrom<JsonNode> for i64 {
+    type Error = WrongType;
+
+    fn try_from(node : JsonNode) -> Result<i64, WrongType> {
+        match node.as_i64() {
+            Some(i) => Ok(i),
+            None => Err(WrongType { expected: "an integer", node })
+        }
+    }
+}
+
+/// Only non-negative integers convert; fractions do not.
+impl TryFrom<JsonNode> for u64 {
+    type Error = WrongType;
+
+    fn try_from(node : JsonNode) -> Result<u64, WrongType> {
+        match node.as_u64() {
+            Some(u) => Ok(u),
+            None => Err(WrongType { expected: "a non-negative integer", node })
+        }
+    }
+}
+
+impl TryFrom<JsonNode> for Vec<JsonNode> {
+    type Error = WrongType;
+
+    fn try_from(mut node : JsonNode) -> Result<Vec<JsonNode>, WrongType> {
+        match &mut node {
+            JsonNode::Array(a) => Ok(std::mem::take(a)),
+            _ => Err(WrongType { expected: "an array", node })
+        }
+    }
+}
+
+impl TryFrom<JsonNode> for Object {
+    type Error = WrongType;
+
+    fn try_from(mut node : JsonNode) -> Result<Object, WrongType> {
+        match &mut node {
+            JsonNode::Object(o) => Ok(std::mem::take(o)),
+            _ => Err(WrongType { expected: "an object", node })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_into_json() {
+        let mut members = HashMap::new();
+        members.insert("n".to_string(), vec![Some(1.5), None]);
+        assert_eq!(JsonNode::from(members).to_string(), "{\"n\":[1.5,null]}");
+        assert_eq!(JsonNode::from(vec!["a", "b"]).to_string(), "[\"a\",\"b\"]");
+        assert_eq!(JsonNode::from(Some(true)), JsonNode::Boolean(true));
+        assert_eq!(JsonNode::from(-3i64).to_string(), "-3");
+        assert_eq!(JsonNode::from(String::from("s")), JsonNode::String("s".to_string()));
+    }
+
+    #[test]
+    fn test_try_from_json() {
+        assert_eq!(String::try_from(json!("s")), Ok("s".to_string()));
+        assert_eq!(bool::try_from(json!(false)), Ok(false));
+        assert_eq!(i64::try_from(json!(-7)), Ok(-7));
+        assert_eq!(u64::try_from(json!(-7)), Err(WrongType { expected: "a non-negative integer", node: json!(-7) }));
+        assert_eq!(f64::try_from(json!(2)), Ok(2.0));
+        assert_eq!(Vec::<JsonNode>::try_from(json!([null])), Ok(vec![JsonNode::Null]));
+        assert_eq!(Object::try_from(json!({"a": 1})).map(|o| o.len()), Ok(1));
+
+        let error = String::try_from(json!([1])).unwrap_err();
+        assert_eq!(error.to_string(), "expected a string, found an array");
+        assert_eq!(error.node, json!([1]));
+    }
+}
@@ -0,0 +1,138 @@
+use std::fmt;
+use crate::json::JsonNode;
+
+/// How `to_csv` writes a table: the character between cells and what
+/// stands for null. The default is CSV with empty cells for null.
+#[derive(PartialEq, Debug, Clone)]
+pub struct CsvOptions {
+    delimiter: char,
+    null: String
+}
+
+impl Default for CsvOptions {
+    fn default() -> CsvOptions {
+        CsvOptions { delimiter: ',', null: String::new() }
+    }
+}
+
+impl CsvOptions {
+    /// Comma separated values.
+    pub fn csv() -> CsvOptions {
+        CsvOptions::default()
+    }
+
+    /// Tab separated values.
+    pub fn tsv() -> CsvOptions {
+        CsvOptions::default().delimiter('\t')
+    }
+
+    pub fn delimiter(mut self, delimiter : char) -> CsvOptions {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Writes `null` as `text`, such as `NULL` or `\N`, instead of leaving the cell empty.
+    pub fn null(mut self, text : &str) -> CsvOptions {
+        self.null = text.to_string();
+        self
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum CsvError {
+    /// The document is not an array of rows.
+    NotAnArray,
+    /// The element at this index of the array is not an object.
+    NotAnObject(usize)
+}
+
+impl fmt::Display for CsvError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CsvError::NotAnArray => write!(f, "expected an array of objects"),
+            CsvError::NotAnObject(i) => write!(f, "element {} is not an object", i)
+        }
+    }
+}
+
+/// The array of objects `node` as a table with a row for each object and a
+/// column for each key any of them has, named in the first row. Cells of
+/// keys an object lacks stay empty, and arrays and objects within rows are
+/// written as compact JSON. Columns come in the order the keys first appear,
+/// which without the `small_objects` or `preserve_order` feature is sorted.
+/// Cells holding the delimiter, a quote or a line break are quoted as in
+/// RFC 4180, and each row ends with `\n`.
+pub fn to_csv(node : &JsonNode, options : &CsvOptions) -> Result<String, CsvError> {
+    let rows = match node {
+        JsonNode::Array(rows) => rows,
+        _ => return Err(CsvError::NotAnArray)
+    };
+    let mut columns : Vec<&String> = Vec::new();
+    for (i, row) in rows.iter().enumerate() {
+        match row {
+            JsonNode::Object(o) => for key in o.keys() {
+                if !columns.contains(&key) {
+                    columns.push(key)
+                }
+            },
+            _ => return Err(CsvError::NotAnObject(i))
+        }
+    }
+    if !cfg!(feature = "small_objects") {
+        columns.sort()
+    }
+
+    let mut out = String::new();
+    write_row(columns.iter().map(|column| column.as_str()), options, &mut out);
+    for row in rows {
+        let cells : Vec<String> = columns.iter().map(|&column| match row.get(column) {
+            None => String::new(),
+            Some(JsonNode::Null) => options.null.clone(),
+            Some(JsonNode::String(s)) => s.clone(),
+            Some(value) => value.to_string()
+        }).collect();
+        write_row(cells.iter().map(String::as_str), options, &mut out)
+    }
+    Ok(out)
+}
+
+fn write_row<'a, I : Iterator<Item = &'a str>>(cells : I, options : &CsvOptions, out : &mut String) {
+    for (i, cell) in cells.enumerate() {
+        if i > 0 {
+            out.push(options.delimiter)
+        }
+        if cell.contains([options.delimiter, '"', '\n', '\r']) {
+            out.push('"');
+            out.push_str(&cell.replace('"', "\"\""));
+            out.push('"')
+        } else {
+            out.push_str(cell)
+        }
+    }
+    out.push('\n')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_to_csv() {
+        let node = JsonNode::from_str("[{\"name\":\"a, b\",\"age\":3},{\"name\":\"say \\\"hi\\\"\",\"tags\":[1,\"x\"]},{\"age\":null}]").unwrap();
+        let expected = "age,name,tags\n3,\"a, b\",\n,\"say \"\"hi\"\"\",\"[1,\"\"x\"\"]\"\n,,\n";
+        if !cfg!(feature = "small_objects") {
+            assert_eq!(to_csv(&node, &CsvOptions::csv()).unwrap(), expected);
+        }
+        let node = JsonNode::from_str("[{\"a\":\"x,y\",\"b\":null},{}]").unwrap();
+        let tsv = to_csv(&node, &CsvOptions::tsv().null("NULL")).unwrap();
+        assert!(tsv == "a\tb\nx,y\tNULL\n\t\n" || tsv == "b\ta\nNULL\tx,y\n\t\n");
+        assert_eq!(to_csv(&JsonNode::from_str("[]").unwrap(), &CsvOptions::csv()).unwrap(), "\n");
+    }
+
+    #[test]
+    fn test_rejects_other_shapes() {
+        assert_eq!(to_csv(&JsonNode::from_str("{}").unwrap(), &CsvOptions::csv()), Err(CsvError::NotAnArray));
+        assert_eq!(to_csv(&JsonNode::from_str("[{},1]").unwrap(), &CsvOptions::csv()), Err(CsvError::NotAnObject(1)));
+    }
+}
@@ -0,0 +1,241 @@
+use std::fmt;
+use crate::json::{maybe_grow, JsonNode, Object};
+use crate::json::patch::{self, PatchOperation};
+use crate::json::pointer::JsonPointer;
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum Change {
+    Added(JsonNode),
+    Removed(JsonNode),
+    /// The old value, then the new one.
+    Changed(JsonNode, JsonNode)
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct Difference {
+    pub path: JsonPointer,
+    pub change: Change
+}
+
+/// Every difference between two documents, in the order a JSON Patch would
+/// apply them. Displays as an indented tree with `+`, `-` and `~` marking
+/// added, removed and changed values.
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct Explanation {
+    pub differences: Vec<Difference>
+}
+
+/// What `explain_with` leaves out of the comparison.
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct DiffOptions {
+    ignored: Vec<JsonPointer>
+}
+
+impl DiffOptions {
+    pub fn new() -> DiffOptions {
+        DiffOptions { ignored: Vec::new() }
+    }
+
+    /// Disregards the value at `path` and everything below it.
+    pub fn ignore(mut self, path : JsonPointer) -> DiffOptions {
+        self.ignored.push(path);
+        self
+    }
+}
+
+/// Explains how `b` differs from `a`.
+pub fn explain(a : &JsonNode, b : &JsonNode) -> Explanation {
+    explain_with(a, b, &DiffOptions::new())
+}
+
+pub fn explain_with(a : &JsonNode, b : &JsonNode, options : &DiffOptions) -> Explanation {
+    let mut old = a.clone();
+    let mut new = b.clone();
+    // ignored values are made equal on both sides, so they never show up
+    for path in &options.ignored {
+        match (path.resolve(&old).cloned(), path.resolve(&new).is_some()) {
+            (Some(value), true) => {
+                let _ = path.assign(&mut new, value);
+            },
+            (Some(_), false) => {
+                let _ = patch::apply_operation(&mut old, &PatchOperation::Remove { path: path.clone() });
+            },
+            (None, true) => {
+                let _ = patch::apply_operation(&mut new, &PatchOperation::Remove { path: path.clone() });
+            },
+            (None, false) => ()
+        }
+    }
+
+    // replaying the patch tells what each operation overwrote or removed
+    let mut differences = Vec::new();
+    for op in patch::diff(&old, &new).operations {
+        let change = match &op {
+            PatchOperation::Add { value, .. } => Change::Added(value.clone()),
+            PatchOperation::Remove { path } => Change::Removed(path.resolve(&old).cloned().unwrap_or(JsonNode::Null)),
+            PatchOperation::Replace { path, value } => Change::Changed(path.resolve(&old).cloned().unwrap_or(JsonNode::Null), value.clone()),
+            _ => continue
+        };
+        differences.push(Difference { path: op.path().clone(), change });
+        let _ = patch::apply_operation(&mut old, &op);
+    }
+    Explanation { differences }
+}
+
+/// Whether `a` and `b` hold the same data, regardless of object key order
+/// and of how numbers were written (`1` and `1.0` are equal). Returns the
+/// first path where they differ, visiting object members in key order, or
+/// `None` when they are equivalent apart from the ignored paths.
+pub fn equivalent(a : &JsonNode, b : &JsonNode, options : &DiffOptions) -> Option<JsonPointer> {
+    first_difference(a, b, &mut JsonPointer::root(), options)
+}
+
+fn first_difference(a : &JsonNode, b : &JsonNode, path : &mut JsonPointer, options : &DiffOptions) -> Option<JsonPointer> {
+    if options.ignored.iter().any(|ignored| path.starts_with(ignored)) {
+        return None
+    }
+    maybe_grow(|| match (a, b) {
+        (JsonNode::Number(x), JsonNode::Number(y)) if x == y => None,
+        (JsonNode::Array(x), JsonNode::Array(y)) => {
+            for i in 0..x.len().max(y.len()) {
+                path.push(i.to_string());
+                let found = match (x.get(i), y.get(i)) {
+                    (Some(x), Some(y)) => first_difference(x, y, path, options),
+                    _ => Some(path.clone())
+                };
+                path.pop();
+                if found.is_some() {
+                    return found
+                }
+            }
+            None
+        },
+        (JsonNode::Object(x), JsonNode::Object(y)) => {
+            let mut keys : Vec<&String> = x.keys().chain(y.keys().filter(|key| !x.contains_key(*key))).collect();
+            keys.sort();
+            for key in keys {
+                path.push(key.as_str());
+                let found = match (x.get(key), y.get(key)) {
+                    (Some(x), Some(y)) => first_difference(x, y, path, options),
+                    _ if options.ignored.iter().any(|ignored| path.starts_with(ignored)) => None,
+                    _ => Some(path.clone())
+                };
+                path.pop();
+                if found.is_some() {
+                    return found
+                }
+            }
+            None
+        },
+        _ if a == b => None,
+        _ => Some(path.clone())
+    })
+}
+
+impl Explanation {
+    pub fn is_empty(&self) -> bool {
+        self.differences.is_empty()
+    }
+
+    /// The differences as JSON, for tools: an array of objects with the
+    /// `path`, the `change` ("added", "removed" or "changed") and the `old`
+    /// and `new` values where there are any.
+    pub fn to_node(&self) -> JsonNode {
+        JsonNode::Array(self.differences.iter().map(|difference| {
+            let mut members = Object::default();
+            let (change, old, new) = match &difference.change {
+                Change::Added(value) => ("added", None, Some(value)),
+                Change::Removed(value) => ("removed", Some(value), None),
+                Change::Changed(old, new) => ("changed", Some(old), Some(new))
+            };
+            members.insert("path".to_string(), JsonNode::String(difference.path.to_string()));
+            members.insert("change".to_string(), JsonNode::String(change.to_string()));
+            if let Some(old) = old {
+                members.insert("old".to_string(), old.clone());
+            }
+            if let Some(new) = new {
+                members.insert("new".to_string(), new.clone());
+            }
+            JsonNode::Object(members)
+        }).collect())
+    }
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // the parent path whose headings are already printed
+        let mut open : &[String] = &[];
+        for difference in &self.differences {
+            let tokens = difference.path.tokens();
+            let (leaf, parent) = match tokens.split_last() {
+                Some((leaf, parent)) => (leaf.as_str(), parent),
+                None => ("(root)", &tokens[..0])
+            };
+            let common = open.iter().zip(parent).take_while(|(a, b)| a == b).count();
+            for (depth, token) in parent.iter().enumerate().skip(common) {
+                writeln!(f, "{:width$}{}", "", token, width = depth * 2)?;
+            }
+            open = parent;
+            let indent = parent.len() * 2;
+            match &difference.change {
+                Change::Added(value) => writeln!(f, "{:width$}+ {}: {}", "", leaf, value, width = indent)?,
+                Change::Removed(value) => writeln!(f, "{:width$}- {}: {}", "", leaf, value, width = indent)?,
+                Change::Changed(old, new) => writeln!(f, "{:width$}~ {}: {} -> {}", "", leaf, old, new, width = indent)?
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_explains_changes_as_a_tree() {
+        let a = JsonNode::from_str("{\"name\":\"svc\",\"limits\":{\"cpu\":1,\"memory\":512},\"ports\":[80,443],\"old\":true}").unwrap();
+        let b = JsonNode::from_str("{\"name\":\"svc\",\"limits\":{\"cpu\":2,\"memory\":512,\"disk\":10},\"ports\":[80,8443,9000]}").unwrap();
+        let explanation = explain(&a, &b);
+        assert_eq!(explanation.to_string(), "\
+            - old: true\n\
+            limits\n  \
+              ~ cpu: 1 -> 2\n  \
+              + disk: 10\n\
+            ports\n  \
+              ~ 1: 443 -> 8443\n  \
+              + 2: 9000\n");
+        assert!(explain(&a, &a).is_empty());
+
+        let listed = explanation.to_node();
+        assert_eq!(listed[0], JsonNode::from_str("{\"path\":\"/old\",\"change\":\"removed\",\"old\":true}").unwrap());
+        assert_eq!(listed[1], JsonNode::from_str("{\"path\":\"/limits/cpu\",\"change\":\"changed\",\"old\":1,\"new\":2}").unwrap());
+        assert_eq!(listed.as_array().map(Vec::len), Some(5));
+    }
+
+    #[test]
+    fn test_ignored_paths_and_root_changes() {
+        let a = JsonNode::from_str("{\"id\":1,\"meta\":{\"at\":\"mon\"},\"v\":1}").unwrap();
+        let b = JsonNode::from_str("{\"id\":2,\"v\":1}").unwrap();
+        let options = DiffOptions::new().ignore(JsonPointer::parse("/id").unwrap()).ignore(JsonPointer::parse("/meta").unwrap());
+        assert!(explain_with(&a, &b, &options).is_empty());
+
+        let explanation = explain(&JsonNode::Number(1.0.into()), &JsonNode::Null);
+        assert_eq!(explanation.differences, vec![Difference { path: JsonPointer::root(), change: Change::Changed(JsonNode::Number(1.0.into()), JsonNode::Null) }]);
+        assert_eq!(explanation.to_string(), "~ (root): 1 -> null\n");
+    }
+
+    #[test]
+    fn test_equivalent() {
+        let a = JsonNode::from_str("{\"b\":[1,{\"x\":2.0}],\"a\":\"s\",\"at\":\"mon\"}").unwrap();
+        let b = JsonNode::from_str("{\"a\":\"s\",\"b\":[1.0,{\"x\":2}],\"at\":\"tue\"}").unwrap();
+        assert_eq!(equivalent(&a, &b, &DiffOptions::new()), Some(JsonPointer::parse("/at").unwrap()));
+        assert_eq!(equivalent(&a, &b, &DiffOptions::new().ignore(JsonPointer::parse("/at").unwrap())), None);
+
+        let c = JsonNode::from_str("{\"a\":\"t\",\"b\":[1,{\"x\":2},3]}").unwrap();
+        assert_eq!(equivalent(&a, &c, &DiffOptions::new()), Some(JsonPointer::parse("/a").unwrap()));
+        let options = DiffOptions::new().ignore(JsonPointer::parse("/a").unwrap()).ignore(JsonPointer::parse("/at").unwrap());
+        assert_eq!(equivalent(&a, &c, &options), Some(JsonPointer::parse("/b/2").unwrap()));
+        assert_eq!(equivalent(&JsonNode::Null, &JsonNode::Boolean(false), &options), Some(JsonPointer::root()));
+    }
+}
@@ -0,0 +1,234 @@
+use std::fmt;
+use crate::json::{JsonNode, Object};
+use crate::json::patch::{self, PatchOperation};
+use crate::json::pointer::{array_index, JsonPointer};
+
+/// A single reversible change, shaped like a JSON Patch (RFC 6902) entry.
+/// Removals and replacements remember the value they displaced so they can be undone.
+#[derive(PartialEq, Debug, Clone)]
+pub enum Operation {
+    Add { path: String, value: JsonNode },
+    Remove { path: String, old: JsonNode },
+    Replace { path: String, old: JsonNode, value: JsonNode }
+}
+
+#[derive(PartialEq, Debug)]
+pub enum EditError {
+    InvalidPath(String),
+    NoSuchPath(String)
+}
+
+impl fmt::Display for EditError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EditError::InvalidPath(path) => write!(f, "invalid path \"{}\"", path),
+            EditError::NoSuchPath(path) => write!(f, "no such path \"{}\"", path)
+        }
+    }
+}
+
+impl Operation {
+    pub fn path(&self) -> &str {
+        match self {
+            Operation::Add { path, .. } | Operation::Remove { path, .. } | Operation::Replace { path, .. } => path
+        }
+    }
+
+    pub fn inverse(&self) -> Operation {
+        match self {
+            Operation::Add { path, value } => Operation::Remove { path: path.clone(), old: value.clone() },
+            Operation::Remove { path, old } => Operation::Add { path: path.clone(), value: old.clone() },
+            Operation::Replace { path, old, value } => Operation::Replace {
+                path: path.clone(), old: value.clone(), value: old.clone()
+            }
+        }
+    }
+
+    /// This operation as a JSON Patch entry.
+    pub fn to_patch(&self) -> JsonNode {
+        let mut entry = Object::default();
+        let (op, value) = match self {
+            Operation::Add { value, .. } => ("add", Some(value)),
+            Operation::Remove { .. } => ("remove", None),
+            Operation::Replace { value, .. } => ("replace", Some(value))
+        };
+        entry.insert("op".to_string(), JsonNode::String(op.to_string()));
+        entry.insert("path".to_string(), JsonNode::String(self.path().to_string()));
+        if let Some(value) = value {
+            entry.insert("value".to_string(), value.clone());
+        }
+        JsonNode::Object(entry)
+    }
+}
+
+/// A JSON document that records every edit so it can be undone, redone,
+/// or exported as a JSON Patch change log.
+#[derive(Debug)]
+pub struct Document {
+    root: JsonNode,
+    done: Vec<Operation>,
+    undone: Vec<Operation>
+}
+
+impl Document {
+    pub fn new(root : JsonNode) -> Document {
+        Document { root, done: Vec::new(), undone: Vec::new() }
+    }
+
+    pub fn root(&self) -> &JsonNode {
+        &self.root
+    }
+
+    pub fn into_root(self) -> JsonNode {
+        self.root
+    }
+
+    /// Adds a value following JSON Patch rules: object members are created or
+    /// overwritten, array elements are inserted before the index, `-` appends.
+    pub fn add(&mut self, path : &str, value : JsonNode) -> Result<(), EditError> {
+        let mut pointer = parse_path(path)?;
+        let op = match pointer.pop() {
+            None => Operation::Replace { path: path.to_string(), old: self.root.clone(), value },
+            Some(last) => match pointer.resolve(&self.root) {
+                Some(JsonNode::Object(o)) => match o.get(&last) {
+                    Some(old) => Operation::Replace { path: path.to_string(), old: old.clone(), value },
+                    None => Operation::Add { path: path.to_string(), value }
+                },
+                Some(JsonNode::Array(a)) => {
+                    let index = if last == "-" { a.len() } else { parse_index(&last, path)? };
+                    if index > a.len() {
+                        return Err(EditError::NoSuchPath(path.to_string()))
+                    }
+                    Operation::Add { path: pointer.child(index.to_string()).to_string(), value }
+                },
+                _ => return Err(EditError::NoSuchPath(path.to_string()))
+            }
+        };
+        self.perform(op)
+    }
+
+    pub fn remove(&mut self, path : &str) -> Result<(), EditError> {
+        let old = self.existing(path)?.clone();
+        self.perform(Operation::Remove { path: path.to_string(), old })
+    }
+
+    pub fn replace(&mut self, path : &str, value : JsonNode) -> Result<(), EditError> {
+        let old = self.existing(path)?.clone();
+        self.perform(Operation::Replace { path: path.to_string(), old, value })
+    }
+
+    /// Reverts the latest edit; returns false when there is nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        match self.done.pop() {
+            Some(op) => {
+                apply(&mut self.root, &op.inverse()).expect("recorded edits stay applicable");
+                self.undone.push(op);
+                true
+            },
+            None => false
+        }
+    }
+
+    /// Re-applies the latest undone edit; returns false when there is nothing to redo.
+    pub fn redo(&mut self) -> bool {
+        match self.undone.pop() {
+            Some(op) => {
+                apply(&mut self.root, &op).expect("recorded edits stay applicable");
+                self.done.push(op);
+                true
+            },
+            None => false
+        }
+    }
+
+    pub fn history(&self) -> &[Operation] {
+        &self.done
+    }
+
+    /// The applied edits as a JSON Patch document.
+    pub fn change_log(&self) -> JsonNode {
+        JsonNode::Array(self.done.iter().map(Operation::to_patch).collect())
+    }
+
+    fn existing(&self, path : &str) -> Result<&JsonNode, EditError> {
+        parse_path(path)?.resolve(&self.root).ok_or_else(|| EditError::NoSuchPath(path.to_string()))
+    }
+
+    fn perform(&mut self, op : Operation) -> Result<(), EditError> {
+        apply(&mut self.root, &op)?;
+        self.done.push(op);
+        self.undone.clear();
+        Ok(())
+    }
+}
+
+fn apply(root : &mut JsonNode, op : &Operation) -> Result<(), EditError> {
+    let path = parse_path(op.path())?;
+    let op = match op {
+        Operation::Add { value, .. } => PatchOperation::Add { path, value: value.clone() },
+        Operation::Remove { .. } => PatchOperation::Remove { path },
+        Operation::Replace { value, .. } => PatchOperation::Replace { path, value: value.clone() }
+    };
+    patch::apply_operation(root, &op).map_err(|_| EditError::NoSuchPath(op.path().to_string()))
+}
+
+fn parse_path(path : &str) -> Result<JsonPointer, EditError> {
+    JsonPointer::parse(path).map_err(|_| EditError::InvalidPath(path.to_string()))
+}
+
+fn parse_index(token : &str, path : &str) -> Result<usize, EditError> {
+    array_index(token).ok_or_else(|| EditError::InvalidPath(path.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_edits_can_be_undone_and_redone() {
+        let mut doc = Document::new(JsonNode::from_str("{\"list\":[1,2]}").unwrap());
+        doc.add("/list/-", JsonNode::Number(3.0.into())).unwrap();
+        doc.replace("/list/0", JsonNode::Null).unwrap();
+        doc.add("/name", JsonNode::String("x".to_string())).unwrap();
+        doc.remove("/list/1").unwrap();
+        assert_eq!(doc.root(), &JsonNode::from_str("{\"list\":[null,3],\"name\":\"x\"}").unwrap());
+
+        while doc.undo() {}
+        assert_eq!(doc.root(), &JsonNode::from_str("{\"list\":[1,2]}").unwrap());
+
+        while doc.redo() {}
+        assert_eq!(doc.root(), &JsonNode::from_str("{\"list\":[null,3],\"name\":\"x\"}").unwrap());
+    }
+
+    #[test]
+    fn test_new_edit_discards_redo() {
+        let mut doc = Document::new(JsonNode::from_str("[]").unwrap());
+        doc.add("/0", JsonNode::Boolean(true)).unwrap();
+        assert!(doc.undo());
+        doc.add("/0", JsonNode::Null).unwrap();
+        assert!(!doc.redo());
+        assert_eq!(doc.root(), &JsonNode::from_str("[null]").unwrap());
+    }
+
+    #[test]
+    fn test_change_log_is_json_patch() {
+        let mut doc = Document::new(JsonNode::from_str("{\"a~b\":1}").unwrap());
+        doc.add("/a~0b", JsonNode::Number(2.0.into())).unwrap();
+        doc.remove("/a~0b").unwrap();
+        let log = doc.change_log().to_string();
+        assert!(log.contains("\"op\":\"replace\""));
+        assert!(log.contains("\"path\":\"/a~0b\""));
+        assert!(log.contains("\"op\":\"remove\""));
+    }
+
+    #[test]
+    fn test_bad_paths_are_rejected() {
+        let mut doc = Document::new(JsonNode::from_str("{\"a\":[]}").unwrap());
+        assert_eq!(doc.remove("/b"), Err(EditError::NoSuchPath("/b".to_string())));
+        assert_eq!(doc.add("a", JsonNode::Null), Err(EditError::InvalidPath("a".to_string())));
+        assert_eq!(doc.add("/a/01", JsonNode::Null), Err(EditError::InvalidPath("/a/01".to_string())));
+        assert_eq!(doc.add("/a/1", JsonNode::Null), Err(EditError::NoSuchPath("/a/1".to_string())));
+        assert!(doc.history().is_empty());
+    }
+}
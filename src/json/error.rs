@@ -0,0 +1,163 @@
+use std::fmt;
+use crate::json::events::{EventError, EventReader};
+
+/// Longest piece of input quoted in an error.
+pub const SNIPPET : usize = 24;
+
+/// What the parser was looking for where the input went wrong.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Expected {
+    Value,
+    /// A value or the `]` of an empty array.
+    ValueOrEnd,
+    Key,
+    /// A member name or the `}` of an empty object.
+    KeyOrEnd,
+    Colon,
+    /// A `,` or the closing bracket of the container.
+    CommaOrEnd(char),
+    /// Nothing more after a complete document.
+    End
+}
+
+impl fmt::Display for Expected {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expected::Value => write!(f, "a value"),
+            Expected::ValueOrEnd => write!(f, "a value or ']'"),
+            Expected::Key => write!(f, "a member name"),
+            Expected::KeyOrEnd => write!(f, "a member name or '}}'"),
+            Expected::Colon => write!(f, "':'"),
+            Expected::CommaOrEnd(close) => write!(f, "',' or '{}'", close),
+            Expected::End => write!(f, "the end of input")
+        }
+    }
+}
+
+/// Why the parser gave up on the input.
+#[derive(PartialEq, Debug, Clone)]
+pub enum Reason {
+    /// It is not JSON, or not in the dialect allowed.
+    Syntax,
+    /// An object repeats this member name, with `DuplicateKeys::Error`.
+    DuplicateKey(String),
+    /// Containers nest deeper than this `ParserOptions::max_depth`.
+    TooDeep(usize),
+    /// A backslash in a string starts no escape sequence, or `\u` escapes a
+    /// lone surrogate.
+    InvalidEscape,
+    /// A string holds bytes that are not UTF-8.
+    InvalidUtf8,
+    /// A string holds a control character, which it may only escape.
+    ControlCharacter,
+    /// Something other than whitespace follows the document, where the
+    /// whole input is to be one.
+    TrailingData
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct JsonParseError {
+    /// Where the offending input starts, or the length of the input when it
+    /// ends too early.
+    pub offset: usize,
+    /// Counting from 1.
+    pub line: usize,
+    /// Counting characters from 1.
+    pub column: usize,
+    pub expected: Expected,
+    /// The input from `offset` to the end of its line, at most `SNIPPET`
+    /// bytes of it.
+    pub snippet: String,
+    /// Whether the input ends before the document does.
+    pub unexpected_end: bool,
+    pub reason: Reason
+}
+
+impl JsonParseError {
+    /// Finds where in `input`, which the parser has rejected, it stops being
+    /// JSON, falling back to `offset` when no better place is found.
+    pub(crate) fn locate(input : &[u8], offset : usize) -> JsonParseError {
+        // the parser has checked the depth already, against its own limit
+        let mut reader = EventReader::new(input).max_depth(usize::MAX);
+        let offset = loop {
+            match reader.next_event() {
+                Ok(Some(_)) => (),
+                Ok(None) | Err(EventError::Io(_)) => break offset,
+                Err(EventError::Syntax(offset)) | Err(EventError::UnexpectedEnd(offset)) | Err(EventError::TooDeep { offset, .. }) => break offset
+            }
+        };
+        JsonParseError::at(input, offset.min(input.len()), reader.expected())
+    }
+
+    pub(crate) fn at(input : &[u8], offset : usize, expected : Expected) -> JsonParseError {
+        let line_start = input[..offset].iter().rposition(|b| *b == b'\n').map_or(0, |i| i + 1);
+        let line = input[..offset].iter().filter(|b| **b == b'\n').count() + 1;
+        let column = String::from_utf8_lossy(&input[line_start..offset]).chars().count() + 1;
+        let rest = &input[offset..];
+        let mut end = rest.iter().take(SNIPPET).position(|b| *b == b'\n' || *b == b'\r').unwrap_or(rest.len().min(SNIPPET));
+        // do not cut a character in two
+        while end < rest.len() && end > 0 && rest[end] & 0xc0 == 0x80 {
+            end -= 1
+        }
+        let snippet = String::from_utf8_lossy(&rest[..end]).into_owned();
+        JsonParseError { offset, line, column, expected, snippet, unexpected_end: offset == input.len(), reason: Reason::Syntax }
+    }
+
+    /// The error for input that is JSON, but that the parser refuses for `reason`.
+    pub(crate) fn refuse(input : &[u8], offset : usize, expected : Expected, reason : Reason) -> JsonParseError {
+        JsonParseError { reason, ..JsonParseError::at(input, offset, expected) }
+    }
+
+    /// What went wrong, without where: `Display` puts the line and column first.
+    pub fn message(&self) -> String {
+        match &self.reason {
+            Reason::DuplicateKey(key) => format!("duplicate member name \"{}\"", key),
+            Reason::TooDeep(depth) => format!("containers nest deeper than {} levels", depth),
+            Reason::InvalidEscape => format!("invalid escape sequence at \"{}\"", self.snippet),
+            Reason::InvalidUtf8 => "invalid UTF-8 in a string".to_string(),
+            Reason::ControlCharacter => "unescaped control character in a string".to_string(),
+            Reason::Syntax | Reason::TrailingData if self.unexpected_end => format!("unexpected end of input, expected {}", self.expected),
+            Reason::Syntax | Reason::TrailingData => format!("expected {} at \"{}\"", self.expected, self.snippet)
+        }
+    }
+}
+
+impl fmt::Display for JsonParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}, column {}: {}", self.line, self.column, self.message())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::json::JsonNode;
+    use super::*;
+    use std::str::FromStr;
+
+    fn error(input : &str) -> JsonParseError {
+        JsonNode::from_str(input).unwrap_err()
+    }
+
+    #[test]
+    fn test_locates_errors() {
+        let e = error("{\"a\":[1,2],\n \"b\":{\"c\" 3}}");
+        assert_eq!((e.offset, e.line, e.column, e.expected), (22, 2, 11, Expected::Colon));
+        assert_eq!(e.snippet, "3}}");
+        assert_eq!(e.to_string(), "line 2, column 11: expected ':' at \"3}}\"");
+        assert_eq!(e.message(), "expected ':' at \"3}}\"");
+
+        assert_eq!(error("[1,]").expected, Expected::Value);
+        assert_eq!(error("[1 2]").expected, Expected::CommaOrEnd(']'));
+        assert_eq!(error("{,}").expected, Expected::KeyOrEnd);
+        assert_eq!(error("[\"é\",tru]").column, 6);
+    }
+
+    #[test]
+    fn test_unexpected_end() {
+        let e = error("[1,\n2,");
+        assert!(e.unexpected_end);
+        assert_eq!((e.offset, e.line, e.column), (6, 2, 3));
+        assert_eq!(e.to_string(), "line 2, column 3: unexpected end of input, expected a value");
+        assert!(error("").unexpected_end);
+    }
+}
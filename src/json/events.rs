@@ -0,0 +1,390 @@
+use std::fmt;
+use std::io::{self, Read};
+use circular::Buffer;
+use nom::IResult;
+use crate::json::{Expected, JsonNode, Number, Object, DEFAULT_MAX_DEPTH};
+use crate::json::parser::{parse_json_boolean, parse_json_escaped_string, parse_json_null, parse_json_number, parse_json_string};
+use crate::json::scan::is_whitespace;
+
+/// One step through a document, in the order its text is read: containers
+/// open and end around their contents, and each key comes before its value.
+#[derive(PartialEq, Debug, Clone)]
+pub enum JsonEvent {
+    StartObject,
+    EndObject,
+    StartArray,
+    EndArray,
+    Key(String),
+    String(String),
+    Number(Number),
+    Boolean(bool),
+    Null
+}
+
+#[derive(Debug)]
+pub enum EventError {
+    Io(io::Error),
+    Syntax(usize),
+    UnexpectedEnd(usize),
+    /// Containers nest deeper than `limit` at byte `offset`.
+    TooDeep { limit: usize, offset: usize }
+}
+
+impl fmt::Display for EventError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EventError::Io(reason) => write!(f, "reading input failed: {}", reason),
+            EventError::Syntax(offset) => write!(f, "syntax error at byte {}", offset),
+            EventError::UnexpectedEnd(offset) => write!(f, "unexpected end of input at byte {}", offset),
+            EventError::TooDeep { limit, offset } => write!(f, "containers nest deeper than {} levels at byte {}", limit, offset)
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+enum State {
+    Value,
+    FirstValue,
+    Key,
+    FirstKey,
+    Colon,
+    Comma,
+    Done
+}
+
+/// Pulls parse events for one document out of a byte source, reading it
+/// chunk by chunk into a circular buffer as tokens need more data, so that
+/// memory use depends on the longest token rather than the whole input.
+/// Iterating yields the same events and stops after the first error.
+/// Containers may nest `DEFAULT_MAX_DEPTH` deep unless `max_depth` says
+/// otherwise.
+pub struct EventReader<R : Read> {
+    source: R,
+    max_depth: usize,
+    buffer: Buffer,
+    eof: bool,
+    containers: Vec<u8>,
+    state: State,
+    offset: usize
+}
+
+impl<R : Read> EventReader<R> {
+    pub fn new(source : R) -> EventReader<R> {
+        EventReader {
+            source,
+            max_depth: DEFAULT_MAX_DEPTH,
+            buffer: Buffer::with_capacity(4096),
+            eof: false,
+            containers: Vec::new(),
+            state: State::Value,
+            offset: 0
+        }
+    }
+
+    /// Fails documents whose containers nest deeper than `depth`, as
+    /// `ParserOptions::max_depth` does.
+    pub fn max_depth(mut self, depth : usize) -> EventReader<R> {
+        self.max_depth = depth;
+        self
+    }
+
+    /// Bytes of input consumed so far.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// What the reader is looking for next.
+    pub(crate) fn expected(&self) -> Expected {
+        match self.state {
+            State::FirstValue => Expected::ValueOrEnd,
+            State::Key => Expected::Key,
+            State::FirstKey => Expected::KeyOrEnd,
+            State::Colon => Expected::Colon,
+            State::Comma => Expected::CommaOrEnd(if self.containers.last() == Some(&b'{') { '}' } else { ']' }),
+            State::Value | State::Done => Expected::Value
+        }
+    }
+
+    /// The next event, or None once the document is complete.
+    pub fn next_event(&mut self) -> Result<Option<JsonEvent>, EventError> {
+        loop {
+            let byte = match self.peek()? {
+                Some(byte) => byte,
+                None if self.state == State::Done || (self.state == State::Value && self.containers.is_empty()) => return Ok(None),
+                None => return Err(EventError::UnexpectedEnd(self.offset))
+            };
+            let top = self.containers.last().cloned();
+
+            match (self.state, byte) {
+                (State::Done, _) => return Ok(None),
+                (State::Colon, b':') => {
+                    self.consume(1);
+                    self.state = State::Value
+                },
+                (State::Comma, b',') => {
+                    self.consume(1);
+                    self.state = if top == Some(b'[') { State::Value } else { State::Key }
+                },
+                (State::Comma, b']') | (State::FirstValue, b']') if top == Some(b'[') => {
+                    self.close();
+                    return Ok(Some(JsonEvent::EndArray))
+                },
+                (State::Comma, b'}') | (State::FirstKey, b'}') if top == Some(b'{') => {
+                    self.close();
+                    return Ok(Some(JsonEvent::EndObject))
+                },
+                (State::Key, b'"') | (State::FirstKey, b'"') => {
+                    let key = self.token(parse_json_escaped_string)?;
+                    self.state = State::Colon;
+                    return Ok(Some(JsonEvent::Key(key)))
+                },
+                (State::Value, _) | (State::FirstValue, _) => return self.value(byte).map(Some),
+                _ => return Err(EventError::Syntax(self.offset))
+            }
+        }
+    }
+
+    /// Builds the complete value that `first` starts, consuming its remaining events.
+    pub fn read_value(&mut self, first : JsonEvent) -> Result<JsonNode, EventError> {
+        let mut open : Vec<(JsonNode, Option<String>)> = Vec::new();
+        let mut event = first;
+        loop {
+            let node = match event {
+                JsonEvent::StartArray => { open.push((JsonNode::Array(Vec::new()), None)); None },
+                JsonEvent::StartObject => { open.push((JsonNode::Object(Object::default()), None)); None },
+                JsonEvent::Key(key) => {
+                    if let Some(top) = open.last_mut() { top.1 = Some(key) }
+                    None
+                },
+                JsonEvent::EndArray | JsonEvent::EndObject => open.pop().map(|(node, _)| node),
+                JsonEvent::String(s) => Some(JsonNode::String(s)),
+                JsonEvent::Number(n) => Some(JsonNode::Number(n)),
+                JsonEvent::Boolean(t) => Some(JsonNode::Boolean(t)),
+                JsonEvent::Null => Some(JsonNode::Null)
+            };
+
+            if let Some(node) = node {
+                match open.last_mut() {
+                    None => return Ok(node),
+                    Some((JsonNode::Array(a), _)) => a.push(node),
+                    // the first of duplicate keys wins, as when parsing
+                    Some((JsonNode::Object(o), key)) => { o.entry(key.take().unwrap_or_default()).or_insert(node); },
+                    Some(_) => unreachable!("only containers are kept open")
+                }
+            }
+
+            event = self.next_event()?.ok_or(EventError::UnexpectedEnd(self.offset))?;
+        }
+    }
+
+    /// Consumes the remaining events of the value that `first` starts.
+    pub fn skip_value(&mut self, first : JsonEvent) -> Result<(), EventError> {
+        let mut depth = 0;
+        let mut event = first;
+        loop {
+            match event {
+                JsonEvent::StartArray | JsonEvent::StartObject => depth += 1,
+                JsonEvent::EndArray | JsonEvent::EndObject => depth -= 1,
+                _ => ()
+            }
+            if depth == 0 {
+                return Ok(())
+            }
+            event = self.next_event()?.ok_or(EventError::UnexpectedEnd(self.offset))?;
+        }
+    }
+
+    fn value(&mut self, byte : u8) -> Result<JsonEvent, EventError> {
+        let mut event = match byte {
+            b'{' | b'[' if self.containers.len() == self.max_depth => {
+                return Err(EventError::TooDeep { limit: self.max_depth, offset: self.offset })
+            },
+            b'{' | b'[' => {
+                self.consume(1);
+                self.containers.push(byte);
+                self.state = if byte == b'{' { State::FirstKey } else { State::FirstValue };
+                return Ok(if byte == b'{' { JsonEvent::StartObject } else { JsonEvent::StartArray })
+            },
+            b'"' => self.token(parse_json_string)?,
+            b't' | b'f' => self.token(parse_json_boolean)?,
+            b'n' => self.token(parse_json_null)?,
+            _ => self.token(parse_json_number)?
+        };
+        self.state = if self.containers.is_empty() { State::Done } else { State::Comma };
+        Ok(match event {
+            JsonNode::String(ref mut s) => JsonEvent::String(std::mem::take(s)),
+            JsonNode::Number(n) => JsonEvent::Number(n),
+            JsonNode::Boolean(t) => JsonEvent::Boolean(t),
+            _ => JsonEvent::Null
+        })
+    }
+
+    fn close(&mut self) {
+        self.consume(1);
+        self.containers.pop();
+        self.state = if self.containers.is_empty() { State::Done } else { State::Comma };
+    }
+
+    fn token<O>(&mut self, parser : fn(&[u8]) -> IResult<&[u8], O>) -> Result<O, EventError> {
+        loop {
+            let available = self.buffer.available_data();
+            let incomplete = match parser(self.buffer.data()) {
+                Ok((rest, out)) => {
+                    self.consume(available - rest.len());
+                    return Ok(out)
+                },
+                Err(nom::Err::Incomplete(_)) => true,
+                Err(_) => false
+            };
+            if !incomplete {
+                return Err(EventError::Syntax(self.offset))
+            }
+            if !self.eof {
+                self.fill()?;
+                continue
+            }
+
+            // at end of input a number is only complete when given a terminator
+            let mut last = self.buffer.data().to_vec();
+            last.push(b' ');
+            if let Ok((rest, out)) = parser(&last) {
+                if rest.len() == 1 {
+                    self.consume(available);
+                    return Ok(out)
+                }
+            }
+            return Err(EventError::UnexpectedEnd(self.offset))
+        }
+    }
+
+    fn peek(&mut self) -> Result<Option<u8>, EventError> {
+        loop {
+            let blank = self.buffer.data().iter().take_while(|b| is_whitespace(**b)).count();
+            self.consume(blank);
+            if let Some(byte) = self.buffer.data().first() {
+                return Ok(Some(*byte))
+            }
+            if self.eof {
+                return Ok(None)
+            }
+            self.fill()?
+        }
+    }
+
+    fn consume(&mut self, count : usize) {
+        self.offset += self.buffer.consume(count);
+    }
+
+    fn fill(&mut self) -> Result<(), EventError> {
+        if self.buffer.available_space() == 0 {
+            self.buffer.shift();
+        }
+        if self.buffer.available_space() == 0 {
+            let capacity = self.buffer.capacity();
+            self.buffer.grow(capacity * 2);
+        }
+        loop {
+            match self.source.read(self.buffer.space()) {
+                Ok(0) => self.eof = true,
+                Ok(length) => { self.buffer.fill(length); },
+                Err(ref reason) if reason.kind() == io::ErrorKind::Interrupted => continue,
+                Err(reason) => return Err(EventError::Io(reason))
+            }
+            return Ok(())
+        }
+    }
+}
+
+impl<R : Read> Iterator for EventReader<R> {
+    type Item = Result<JsonEvent, EventError>;
+
+    fn next(&mut self) -> Option<Result<JsonEvent, EventError>> {
+        let next = self.next_event();
+        if next.is_err() {
+            self.state = State::Done
+        }
+        next.transpose()
+    }
+}
+
+/// Reads one document from `source`, passing each of its events to `handler`
+/// as soon as it is parsed, without building the document.
+pub fn for_each_event<R : Read, F : FnMut(JsonEvent)>(source : R, mut handler : F) -> Result<(), EventError> {
+    let mut reader = EventReader::new(source);
+    while let Some(event) = reader.next_event()? {
+        handler(event)
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn events(input : &str) -> Result<Vec<JsonEvent>, EventError> {
+        let mut reader = EventReader::new(input.as_bytes());
+        let mut events = Vec::new();
+        while let Some(event) = reader.next_event()? {
+            events.push(event)
+        }
+        Ok(events)
+    }
+
+    #[test]
+    fn test_events_of_nested_document() {
+        assert_eq!(events(" { \"a\" : [ 1 , true ] , \"b\" : { } } ").unwrap(), vec![
+            JsonEvent::StartObject,
+            JsonEvent::Key("a".to_string()),
+            JsonEvent::StartArray,
+            JsonEvent::Number(1.0.into()),
+            JsonEvent::Boolean(true),
+            JsonEvent::EndArray,
+            JsonEvent::Key("b".to_string()),
+            JsonEvent::StartObject,
+            JsonEvent::EndObject,
+            JsonEvent::EndObject
+        ]);
+    }
+
+    #[test]
+    fn test_number_at_end_of_input() {
+        assert_eq!(events("12.5").unwrap(), vec![JsonEvent::Number(12.5.into())]);
+    }
+
+    #[test]
+    fn test_tokens_split_across_reads() {
+        let long = "x".repeat(10000);
+        let input = format!("[\"{}\",null]", long);
+        let mut reader = EventReader::new(input.as_bytes());
+        let first = reader.next_event().unwrap().unwrap();
+        assert_eq!(reader.read_value(first).unwrap(),
+            JsonNode::Array(vec![JsonNode::String(long), JsonNode::Null]));
+    }
+
+    #[test]
+    fn test_syntax_errors() {
+        assert!(matches!(events("[1 2]"), Err(EventError::Syntax(3))));
+        assert!(matches!(events("{\"a\" 1}"), Err(EventError::Syntax(5))));
+        assert!(matches!(events("[1,]"), Err(EventError::Syntax(3))));
+        assert!(matches!(events("[1,"), Err(EventError::UnexpectedEnd(3))));
+        for number in &["[+1]", "[01]", "[.5]", "[1.]"] {
+            assert!(matches!(events(number), Err(EventError::Syntax(1))), "{}", number);
+        }
+    }
+
+    #[test]
+    fn test_iterator_and_callback() {
+        let input = "[\"a\", {\"b\": null}]";
+        let mut strings = 0;
+        for_each_event(input.as_bytes(), |event| if let JsonEvent::String(_) = event { strings += 1 }).unwrap();
+        assert_eq!(strings, 1);
+        let iterated : Result<Vec<JsonEvent>, EventError> = EventReader::new(input.as_bytes()).collect();
+        assert_eq!(iterated.unwrap(), events(input).unwrap());
+
+        let mut reader = EventReader::new("[1 2]".as_bytes());
+        assert_eq!(reader.next().unwrap().unwrap(), JsonEvent::StartArray);
+        assert_eq!(reader.next().unwrap().unwrap(), JsonEvent::Number(1.0.into()));
+        assert!(matches!(reader.next(), Some(Err(EventError::Syntax(3)))));
+        assert!(reader.next().is_none());
+    }
+}
@@ -0,0 +1,876 @@
+use std::cmp::Ordering;
+use std::fmt;
+use crate::json::{maybe_grow, JsonNode, Number, Object};
+
+/// A compiled filter in a small jq-like language. Supported: `.`, `..`,
+/// `.name`, `.[index]`, `.[start:end]`, `.[]`, `?`, pipes, commas, array and
+/// object construction, literals, `+ - * / %`, comparisons, `and`, `or`,
+/// `//`, and the functions `length`, `keys`, `map`, `select`, `has`, `not`,
+/// `type`, `add`, `empty`, `sort`, `reverse`, `tostring` and `tonumber`.
+///
+/// This is the query type of the library, also named `Query`, and the one
+/// the `filter` command runs: `Filter::parse` compiles an expression once
+/// and `run` evaluates it against any number of documents.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Filter {
+    expr: Expr
+}
+
+/// The library's query type: a compiled `Filter` expression.
+pub type Query = Filter;
+
+#[derive(PartialEq, Debug)]
+pub enum FilterError {
+    /// The expression does not parse: position in characters and what was expected.
+    Syntax(usize, &'static str),
+    /// The filter does not apply to the input it was given.
+    Runtime(String)
+}
+
+impl fmt::Display for FilterError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FilterError::Syntax(position, message) => write!(f, "invalid filter at character {}: {}", position, message),
+            FilterError::Runtime(message) => write!(f, "{}", message)
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+enum Expr {
+    Identity,
+    Recurse,
+    Literal(JsonNode),
+    Field(Box<Expr>, String),
+    Index(Box<Expr>, Box<Expr>),
+    Slice(Box<Expr>, Option<Box<Expr>>, Option<Box<Expr>>),
+    Iterate(Box<Expr>),
+    Optional(Box<Expr>),
+    Array(Option<Box<Expr>>),
+    Object(Vec<(Expr, Expr)>),
+    Negate(Box<Expr>),
+    Pipe(Box<Expr>, Box<Expr>),
+    Comma(Box<Expr>, Box<Expr>),
+    Alternative(Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Binary(Box<Expr>, Operator, Box<Expr>),
+    Call(String, Vec<Expr>)
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual
+}
+
+/// Runs `expression` on `input`, returning every value it produces.
+pub fn run(input : &JsonNode, expression : &str) -> Result<Vec<JsonNode>, FilterError> {
+    Filter::parse(expression)?.run(input)
+}
+
+impl Filter {
+    pub fn parse(expression : &str) -> Result<Filter, FilterError> {
+        let mut parser = Parser { chars: expression.chars().collect(), pos: 0 };
+        let expr = parser.pipe()?;
+        parser.skip_blank();
+        if parser.pos < parser.chars.len() {
+            return Err(parser.error("unexpected character"))
+        }
+        Ok(Filter { expr })
+    }
+
+    pub fn run(&self, input : &JsonNode) -> Result<Vec<JsonNode>, FilterError> {
+        evaluate(&self.expr, input)
+    }
+}
+
+fn evaluate(expr : &Expr, input : &JsonNode) -> Result<Vec<JsonNode>, FilterError> {
+    Ok(match expr {
+        Expr::Identity => vec![input.clone()],
+        Expr::Recurse => {
+            let mut out = Vec::new();
+            recurse(input, &mut out);
+            out
+        },
+        Expr::Literal(value) => vec![value.clone()],
+        Expr::Field(target, name) => {
+            let key = JsonNode::String(name.clone());
+            evaluate(target, input)?.iter().map(|value| index(value, &key)).collect::<Result<_, _>>()?
+        },
+        Expr::Index(target, key) => {
+            let keys = evaluate(key, input)?;
+            let mut out = Vec::new();
+            for value in evaluate(target, input)? {
+                for key in &keys {
+                    out.push(index(&value, key)?)
+                }
+            }
+            out
+        },
+        Expr::Slice(target, start, end) => {
+            let starts = bound(start, input)?;
+            let ends = bound(end, input)?;
+            let mut out = Vec::new();
+            for value in evaluate(target, input)? {
+                for start in &starts {
+                    for end in &ends {
+                        out.push(slice(&value, start, end)?)
+                    }
+                }
+            }
+            out
+        },
+        Expr::Iterate(target) => {
+            let mut out = Vec::new();
+            for value in evaluate(target, input)? {
+                out.extend(iterate(&value)?)
+            }
+            out
+        },
+        Expr::Optional(inner) => evaluate(inner, input).unwrap_or_default(),
+        Expr::Array(None) => vec![JsonNode::Array(Vec::new())],
+        Expr::Array(Some(inner)) => vec![JsonNode::Array(evaluate(inner, input)?)],
+        Expr::Object(members) => {
+            let mut objects = vec![Object::default()];
+            for (key, value) in members {
+                let keys = evaluate(key, input)?;
+                let values = evaluate(value, input)?;
+                let mut extended = Vec::new();
+                for object in &objects {
+                    for key in &keys {
+                        let key = match key {
+                            JsonNode::String(key) => key,
+                            _ => return Err(runtime(format!("object keys must be strings, not {}", type_name(key))))
+                        };
+                        for value in &values {
+                            let mut object = object.clone();
+                            object.insert(key.clone(), value.clone());
+                            extended.push(object)
+                        }
+                    }
+                }
+                objects = extended
+            }
+            objects.into_iter().map(JsonNode::Object).collect()
+        },
+        Expr::Negate(inner) => evaluate(inner, input)?.into_iter().map(|value| match value {
+            JsonNode::Number(n) => Ok(JsonNode::Number(-n)),
+            _ => Err(runtime(format!("{} cannot be negated", type_name(&value))))
+        }).collect::<Result<_, _>>()?,
+        Expr::Pipe(first, second) => {
+            let mut out = Vec::new();
+            for value in evaluate(first, input)? {
+                out.extend(evaluate(second, &value)?)
+            }
+            out
+        },
+        Expr::Comma(first, second) => {
+            let mut out = evaluate(first, input)?;
+            out.extend(evaluate(second, input)?);
+            out
+        },
+        Expr::Alternative(first, second) => {
+            let truthy : Vec<JsonNode> = evaluate(first, input).unwrap_or_default().into_iter().filter(is_truthy).collect();
+            if truthy.is_empty() { evaluate(second, input)? } else { truthy }
+        },
+        Expr::And(left, right) => logical(left, right, input, false)?,
+        Expr::Or(left, right) => logical(left, right, input, true)?,
+        Expr::Binary(left, operator, right) => {
+            let rights = evaluate(right, input)?;
+            let mut out = Vec::new();
+            for left in evaluate(left, input)? {
+                for right in &rights {
+                    out.push(binary(&left, *operator, right)?)
+                }
+            }
+            out
+        },
+        Expr::Call(name, args) => call(name, args, input)?
+    })
+}
+
+fn runtime(message : String) -> FilterError {
+    FilterError::Runtime(message)
+}
+
+fn recurse(value : &JsonNode, out : &mut Vec<JsonNode>) {
+    maybe_grow(|| {
+        out.push(value.clone());
+        if let Ok(children) = iterate(value) {
+            for child in &children {
+                recurse(child, out)
+            }
+        }
+    })
+}
+
+fn bound(expr : &Option<Box<Expr>>, input : &JsonNode) -> Result<Vec<JsonNode>, FilterError> {
+    match expr {
+        Some(expr) => evaluate(expr, input),
+        None => Ok(vec![JsonNode::Null])
+    }
+}
+
+fn index(value : &JsonNode, key : &JsonNode) -> Result<JsonNode, FilterError> {
+    match (value, key) {
+        (JsonNode::Null, JsonNode::String(_)) | (JsonNode::Null, JsonNode::Number(_)) => Ok(JsonNode::Null),
+        (JsonNode::Object(o), JsonNode::String(key)) => Ok(o.get(key).cloned().unwrap_or(JsonNode::Null)),
+        (JsonNode::Array(a), JsonNode::Number(n)) => {
+            let n = n.as_f64();
+            let i = if n < 0.0 { a.len() as f64 + n.floor() } else { n.floor() };
+            Ok(if i < 0.0 { JsonNode::Null } else { a.get(i as usize).cloned().unwrap_or(JsonNode::Null) })
+        },
+        _ => Err(runtime(format!("cannot index {} with {}", type_name(value), type_name(key))))
+    }
+}
+
+fn slice(value : &JsonNode, start : &JsonNode, end : &JsonNode) -> Result<JsonNode, FilterError> {
+    let range = |len : usize| -> Result<(usize, usize), FilterError> {
+        let clamp = |bound : &JsonNode, default : usize| match bound {
+            JsonNode::Null => Ok(default),
+            JsonNode::Number(n) => {
+                let n = n.as_f64();
+                let n = if n < 0.0 { len as f64 + n.floor() } else { n.floor() };
+                Ok(n.max(0.0).min(len as f64) as usize)
+            },
+            _ => Err(runtime(format!("slice bounds must be numbers, not {}", type_name(bound))))
+        };
+        let start = clamp(start, 0)?;
+        Ok((start, clamp(end, len)?.max(start)))
+    };
+    match value {
+        JsonNode::Null => Ok(JsonNode::Null),
+        JsonNode::Array(a) => {
+            let (start, end) = range(a.len())?;
+            Ok(JsonNode::Array(a[start..end].to_vec()))
+        },
+        JsonNode::String(s) => {
+            let chars : Vec<char> = s.chars().collect();
+            let (start, end) = range(chars.len())?;
+            Ok(JsonNode::String(chars[start..end].iter().collect()))
+        },
+        _ => Err(runtime(format!("cannot slice {}", type_name(value))))
+    }
+}
+
+// object members come out in key order, so results do not depend on hashing
+fn iterate(value : &JsonNode) -> Result<Vec<JsonNode>, FilterError> {
+    match value {
+        JsonNode::Array(a) => Ok(a.clone()),
+        JsonNode::Object(o) => Ok(sorted_keys(o).into_iter().map(|key| o[key].clone()).collect()),
+        _ => Err(runtime(format!("cannot iterate over {}", type_name(value))))
+    }
+}
+
+fn sorted_keys(o : &Object) -> Vec<&String> {
+    let mut keys : Vec<&String> = o.keys().collect();
+    keys.sort();
+    keys
+}
+
+fn is_truthy(value : &JsonNode) -> bool {
+    !matches!(value, JsonNode::Null | JsonNode::Boolean(false))
+}
+
+fn logical(left : &Expr, right : &Expr, input : &JsonNode, or : bool) -> Result<Vec<JsonNode>, FilterError> {
+    let mut out = Vec::new();
+    for left in evaluate(left, input)? {
+        // `or` is decided by a true left side and `and` by a false one
+        if is_truthy(&left) == or {
+            out.push(JsonNode::Boolean(or));
+            continue
+        }
+        out.extend(evaluate(right, input)?.iter().map(|right| JsonNode::Boolean(is_truthy(right))))
+    }
+    Ok(out)
+}
+
+fn binary(left : &JsonNode, operator : Operator, right : &JsonNode) -> Result<JsonNode, FilterError> {
+    let ordering = || compare(left, right);
+    Ok(match (operator, left, right) {
+        (Operator::Equal, _, _) => JsonNode::Boolean(ordering() == Ordering::Equal),
+        (Operator::NotEqual, _, _) => JsonNode::Boolean(ordering() != Ordering::Equal),
+        (Operator::Less, _, _) => JsonNode::Boolean(ordering() == Ordering::Less),
+        (Operator::LessOrEqual, _, _) => JsonNode::Boolean(ordering() != Ordering::Greater),
+        (Operator::Greater, _, _) => JsonNode::Boolean(ordering() == Ordering::Greater),
+        (Operator::GreaterOrEqual, _, _) => JsonNode::Boolean(ordering() != Ordering::Less),
+        (Operator::Add, JsonNode::Null, value) | (Operator::Add, value, JsonNode::Null) => value.clone(),
+        (Operator::Add, JsonNode::Number(a), JsonNode::Number(b)) => JsonNode::Number((a.as_f64() + b.as_f64()).into()),
+        (Operator::Add, JsonNode::String(a), JsonNode::String(b)) => JsonNode::String(format!("{}{}", a, b)),
+        (Operator::Add, JsonNode::Array(a), JsonNode::Array(b)) => JsonNode::Array(a.iter().chain(b).cloned().collect()),
+        (Operator::Add, JsonNode::Object(a), JsonNode::Object(b)) => {
+            let mut merged = a.clone();
+            merged.extend(b.iter().map(|(k, v)| (k.clone(), v.clone())));
+            JsonNode::Object(merged)
+        },
+        (Operator::Subtract, JsonNode::Number(a), JsonNode::Number(b)) => JsonNode::Number((a.as_f64() - b.as_f64()).into()),
+        (Operator::Subtract, JsonNode::Array(a), JsonNode::Array(b)) => JsonNode::Array(a.iter().filter(|item| !b.contains(item)).cloned().collect()),
+        (Operator::Multiply, JsonNode::Number(a), JsonNode::Number(b)) => JsonNode::Number((a.as_f64() * b.as_f64()).into()),
+        (Operator::Divide, JsonNode::Number(_), JsonNode::Number(b)) if b.as_f64() == 0.0 => return Err(runtime("division by zero".to_string())),
+        (Operator::Divide, JsonNode::Number(a), JsonNode::Number(b)) => JsonNode::Number((a.as_f64() / b.as_f64()).into()),
+        (Operator::Divide, JsonNode::String(a), JsonNode::String(b)) => JsonNode::Array(a.split(b.as_str()).map(|s| JsonNode::String(s.to_string())).collect()),
+        (Operator::Remainder, JsonNode::Number(_), JsonNode::Number(b)) if b.as_f64().trunc() == 0.0 => return Err(runtime("division by zero".to_string())),
+        (Operator::Remainder, JsonNode::Number(a), JsonNode::Number(b)) => JsonNode::Number((a.as_f64().trunc() % b.as_f64().trunc()).into()),
+        _ => return Err(runtime(format!("cannot apply {:?} to {} and {}", operator, type_name(left), type_name(right))))
+    })
+}
+
+// jq's total order: null < false < true < numbers < strings < arrays < objects
+fn compare(a : &JsonNode, b : &JsonNode) -> Ordering {
+    let rank = |value : &JsonNode| match value {
+        JsonNode::Null => 0,
+        JsonNode::Boolean(false) => 1,
+        JsonNode::Boolean(true) => 2,
+        JsonNode::Number(_) => 3,
+        JsonNode::String(_) => 4,
+        JsonNode::Array(_) => 5,
+        JsonNode::Object(_) => 6
+    };
+    maybe_grow(|| match (a, b) {
+        // NaN sorts below every other number
+        (JsonNode::Number(a), JsonNode::Number(b)) => a.partial_cmp(b).unwrap_or_else(|| b.as_f64().is_nan().cmp(&a.as_f64().is_nan())),
+        (JsonNode::String(a), JsonNode::String(b)) => a.cmp(b),
+        (JsonNode::Array(a), JsonNode::Array(b)) => a.iter().zip(b).map(|(a, b)| compare(a, b))
+            .find(|o| *o != Ordering::Equal).unwrap_or_else(|| a.len().cmp(&b.len())),
+        (JsonNode::Object(a), JsonNode::Object(b)) => {
+            let (keys_a, keys_b) = (sorted_keys(a), sorted_keys(b));
+            keys_a.cmp(&keys_b).then_with(|| keys_a.iter().map(|key| compare(&a[*key], &b[*key]))
+                .find(|o| *o != Ordering::Equal).unwrap_or(Ordering::Equal))
+        },
+        _ => rank(a).cmp(&rank(b))
+    })
+}
+
+fn type_name(value : &JsonNode) -> &'static str {
+    match value {
+        JsonNode::Null => "null",
+        JsonNode::Boolean(_) => "boolean",
+        JsonNode::Number(_) => "number",
+        JsonNode::String(_) => "string",
+        JsonNode::Array(_) => "array",
+        JsonNode::Object(_) => "object"
+    }
+}
+
+fn call(name : &str, args : &[Expr], input : &JsonNode) -> Result<Vec<JsonNode>, FilterError> {
+    Ok(match (name, args) {
+        ("empty", []) => Vec::new(),
+        ("not", []) => vec![JsonNode::Boolean(!is_truthy(input))],
+        ("type", []) => vec![JsonNode::String(type_name(input).to_string())],
+        ("length", []) => vec![JsonNode::Number(match input {
+            JsonNode::Null => Number::from(0),
+            JsonNode::Number(n) if *n < Number::from(0) => -*n,
+            JsonNode::Number(n) => *n,
+            JsonNode::String(s) => s.chars().count().into(),
+            JsonNode::Array(a) => a.len().into(),
+            JsonNode::Object(o) => o.len().into(),
+            JsonNode::Boolean(_) => return Err(runtime("boolean has no length".to_string()))
+        })],
+        ("keys", []) => vec![JsonNode::Array(match input {
+            JsonNode::Object(o) => sorted_keys(o).into_iter().map(|key| JsonNode::String(key.clone())).collect(),
+            JsonNode::Array(a) => (0..a.len()).map(|i| JsonNode::Number(i.into())).collect(),
+            _ => return Err(runtime(format!("{} has no keys", type_name(input))))
+        })],
+        ("has", [key]) => evaluate(key, input)?.iter().map(|key| match (input, key) {
+            (JsonNode::Object(o), JsonNode::String(key)) => Ok(JsonNode::Boolean(o.contains_key(key))),
+            (JsonNode::Array(a), JsonNode::Number(n)) => Ok(JsonNode::Boolean(n.as_f64() >= 0.0 && (n.as_f64() as usize) < a.len())),
+            _ => Err(runtime(format!("cannot check whether {} has a {} key", type_name(input), type_name(key))))
+        }).collect::<Result<_, _>>()?,
+        ("map", [f]) => {
+            let mut out = Vec::new();
+            for item in iterate(input)? {
+                out.extend(evaluate(f, &item)?)
+            }
+            vec![JsonNode::Array(out)]
+        },
+        ("select", [f]) => evaluate(f, input)?.iter().filter(|keep| is_truthy(keep)).map(|_| input.clone()).collect(),
+        ("add", []) => {
+            let mut sum = JsonNode::Null;
+            for item in iterate(input)? {
+                sum = binary(&sum, Operator::Add, &item)?
+            }
+            vec![sum]
+        },
+        ("sort", []) => match input {
+            JsonNode::Array(a) => {
+                let mut sorted = a.clone();
+                sorted.sort_by(compare);
+                vec![JsonNode::Array(sorted)]
+            },
+            _ => return Err(runtime(format!("{} cannot be sorted", type_name(input))))
+        },
+        ("reverse", []) => match input {
+            JsonNode::Array(a) => vec![JsonNode::Array(a.iter().rev().cloned().collect())],
+            JsonNode::String(s) => vec![JsonNode::String(s.chars().rev().collect())],
+            JsonNode::Null => vec![JsonNode::Array(Vec::new())],
+            _ => return Err(runtime(format!("{} cannot be reversed", type_name(input))))
+        },
+        ("tostring", []) => vec![match input {
+            JsonNode::String(_) => input.clone(),
+            _ => JsonNode::String(input.to_string())
+        }],
+        ("tonumber", []) => vec![match input {
+            JsonNode::Number(_) => input.clone(),
+            JsonNode::String(s) => JsonNode::Number(s.trim().parse().map_err(|_| runtime(format!("cannot parse \"{}\" as a number", s)))?),
+            _ => return Err(runtime(format!("{} cannot be parsed as a number", type_name(input))))
+        }],
+        _ => return Err(runtime(format!("{}/{} is not defined", name, args.len())))
+    })
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize
+}
+
+impl Parser {
+    fn error(&self, message : &'static str) -> FilterError {
+        FilterError::Syntax(self.pos, message)
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).cloned()
+    }
+
+    fn peek_at(&self, offset : usize) -> Option<char> {
+        self.chars.get(self.pos + offset).cloned()
+    }
+
+    fn looking_at(&self, text : &str) -> bool {
+        text.chars().enumerate().all(|(i, c)| self.chars.get(self.pos + i) == Some(&c))
+    }
+
+    fn eat(&mut self, text : &str) -> bool {
+        self.skip_blank();
+        let found = self.looking_at(text);
+        if found {
+            self.pos += text.chars().count()
+        }
+        found
+    }
+
+    // a keyword, only when it is not the start of a longer identifier
+    fn eat_word(&mut self, word : &str) -> bool {
+        self.skip_blank();
+        let end = self.pos + word.chars().count();
+        let found = self.looking_at(word) && !self.chars.get(end).is_some_and(|c| is_identifier(*c));
+        if found {
+            self.pos = end
+        }
+        found
+    }
+
+    fn expect(&mut self, text : &str, message : &'static str) -> Result<(), FilterError> {
+        if self.eat(text) { Ok(()) } else { Err(self.error(message)) }
+    }
+
+    fn skip_blank(&mut self) {
+        while let Some(' ') | Some('\t') | Some('\n') | Some('\r') = self.peek() {
+            self.pos += 1
+        }
+    }
+
+    fn pipe(&mut self) -> Result<Expr, FilterError> {
+        let first = self.comma()?;
+        if self.looking_at_operator("|") {
+            self.pos += 1;
+            return Ok(Expr::Pipe(Box::new(first), Box::new(self.pipe()?)))
+        }
+        Ok(first)
+    }
+
+    // true for `text` but not for a longer operator starting with it, like `|` in `||`
+    fn looking_at_operator(&mut self, text : &str) -> bool {
+        self.skip_blank();
+        self.looking_at(text) && !matches!(self.peek_at(text.len()), Some('=') | Some('/') | Some('|'))
+    }
+
+    fn comma(&mut self) -> Result<Expr, FilterError> {
+        let mut expr = self.alternative()?;
+        while self.eat(",") {
+            expr = Expr::Comma(Box::new(expr), Box::new(self.alternative()?))
+        }
+        Ok(expr)
+    }
+
+    fn alternative(&mut self) -> Result<Expr, FilterError> {
+        let first = self.or()?;
+        if self.eat("//") {
+            return Ok(Expr::Alternative(Box::new(first), Box::new(self.alternative()?)))
+        }
+        Ok(first)
+    }
+
+    fn or(&mut self) -> Result<Expr, FilterError> {
+        let mut expr = self.and()?;
+        while self.eat_word("or") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?))
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, FilterError> {
+        let mut expr = self.comparison()?;
+        while self.eat_word("and") {
+            expr = Expr::And(Box::new(expr), Box::new(self.comparison()?))
+        }
+        Ok(expr)
+    }
+
+    fn comparison(&mut self) -> Result<Expr, FilterError> {
+        let left = self.additive()?;
+        self.skip_blank();
+        let operator = [
+            ("==", Operator::Equal), ("!=", Operator::NotEqual),
+            ("<=", Operator::LessOrEqual), (">=", Operator::GreaterOrEqual),
+            ("<", Operator::Less), (">", Operator::Greater)
+        ].iter().find(|(text, _)| self.looking_at(text)).cloned();
+        match operator {
+            Some((text, operator)) => {
+                self.pos += text.len();
+                Ok(Expr::Binary(Box::new(left), operator, Box::new(self.additive()?)))
+            },
+            None => Ok(left)
+        }
+    }
+
+    fn additive(&mut self) -> Result<Expr, FilterError> {
+        let mut expr = self.multiplicative()?;
+        loop {
+            let operator = if self.looking_at_operator("+") {
+                Operator::Add
+            } else if self.looking_at_operator("-") {
+                Operator::Subtract
+            } else {
+                return Ok(expr)
+            };
+            self.pos += 1;
+            expr = Expr::Binary(Box::new(expr), operator, Box::new(self.multiplicative()?))
+        }
+    }
+
+    fn multiplicative(&mut self) -> Result<Expr, FilterError> {
+        let mut expr = self.postfix()?;
+        loop {
+            let operator = if self.looking_at_operator("*") {
+                Operator::Multiply
+            } else if self.looking_at_operator("/") {
+                Operator::Divide
+            } else if self.looking_at_operator("%") {
+                Operator::Remainder
+            } else {
+                return Ok(expr)
+            };
+            self.pos += 1;
+            expr = Expr::Binary(Box::new(expr), operator, Box::new(self.postfix()?))
+        }
+    }
+
+    fn postfix(&mut self) -> Result<Expr, FilterError> {
+        let mut expr = self.primary()?;
+        loop {
+            self.skip_blank();
+            match (self.peek(), self.peek_at(1)) {
+                (Some('.'), Some('[')) => {
+                    self.pos += 1;
+                    expr = self.bracket(expr)?
+                },
+                (Some('.'), Some(c)) if c == '"' || is_identifier_start(c) => {
+                    self.pos += 1;
+                    expr = Expr::Field(Box::new(expr), self.name()?)
+                },
+                (Some('['), _) => expr = self.bracket(expr)?,
+                (Some('?'), _) => {
+                    self.pos += 1;
+                    expr = Expr::Optional(Box::new(expr))
+                },
+                _ => return Ok(expr)
+            }
+        }
+    }
+
+    fn bracket(&mut self, target : Expr) -> Result<Expr, FilterError> {
+        self.pos += 1;
+        let target = Box::new(target);
+        if self.eat("]") {
+            return Ok(Expr::Iterate(target))
+        }
+        let start = if self.looking_at_colon() { None } else { Some(Box::new(self.pipe()?)) };
+        if !self.eat(":") {
+            self.expect("]", "expected ]")?;
+            return start.map(|key| Expr::Index(target, key)).ok_or_else(|| self.error("expected an index"))
+        }
+        let end = if self.eat("]") { None } else {
+            let end = self.pipe()?;
+            self.expect("]", "expected ]")?;
+            Some(Box::new(end))
+        };
+        if start.is_none() && end.is_none() {
+            return Err(self.error("expected a slice bound"))
+        }
+        Ok(Expr::Slice(target, start, end))
+    }
+
+    fn looking_at_colon(&mut self) -> bool {
+        self.skip_blank();
+        self.peek() == Some(':')
+    }
+
+    fn name(&mut self) -> Result<String, FilterError> {
+        if self.peek() == Some('"') {
+            return self.string()
+        }
+        Ok(self.identifier())
+    }
+
+    fn identifier(&mut self) -> String {
+        let start = self.pos;
+        while self.peek().is_some_and(is_identifier) {
+            self.pos += 1
+        }
+        self.chars[start..self.pos].iter().collect()
+    }
+
+    fn primary(&mut self) -> Result<Expr, FilterError> {
+        self.skip_blank();
+        match self.peek() {
+            Some('.') => {
+                self.pos += 1;
+                match self.peek() {
+                    Some('.') => {
+                        self.pos += 1;
+                        Ok(Expr::Recurse)
+                    },
+                    Some(c) if c == '"' || is_identifier_start(c) => Ok(Expr::Field(Box::new(Expr::Identity), self.name()?)),
+                    _ => Ok(Expr::Identity)
+                }
+            },
+            Some('"') => Ok(Expr::Literal(JsonNode::String(self.string()?))),
+            Some('0'..='9') => self.number(),
+            Some('-') => {
+                self.pos += 1;
+                Ok(Expr::Negate(Box::new(self.postfix()?)))
+            },
+            Some('(') => {
+                self.pos += 1;
+                let expr = self.pipe()?;
+                self.expect(")", "expected )")?;
+                Ok(expr)
+            },
+            Some('[') => {
+                self.pos += 1;
+                if self.eat("]") {
+                    return Ok(Expr::Array(None))
+                }
+                let inner = self.pipe()?;
+                self.expect("]", "expected ]")?;
+                Ok(Expr::Array(Some(Box::new(inner))))
+            },
+            Some('{') => self.object(),
+            Some(c) if is_identifier_start(c) => {
+                let name = self.identifier();
+                match name.as_str() {
+                    "true" => return Ok(Expr::Literal(JsonNode::Boolean(true))),
+                    "false" => return Ok(Expr::Literal(JsonNode::Boolean(false))),
+                    "null" => return Ok(Expr::Literal(JsonNode::Null)),
+                    "and" | "or" => return Err(self.error("expected a value")),
+                    _ => ()
+                }
+                let mut args = Vec::new();
+                if self.eat("(") {
+                    args.push(self.pipe()?);
+                    while self.eat(";") {
+                        args.push(self.pipe()?)
+                    }
+                    self.expect(")", "expected )")?;
+                }
+                Ok(Expr::Call(name, args))
+            },
+            _ => Err(self.error("expected a value"))
+        }
+    }
+
+    fn object(&mut self) -> Result<Expr, FilterError> {
+        self.pos += 1;
+        let mut members = Vec::new();
+        if self.eat("}") {
+            return Ok(Expr::Object(members))
+        }
+        loop {
+            self.skip_blank();
+            let (key, shorthand) = match self.peek() {
+                Some('"') => {
+                    let key = self.string()?;
+                    (Expr::Literal(JsonNode::String(key.clone())), key)
+                },
+                Some('(') => {
+                    self.pos += 1;
+                    let key = self.pipe()?;
+                    self.expect(")", "expected )")?;
+                    (key, String::new())
+                },
+                Some(c) if is_identifier_start(c) => {
+                    let key = self.identifier();
+                    (Expr::Literal(JsonNode::String(key.clone())), key)
+                },
+                _ => return Err(self.error("expected an object key"))
+            };
+            let value = if self.eat(":") {
+                self.object_value()?
+            } else if shorthand.is_empty() {
+                return Err(self.error("expected :"))
+            } else {
+                Expr::Field(Box::new(Expr::Identity), shorthand)
+            };
+            members.push((key, value));
+            if self.eat("}") {
+                return Ok(Expr::Object(members))
+            }
+            self.expect(",", "expected , or }")?
+        }
+    }
+
+    // a member value stops at `,` and `}`, so pipes and commas need parentheses
+    fn object_value(&mut self) -> Result<Expr, FilterError> {
+        let first = self.or()?;
+        if self.eat("//") {
+            return Ok(Expr::Alternative(Box::new(first), Box::new(self.object_value()?)))
+        }
+        Ok(first)
+    }
+
+    fn number(&mut self) -> Result<Expr, FilterError> {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            let sign = (c == '-' || c == '+') && matches!(self.chars[self.pos - 1], 'e' | 'E');
+            if c.is_ascii_digit() || c == '.' || c == 'e' || c == 'E' || sign { self.pos += 1 } else { break }
+        }
+        let text : String = self.chars[start..self.pos].iter().collect();
+        text.parse().map(|n| Expr::Literal(JsonNode::Number(n))).map_err(|_| self.error("invalid number"))
+    }
+
+    fn string(&mut self) -> Result<String, FilterError> {
+        self.pos += 1;
+        let mut out = String::new();
+        loop {
+            let c = self.peek().ok_or_else(|| self.error("unterminated string"))?;
+            self.pos += 1;
+            if c == '"' {
+                return Ok(out)
+            }
+            if c != '\\' {
+                out.push(c);
+                continue
+            }
+            let escaped = self.peek().ok_or_else(|| self.error("unterminated string"))?;
+            self.pos += 1;
+            out.push(match escaped {
+                'b' => '\u{8}',
+                'f' => '\u{c}',
+                'n' => '\n',
+                'r' => '\r',
+                't' => '\t',
+                'u' => {
+                    let hex : String = self.chars.iter().skip(self.pos).take(4).collect();
+                    self.pos += 4;
+                    u32::from_str_radix(&hex, 16).ok().and_then(std::char::from_u32)
+                        .ok_or_else(|| self.error("invalid unicode escape"))?
+                },
+                '\\' | '/' | '"' => escaped,
+                _ => return Err(self.error("invalid escape"))
+            })
+        }
+    }
+}
+
+fn is_identifier_start(c : char) -> bool {
+    c.is_ascii_alphabetic() || c == '_'
+}
+
+fn is_identifier(c : char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    const USERS : &str = "{\"users\":[\
+        {\"name\":\"ann\",\"age\":31,\"tags\":[\"admin\"]},\
+        {\"name\":\"bob\",\"age\":17,\"tags\":[]},\
+        {\"name\":\"cy\",\"age\":45}]}";
+
+    fn results(expression : &str) -> Vec<String> {
+        let doc = JsonNode::from_str(USERS).unwrap();
+        run(&doc, expression).unwrap().iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn test_paths_and_iteration() {
+        assert_eq!(results(".users[0].name"), vec!["\"ann\""]);
+        assert_eq!(results(".users[-1][\"name\"]"), vec!["\"cy\""]);
+        assert_eq!(results(".users[].name"), vec!["\"ann\"", "\"bob\"", "\"cy\""]);
+        assert_eq!(results(".users[1:][].age"), vec!["17", "45"]);
+        assert_eq!(results(".users[2].tags"), vec!["null"]);
+        assert_eq!(results(".users[0] | .name, .age"), vec!["\"ann\"", "31"]);
+        assert_eq!(results("[..] | length"), vec!["14"]);
+        let query = Query::parse("[.. | .age? // empty] | add").unwrap();
+        assert_eq!(query.run(&JsonNode::from_str(USERS).unwrap()).unwrap(), vec![JsonNode::from(93)]);
+    }
+
+    #[test]
+    fn test_map_select_and_arithmetic() {
+        assert_eq!(results(".users | map(.age) | add"), vec!["93"]);
+        assert_eq!(results(".users[] | select(.age >= 18 and (.tags | length) > 0) | .name"), vec!["\"ann\""]);
+        assert_eq!(results(".users | map(select(.tags)) | length"), vec!["2"]);
+        assert_eq!(results("[.users[].age] | sort | reverse"), vec!["[45,31,17]"]);
+        assert_eq!(results(".users[1].age * 2 - 4 / 2 % 3"), vec!["32"]);
+        assert_eq!(results(".users[2].tags // \"none\""), vec!["\"none\""]);
+        assert_eq!(results("[.users[] | .name + \"!\"]"), vec!["[\"ann!\",\"bob!\",\"cy!\"]"]);
+        assert_eq!(results(".users[0] | keys"), vec!["[\"age\",\"name\",\"tags\"]"]);
+        assert_eq!(results(".users[0] | has(\"age\"), (.age | tostring | tonumber | type)"), vec!["true", "\"number\""]);
+        assert_eq!(results("[1, null, \"a\", false] | sort"), vec!["[null,false,1,\"a\"]"]);
+    }
+
+    #[test]
+    fn test_nan_sorts_below_numbers() {
+        let nan = JsonNode::Number(f64::NAN.into());
+        assert_eq!(compare(&nan, &json!(-1e300)), Ordering::Less);
+        assert_eq!(compare(&nan, &nan), Ordering::Equal);
+        let values = JsonNode::Array(vec![json!(2), nan.clone(), json!(1), nan]);
+        let sorted = run(&values, "sort").unwrap().remove(0);
+        let sorted : Vec<f64> = (0..4).map(|i| sorted[i].as_number().unwrap().as_f64()).collect();
+        assert!(sorted[0].is_nan() && sorted[1].is_nan());
+        assert_eq!(sorted[2..], [1.0, 2.0]);
+    }
+
+    #[test]
+    fn test_object_construction() {
+        let doc = JsonNode::from_str(USERS).unwrap();
+        let built = run(&doc, ".users[0] | {name, \"years\": .age, (.name): true}").unwrap();
+        assert_eq!(built, vec![JsonNode::from_str("{\"name\":\"ann\",\"years\":31,\"ann\":true}").unwrap()]);
+        assert_eq!(run(&doc, "{a: (1, 2)} | .a").unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_errors() {
+        let doc = JsonNode::from_str(USERS).unwrap();
+        assert_eq!(run(&doc, ".users[0].name[0]").unwrap_err(), FilterError::Runtime("cannot index string with number".to_string()));
+        assert_eq!(run(&doc, ".users[0].name[0]?").unwrap(), vec![]);
+        assert_eq!(run(&doc, "nope").unwrap_err(), FilterError::Runtime("nope/0 is not defined".to_string()));
+        assert_eq!(Filter::parse(".users[").unwrap_err(), FilterError::Syntax(7, "expected a value"));
+        assert_eq!(Filter::parse(".a | ").unwrap_err(), FilterError::Syntax(5, "expected a value"));
+        assert!(Filter::parse("{a b}").is_err());
+    }
+}
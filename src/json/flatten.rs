@@ -0,0 +1,417 @@
+use std::fmt;
+use crate::json::{maybe_grow, JsonNode, Object};
+use crate::json::pointer::{array_index, JsonPointer};
+
+/// How the keys of a flattened document spell out paths.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Separator {
+    /// `a.b.0`, with `.`, `[` and `\` in keys escaped by a backslash.
+    Dot,
+    /// JSON Pointers: `/a/b/0`.
+    Pointer
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct FlattenOptions {
+    separator: Separator,
+    brackets: bool
+}
+
+impl FlattenOptions {
+    pub fn new(separator : Separator) -> FlattenOptions {
+        FlattenOptions { separator, brackets: false }
+    }
+
+    /// Writes array indices as `a[0]` instead of `a.0`, so that numeric object
+    /// keys stay distinguishable from indices. Only affects `Separator::Dot`.
+    pub fn bracket_indices(mut self) -> FlattenOptions {
+        self.brackets = true;
+        self
+    }
+}
+
+#[derive(PartialEq, Debug)]
+pub struct FlattenError {
+    pub path: String,
+    pub message: &'static str
+}
+
+impl fmt::Display for FlattenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "cannot unflatten \"{}\": {}", self.path, self.message)
+    }
+}
+
+/// Every leaf of `node` with its path, object members in key order. Empty
+/// arrays and objects are leaves too, so `unflatten` restores them.
+pub fn flatten(node : &JsonNode, separator : Separator) -> Vec<(String, JsonNode)> {
+    flatten_with(node, FlattenOptions::new(separator))
+}
+
+pub fn flatten_with(node : &JsonNode, options : FlattenOptions) -> Vec<(String, JsonNode)> {
+    let mut out = Vec::new();
+    flatten_into(node, &mut Vec::new(), options, &mut out);
+    out
+}
+
+enum Step {
+    Key(String),
+    Index(usize),
+    // a key, or an index when it is numeric and the container is not already an object
+    Either(String)
+}
+
+fn flatten_into(node : &JsonNode, path : &mut Vec<Step>, options : FlattenOptions, out : &mut Vec<(String, JsonNode)>) {
+    maybe_grow(|| match node {
+        JsonNode::Array(a) if !a.is_empty() => for (i, item) in a.iter().enumerate() {
+            path.push(Step::Index(i));
+            flatten_into(item, path, options, out);
+            path.pop();
+        },
+        JsonNode::Object(o) if !o.is_empty() => {
+            let mut keys : Vec<&String> = o.keys().collect();
+            keys.sort();
+            for key in keys {
+                path.push(Step::Key(key.clone()));
+                flatten_into(&o[key], path, options, out);
+                path.pop();
+            }
+        },
+        _ => out.push((format_path(path, options), node.clone()))
+    })
+}
+
+fn format_path(path : &[Step], options : FlattenOptions) -> String {
+    let token = |step : &Step| match step {
+        Step::Key(key) | Step::Either(key) => key.clone(),
+        Step::Index(i) => i.to_string()
+    };
+    if options.separator == Separator::Pointer {
+        return JsonPointer::from_tokens(path.iter().map(token)).to_string()
+    }
+    let mut out = String::new();
+    for step in path {
+        match step {
+            Step::Index(i) if options.brackets => out.push_str(&format!("[{}]", i)),
+            _ => {
+                if !out.is_empty() {
+                    out.push('.')
+                }
+                for c in token(step).chars() {
+                    if c == '.' || c == '[' || c == '\\' {
+                        out.push('\\')
+                    }
+                    out.push(c)
+                }
+            }
+        }
+    }
+    out
+}
+
+/// How far past the end of an array an index given to `unflatten` or `ungron`
+/// may reach, so that one short path cannot pad an array with nulls without bound.
+pub const MAX_INDEX_GAP : usize = 1 << 16;
+
+/// Rebuilds a document from `flatten` output, in any order. Indices beyond the
+/// end of an array pad it with nulls, up to `MAX_INDEX_GAP` of them at a time;
+/// with plain dot or pointer paths a numeric token makes an array unless an
+/// object is already there.
+pub fn unflatten<'a, I>(pairs : I, options : FlattenOptions) -> Result<JsonNode, FlattenError>
+    where I : IntoIterator<Item = &'a (String, JsonNode)>
+{
+    let mut root = JsonNode::Null;
+    for (path, value) in pairs {
+        let error = |message| FlattenError { path: path.clone(), message };
+        let steps = parse_path(path, options).map_err(error)?;
+        insert(&mut root, &steps, value.clone()).map_err(error)?;
+    }
+    Ok(root)
+}
+
+/// The JSON Pointer that a dotted path like `users[0].name` or `users.0.name`
+/// spells, escaped as `flatten` writes keys. A single leading `.`, as in
+/// `.users`, is allowed, and `.` alone is the whole document.
+pub fn dotted_pointer(path : &str) -> Result<JsonPointer, FlattenError> {
+    let relative = path.strip_prefix('.').unwrap_or(path);
+    let steps = parse_path(relative, FlattenOptions::new(Separator::Dot).bracket_indices())
+        .map_err(|message| FlattenError { path: path.to_string(), message })?;
+    Ok(JsonPointer::from_tokens(steps.into_iter().map(|step| match step {
+        Step::Key(key) | Step::Either(key) => key,
+        Step::Index(i) => i.to_string()
+    })))
+}
+
+fn parse_path(path : &str, options : FlattenOptions) -> Result<Vec<Step>, &'static str> {
+    if options.separator == Separator::Pointer {
+        let pointer = JsonPointer::parse(path).map_err(|_| "invalid JSON pointer")?;
+        return Ok(pointer.tokens().iter().map(|token| Step::Either(token.clone())).collect())
+    }
+    let mut steps = Vec::new();
+    let mut chars = path.chars().peekable();
+    let mut key = String::new();
+    // whether `key` holds a segment, which may be empty
+    let mut pending = !path.is_empty() && !path.starts_with('[');
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => key.push(chars.next().ok_or("path ends in a backslash")?),
+            '.' => {
+                if pending {
+                    steps.push(segment(&mut key, options))
+                }
+                pending = true
+            },
+            '[' if options.brackets => {
+                if pending {
+                    steps.push(segment(&mut key, options))
+                }
+                let digits : String = chars.by_ref().take_while(|c| *c != ']').collect();
+                steps.push(Step::Index(array_index(&digits).ok_or("expected an array index in brackets")?));
+                pending = false
+            },
+            _ => {
+                key.push(c);
+                pending = true
+            }
+        }
+    }
+    if pending {
+        steps.push(segment(&mut key, options))
+    }
+    Ok(steps)
+}
+
+/// The document as statements in the form gron uses, one per line, so that
+/// `grep` can find values by path: `json.users[0].name = "a";`. Containers
+/// come as `{}` or `[]` before what they hold, object members in key order.
+pub fn gron(node : &JsonNode) -> String {
+    let mut out = String::new();
+    gron_into(node, &mut "json".to_string(), &mut out);
+    out
+}
+
+fn gron_into(node : &JsonNode, path : &mut String, out : &mut String) {
+    let length = path.len();
+    maybe_grow(|| match node {
+        JsonNode::Array(a) => {
+            out.push_str(&format!("{} = [];\n", path));
+            for (i, item) in a.iter().enumerate() {
+                path.push_str(&format!("[{}]", i));
+                gron_into(item, path, out);
+                path.truncate(length)
+            }
+        },
+        JsonNode::Object(o) => {
+            out.push_str(&format!("{} = {{}};\n", path));
+            let mut keys : Vec<&String> = o.keys().collect();
+            keys.sort();
+            for key in keys {
+                if is_gron_identifier(key) {
+                    path.push('.');
+                    path.push_str(key)
+                } else {
+                    path.push_str(&format!("[{}]", JsonNode::String(key.clone())))
+                }
+                gron_into(&o[key], path, out);
+                path.truncate(length)
+            }
+        },
+        _ => out.push_str(&format!("{} = {};\n", path, node))
+    })
+}
+
+fn is_gron_identifier(key : &str) -> bool {
+    let mut chars = key.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '$')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+}
+
+/// Rebuilds a document from `gron` statements, in any order and with any of
+/// them left out, as after filtering them with `grep`: the containers on the
+/// way to a value are made as needed. The `;` ending a statement is optional.
+pub fn ungron(text : &str) -> Result<JsonNode, FlattenError> {
+    let mut root = JsonNode::Null;
+    for statement in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        let error = |message| FlattenError { path: statement.to_string(), message };
+        let (steps, rest) = parse_gron_path(statement).map_err(error)?;
+        let value = rest.trim_start().strip_prefix('=').ok_or("expected = after the path").map_err(error)?;
+        let value = value.strip_suffix(';').unwrap_or(value);
+        let value = JsonNode::from_str_strict(value).map_err(|_| error("expected a JSON value after ="))?;
+        insert(&mut root, &steps, value).map_err(error)?;
+    }
+    Ok(root)
+}
+
+// the steps of the path that `statement` starts with, and what follows it
+fn parse_gron_path(statement : &str) -> Result<(Vec<Step>, &str), &'static str> {
+    let mut rest = statement.strip_prefix("json").ok_or("a path starts with json")?;
+    let mut steps = Vec::new();
+    loop {
+        if let Some(after) = rest.strip_prefix('.') {
+            let end = after.find(|c : char| !(c.is_ascii_alphanumeric() || c == '_' || c == '$')).unwrap_or(after.len());
+            if end == 0 {
+                return Err("expected a key after .")
+            }
+            steps.push(Step::Key(after[..end].to_string()));
+            rest = &after[end..]
+        } else if let Some(after) = rest.strip_prefix('[') {
+            let (step, length) = match JsonNode::parse_prefix(after.as_bytes()) {
+                Ok(Some((JsonNode::String(ref mut key), length))) => (Step::Key(std::mem::take(key)), length),
+                _ => {
+                    let digits = after.find(']').map_or(after, |end| &after[..end]);
+                    (Step::Index(array_index(digits).ok_or("expected an index or a quoted key in brackets")?), digits.len())
+                }
+            };
+            steps.push(step);
+            rest = after[length..].strip_prefix(']').ok_or("expected ] after a key or index")?
+        } else {
+            return Ok((steps, rest))
+        }
+    }
+}
+
+fn segment(key : &mut String, options : FlattenOptions) -> Step {
+    let key = std::mem::take(key);
+    if options.brackets { Step::Key(key) } else { Step::Either(key) }
+}
+
+fn insert(node : &mut JsonNode, steps : &[Step], value : JsonNode) -> Result<(), &'static str> {
+    let (step, rest) = match steps.split_first() {
+        Some(split) => split,
+        None => {
+            match (&*node, &value) {
+                (JsonNode::Null, _) => *node = value,
+                // a container declared after some of what it holds
+                (JsonNode::Array(_), JsonNode::Array(empty)) if empty.is_empty() => (),
+                (JsonNode::Object(_), JsonNode::Object(empty)) if empty.is_empty() => (),
+                _ => return Err("another path already set this value")
+            }
+            return Ok(())
+        }
+    };
+    if *node == JsonNode::Null {
+        *node = match step {
+            Step::Index(_) => JsonNode::Array(Vec::new()),
+            Step::Either(token) if array_index(token).is_some() => JsonNode::Array(Vec::new()),
+            _ => JsonNode::Object(Object::default())
+        }
+    }
+    maybe_grow(|| match (node, step) {
+        (JsonNode::Object(o), Step::Key(key)) | (JsonNode::Object(o), Step::Either(key)) => {
+            insert(o.entry(key.clone()).or_insert(JsonNode::Null), rest, value)
+        },
+        (JsonNode::Array(a), Step::Index(index)) => insert_at(a, *index, rest, value),
+        (JsonNode::Array(a), Step::Either(token)) => match array_index(token) {
+            Some(index) => insert_at(a, index, rest, value),
+            None => Err("a key cannot go into an array")
+        },
+        (JsonNode::Array(_), Step::Key(_)) => Err("a key cannot go into an array"),
+        (JsonNode::Object(_), Step::Index(_)) => Err("an index cannot go into an object"),
+        _ => Err("another path set a value where this one needs a container")
+    })
+}
+
+fn insert_at(a : &mut Vec<JsonNode>, index : usize, rest : &[Step], value : JsonNode) -> Result<(), &'static str> {
+    if a.len() <= index {
+        match index.checked_add(1) {
+            Some(length) if index - a.len() <= MAX_INDEX_GAP => a.resize(length, JsonNode::Null),
+            _ => return Err("the index is too far past the end of the array")
+        }
+    }
+    insert(&mut a[index], rest, value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    const DOC : &str = "{\"a.b\":{\"c\":[1,{\"d\":null}]},\"e\":[],\"f\":{},\"10\":true}";
+
+    fn keys(pairs : &[(String, JsonNode)]) -> Vec<&str> {
+        pairs.iter().map(|(k, _)| k.as_str()).collect()
+    }
+
+    #[test]
+    fn test_flatten_separators() {
+        let doc = JsonNode::from_str(DOC).unwrap();
+        assert_eq!(keys(&flatten(&doc, Separator::Dot)), vec!["10", "a\\.b.c.0", "a\\.b.c.1.d", "e", "f"]);
+        assert_eq!(keys(&flatten(&doc, Separator::Pointer)), vec!["/10", "/a.b/c/0", "/a.b/c/1/d", "/e", "/f"]);
+        let bracketed = flatten_with(&doc, FlattenOptions::new(Separator::Dot).bracket_indices());
+        assert_eq!(keys(&bracketed), vec!["10", "a\\.b.c[0]", "a\\.b.c[1].d", "e", "f"]);
+        assert_eq!(flatten(&JsonNode::Number(1.0.into()), Separator::Dot), vec![(String::new(), JsonNode::Number(1.0.into()))]);
+    }
+
+    #[test]
+    fn test_round_trips() {
+        let doc = JsonNode::from_str(DOC).unwrap();
+        for options in &[FlattenOptions::new(Separator::Dot), FlattenOptions::new(Separator::Pointer), FlattenOptions::new(Separator::Dot).bracket_indices()] {
+            let mut pairs = flatten_with(&doc, *options);
+            pairs.reverse();
+            assert_eq!(unflatten(&pairs, *options).unwrap(), doc);
+        }
+    }
+
+    #[test]
+    fn test_unflatten_builds_and_rejects() {
+        let pairs = vec![("[2].x".to_string(), JsonNode::Boolean(true))];
+        let options = FlattenOptions::new(Separator::Dot).bracket_indices();
+        assert_eq!(unflatten(&pairs, options).unwrap(), JsonNode::from_str("[null,null,{\"x\":true}]").unwrap());
+
+        let pairs = vec![("a".to_string(), JsonNode::Number(1.0.into())), ("a.b".to_string(), JsonNode::Number(2.0.into()))];
+        let error = unflatten(&pairs, FlattenOptions::new(Separator::Dot)).unwrap_err();
+        assert_eq!(error.path, "a.b");
+        let pairs = vec![("a[x]".to_string(), JsonNode::Null)];
+        assert!(unflatten(&pairs, options).is_err());
+        let pairs = vec![("a".to_string(), JsonNode::Null)];
+        assert!(unflatten(&pairs, FlattenOptions::new(Separator::Pointer)).is_err());
+        let pairs = vec![(format!("/{}", usize::MAX), JsonNode::Null)];
+        assert_eq!(unflatten(&pairs, FlattenOptions::new(Separator::Pointer)).unwrap_err().path, pairs[0].0);
+    }
+
+    #[test]
+    fn test_dotted_pointer() {
+        let doc = JsonNode::from_str(DOC).unwrap();
+        for path in &["a\\.b.c[1].d", ".a\\.b.c.1.d"] {
+            assert_eq!(dotted_pointer(path).unwrap().to_string(), "/a.b/c/1/d");
+        }
+        assert_eq!(dotted_pointer("a\\.b.c[0]").unwrap().resolve(&doc), Some(&JsonNode::Number(1.0.into())));
+        assert!(dotted_pointer(".").unwrap().is_root());
+        assert!(dotted_pointer("c[x]").is_err());
+    }
+
+    #[test]
+    fn test_gron_round_trips() {
+        let doc = JsonNode::from_str("{\"users\":[{\"name\":\"a\",\"a b\":[]},null],\"$x_1\":{},\"9\":\"s;\"}").unwrap();
+        let statements = gron(&doc);
+        assert_eq!(statements, "json = {};\n\
+            json.$x_1 = {};\n\
+            json[\"9\"] = \"s;\";\n\
+            json.users = [];\n\
+            json.users[0] = {};\n\
+            json.users[0][\"a b\"] = [];\n\
+            json.users[0].name = \"a\";\n\
+            json.users[1] = null;\n");
+        assert_eq!(ungron(&statements).unwrap(), doc);
+        let reversed : Vec<&str> = statements.lines().rev().collect();
+        assert_eq!(ungron(&reversed.join("\n")).unwrap(), doc);
+        assert_eq!(gron(&JsonNode::from(1)), "json = 1;\n");
+    }
+
+    #[test]
+    fn test_ungron_fills_in_and_rejects() {
+        assert_eq!(ungron("json.a[1].b = true").unwrap(), JsonNode::from_str("{\"a\":[null,{\"b\":true}]}").unwrap());
+        let error = ungron("json.a = 1;\njson.a.b = 2;").unwrap_err();
+        assert_eq!(error.path, "json.a.b = 2;");
+        assert_eq!(ungron("json[x] = 1").unwrap_err().message, "expected an index or a quoted key in brackets");
+        assert_eq!(ungron("json.a 1").unwrap_err().message, "expected = after the path");
+        assert_eq!(ungron("json.a = tru;").unwrap_err().message, "expected a JSON value after =");
+        assert!(ungron("js.a = 1").is_err());
+        let too_far = "the index is too far past the end of the array";
+        assert_eq!(ungron("json[18446744073709551615] = 1;").unwrap_err().message, too_far);
+        assert_eq!(ungron("json[99999999999] = 1;").unwrap_err().message, too_far);
+        let gap = format!("json[0] = 0;\njson[{}] = 1;", MAX_INDEX_GAP + 1);
+        assert_eq!(ungron(&gap).unwrap().as_array().unwrap().len(), MAX_INDEX_GAP + 2);
+        assert_eq!(ungron(&format!("json[{}] = 1;", MAX_INDEX_GAP + 1)).unwrap_err().message, too_far);
+    }
+}
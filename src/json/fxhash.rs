@@ -0,0 +1,70 @@
+use std::hash::{BuildHasherDefault, Hasher};
+
+// The Firefox / rustc hash: a multiply and rotate per word. Much cheaper
+// than SipHash on short keys, but not resistant to crafted collisions.
+
+pub type FxBuildHasher = BuildHasherDefault<FxHasher>;
+
+const SEED : u64 = 0x51_7c_c1_b7_27_22_0a_95;
+
+#[derive(Default, Clone, Copy)]
+pub struct FxHasher {
+    hash: u64
+}
+
+impl FxHasher {
+    fn add(&mut self, word : u64) {
+        self.hash = (self.hash.rotate_left(5) ^ word).wrapping_mul(SEED);
+    }
+}
+
+impl Hasher for FxHasher {
+    fn write(&mut self, mut bytes : &[u8]) {
+        // whole words, then the tail in halves, so trailing zeros still count
+        while bytes.len() >= 8 {
+            self.add(u64::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7]]));
+            bytes = &bytes[8..]
+        }
+        if bytes.len() >= 4 {
+            self.add(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as u64);
+            bytes = &bytes[4..]
+        }
+        if bytes.len() >= 2 {
+            self.add(u16::from_le_bytes([bytes[0], bytes[1]]) as u64);
+            bytes = &bytes[2..]
+        }
+        if let Some(byte) = bytes.first() {
+            self.add(*byte as u64)
+        }
+    }
+
+    fn write_u8(&mut self, byte : u8) {
+        self.add(byte as u64)
+    }
+
+    fn write_usize(&mut self, value : usize) {
+        self.add(value as u64)
+    }
+
+    fn finish(&self) -> u64 {
+        self.hash
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::hash::BuildHasher;
+
+    fn hash(key : &str) -> u64 {
+        FxBuildHasher::default().hash_one(key)
+    }
+
+    #[test]
+    fn test_hash_is_deterministic_and_spreads() {
+        assert_eq!(hash("id"), hash("id"));
+        assert_ne!(hash("id"), hash("di"));
+        assert_ne!(hash("name"), hash("name\0"));
+        assert_ne!(hash(""), hash("a"));
+    }
+}
@@ -0,0 +1,47 @@
+use crate::json::JsonNode;
+
+/// Callbacks invoked while a document is parsed, for collecting metrics
+/// without a second traversal. Every method defaults to doing nothing.
+pub trait ParseHooks {
+    /// A complete value was parsed out of `size` bytes of input.
+    fn on_value(&mut self, _value : &JsonNode, _size : usize) {}
+
+    /// An object key was parsed; its value follows.
+    fn on_key(&mut self, _key : &str) {}
+
+    /// The whole document was parsed out of `size` bytes of input.
+    fn on_document_end(&mut self, _size : usize) {}
+}
+
+pub(crate) struct NoHooks;
+
+impl ParseHooks for NoHooks {}
+
+/// Ready-made hooks counting what a document contains.
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct ParseMetrics {
+    pub values: usize,
+    pub containers: usize,
+    pub keys: usize,
+    pub documents: usize,
+    pub bytes: usize
+}
+
+impl ParseHooks for ParseMetrics {
+    fn on_value(&mut self, value : &JsonNode, _size : usize) {
+        self.values += 1;
+        match value {
+            JsonNode::Array(_) | JsonNode::Object(_) => self.containers += 1,
+            _ => ()
+        }
+    }
+
+    fn on_key(&mut self, _key : &str) {
+        self.keys += 1
+    }
+
+    fn on_document_end(&mut self, size : usize) {
+        self.documents += 1;
+        self.bytes += size
+    }
+}
@@ -0,0 +1,68 @@
+use std::collections::HashMap;
+use crate::json::{maybe_grow, JsonNode};
+use crate::json::pointer::escape_token;
+
+/// Maps every JSON Pointer (RFC 6901) in a document to the node it refers to,
+/// so repeated lookups are a single hash probe instead of a walk from the root.
+#[derive(Debug)]
+pub struct JsonIndex<'a> {
+    nodes: HashMap<String, &'a JsonNode>
+}
+
+impl<'a> JsonIndex<'a> {
+    pub fn build(root : &'a JsonNode) -> JsonIndex<'a> {
+        let mut index = JsonIndex { nodes: HashMap::new() };
+        index.add(String::new(), root);
+        index
+    }
+
+    pub fn get(&self, pointer : &str) -> Option<&'a JsonNode> {
+        self.nodes.get(pointer).cloned()
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// All indexed pointers, in no particular order.
+    pub fn pointers(&self) -> impl Iterator<Item = &str> {
+        self.nodes.keys().map(String::as_str)
+    }
+
+    fn add(&mut self, path : String, node : &'a JsonNode) {
+        maybe_grow(|| {
+            match node {
+                JsonNode::Array(a) => for (i, child) in a.iter().enumerate() {
+                    self.add(format!("{}/{}", path, i), child)
+                },
+                JsonNode::Object(o) => for (key, child) in o {
+                    self.add(format!("{}/{}", path, escape_token(key)), child)
+                },
+                _ => ()
+            }
+            self.nodes.insert(path, node);
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_every_node_is_indexed() {
+        let root = JsonNode::from_str("{\"a\":[1,{\"b/c\":null}],\"~\":true}").unwrap();
+        let index = JsonIndex::build(&root);
+        assert_eq!(index.len(), 6);
+        assert_eq!(index.get(""), Some(&root));
+        assert_eq!(index.get("/a/0"), Some(&JsonNode::Number(1.0.into())));
+        assert_eq!(index.get("/a/1/b~1c"), Some(&JsonNode::Null));
+        assert_eq!(index.get("/~0"), Some(&JsonNode::Boolean(true)));
+        assert_eq!(index.get("/a/2"), None);
+    }
+}
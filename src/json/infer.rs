@@ -0,0 +1,182 @@
+use std::collections::{BTreeMap, BTreeSet};
+use crate::json::{maybe_grow, JsonNode, Object};
+
+/// Strings with at most this many distinct values become an `enum`.
+pub const MAX_ENUM : usize = 5;
+
+const TYPES : [&str; 7] = ["null", "boolean", "integer", "number", "string", "array", "object"];
+
+// what the samples seen at one location have in common
+#[derive(Default)]
+struct Shape {
+    types: BTreeSet<usize>,
+    strings: usize,
+    // distinct strings, until there are too many for an enum
+    values: Option<BTreeSet<String>>,
+    items: Option<Box<Shape>>,
+    objects: usize,
+    properties: BTreeMap<String, (usize, Shape)>
+}
+
+impl Shape {
+    // moves the shapes nested in this one into `pending`
+    fn take_nested(&mut self, pending : &mut Vec<Shape>) {
+        pending.extend(self.items.take().map(|items| *items));
+        pending.extend(std::mem::take(&mut self.properties).into_values().map(|(_, shape)| shape))
+    }
+}
+
+// shapes nest as deep as the samples, so they come apart without recursion
+impl Drop for Shape {
+    fn drop(&mut self) {
+        let mut pending = Vec::new();
+        self.take_nested(&mut pending);
+        while let Some(mut shape) = pending.pop() {
+            shape.take_nested(&mut pending)
+        }
+    }
+}
+
+/// A draft-07 JSON Schema that every sample validates against. Types seen
+/// at the same place become a union (integers fold into numbers when both
+/// occur), object members present in every sample are `required`, and
+/// strings repeating at most `MAX_ENUM` distinct values become an `enum`.
+pub fn schema<'a, I>(samples : I) -> JsonNode
+    where I : IntoIterator<Item = &'a JsonNode>
+{
+    let mut shape = Shape::default();
+    for sample in samples {
+        shape.add(sample)
+    }
+    let mut out = match shape.to_schema() {
+        JsonNode::Object(ref mut o) => std::mem::take(o),
+        _ => Object::default()
+    };
+    out.insert("$schema".to_string(), JsonNode::String("http://json-schema.org/draft-07/schema#".to_string()));
+    JsonNode::Object(out)
+}
+
+impl Shape {
+    fn add(&mut self, node : &JsonNode) {
+        let kind = match node {
+            JsonNode::Null => 0,
+            JsonNode::Boolean(_) => 1,
+            JsonNode::Number(n) if n.as_f64().fract() == 0.0 => 2,
+            JsonNode::Number(_) => 3,
+            JsonNode::String(_) => 4,
+            JsonNode::Array(_) => 5,
+            JsonNode::Object(_) => 6
+        };
+        self.types.insert(kind);
+        maybe_grow(|| match node {
+            JsonNode::String(s) => {
+                if self.strings == 0 {
+                    self.values = Some(BTreeSet::new())
+                }
+                self.strings += 1;
+                if let Some(values) = &mut self.values {
+                    values.insert(s.clone());
+                    if values.len() > MAX_ENUM {
+                        self.values = None
+                    }
+                }
+            },
+            JsonNode::Array(a) => {
+                let items = self.items.get_or_insert_with(Box::default);
+                for item in a {
+                    items.add(item)
+                }
+            },
+            JsonNode::Object(o) => {
+                self.objects += 1;
+                for (key, value) in o {
+                    let (seen, shape) = self.properties.entry(key.clone()).or_default();
+                    *seen += 1;
+                    shape.add(value)
+                }
+            },
+            _ => ()
+        })
+    }
+
+    fn to_schema(&self) -> JsonNode {
+        maybe_grow(|| {
+            let mut out = Object::default();
+            let mut types : Vec<JsonNode> = self.types.iter()
+                .filter(|kind| !(**kind == 2 && self.types.contains(&3)))
+                .map(|kind| JsonNode::String(TYPES[*kind].to_string()))
+                .collect();
+            match types.len() {
+                0 => (),
+                1 => {
+                    out.insert("type".to_string(), types.remove(0));
+                },
+                _ => {
+                    out.insert("type".to_string(), JsonNode::Array(types));
+                }
+            }
+            // an enum would reject the other types
+            if let (Some(values), 1) = (&self.values, self.types.len()) {
+                if values.len() < self.strings {
+                    out.insert("enum".to_string(), JsonNode::Array(values.iter().map(|v| JsonNode::String(v.clone())).collect()));
+                }
+            }
+            if let Some(items) = &self.items {
+                if !items.types.is_empty() {
+                    out.insert("items".to_string(), items.to_schema());
+                }
+            }
+            if self.objects > 0 {
+                let properties = self.properties.iter().map(|(key, (_, shape))| (key.clone(), shape.to_schema())).collect();
+                out.insert("properties".to_string(), JsonNode::Object(properties));
+                let required : Vec<JsonNode> = self.properties.iter()
+                    .filter(|(_, (seen, _))| *seen == self.objects)
+                    .map(|(key, _)| JsonNode::String(key.clone()))
+                    .collect();
+                if !required.is_empty() {
+                    out.insert("required".to_string(), JsonNode::Array(required));
+                }
+            }
+            JsonNode::Object(out)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+    use crate::json::schema::Schema;
+
+    fn samples(texts : &[&str]) -> Vec<JsonNode> {
+        texts.iter().map(|text| JsonNode::from_str(text).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_infers_unions_required_and_enums() {
+        let samples = samples(&[
+            "{\"id\":1,\"level\":\"warn\",\"tags\":[\"a\"],\"at\":null}",
+            "{\"id\":2.5,\"level\":\"info\",\"tags\":[],\"at\":\"mon\"}",
+            "{\"id\":3,\"level\":\"warn\"}"
+        ]);
+        let inferred = schema(&samples);
+        let property = |name : &str| inferred.get_all(&format!("/properties/{}", name)).unwrap()[0].1.clone();
+        assert_eq!(property("id"), JsonNode::from_str("{\"type\":\"number\"}").unwrap());
+        assert_eq!(property("level"), JsonNode::from_str("{\"type\":\"string\",\"enum\":[\"info\",\"warn\"]}").unwrap());
+        assert_eq!(property("tags"), JsonNode::from_str("{\"type\":\"array\",\"items\":{\"type\":\"string\"}}").unwrap());
+        assert_eq!(property("at"), JsonNode::from_str("{\"type\":[\"null\",\"string\"]}").unwrap());
+        assert_eq!(inferred.get_all("/required").unwrap()[0].1, &JsonNode::from_str("[\"id\",\"level\"]").unwrap());
+
+        let compiled = Schema::compile(&inferred).unwrap();
+        assert!(samples.iter().all(|sample| compiled.is_valid(sample)));
+        assert!(!compiled.is_valid(&JsonNode::from_str("{\"id\":1,\"level\":\"debug\"}").unwrap()));
+    }
+
+    #[test]
+    fn test_high_cardinality_and_no_samples() {
+        let samples = samples(&["[\"a\",\"b\",\"c\",\"d\",\"e\",\"f\",\"a\"]", "[1]"]);
+        assert_eq!(schema(&samples).get_all("/items").unwrap()[0].1, &JsonNode::from_str("{\"type\":[\"integer\",\"string\"]}").unwrap());
+        assert_eq!(schema(&samples[..1]).get_all("/items").unwrap()[0].1, &JsonNode::from_str("{\"type\":\"string\"}").unwrap());
+        assert_eq!(schema(&[]), JsonNode::from_str("{\"$schema\":\"http://json-schema.org/draft-07/schema#\"}").unwrap());
+    }
+}
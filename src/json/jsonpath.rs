@@ -0,0 +1,934 @@
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::fmt;
+use std::io::Read;
+use crate::json::{maybe_grow, JsonNode};
+use crate::json::events::{EventError, EventReader, JsonEvent};
+use crate::json::pointer::JsonPointer;
+
+/// A compiled JSONPath query (RFC 9535). Supported: name, wildcard, index and
+/// slice selectors, descendant segments, and filters with comparisons, `&&`,
+/// `||`, `!`, existence tests and the `length`, `count` and `value` functions.
+#[derive(PartialEq, Debug, Clone)]
+pub struct JsonPath {
+    query: Query
+}
+
+#[derive(PartialEq, Debug)]
+pub struct JsonPathError {
+    pub position: usize,
+    pub message: &'static str
+}
+
+impl fmt::Display for JsonPathError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid JSONPath at character {}: {}", self.position, self.message)
+    }
+}
+
+#[derive(Debug)]
+pub enum StreamError {
+    /// The query needs the whole document: negative indices or slice bounds, or `$` inside a filter.
+    Unsupported(&'static str),
+    Input(EventError)
+}
+
+impl fmt::Display for StreamError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StreamError::Unsupported(reason) => write!(f, "query cannot be streamed: {}", reason),
+            StreamError::Input(reason) => write!(f, "{}", reason)
+        }
+    }
+}
+
+impl From<EventError> for StreamError {
+    fn from(reason : EventError) -> StreamError {
+        StreamError::Input(reason)
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+struct Query {
+    relative: bool,
+    segments: Vec<Segment>
+}
+
+#[derive(PartialEq, Debug, Clone)]
+enum Segment {
+    Child(Vec<Selector>),
+    Descendant(Vec<Selector>)
+}
+
+#[derive(PartialEq, Debug, Clone)]
+enum Selector {
+    Name(String),
+    Wildcard,
+    Index(i64),
+    Slice(Option<i64>, Option<i64>, Option<i64>),
+    Filter(Expr)
+}
+
+#[derive(PartialEq, Debug, Clone)]
+enum Expr {
+    Or(Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Compare(Operand, Comparison, Operand),
+    Exists(Query)
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual
+}
+
+#[derive(PartialEq, Debug, Clone)]
+enum Operand {
+    Literal(JsonNode),
+    Query(Query),
+    Length(Box<Operand>),
+    Count(Query),
+    Value(Query)
+}
+
+/// Selects the nodes matching `expression` in `root`.
+pub fn select<'a>(root : &'a JsonNode, expression : &str) -> Result<Vec<&'a JsonNode>, JsonPathError> {
+    Ok(JsonPath::parse(expression)?.select(root))
+}
+
+impl JsonPath {
+    pub fn parse(expression : &str) -> Result<JsonPath, JsonPathError> {
+        let mut parser = Parser { chars: expression.chars().collect(), pos: 0 };
+        if parser.peek() != Some('$') {
+            return Err(parser.error("query must start with $"))
+        }
+        let query = parser.query()?;
+        if parser.pos < parser.chars.len() {
+            return Err(parser.error("unexpected character"))
+        }
+        Ok(JsonPath { query })
+    }
+
+    pub fn select<'a>(&self, root : &'a JsonNode) -> Vec<&'a JsonNode> {
+        evaluate(&self.query, root, root)
+    }
+
+    /// Like `select`, pairing each node with its normalized path, the
+    /// canonical query for just that node, such as `$['store']['book'][0]`.
+    pub fn select_located<'a>(&self, root : &'a JsonNode) -> Vec<(String, &'a JsonNode)> {
+        locate(&self.query, root)
+    }
+
+    /// Evaluates the query over one document read from `source`, calling
+    /// `callback` with the pointer and value of each match as soon as it is
+    /// complete. Matches come in document order, a value before the matches
+    /// inside it; only matched values and the elements filters look at are
+    /// materialized. Containers may nest `DEFAULT_MAX_DEPTH` deep. Returns the
+    /// number of matches.
+    pub fn stream<R, F>(&self, source : R, mut callback : F) -> Result<usize, StreamError>
+        where R : Read, F : FnMut(&str, JsonNode)
+    {
+        for segment in &self.query.segments {
+            let selectors = match segment {
+                Segment::Child(selectors) | Segment::Descendant(selectors) => selectors
+            };
+            for selector in selectors {
+                match selector {
+                    Selector::Index(i) if *i < 0 => return Err(StreamError::Unsupported("negative index")),
+                    Selector::Slice(start, end, step) if start.unwrap_or(0) < 0 || end.is_some_and(|end| end < 0) || step.unwrap_or(1) < 1 =>
+                        return Err(StreamError::Unsupported("slice counting from the end")),
+                    Selector::Filter(expr) if uses_root(expr) => return Err(StreamError::Unsupported("$ inside a filter")),
+                    _ => ()
+                }
+            }
+        }
+        let mut reader = EventReader::new(source);
+        let mut streamer = Streamer { segments: &self.query.segments, callback: &mut callback, matches: 0 };
+        if let Some(first) = reader.next_event()? {
+            streamer.visit(&mut reader, &mut JsonPointer::root(), first, vec![0])?
+        }
+        Ok(streamer.matches)
+    }
+}
+
+fn uses_root(expr : &Expr) -> bool {
+    let query_uses_root = |query : &Query| !query.relative || query.segments.iter().any(|segment| match segment {
+        Segment::Child(selectors) | Segment::Descendant(selectors) => selectors.iter().any(|selector| match selector {
+            Selector::Filter(expr) => uses_root(expr),
+            _ => false
+        })
+    });
+    let operand_uses_root = |operand : &Operand| match operand {
+        Operand::Literal(_) => false,
+        Operand::Query(query) | Operand::Count(query) | Operand::Value(query) => query_uses_root(query),
+        Operand::Length(inner) => match inner.as_ref() {
+            Operand::Query(query) | Operand::Count(query) | Operand::Value(query) => query_uses_root(query),
+            _ => false
+        }
+    };
+    match expr {
+        Expr::Or(a, b) | Expr::And(a, b) => uses_root(a) || uses_root(b),
+        Expr::Not(a) => uses_root(a),
+        Expr::Compare(left, _, right) => operand_uses_root(left) || operand_uses_root(right),
+        Expr::Exists(query) => query_uses_root(query)
+    }
+}
+
+// Runs the query's segments like an NFA: a state is the number of segments
+// matched so far, and a descendant segment stays in its state while descending.
+struct Streamer<'q, 'f> {
+    segments: &'q [Segment],
+    callback: &'f mut dyn FnMut(&str, JsonNode),
+    matches: usize
+}
+
+impl<'q, 'f> Streamer<'q, 'f> {
+    // evaluates the value `event` starts, keeping the containers open around
+    // the current value on a stack of its own rather than recursing
+    fn visit<R : Read>(&mut self, reader : &mut EventReader<R>, path : &mut JsonPointer, event : JsonEvent, states : Vec<usize>) -> Result<(), StreamError> {
+        let mut open : Vec<Visit> = Vec::new();
+        let (mut event, mut states) = (event, states);
+        loop {
+            if states.contains(&self.segments.len()) {
+                let value = reader.read_value(event)?;
+                self.walk(&value, path, &states);
+                if !open.is_empty() {
+                    path.pop();
+                }
+            } else {
+                let filtered = self.needs_values(&states);
+                match event {
+                    JsonEvent::StartArray => open.push(Visit { states, filtered, index: Some(0) }),
+                    JsonEvent::StartObject => open.push(Visit { states, filtered, index: None }),
+                    other => {
+                        reader.skip_value(other)?;
+                        if !open.is_empty() {
+                            path.pop();
+                        }
+                    }
+                }
+            }
+            // on to the next value worth descending into, closing the containers that end first
+            (event, states) = loop {
+                let top = match open.last_mut() {
+                    Some(top) => top,
+                    None => return Ok(())
+                };
+                let (member, key, index) = match (next(reader)?, top.index) {
+                    (JsonEvent::EndArray, Some(_)) | (JsonEvent::EndObject, None) => {
+                        open.pop();
+                        if !open.is_empty() {
+                            path.pop();
+                        }
+                        continue
+                    },
+                    (JsonEvent::Key(key), None) => (next(reader)?, Some(key), 0),
+                    (element, Some(i)) => {
+                        top.index = Some(i + 1);
+                        (element, None, i)
+                    },
+                    _ => return Err(EventError::Syntax(reader.offset()).into())
+                };
+                let token = match &key {
+                    Some(key) => {
+                        path.push(key.as_str());
+                        Token::Name(key)
+                    },
+                    None => {
+                        path.push(index.to_string());
+                        Token::Index(index)
+                    }
+                };
+                if top.filtered {
+                    let value = reader.read_value(member)?;
+                    let next = self.advance(&top.states, token, Some(&value));
+                    self.walk(&value, path, &next);
+                    path.pop();
+                    continue
+                }
+                let next = self.advance(&top.states, token, None);
+                if next.is_empty() {
+                    reader.skip_value(member)?;
+                    path.pop();
+                    continue
+                }
+                break (member, next)
+            }
+        }
+    }
+
+    // the same evaluation over a value that is already materialized
+    fn walk(&mut self, value : &JsonNode, path : &mut JsonPointer, states : &[usize]) {
+        maybe_grow(|| self.walk_here(value, path, states))
+    }
+
+    fn walk_here(&mut self, value : &JsonNode, path : &mut JsonPointer, states : &[usize]) {
+        if states.is_empty() {
+            return
+        }
+        if states.contains(&self.segments.len()) {
+            let pointer = path.to_string();
+            (self.callback)(&pointer, value.clone());
+            self.matches += 1
+        }
+        match value {
+            JsonNode::Array(a) => for (i, child) in a.iter().enumerate() {
+                let next = self.advance(states, Token::Index(i), Some(child));
+                path.push(i.to_string());
+                self.walk(child, path, &next);
+                path.pop();
+            },
+            JsonNode::Object(o) => {
+                let mut keys : Vec<&String> = o.keys().collect();
+                keys.sort();
+                for key in keys {
+                    let next = self.advance(states, Token::Name(key), Some(&o[key]));
+                    path.push(key.as_str());
+                    self.walk(&o[key], path, &next);
+                    path.pop();
+                }
+            },
+            _ => ()
+        }
+    }
+
+    fn needs_values(&self, states : &[usize]) -> bool {
+        states.iter().filter_map(|s| self.segments.get(*s)).any(|segment| match segment {
+            Segment::Child(selectors) | Segment::Descendant(selectors) => selectors.iter().any(|s| matches!(s, Selector::Filter(_)))
+        })
+    }
+
+    fn advance(&self, states : &[usize], token : Token, value : Option<&JsonNode>) -> Vec<usize> {
+        let mut next = Vec::new();
+        for s in states.iter().filter(|s| **s < self.segments.len()) {
+            let (selectors, descendant) = match &self.segments[*s] {
+                Segment::Child(selectors) => (selectors, false),
+                Segment::Descendant(selectors) => (selectors, true)
+            };
+            if descendant {
+                next.push(*s)
+            }
+            if selectors.iter().any(|selector| token_matches(selector, token, value)) {
+                next.push(s + 1)
+            }
+        }
+        next.sort_unstable();
+        next.dedup();
+        next
+    }
+}
+
+// a container being evaluated: the states its members start from, whether
+// a filter needs them materialized, and for an array the index of its next element
+struct Visit {
+    states: Vec<usize>,
+    filtered: bool,
+    index: Option<usize>
+}
+
+#[derive(Clone, Copy)]
+enum Token<'k> {
+    Name(&'k str),
+    Index(usize)
+}
+
+fn token_matches(selector : &Selector, token : Token, value : Option<&JsonNode>) -> bool {
+    match (selector, token) {
+        (Selector::Name(name), Token::Name(key)) => name == key,
+        (Selector::Wildcard, _) => true,
+        (Selector::Index(i), Token::Index(index)) => *i as usize == index,
+        (Selector::Slice(start, end, step), Token::Index(index)) => {
+            let (start, step, index) = (start.unwrap_or(0), step.unwrap_or(1), index as i64);
+            index >= start && end.is_none_or(|end| index < end) && (index - start) % step == 0
+        },
+        (Selector::Filter(expr), _) => value.is_some_and(|value| test(expr, value, value)),
+        _ => false
+    }
+}
+
+fn next<R : Read>(reader : &mut EventReader<R>) -> Result<JsonEvent, EventError> {
+    reader.next_event()?.ok_or_else(|| EventError::UnexpectedEnd(reader.offset()))
+}
+
+fn evaluate<'a>(query : &Query, root : &'a JsonNode, current : &'a JsonNode) -> Vec<&'a JsonNode> {
+    let mut nodes = vec![if query.relative { current } else { root }];
+    for segment in &query.segments {
+        let mut selected = Vec::new();
+        let mut emit = |_ : &[Token], _ : Token, child : &'a JsonNode| selected.push(child);
+        for node in nodes {
+            match segment {
+                Segment::Child(selectors) => apply_selectors(selectors, root, &[], node, &mut emit),
+                Segment::Descendant(selectors) => descend(selectors, root, &mut Vec::new(), node, &mut emit)
+            }
+        }
+        nodes = selected
+    }
+    nodes
+}
+
+// like `evaluate` from the root, with the normalized path of each node
+fn locate<'a>(query : &Query, root : &'a JsonNode) -> Vec<(String, &'a JsonNode)> {
+    let mut nodes = vec![("$".to_string(), root)];
+    for segment in &query.segments {
+        let mut selected = Vec::new();
+        for (location, node) in nodes {
+            let mut emit = |path : &[Token], last : Token, child : &'a JsonNode| {
+                let mut location = location.clone();
+                for token in path.iter().chain(Some(&last)) {
+                    push_normalized(&mut location, *token)
+                }
+                selected.push((location, child))
+            };
+            match segment {
+                Segment::Child(selectors) => apply_selectors(selectors, root, &[], node, &mut emit),
+                Segment::Descendant(selectors) => descend(selectors, root, &mut Vec::new(), node, &mut emit)
+            }
+        }
+        nodes = selected
+    }
+    nodes
+}
+
+// appends the normalized path segment (RFC 9535, section 2.7) for `token`
+fn push_normalized(location : &mut String, token : Token) {
+    match token {
+        Token::Index(i) => location.push_str(&format!("[{}]", i)),
+        Token::Name(name) => {
+            location.push_str("['");
+            for c in name.chars() {
+                match c {
+                    '\'' => location.push_str("\\'"),
+                    '\\' => location.push_str("\\\\"),
+                    '\u{8}' => location.push_str("\\b"),
+                    '\u{c}' => location.push_str("\\f"),
+                    '\n' => location.push_str("\\n"),
+                    '\r' => location.push_str("\\r"),
+                    '\t' => location.push_str("\\t"),
+                    c if c < ' ' => location.push_str(&format!("\\u{:04x}", c as u32)),
+                    c => location.push(c)
+                }
+            }
+            location.push_str("']")
+        }
+    }
+}
+
+fn descend<'a, 'p, F>(selectors : &[Selector], root : &'a JsonNode, path : &mut Vec<Token<'p>>, node : &'a JsonNode, emit : &mut F)
+    where F : FnMut(&[Token], Token, &'a JsonNode), 'a : 'p
+{
+    maybe_grow(|| {
+        apply_selectors(selectors, root, path, node, emit);
+        for (token, child) in children(node) {
+            path.push(token);
+            descend(selectors, root, path, child, emit);
+            path.pop();
+        }
+    })
+}
+
+fn children(node : &JsonNode) -> Vec<(Token<'_>, &JsonNode)> {
+    match node {
+        JsonNode::Array(a) => a.iter().enumerate().map(|(i, item)| (Token::Index(i), item)).collect(),
+        JsonNode::Object(o) => o.iter().map(|(key, value)| (Token::Name(key), value)).collect(),
+        _ => Vec::new()
+    }
+}
+
+// hands each node that `selectors` select among the children of `node` to
+// `emit`, along with `path`, the steps to `node`, and the step from it
+fn apply_selectors<'a, F>(selectors : &[Selector], root : &'a JsonNode, path : &[Token], node : &'a JsonNode, emit : &mut F)
+    where F : FnMut(&[Token], Token, &'a JsonNode)
+{
+    for selector in selectors {
+        match (selector, node) {
+            (Selector::Name(name), JsonNode::Object(o)) => if let Some(value) = o.get(name) {
+                emit(path, Token::Name(name), value)
+            },
+            (Selector::Wildcard, _) => for (token, child) in children(node) {
+                emit(path, token, child)
+            },
+            (Selector::Index(index), JsonNode::Array(a)) => {
+                let index = if *index < 0 { a.len() as i64 + index } else { *index };
+                if let Some(item) = a.get(index as usize).filter(|_| index >= 0) {
+                    emit(path, Token::Index(index as usize), item)
+                }
+            },
+            (Selector::Slice(start, end, step), JsonNode::Array(a)) => {
+                for i in slice_indices(a.len() as i64, *start, *end, step.unwrap_or(1)) {
+                    emit(path, Token::Index(i), &a[i])
+                }
+            },
+            (Selector::Filter(expr), _) => for (token, child) in children(node) {
+                if test(expr, root, child) {
+                    emit(path, token, child)
+                }
+            },
+            _ => ()
+        }
+    }
+}
+
+fn slice_indices(len : i64, start : Option<i64>, end : Option<i64>, step : i64) -> Vec<usize> {
+    let normalize = |i : i64| if i < 0 { len + i } else { i };
+    let mut indices = Vec::new();
+    if step > 0 {
+        let lower = start.map(normalize).unwrap_or(0).clamp(0, len);
+        let upper = end.map(normalize).unwrap_or(len).clamp(0, len);
+        let mut i = lower;
+        while i < upper {
+            indices.push(i as usize);
+            i = match i.checked_add(step) {
+                Some(next) => next,
+                None => break
+            }
+        }
+    } else if step < 0 {
+        let upper = start.map(normalize).unwrap_or(len - 1).clamp(-1, len - 1);
+        let lower = end.map(normalize).unwrap_or(-len - 1).clamp(-1, len - 1);
+        let mut i = upper;
+        while lower < i {
+            indices.push(i as usize);
+            i = match i.checked_add(step) {
+                Some(next) => next,
+                None => break
+            }
+        }
+    }
+    indices
+}
+
+fn test(expr : &Expr, root : &JsonNode, current : &JsonNode) -> bool {
+    match expr {
+        Expr::Or(a, b) => test(a, root, current) || test(b, root, current),
+        Expr::And(a, b) => test(a, root, current) && test(b, root, current),
+        Expr::Not(a) => !test(a, root, current),
+        Expr::Exists(query) => !evaluate(query, root, current).is_empty(),
+        Expr::Compare(left, comparison, right) => {
+            let left = value(left, root, current);
+            let right = value(right, root, current);
+            compare(left.as_deref(), *comparison, right.as_deref())
+        }
+    }
+}
+
+fn value<'a>(operand : &'a Operand, root : &'a JsonNode, current : &'a JsonNode) -> Option<Cow<'a, JsonNode>> {
+    match operand {
+        Operand::Literal(node) => Some(Cow::Borrowed(node)),
+        Operand::Query(query) | Operand::Value(query) => {
+            let nodes = evaluate(query, root, current);
+            if nodes.len() == 1 { Some(Cow::Borrowed(nodes[0])) } else { None }
+        },
+        Operand::Count(query) => Some(Cow::Owned(JsonNode::Number(evaluate(query, root, current).len().into()))),
+        Operand::Length(inner) => match value(inner, root, current)?.as_ref() {
+            JsonNode::String(s) => Some(s.chars().count()),
+            JsonNode::Array(a) => Some(a.len()),
+            JsonNode::Object(o) => Some(o.len()),
+            _ => None
+        }.map(|n| Cow::Owned(JsonNode::Number(n.into())))
+    }
+}
+
+fn compare(left : Option<&JsonNode>, comparison : Comparison, right : Option<&JsonNode>) -> bool {
+    let less = |a : Option<&JsonNode>, b : Option<&JsonNode>| match (a, b) {
+        (Some(JsonNode::Number(a)), Some(JsonNode::Number(b))) => a.partial_cmp(b) == Some(Ordering::Less),
+        (Some(JsonNode::String(a)), Some(JsonNode::String(b))) => a < b,
+        _ => false
+    };
+    match comparison {
+        Comparison::Equal => left == right,
+        Comparison::NotEqual => left != right,
+        Comparison::Less => less(left, right),
+        Comparison::LessOrEqual => less(left, right) || left == right,
+        Comparison::Greater => less(right, left),
+        Comparison::GreaterOrEqual => less(right, left) || left == right
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize
+}
+
+impl Parser {
+    fn error(&self, message : &'static str) -> JsonPathError {
+        JsonPathError { position: self.pos, message }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).cloned()
+    }
+
+    fn looking_at(&self, text : &str) -> bool {
+        text.chars().enumerate().all(|(i, c)| self.chars.get(self.pos + i) == Some(&c))
+    }
+
+    fn eat(&mut self, text : &str) -> bool {
+        let found = self.looking_at(text);
+        if found {
+            self.pos += text.chars().count()
+        }
+        found
+    }
+
+    fn expect(&mut self, text : &str, message : &'static str) -> Result<(), JsonPathError> {
+        self.skip_blank();
+        if self.eat(text) { Ok(()) } else { Err(self.error(message)) }
+    }
+
+    fn skip_blank(&mut self) {
+        while let Some(' ') | Some('\t') | Some('\n') | Some('\r') = self.peek() {
+            self.pos += 1
+        }
+    }
+
+    fn query(&mut self) -> Result<Query, JsonPathError> {
+        let relative = self.peek() == Some('@');
+        self.pos += 1;
+        let mut segments = Vec::new();
+        loop {
+            let start = self.pos;
+            self.skip_blank();
+            if self.eat("..") {
+                segments.push(Segment::Descendant(self.segment_selectors()?))
+            } else if self.eat(".") {
+                segments.push(Segment::Child(self.segment_selectors()?))
+            } else if self.peek() == Some('[') {
+                segments.push(Segment::Child(self.bracket()?))
+            } else {
+                self.pos = start;
+                return Ok(Query { relative, segments })
+            }
+        }
+    }
+
+    // the selectors after `.` or `..`: a member name, `*` or a bracketed list
+    fn segment_selectors(&mut self) -> Result<Vec<Selector>, JsonPathError> {
+        if self.eat("*") {
+            return Ok(vec![Selector::Wildcard])
+        }
+        if self.peek() == Some('[') {
+            return self.bracket()
+        }
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if c.is_alphanumeric() || c == '_' || !c.is_ascii() { self.pos += 1 } else { break }
+        }
+        if self.pos == start || self.chars[start].is_ascii_digit() {
+            return Err(self.error("expected a member name"))
+        }
+        Ok(vec![Selector::Name(self.chars[start..self.pos].iter().collect())])
+    }
+
+    fn bracket(&mut self) -> Result<Vec<Selector>, JsonPathError> {
+        self.pos += 1;
+        let mut selectors = vec![self.selector()?];
+        loop {
+            self.skip_blank();
+            if self.eat(",") {
+                selectors.push(self.selector()?)
+            } else if self.eat("]") {
+                return Ok(selectors)
+            } else {
+                return Err(self.error("expected , or ]"))
+            }
+        }
+    }
+
+    fn selector(&mut self) -> Result<Selector, JsonPathError> {
+        self.skip_blank();
+        match self.peek() {
+            Some('\'') | Some('"') => Ok(Selector::Name(self.string()?)),
+            Some('*') => {
+                self.pos += 1;
+                Ok(Selector::Wildcard)
+            },
+            Some('?') => {
+                self.pos += 1;
+                Ok(Selector::Filter(self.or()?))
+            },
+            _ => {
+                let start = self.integer()?;
+                self.skip_blank();
+                if !self.eat(":") {
+                    return start.map(Selector::Index).ok_or_else(|| self.error("expected a selector"))
+                }
+                let end = self.integer()?;
+                self.skip_blank();
+                let step = if self.eat(":") { self.integer()? } else { None };
+                Ok(Selector::Slice(start, end, step))
+            }
+        }
+    }
+
+    fn integer(&mut self) -> Result<Option<i64>, JsonPathError> {
+        self.skip_blank();
+        let start = self.pos;
+        self.eat("-");
+        while let Some('0'..='9') = self.peek() {
+            self.pos += 1
+        }
+        if self.pos == start {
+            return Ok(None)
+        }
+        let text : String = self.chars[start..self.pos].iter().collect();
+        text.parse().map(Some).map_err(|_| self.error("expected an integer"))
+    }
+
+    fn string(&mut self) -> Result<String, JsonPathError> {
+        let quote = self.chars[self.pos];
+        self.pos += 1;
+        let mut out = String::new();
+        loop {
+            let c = self.peek().ok_or_else(|| self.error("unterminated string"))?;
+            self.pos += 1;
+            if c == quote {
+                return Ok(out)
+            }
+            if c != '\\' {
+                out.push(c);
+                continue
+            }
+            let escaped = self.peek().ok_or_else(|| self.error("unterminated string"))?;
+            self.pos += 1;
+            out.push(match escaped {
+                'b' => '\u{8}',
+                'f' => '\u{c}',
+                'n' => '\n',
+                'r' => '\r',
+                't' => '\t',
+                'u' => {
+                    let hex : String = self.chars.iter().skip(self.pos).take(4).collect();
+                    self.pos += 4;
+                    u32::from_str_radix(&hex, 16).ok().and_then(std::char::from_u32)
+                        .ok_or_else(|| self.error("invalid unicode escape"))?
+                },
+                '\\' | '/' | '\'' | '"' => escaped,
+                _ => return Err(self.error("invalid escape"))
+            })
+        }
+    }
+
+    fn or(&mut self) -> Result<Expr, JsonPathError> {
+        let mut expr = self.and()?;
+        loop {
+            self.skip_blank();
+            if !self.eat("||") {
+                return Ok(expr)
+            }
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?))
+        }
+    }
+
+    fn and(&mut self) -> Result<Expr, JsonPathError> {
+        let mut expr = self.basic()?;
+        loop {
+            self.skip_blank();
+            if !self.eat("&&") {
+                return Ok(expr)
+            }
+            expr = Expr::And(Box::new(expr), Box::new(self.basic()?))
+        }
+    }
+
+    fn basic(&mut self) -> Result<Expr, JsonPathError> {
+        self.skip_blank();
+        if self.eat("!") {
+            return Ok(Expr::Not(Box::new(self.basic()?)))
+        }
+        if self.eat("(") {
+            let expr = self.or()?;
+            self.expect(")", "expected )")?;
+            return Ok(expr)
+        }
+
+        let left = self.operand()?;
+        self.skip_blank();
+        let comparison = [
+            ("==", Comparison::Equal), ("!=", Comparison::NotEqual),
+            ("<=", Comparison::LessOrEqual), (">=", Comparison::GreaterOrEqual),
+            ("<", Comparison::Less), (">", Comparison::Greater)
+        ].iter().find(|(text, _)| self.looking_at(text)).cloned();
+
+        match (comparison, left) {
+            (Some((text, comparison)), left) => {
+                self.pos += text.len();
+                self.skip_blank();
+                Ok(Expr::Compare(left, comparison, self.operand()?))
+            },
+            (None, Operand::Query(query)) => Ok(Expr::Exists(query)),
+            (None, _) => Err(self.error("expected a comparison"))
+        }
+    }
+
+    fn operand(&mut self) -> Result<Operand, JsonPathError> {
+        self.skip_blank();
+        match self.peek() {
+            Some('@') | Some('$') => Ok(Operand::Query(self.query()?)),
+            Some('\'') | Some('"') => Ok(Operand::Literal(JsonNode::String(self.string()?))),
+            Some('-') | Some('0'..='9') => self.number(),
+            _ => {
+                for (word, value) in &[("true", JsonNode::Boolean(true)), ("false", JsonNode::Boolean(false)), ("null", JsonNode::Null)] {
+                    if self.eat(word) {
+                        return Ok(Operand::Literal(value.clone()))
+                    }
+                }
+                if self.eat("length(") {
+                    let inner = self.operand()?;
+                    self.expect(")", "expected )")?;
+                    return Ok(Operand::Length(Box::new(inner)))
+                }
+                for (word, wrap) in &[("count(", Operand::Count as fn(Query) -> Operand), ("value(", Operand::Value)] {
+                    if self.eat(word) {
+                        self.skip_blank();
+                        let query = match self.peek() {
+                            Some('@') | Some('$') => self.query()?,
+                            _ => return Err(self.error("expected a query"))
+                        };
+                        self.expect(")", "expected )")?;
+                        return Ok(wrap(query))
+                    }
+                }
+                Err(self.error("expected a value"))
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Operand, JsonPathError> {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if c.is_ascii_digit() || c == '-' || c == '+' || c == '.' || c == 'e' || c == 'E' { self.pos += 1 } else { break }
+        }
+        let text : String = self.chars[start..self.pos].iter().collect();
+        text.parse::<f64>().map(|n| Operand::Literal(JsonNode::Number(n.into()))).map_err(|_| self.error("invalid number"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+    use crate::json::DEFAULT_MAX_DEPTH;
+
+    const STORE : &str = "{\"store\":{\"book\":[\
+        {\"author\":\"Rees\",\"price\":8.95},\
+        {\"author\":\"Waugh\",\"price\":12.99},\
+        {\"author\":\"Melville\",\"price\":8.99,\"isbn\":\"0-553\"},\
+        {\"author\":\"Tolkien\",\"price\":22.99,\"isbn\":\"0-395\"}],\
+        \"bicycle\":{\"color\":\"red\",\"price\":399}}}";
+
+    fn authors(expression : &str) -> Vec<String> {
+        let doc = JsonNode::from_str(STORE).unwrap();
+        select(&doc, expression).unwrap().iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn test_child_and_index_selectors() {
+        assert_eq!(authors("$.store.book[0].author"), vec!["\"Rees\""]);
+        assert_eq!(authors("$['store']['book'][-1]['author']"), vec!["\"Tolkien\""]);
+        assert_eq!(authors("$.store.book[0,2].author"), vec!["\"Rees\"", "\"Melville\""]);
+        assert!(authors("$.store.book[9]").is_empty());
+    }
+
+    #[test]
+    fn test_slices() {
+        assert_eq!(authors("$.store.book[1:3].author"), vec!["\"Waugh\"", "\"Melville\""]);
+        assert_eq!(authors("$.store.book[::-2].author"), vec!["\"Tolkien\"", "\"Waugh\""]);
+        assert_eq!(authors("$.store.book[-2:].author"), vec!["\"Melville\"", "\"Tolkien\""]);
+        assert!(authors("$.store.book[::0]").is_empty());
+        assert_eq!(authors("$.store.book[1::9223372036854775807].author"), vec!["\"Waugh\""]);
+        assert_eq!(authors("$.store.book[2::-9223372036854775808].author"), vec!["\"Melville\""]);
+    }
+
+    #[test]
+    fn test_wildcards_and_descendants() {
+        assert_eq!(authors("$.store.book[*].author").len(), 4);
+        assert_eq!(authors("$..author").len(), 4);
+        assert_eq!(authors("$..price").len(), 5);
+        assert_eq!(authors("$.store.*").len(), 2);
+    }
+
+    #[test]
+    fn test_filters() {
+        assert_eq!(authors("$..book[?(@.price<10)].author"), vec!["\"Rees\"", "\"Melville\""]);
+        assert_eq!(authors("$..book[?@.isbn].author"), vec!["\"Melville\"", "\"Tolkien\""]);
+        assert_eq!(authors("$..book[?@.isbn && @.price > 10].author"), vec!["\"Tolkien\""]);
+        assert_eq!(authors("$..book[?!@.isbn || @.author == 'Melville'].author"), vec!["\"Rees\"", "\"Waugh\"", "\"Melville\""]);
+        assert_eq!(authors("$..book[?length(@.author) == 5].author"), vec!["\"Waugh\""]);
+        assert_eq!(authors("$.store[?count(@.*) == 2].color"), vec!["\"red\""]);
+        assert_eq!(authors("$.store.book[?@.price == $.store.book[0].price].author"), vec!["\"Rees\""]);
+    }
+
+    #[test]
+    fn test_normalized_paths() {
+        let doc = JsonNode::from_str(STORE).unwrap();
+        let located = JsonPath::parse("$..book[?@.price < 10].author").unwrap().select_located(&doc);
+        let paths : Vec<&str> = located.iter().map(|(path, _)| path.as_str()).collect();
+        assert_eq!(paths, vec!["$['store']['book'][0]['author']", "$['store']['book'][2]['author']"]);
+        assert_eq!(located[1].1, &JsonNode::from("Melville"));
+        assert_eq!(JsonPath::parse("$.store.book[-1:]").unwrap().select_located(&doc)[0].0, "$['store']['book'][3]");
+
+        let doc = JsonNode::from_str("{\"it's\":{\"a\\\\b\\n\":0}}").unwrap();
+        assert_eq!(JsonPath::parse("$..*").unwrap().select_located(&doc).last().unwrap().0, "$['it\\'s']['a\\\\b\\n']");
+        assert_eq!(JsonPath::parse("$").unwrap().select_located(&doc)[0].0, "$");
+    }
+
+    #[test]
+    fn test_streaming_matches_the_dom() {
+        for expression in &["$.store.book[*].author", "$..price", "$.store.book[1:3].author", "$..book[?@.price < 10].author", "$.store", "$..*"] {
+            let query = JsonPath::parse(expression).unwrap();
+            let doc = JsonNode::from_str(STORE).unwrap();
+            let mut selected = query.select(&doc);
+            let count = query.stream(STORE.as_bytes(), |_, value| {
+                let found = selected.iter().position(|node| **node == value).expect(expression);
+                selected.remove(found);
+            }).unwrap();
+            assert!(selected.is_empty(), "{}", expression);
+            assert_eq!(count, query.select(&doc).len());
+        }
+    }
+
+    #[test]
+    fn test_streaming_paths_and_limits() {
+        let mut paths = Vec::new();
+        JsonPath::parse("$..book[?@.isbn].price").unwrap()
+            .stream(STORE.as_bytes(), |path, _| paths.push(path.to_string())).unwrap();
+        assert_eq!(paths, vec!["/store/book/2/price", "/store/book/3/price"]);
+
+        let unsupported = |expression : &str| matches!(JsonPath::parse(expression).unwrap().stream(STORE.as_bytes(), |_, _| ()), Err(StreamError::Unsupported(_)));
+        assert!(unsupported("$.store.book[-1]"));
+        assert!(unsupported("$.store.book[-2:]"));
+        assert!(unsupported("$.store.book[?@.price == $.store.bicycle.price]"));
+        assert!(matches!(JsonPath::parse("$.a").unwrap().stream("{\"a\":".as_bytes(), |_, _| ()), Err(StreamError::Input(_))));
+    }
+
+    #[test]
+    fn test_streaming_deep_nesting() {
+        let deep = format!("{}1{}", "[{\"a\":".repeat(250000), "}]".repeat(250000));
+        let query = JsonPath::parse("$..b").unwrap();
+        let result = query.stream(deep.as_bytes(), |_, _| ());
+        if cfg!(feature = "grow_stack") {
+            assert_eq!(result.unwrap(), 0)
+        } else {
+            assert!(matches!(result, Err(StreamError::Input(EventError::TooDeep { limit: DEFAULT_MAX_DEPTH, .. }))))
+        }
+        let mut found = Vec::new();
+        let shallow = format!("{}1{}", "[{\"a\":".repeat(60), "}]".repeat(60));
+        JsonPath::parse("$[0].a[0].a").unwrap().stream(shallow.as_bytes(), |path, _| found.push(path.to_string())).unwrap();
+        assert_eq!(found, vec!["/0/a/0/a"]);
+    }
+
+    #[test]
+    fn test_syntax_errors() {
+        assert_eq!(JsonPath::parse("store").unwrap_err().position, 0);
+        assert_eq!(JsonPath::parse("$.store[").unwrap_err().message, "expected a selector");
+        assert_eq!(JsonPath::parse("$[?@.a ==]").unwrap_err().message, "expected a value");
+        assert_eq!(JsonPath::parse("$.1").unwrap_err().message, "expected a member name");
+        assert!(JsonPath::parse("$['a").is_err());
+    }
+}
@@ -0,0 +1,147 @@
+use std::ops::Range;
+use nom::IResult;
+use crate::json::{Expected, JsonNode, JsonParseError, Number};
+use crate::json::parser::{parse_json_boolean, parse_json_escaped_string, parse_json_null, parse_json_number, reason};
+use crate::json::scan::skip_whitespace;
+
+/// The smallest unit of JSON text, with strings unescaped and numbers decoded.
+#[derive(PartialEq, Debug, Clone)]
+pub enum Token {
+    BeginObject,
+    EndObject,
+    BeginArray,
+    EndArray,
+    Colon,
+    Comma,
+    String(String),
+    Number(Number),
+    Boolean(bool),
+    Null
+}
+
+/// A token and the range of input bytes it was read from.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Lexeme {
+    pub token: Token,
+    pub span: Range<usize>
+}
+
+/// Splits JSON text into tokens without checking how they fit together, for
+/// tools that need the text itself rather than the document: highlighters,
+/// linters and parsers of their own. Whitespace between tokens is skipped;
+/// it lies between the spans. Iterating yields the same tokens and stops
+/// after the first error.
+pub struct JsonLexer<'a> {
+    input: &'a [u8],
+    offset: usize
+}
+
+impl<'a> JsonLexer<'a> {
+    pub fn new(input : &'a [u8]) -> JsonLexer<'a> {
+        JsonLexer { input, offset: 0 }
+    }
+
+    /// Bytes of input consumed so far.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// The next token, or None at the end of input.
+    pub fn next_token(&mut self) -> Result<Option<Lexeme>, JsonParseError> {
+        let start = skip_whitespace(self.input, self.offset);
+        self.offset = start;
+        let (length, token) = match self.input.get(start) {
+            None => return Ok(None),
+            Some(b'{') => (1, Token::BeginObject),
+            Some(b'}') => (1, Token::EndObject),
+            Some(b'[') => (1, Token::BeginArray),
+            Some(b']') => (1, Token::EndArray),
+            Some(b':') => (1, Token::Colon),
+            Some(b',') => (1, Token::Comma),
+            Some(b'"') => self.token(parse_json_escaped_string).map(|(length, s)| (length, Token::String(s)))?,
+            Some(b't') | Some(b'f') => self.token(parse_json_boolean).map(|(length, node)| (length, Token::Boolean(node == JsonNode::Boolean(true))))?,
+            Some(b'n') => self.token(parse_json_null).map(|(length, _)| (length, Token::Null))?,
+            Some(b'-') | Some(b'0'..=b'9') => match self.token(parse_json_number)? {
+                (length, JsonNode::Number(n)) => (length, Token::Number(n)),
+                _ => unreachable!("numbers parse into numbers")
+            },
+            Some(_) => return Err(JsonParseError::at(self.input, start, Expected::Value))
+        };
+        self.offset = start + length;
+        Ok(Some(Lexeme { token, span: start..self.offset }))
+    }
+
+    // how many bytes from the current offset `parser` takes, and what it makes of them
+    fn token<O>(&self, parser : fn(&[u8]) -> IResult<&[u8], O>) -> Result<(usize, O), JsonParseError> {
+        let rest = &self.input[self.offset..];
+        match parser(rest) {
+            Ok((after, out)) => Ok((rest.len() - after.len(), out)),
+            Err(nom::Err::Incomplete(_)) => {
+                // at end of input a number is only complete when given a terminator
+                let mut terminated = rest.to_vec();
+                terminated.push(b' ');
+                match parser(&terminated) {
+                    Ok((after, out)) if after.len() == 1 => Ok((rest.len(), out)),
+                    _ => Err(JsonParseError::at(self.input, self.input.len(), Expected::Value))
+                }
+            },
+            Err(nom::Err::Error(nom::Context::Code(after, kind))) | Err(nom::Err::Failure(nom::Context::Code(after, kind))) =>
+                Err(JsonParseError::refuse(self.input, self.input.len() - after.len(), Expected::Value, reason(&kind)))
+        }
+    }
+}
+
+impl<'a> Iterator for JsonLexer<'a> {
+    type Item = Result<Lexeme, JsonParseError>;
+
+    fn next(&mut self) -> Option<Result<Lexeme, JsonParseError>> {
+        let next = self.next_token();
+        if next.is_err() {
+            self.offset = self.input.len()
+        }
+        next.transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json::Reason;
+
+    #[test]
+    fn test_tokens_and_spans() {
+        let input = b" {\"a\\n\": [-1.5e2, true]}\n, null 12";
+        let lexemes : Vec<Lexeme> = JsonLexer::new(input).collect::<Result<_, _>>().unwrap();
+        let tokens : Vec<&Token> = lexemes.iter().map(|lexeme| &lexeme.token).collect();
+        assert_eq!(tokens, vec![
+            &Token::BeginObject,
+            &Token::String("a\n".to_string()),
+            &Token::Colon,
+            &Token::BeginArray,
+            &Token::Number((-150.0).into()),
+            &Token::Comma,
+            &Token::Boolean(true),
+            &Token::EndArray,
+            &Token::EndObject,
+            &Token::Comma,
+            &Token::Null,
+            &Token::Number(12.into())
+        ]);
+        assert_eq!(lexemes[1].span, 2..7);
+        assert_eq!(&input[lexemes[4].span.clone()], b"-1.5e2");
+        assert_eq!(lexemes[11].span, 32..34);
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut lexer = JsonLexer::new(b"[tru");
+        assert_eq!(lexer.next().unwrap().unwrap().token, Token::BeginArray);
+        assert!(lexer.next().unwrap().unwrap_err().unexpected_end);
+        assert!(lexer.next().is_none());
+
+        let error = JsonLexer::new(b"1 @").nth(1).unwrap().unwrap_err();
+        assert_eq!((error.offset, error.reason), (2, Reason::Syntax));
+        let error = JsonLexer::new(b"\"a\\q\"").next().unwrap().unwrap_err();
+        assert_eq!((error.offset, error.reason), (2, Reason::InvalidEscape));
+    }
+}
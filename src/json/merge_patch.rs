@@ -0,0 +1,98 @@
+use crate::json::{maybe_grow, JsonNode, Object};
+
+/// Applies a JSON Merge Patch (RFC 7386): objects merge recursively, null
+/// members delete keys, and any other patch value replaces the target.
+pub fn apply(target : &mut JsonNode, patch : &JsonNode) {
+    maybe_grow(|| {
+        let members = match patch {
+            JsonNode::Object(members) => members,
+            _ => {
+                *target = patch.clone();
+                return
+            }
+        };
+        if !matches!(target, JsonNode::Object(_)) {
+            *target = JsonNode::Object(Object::default())
+        }
+        if let JsonNode::Object(o) = target {
+            for (key, value) in members {
+                if *value == JsonNode::Null {
+                    o.remove(key);
+                } else {
+                    apply(o.entry(key.clone()).or_insert(JsonNode::Null), value)
+                }
+            }
+        }
+    })
+}
+
+/// A merge patch turning `a` into `b`, or None when they are equal.
+/// Merge patches cannot set a member to null, so null members of `b`
+/// come out as deletions.
+pub fn diff(a : &JsonNode, b : &JsonNode) -> Option<JsonNode> {
+    if a == b {
+        return None
+    }
+    maybe_grow(|| match (a, b) {
+        (JsonNode::Object(a), JsonNode::Object(b)) => {
+            let mut patch = Object::default();
+            for key in a.keys().filter(|k| !b.contains_key(*k)) {
+                patch.insert(key.clone(), JsonNode::Null);
+            }
+            for (key, value) in b {
+                let change = match a.get(key) {
+                    Some(old) => diff(old, value),
+                    None => Some(value.clone())
+                };
+                if let Some(change) = change {
+                    patch.insert(key.clone(), change);
+                }
+            }
+            Some(JsonNode::Object(patch))
+        },
+        _ => Some(b.clone())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_rfc_7386_examples() {
+        let cases = [
+            ("{\"a\":\"b\"}", "{\"a\":\"c\"}", "{\"a\":\"c\"}"),
+            ("{\"a\":\"b\"}", "{\"b\":\"c\"}", "{\"a\":\"b\",\"b\":\"c\"}"),
+            ("{\"a\":\"b\"}", "{\"a\":null}", "{}"),
+            ("{\"a\":\"b\",\"b\":\"c\"}", "{\"a\":null}", "{\"b\":\"c\"}"),
+            ("{\"a\":[\"b\"]}", "{\"a\":\"c\"}", "{\"a\":\"c\"}"),
+            ("{\"a\":\"c\"}", "{\"a\":[\"b\"]}", "{\"a\":[\"b\"]}"),
+            ("{\"a\":{\"b\":\"c\"}}", "{\"a\":{\"b\":\"d\",\"c\":null}}", "{\"a\":{\"b\":\"d\"}}"),
+            ("{\"a\":[{\"b\":\"c\"}]}", "{\"a\":[1]}", "{\"a\":[1]}"),
+            ("[\"a\",\"b\"]", "[\"c\",\"d\"]", "[\"c\",\"d\"]"),
+            ("{\"a\":\"b\"}", "[\"c\"]", "[\"c\"]"),
+            ("{\"e\":null}", "{\"a\":1}", "{\"e\":null,\"a\":1}"),
+            ("[1,2]", "{\"a\":\"b\",\"c\":null}", "{\"a\":\"b\"}"),
+            ("{}", "{\"a\":{\"bb\":{\"ccc\":null}}}", "{\"a\":{\"bb\":{}}}")
+        ];
+        for (target, patch, expected) in cases.iter() {
+            let mut target = JsonNode::from_str(target).unwrap();
+            apply(&mut target, &JsonNode::from_str(patch).unwrap());
+            assert_eq!(target, JsonNode::from_str(expected).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_diff_round_trips() {
+        let a = JsonNode::from_str("{\"title\":\"Goodbye!\",\"author\":{\"givenName\":\"John\",\"familyName\":\"Doe\"},\"tags\":[\"example\",\"sample\"]}").unwrap();
+        let b = JsonNode::from_str("{\"title\":\"Hello!\",\"author\":{\"givenName\":\"John\"},\"tags\":[\"example\"],\"phoneNumber\":\"+01-123-456-7890\"}").unwrap();
+        let patch = diff(&a, &b).unwrap();
+        assert_eq!(patch, JsonNode::from_str("{\"title\":\"Hello!\",\"author\":{\"familyName\":null},\"tags\":[\"example\"],\"phoneNumber\":\"+01-123-456-7890\"}").unwrap());
+
+        let mut patched = a.clone();
+        apply(&mut patched, &patch);
+        assert_eq!(patched, b);
+        assert_eq!(diff(&a, &a), None);
+    }
+}
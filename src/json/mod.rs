@@ -1,82 +1,888 @@
 use std::fmt;
-#[warn(unused_imports)]
+use std::io;
+use std::mem;
+use std::ops::{Index, IndexMut};
+#[cfg(not(feature = "small_objects"))]
 use std::collections::HashMap;
 mod parser;
-use self::parser::parse_json;
+pub mod aggregate;
+pub mod arrays;
+mod canonical;
+pub mod case;
+pub mod cbor;
+mod convert;
+pub mod csv;
+pub mod diff;
+pub mod document;
+mod error;
+pub mod events;
+pub mod filter;
+pub mod flatten;
+#[cfg(feature = "fxhash")]
+mod fxhash;
+pub mod hooks;
+pub mod index;
+pub mod infer;
+pub mod jsonpath;
+pub mod lexer;
+pub mod merge_patch;
+#[cfg(feature = "msgpack")]
+pub mod msgpack;
+pub mod ndjson;
+mod nfc;
+mod number;
+mod options;
+pub mod patch;
+pub mod pipeline;
+pub mod pointer;
+pub mod pool;
+pub mod redact;
+pub mod refs;
+mod regex;
+pub mod rewrite;
+pub mod sanitize;
+mod scan;
+pub mod schema;
+pub mod serialize;
+#[cfg(feature = "serde")]
+mod serde_support;
+mod sha256;
+pub mod shape;
+pub mod shared;
+pub mod slice;
+#[cfg(feature = "small_objects")]
+mod small_map;
+#[cfg(feature = "grow_stack")]
+mod stack;
+pub mod streaming;
+pub mod subscribe;
+pub mod template;
+#[cfg(feature = "toml")]
+pub mod toml;
+#[cfg(feature = "serde")]
+pub mod typed;
+#[cfg(feature = "yaml")]
+pub mod yaml;
+pub use self::convert::WrongType;
+pub use self::error::{Expected, JsonParseError, Reason};
+pub use self::number::Number;
+pub use self::options::{DuplicateKeys, NumberFormat, ParserOptions, SerializeOptions, DEFAULT_MAX_DEPTH};
+use self::hooks::{NoHooks, ParseHooks};
+use self::serialize::Serializer;
+use self::patch::{Patch, PatchError};
+use self::pointer::{JsonPointer, PointerError};
+use self::parser::{expected, parse_json_with, reason, ws, Context};
 
-#[derive(PartialEq, Debug)]
+/// How object keys are hashed: SipHash by default, FxHash with the `fxhash` feature.
+#[cfg(not(feature = "fxhash"))]
+pub type ObjectHasher = std::collections::hash_map::RandomState;
+#[cfg(feature = "fxhash")]
+pub type ObjectHasher = self::fxhash::FxBuildHasher;
+
+/// The members of an object: a hash map by default, and with the
+/// `small_objects` or `preserve_order` feature a vector in the order the
+/// members were added, searched in order until it grows past eight members.
+#[cfg(not(feature = "small_objects"))]
+pub type Object = HashMap<String, JsonNode, ObjectHasher>;
+#[cfg(feature = "small_objects")]
+pub type Object = self::small_map::SmallMap<String, JsonNode, ObjectHasher>;
+#[cfg(not(feature = "small_objects"))]
+pub use std::collections::hash_map::Entry as ObjectEntry;
+#[cfg(feature = "small_objects")]
+pub use self::small_map::Entry as ObjectEntry;
+
+/// A member slot of an object, as `JsonNode::entry` returns it.
+#[cfg(not(feature = "small_objects"))]
+pub type MemberEntry<'a> = ObjectEntry<'a, String, JsonNode>;
+#[cfg(feature = "small_objects")]
+pub type MemberEntry<'a> = ObjectEntry<'a, String, JsonNode, ObjectHasher>;
+
+#[cfg(feature = "grow_stack")]
+use self::stack::maybe_grow;
+
+// recursion runs on the stack it is given
+#[cfg(not(feature = "grow_stack"))]
+fn maybe_grow<R, F : FnOnce() -> R>(f : F) -> R {
+    f()
+}
+
+/// A parsed JSON value and everything in it. Dropping one takes apart its
+/// containers without recursion, and comparing, cloning and debug printing
+/// recurse through `grow_stack`, so documents nest as deep as parsing
+/// allows.
 pub enum JsonNode {
-    Number(f64),
+    Number(Number),
     String(String),
     Array(Vec<JsonNode>),
-    Object(HashMap<String, JsonNode>),
+    Object(Object),
     Boolean(bool),
     Null
 }
 
+impl JsonNode {
+    // moves the members of a container holding containers into `pending`
+    fn take_nested(&mut self, pending : &mut Vec<JsonNode>) {
+        let nested = |node : &JsonNode| matches!(node, JsonNode::Array(_) | JsonNode::Object(_));
+        match self {
+            JsonNode::Array(a) if a.iter().any(nested) => pending.append(a),
+            JsonNode::Object(o) if o.values().any(nested) => pending.extend(o.drain().map(|(_, value)| value)),
+            _ => ()
+        }
+    }
+}
+
+impl Drop for JsonNode {
+    fn drop(&mut self) {
+        let mut pending = Vec::new();
+        self.take_nested(&mut pending);
+        while let Some(mut node) = pending.pop() {
+            node.take_nested(&mut pending)
+        }
+    }
+}
+
+impl Clone for JsonNode {
+    fn clone(&self) -> JsonNode {
+        maybe_grow(|| match self {
+            JsonNode::Number(n) => JsonNode::Number(*n),
+            JsonNode::String(s) => JsonNode::String(s.clone()),
+            JsonNode::Array(a) => JsonNode::Array(a.clone()),
+            JsonNode::Object(o) => JsonNode::Object(o.clone()),
+            JsonNode::Boolean(b) => JsonNode::Boolean(*b),
+            JsonNode::Null => JsonNode::Null
+        })
+    }
+}
+
+impl PartialEq for JsonNode {
+    fn eq(&self, other : &JsonNode) -> bool {
+        maybe_grow(|| match (self, other) {
+            (JsonNode::Number(a), JsonNode::Number(b)) => a == b,
+            (JsonNode::String(a), JsonNode::String(b)) => a == b,
+            (JsonNode::Array(a), JsonNode::Array(b)) => a == b,
+            (JsonNode::Object(a), JsonNode::Object(b)) => a == b,
+            (JsonNode::Boolean(a), JsonNode::Boolean(b)) => a == b,
+            (JsonNode::Null, JsonNode::Null) => true,
+            _ => false
+        })
+    }
+}
+
+impl fmt::Debug for JsonNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        maybe_grow(|| match self {
+            JsonNode::Number(n) => f.debug_tuple("Number").field(n).finish(),
+            JsonNode::String(s) => f.debug_tuple("String").field(s).finish(),
+            JsonNode::Array(a) => f.debug_tuple("Array").field(a).finish(),
+            JsonNode::Object(o) => f.debug_tuple("Object").field(o).finish(),
+            JsonNode::Boolean(b) => f.debug_tuple("Boolean").field(b).finish(),
+            JsonNode::Null => f.write_str("Null")
+        })
+    }
+}
+
 impl fmt::Display for JsonNode {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if f.alternate() {
+            Serializer::new().indent(serialize::INDENT).write(self, f)
+        } else {
+            Serializer::new().write(self, f)
+        }
+    }
+}
+
+// a number at the very end of the input only completes with a terminator:
+// `buffer` with one added, when that is what it ran out for
+fn terminated(buffer : &[u8], error : &nom::Err<&[u8]>) -> Option<Vec<u8>> {
+    match error {
+        nom::Err::Incomplete(_) if buffer.last().is_some_and(|b| b.is_ascii_alphanumeric() || *b == b'.') => {
+            let mut terminated = buffer.to_vec();
+            terminated.push(b' ');
+            Some(terminated)
+        },
+        _ => None
+    }
+}
+
+/// `str::parse`, and `JsonNode::from_str` with `FromStr` in scope, take the
+/// whole string as one document, which only whitespace may follow.
+impl std::str::FromStr for JsonNode {
+    type Err = JsonParseError;
+
+    fn from_str(json : &str) -> Result<JsonNode, JsonParseError> {
+        JsonNode::parse_complete(json.as_bytes(), ParserOptions::default())
+    }
+}
+
+/// Null, so that a `JsonNode` can be taken or default-filled.
+impl Default for JsonNode {
+    fn default() -> JsonNode {
+        JsonNode::Null
+    }
+}
+
+static NULL : JsonNode = JsonNode::Null;
+
+/// `node["key"]` is the member, or null when there is no such member or
+/// `node` is not an object, so that lookups can be chained.
+impl Index<&str> for JsonNode {
+    type Output = JsonNode;
+
+    fn index(&self, key : &str) -> &JsonNode {
+        self.get(key).unwrap_or(&NULL)
+    }
+}
+
+/// `node[i]` is the element, or null past the end or when `node` is not an array.
+impl Index<usize> for JsonNode {
+    type Output = JsonNode;
+
+    fn index(&self, index : usize) -> &JsonNode {
+        self.get_index(index).unwrap_or(&NULL)
+    }
+}
+
+/// Assigning to `node["key"]` adds the member if it is missing, turning a
+/// null `node` into an object first. Panics if `node` is anything else,
+/// like `JsonNode::entry`.
+impl IndexMut<&str> for JsonNode {
+    fn index_mut(&mut self, key : &str) -> &mut JsonNode {
+        self.entry(key).or_insert(JsonNode::Null)
+    }
+}
+
+/// Panics unless `node` is an array with an element at `index`.
+impl IndexMut<usize> for JsonNode {
+    fn index_mut(&mut self, index : usize) -> &mut JsonNode {
         match self {
-            JsonNode::Number(n) => f64::fmt(n, f),
-            JsonNode::String(s) => write!(f, "\"{}\"", s),
-            JsonNode::Array(a) => JsonNode::fmt_array(a, f),
-            JsonNode::Object(o) => JsonNode::fmt_object(o, f),
-            JsonNode::Boolean(t) => write!(f, "{}", if *t { "true" } else { "false" }),
-            JsonNode::Null => write!(f, "null")
+            JsonNode::Array(a) => {
+                let len = a.len();
+                a.get_mut(index).unwrap_or_else(|| panic!("index {} is out of bounds of an array of {}", index, len))
+            },
+            _ => panic!("cannot index a non-array with {}", index)
         }
     }
 }
 
 impl JsonNode {
-    pub fn from_str(json : &str) -> JsonNode {
-        JsonNode::from_bytes(json.as_bytes())
+    /// Parses the document at the start of `buffer`. Of members with the
+    /// same name, the first wins.
+    pub fn from_bytes(buffer : &[u8]) -> Result<JsonNode, JsonParseError> {
+        JsonNode::from_bytes_with(buffer, ParserOptions::default())
     }
 
-    pub fn from_bytes(buffer : &[u8]) -> JsonNode {
-        let result = parse_json(&buffer);
-        match result {
-            Ok(rest_and_json) => rest_and_json.1,
-            Err(reason) => panic!("JSON parsing failed: {}", reason.to_string())
+    /// Like `from_bytes`, accepting the departures from RFC 8259 that `options` allow.
+    pub fn from_str_with(json : &str, options : ParserOptions) -> Result<JsonNode, JsonParseError> {
+        JsonNode::from_bytes_with(json.as_bytes(), options)
+    }
+
+    /// Like `from_bytes`, accepting the departures from RFC 8259 that `options` allow.
+    pub fn from_bytes_with(buffer : &[u8], options : ParserOptions) -> Result<JsonNode, JsonParseError> {
+        JsonNode::parse_with_hooks(buffer, options, &mut NoHooks)
+    }
+
+    fn parse_with_hooks(buffer : &[u8], options : ParserOptions, hooks : &mut dyn ParseHooks) -> Result<JsonNode, JsonParseError> {
+        let mut ctx = Context::new(hooks, options);
+        match parse_json_with(buffer, &mut ctx) {
+            Ok((_, json)) => Ok(json),
+            Err(error) => {
+                if let Some(terminated) = terminated(buffer, &error) {
+                    if let Ok(json) = JsonNode::parse_with_hooks(&terminated, options, &mut *ctx.hooks) {
+                        return Ok(json)
+                    }
+                }
+                Err(JsonNode::parse_error(buffer, error, ctx))
+            }
         }
     }
 
-    fn fmt_array(a : &Vec<JsonNode>, f: &mut fmt::Formatter) -> fmt::Result {
-        let mut comma = false;
-        let mut elements = a.iter();
+    // the error for `buffer`, which parsing with `ctx` failed on
+    fn parse_error(buffer : &[u8], error : nom::Err<&[u8]>, ctx : Context) -> JsonParseError {
+        let (offset, kind) = match error {
+            nom::Err::Incomplete(_) => (buffer.len(), None),
+            nom::Err::Error(nom::Context::Code(rest, kind)) | nom::Err::Failure(nom::Context::Code(rest, kind)) =>
+                (buffer.len() - rest.len(), Some(kind))
+        };
+        let expected = kind.as_ref().map_or(Expected::Value, expected);
+        let refusal = match ctx.reason {
+            Reason::Syntax => kind.as_ref().map_or(Reason::Syntax, reason),
+            refusal => refusal
+        };
+        match refusal {
+            // strict input can be rescanned for a precise location, lenient input only as the parser left it
+            Reason::Syntax if ctx.options.is_strict() => JsonParseError::locate(buffer, offset),
+            Reason::Syntax => JsonParseError::at(buffer, offset, expected),
+            refusal => JsonParseError::refuse(buffer, offset, expected, refusal)
+        }
+    }
 
-        let r = write!(f, "[");
-        match r { Err(_) => return r, Ok(_) => () }
+    /// Like `from_str`: fails with `Reason::TrailingData` when anything but
+    /// whitespace follows the document.
+    pub fn from_str_strict(json : &str) -> Result<JsonNode, JsonParseError> {
+        JsonNode::parse_complete(json.as_bytes(), ParserOptions::default())
+    }
 
-        while let Some(it) = elements.next() {
-            if comma {
-                let r = write!(f, ",");
-                match r { Err(_) => return r, Ok(_) => () }
+    /// Parses all of `buffer` as one document, which only whitespace may
+    /// follow, and comments too if `options` allow them.
+    pub fn parse_complete(buffer : &[u8], options : ParserOptions) -> Result<JsonNode, JsonParseError> {
+        let mut hooks = NoHooks;
+        let mut ctx = Context::new(&mut hooks, options);
+        let rest = match parse_json_with(buffer, &mut ctx) {
+            Ok((rest, json)) => match ws(rest, &options) {
+                Ok((&[], ())) => return Ok(json),
+                Ok((rest, ())) => rest,
+                // a comment left open
+                Err(_) => &buffer[buffer.len()..]
+            },
+            Err(error) => {
+                if let Some(terminated) = terminated(buffer, &error) {
+                    if let Ok(json) = JsonNode::parse_complete(&terminated, options) {
+                        return Ok(json)
+                    }
+                }
+                return Err(JsonNode::parse_error(buffer, error, ctx))
             }
-            let r = write!(f, "{}", it);
-            match r { Err(_) => return r, Ok(_) => () }
-            comma = true
+        };
+        let offset = buffer.len() - rest.len();
+        Err(JsonParseError::refuse(buffer, offset, Expected::End, Reason::TrailingData))
+    }
+
+    /// Like `from_str`, for input known to be valid: panics on malformed JSON.
+    pub fn from_str_unchecked(json : &str) -> JsonNode {
+        match json.parse() {
+            Ok(json) => json,
+            Err(reason) => panic!("JSON parsing failed: {}", reason)
+        }
+    }
+
+    /// Parses the document at the start of `buffer`, after any whitespace,
+    /// returning it with the number of bytes taken, or None when `buffer`
+    /// ends before a document does.
+    pub fn parse_prefix(buffer : &[u8]) -> Result<Option<(JsonNode, usize)>, JsonParseError> {
+        let start = scan::skip_whitespace(buffer, 0);
+        if start == buffer.len() {
+            return Ok(None)
+        }
+        // the parser reports a truncated container as malformed
+        let truncated = match buffer[start] {
+            b'[' | b'{' | b'"' => scan::value_end(buffer, start).is_none(),
+            _ => false
+        };
+        if truncated {
+            return Ok(None)
+        }
+        let mut hooks = NoHooks;
+        let mut ctx = Context::new(&mut hooks, ParserOptions::default());
+        match parse_json_with(&buffer[start..], &mut ctx) {
+            Ok((rest, json)) => Ok(Some((json, buffer.len() - rest.len()))),
+            Err(nom::Err::Incomplete(_)) => Ok(None),
+            Err(error) => Err(JsonNode::parse_error(buffer, error, ctx))
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            JsonNode::String(s) => Some(s),
+            _ => None
+        }
+    }
+
+    pub fn as_number(&self) -> Option<Number> {
+        match self {
+            JsonNode::Number(n) => Some(*n),
+            _ => None
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        self.as_number().map(Number::as_f64)
+    }
+
+    /// The value of an integer that fits in an i64; None for fractions.
+    pub fn as_i64(&self) -> Option<i64> {
+        self.as_number().and_then(Number::as_i64)
+    }
+
+    /// The value of a non-negative integer; None for fractions.
+    pub fn as_u64(&self) -> Option<u64> {
+        self.as_number().and_then(Number::as_u64)
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            JsonNode::Boolean(t) => Some(*t),
+            _ => None
         }
+    }
 
-        write!(f, "]")
+    pub fn as_array(&self) -> Option<&Vec<JsonNode>> {
+        match self {
+            JsonNode::Array(a) => Some(a),
+            _ => None
+        }
     }
 
-    fn fmt_object(o : &HashMap<String, JsonNode>, f: &mut fmt::Formatter) -> fmt::Result {
-        let mut comma = false;
-        let mut elements = o.iter();
+    pub fn as_array_mut(&mut self) -> Option<&mut Vec<JsonNode>> {
+        match self {
+            JsonNode::Array(a) => Some(a),
+            _ => None
+        }
+    }
 
-        let r = write!(f, "{{");
-        match r { Err(_) => return r, Ok(_) => () }
+    pub fn as_object(&self) -> Option<&Object> {
+        match self {
+            JsonNode::Object(o) => Some(o),
+            _ => None
+        }
+    }
 
-        while let Some((k, v)) = elements.next() {
-            if comma {
-                let r = write!(f, ",");
-                match r { Err(_) => return r, Ok(_) => () }
+    pub fn as_object_mut(&mut self) -> Option<&mut Object> {
+        match self {
+            JsonNode::Object(o) => Some(o),
+            _ => None
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, JsonNode::Null)
+    }
+
+    pub fn is_boolean(&self) -> bool {
+        matches!(self, JsonNode::Boolean(_))
+    }
+
+    pub fn is_number(&self) -> bool {
+        matches!(self, JsonNode::Number(_))
+    }
+
+    pub fn is_string(&self) -> bool {
+        matches!(self, JsonNode::String(_))
+    }
+
+    pub fn is_array(&self) -> bool {
+        matches!(self, JsonNode::Array(_))
+    }
+
+    pub fn is_object(&self) -> bool {
+        matches!(self, JsonNode::Object(_))
+    }
+
+    /// An object member; None for a missing key or any other kind of value.
+    pub fn get(&self, key : &str) -> Option<&JsonNode> {
+        self.as_object().and_then(|o| o.get(key))
+    }
+
+    /// An array element; None past the end or for any other kind of value.
+    pub fn get_index(&self, index : usize) -> Option<&JsonNode> {
+        self.as_array().and_then(|a| a.get(index))
+    }
+
+    pub fn get_mut(&mut self, key : &str) -> Option<&mut JsonNode> {
+        self.as_object_mut().and_then(|o| o.get_mut(key))
+    }
+
+    pub fn get_index_mut(&mut self, index : usize) -> Option<&mut JsonNode> {
+        self.as_array_mut().and_then(|a| a.get_mut(index))
+    }
+
+    /// Appends to an array, turning null into an empty array first.
+    /// Panics for any other kind of value.
+    pub fn push(&mut self, value : JsonNode) {
+        self.array_or_panic("push onto").push(value)
+    }
+
+    /// Inserts into an array at `index`, shifting later elements along,
+    /// like `Vec::insert`. Panics past the end or for any other kind of value.
+    pub fn insert_at(&mut self, index : usize, value : JsonNode) {
+        self.array_or_panic("insert into").insert(index, value)
+    }
+
+    /// Takes an element out of an array, shifting later elements back; None
+    /// past the end or for any other kind of value.
+    pub fn remove_at(&mut self, index : usize) -> Option<JsonNode> {
+        self.as_array_mut().filter(|a| index < a.len()).map(|a| a.remove(index))
+    }
+
+    /// Sets an object member, returning the value it replaces. Turns null
+    /// into an empty object first and panics for any other kind of value.
+    pub fn insert(&mut self, key : &str, value : JsonNode) -> Option<JsonNode> {
+        self.object_or_panic("insert into").insert(key.to_string(), value)
+    }
+
+    /// Takes a member out of an object; None when it is missing or for any
+    /// other kind of value.
+    pub fn remove(&mut self, key : &str) -> Option<JsonNode> {
+        self.as_object_mut().and_then(|o| o.remove(key))
+    }
+
+    /// The slot of an object member, for in-place updates. Turns null into
+    /// an empty object first and panics for any other kind of value.
+    pub fn entry(&mut self, key : &str) -> MemberEntry<'_> {
+        self.object_or_panic("look up an entry in").entry(key.to_string())
+    }
+
+    /// Moves the value out, leaving null in its place.
+    pub fn take(&mut self) -> JsonNode {
+        mem::take(self)
+    }
+
+    /// Puts `value` in place of this one, returning the old value.
+    pub fn replace(&mut self, value : JsonNode) -> JsonNode {
+        mem::replace(self, value)
+    }
+
+    fn array_or_panic(&mut self, action : &str) -> &mut Vec<JsonNode> {
+        if self.is_null() {
+            *self = JsonNode::Array(Vec::new())
+        }
+        match self {
+            JsonNode::Array(a) => a,
+            _ => panic!("cannot {} a non-array", action)
+        }
+    }
+
+    fn object_or_panic(&mut self, action : &str) -> &mut Object {
+        if self.is_null() {
+            *self = JsonNode::Object(Object::default())
+        }
+        match self {
+            JsonNode::Object(o) => o,
+            _ => panic!("cannot {} a non-object", action)
+        }
+    }
+
+    /// The value at a JSON Pointer such as `/a/b~1c/0`; None when there is
+    /// no such value or `pointer` is malformed. See `JsonPointer::resolve`.
+    pub fn pointer(&self, pointer : &str) -> Option<&JsonNode> {
+        JsonPointer::parse(pointer).ok()?.resolve(self)
+    }
+
+    pub fn pointer_mut(&mut self, pointer : &str) -> Option<&mut JsonNode> {
+        JsonPointer::parse(pointer).ok()?.resolve_mut(self)
+    }
+
+    /// Applies a JSON Patch (RFC 6902) document, all of it or, when an
+    /// operation fails, none of it; see `patch::apply`.
+    pub fn apply_patch(&mut self, patch : &JsonNode) -> Result<(), PatchError> {
+        patch::apply(self, &Patch::from_node(patch)?)
+    }
+
+    /// Overlays a JSON Merge Patch (RFC 7386): objects merge recursively,
+    /// null members remove keys, and anything else replaces; see `merge_patch::apply`.
+    pub fn merge_patch(&mut self, patch : &JsonNode) {
+        merge_patch::apply(self, patch)
+    }
+
+    /// Every value matching a glob-style JSON Pointer such as `/users/*/email`,
+    /// with its concrete path; see `JsonPointer::select_all`.
+    pub fn get_all(&self, pattern : &str) -> Result<Vec<(JsonPointer, &JsonNode)>, PointerError> {
+        Ok(JsonPointer::parse(pattern)?.select_all(self))
+    }
+
+    /// Every value, this one included, for which `predicate` holds, with its
+    /// path. Visits parents first and object members in key order.
+    pub fn find_all<F>(&self, mut predicate : F) -> Vec<(JsonPointer, &JsonNode)>
+        where F : FnMut(&JsonPointer, &JsonNode) -> bool
+    {
+        let mut out = Vec::new();
+        self.find_into(&mut JsonPointer::root(), &mut predicate, &mut out);
+        out
+    }
+
+    fn find_into<'a, F>(&'a self, path : &mut JsonPointer, predicate : &mut F, out : &mut Vec<(JsonPointer, &'a JsonNode)>)
+        where F : FnMut(&JsonPointer, &JsonNode) -> bool
+    {
+        if predicate(path, self) {
+            out.push((path.clone(), self))
+        }
+        maybe_grow(|| match self {
+            JsonNode::Array(a) => for (i, item) in a.iter().enumerate() {
+                path.push(i.to_string());
+                item.find_into(path, predicate, out);
+                path.pop();
+            },
+            JsonNode::Object(o) => {
+                let mut keys : Vec<&String> = o.keys().collect();
+                keys.sort();
+                for key in keys {
+                    path.push(key.as_str());
+                    o[key].find_into(path, predicate, out);
+                    path.pop();
+                }
+            },
+            _ => ()
+        })
+    }
+
+    /// The document indented by two spaces per level, one item per line.
+    pub fn to_string_pretty(&self) -> String {
+        format!("{:#}", self)
+    }
+
+    /// The document written as `options` say.
+    pub fn to_string_with(&self, options : &SerializeOptions) -> String {
+        Serializer::with_options(options.clone()).to_string(self)
+    }
+
+    /// Writes the document compactly to `out` as it is serialized, without
+    /// building a `String`.
+    pub fn write_to<W : io::Write>(&self, out : &mut W) -> io::Result<()> {
+        Serializer::new().write_to(self, out)
+    }
+
+    /// Writes the document to `out` as `to_string_pretty` formats it.
+    pub fn write_pretty_to<W : io::Write>(&self, out : &mut W) -> io::Result<()> {
+        Serializer::new().indent(serialize::INDENT).write_to(self, out)
+    }
+
+    /// Approximate number of heap bytes owned by this node and its children.
+    /// Counts allocated capacity rather than length, plus a per-entry estimate
+    /// for hash map bookkeeping; the node itself is not included.
+    pub fn approx_heap_bytes(&self) -> usize {
+        maybe_grow(|| match self {
+            JsonNode::String(s) => s.capacity(),
+            JsonNode::Array(a) => {
+                a.capacity() * mem::size_of::<JsonNode>()
+                    + a.iter().map(JsonNode::approx_heap_bytes).sum::<usize>()
+            },
+            JsonNode::Object(o) => {
+                // hashbrown keeps one control byte per bucket next to each (key, value) slot
+                let slot = mem::size_of::<String>() + mem::size_of::<JsonNode>() + 1;
+                o.capacity() * slot
+                    + o.iter().map(|(k, v)| k.capacity() + v.approx_heap_bytes()).sum::<usize>()
+            },
+            _ => 0
+        })
+    }
+
+    /// Parses like `from_bytes`, reporting every value and key to `hooks` on the way.
+    pub fn from_bytes_with_hooks(buffer : &[u8], hooks : &mut dyn ParseHooks) -> Result<JsonNode, JsonParseError> {
+        JsonNode::parse_with_hooks(buffer, ParserOptions::default(), hooks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_parse_prefix() {
+        assert_eq!(JsonNode::parse_prefix(b"\n [1,\"a\"]{}"), Ok(Some((JsonNode::from_str("[1,\"a\"]").unwrap(), 9))));
+        for truncated in &["{\"k\":[\"x", "[1,", "\"ab", "tr", "12", " "] {
+            assert_eq!(JsonNode::parse_prefix(truncated.as_bytes()), Ok(None));
+        }
+        assert!(JsonNode::parse_prefix(b"[1 2]").is_err());
+    }
+
+    #[test]
+    fn test_scalar_documents() {
+        for (text, expected) in &[("42", json!(42)), (" 1.5", json!(1.5)), ("-7", json!(-7)), ("1e3", json!(1000)), ("true", json!(true)), ("null", JsonNode::Null), ("\"s\"", json!("s"))] {
+            assert_eq!(JsonNode::from_str(text).as_ref(), Ok(expected), "{}", text);
+            assert_eq!(JsonNode::from_bytes(text.as_bytes()).as_ref(), Ok(expected), "{}", text);
+        }
+        assert!(JsonNode::from_str("-").is_err());
+        assert!(JsonNode::from_str("tru").is_err());
+    }
+
+    #[test]
+    fn test_trailing_data() {
+        let error = JsonNode::from_str_strict("123abc ").unwrap_err();
+        assert_eq!((error.offset, error.reason, error.expected), (3, Reason::TrailingData, Expected::End));
+        assert_eq!(JsonNode::from_str_strict("123"), Ok(json!(123)));
+        assert_eq!(JsonNode::from_str_strict(" [1]\n"), Ok(json!([1])));
+        assert_eq!(JsonNode::from_str_strict("[1] x").unwrap_err().offset, 4);
+        assert_eq!(JsonNode::from_str("[1] x").unwrap_err().reason, Reason::TrailingData);
+        assert_eq!(JsonNode::from_str("0 \n"), Ok(json!(0)));
+        assert_eq!(JsonNode::from_str_strict("{} {}").unwrap_err().offset, 3);
+        assert!(JsonNode::from_str_strict("[1, 2").unwrap_err().unexpected_end);
+        assert!(JsonNode::from_str_strict("1 // c").is_err());
+        assert_eq!(JsonNode::parse_complete(b"[1,] // c", ParserOptions::jsonc()), Ok(json!([1])));
+        assert_eq!(JsonNode::parse_complete(b"1 /* c", ParserOptions::jsonc()).unwrap_err().offset, 6);
+    }
+
+    #[test]
+    fn test_booleans_round_trip() {
+        let node = JsonNode::from_str("[true,false,{\"ok\":true}]").unwrap();
+        assert_eq!(node.to_string(), "[true,false,{\"ok\":true}]");
+        assert_eq!(JsonNode::from_str(&node.to_string()).unwrap(), node);
+    }
+
+    #[test]
+    fn test_integers_round_trip() {
+        let text = "[9007199254740993,-9223372036854775807,18446744073709551615,0,1.5,-0.0]";
+        let node = JsonNode::from_str(text).unwrap();
+        assert_eq!(node.to_string(), text.replace("-0.0", "-0"));
+        assert_ne!(node, JsonNode::from_str("[9007199254740992,-9223372036854775807,18446744073709551615,0,1.5,-0.0]").unwrap());
+    }
+
+    #[test]
+    fn test_standard_traits() {
+        let node : JsonNode = "[1,{\"a\":null}]".parse().unwrap();
+        assert_eq!(node.clone(), node);
+        assert_eq!(JsonNode::default(), JsonNode::Null);
+        let mut nodes = [node];
+        assert_eq!(mem::take(&mut nodes[0])[1]["a"], JsonNode::Null);
+        assert!(nodes[0].is_null());
+        assert!("[1,".parse::<JsonNode>().is_err());
+        assert_eq!("42".parse::<JsonNode>(), Ok(json!(42)));
+        assert_eq!(" -1.5\n".parse::<JsonNode>(), Ok(json!(-1.5)));
+        assert_eq!("[1] [2]".parse::<JsonNode>().unwrap_err().reason, Reason::TrailingData);
+        assert_eq!(format!("{:?}", JsonNode::Array(vec![JsonNode::Null, JsonNode::from(true)])), "Array([Null, Boolean(true)])");
+    }
+
+    #[test]
+    fn test_drops_deep_nesting() {
+        let mut node = JsonNode::Null;
+        for i in 0..1_000_000 {
+            node = if i % 2 == 0 {
+                JsonNode::Array(vec![node, JsonNode::from(1)])
+            } else {
+                let mut object = Object::default();
+                object.insert("k".to_string(), node);
+                JsonNode::Object(object)
             }
-            let r = write!(f, "\"{}\":{}", k, v);
-            match r { Err(_) => return r, Ok(_) => () }
-            comma = true
         }
+        drop(node)
+    }
+
+    #[test]
+    fn test_accessors() {
+        let mut node = JsonNode::from_str("{\"name\":\"x\",\"port\":8080,\"ratio\":0.5,\"on\":true,\"tags\":[\"a\",null]}").unwrap();
+        assert_eq!(node.get("name").and_then(JsonNode::as_str), Some("x"));
+        assert_eq!(node.get("port").and_then(JsonNode::as_u64), Some(8080));
+        assert_eq!(node.get("ratio").and_then(JsonNode::as_i64), None);
+        assert_eq!(node.get("ratio").and_then(JsonNode::as_f64), Some(0.5));
+        assert_eq!(node.get("on").and_then(JsonNode::as_bool), Some(true));
+        assert!(node.get("tags").and_then(|tags| tags.get_index(1)).is_some_and(JsonNode::is_null));
+        assert_eq!(node.get("tags").and_then(|tags| tags.get_index(2)), None);
+        assert_eq!(node.get("name").and_then(|name| name.get("x")), None);
+        assert!(node.is_object() && !node.is_array() && node.as_object().is_some_and(|o| o.len() == 5));
+
+        if let Some(tags) = node.get_mut("tags").and_then(JsonNode::as_array_mut) {
+            tags.push(JsonNode::Boolean(false))
+        }
+        *node.get_mut("tags").and_then(|tags| tags.get_index_mut(0)).unwrap() = JsonNode::Null;
+        assert_eq!(node.get("tags").unwrap().to_string(), "[null,null,false]");
+    }
+
+    #[test]
+    fn test_index() {
+        let node = JsonNode::from_str("{\"config\":{\"port\":80,\"hosts\":[\"a\",\"b\"]}}").unwrap();
+        assert_eq!(node["config"]["port"], JsonNode::Number(80.into()));
+        assert_eq!(node["config"]["hosts"][1], JsonNode::String("b".to_string()));
+        assert!(node["config"]["hosts"][2].is_null());
+        assert!(node["missing"]["deeper"][0].is_null());
+
+        let mut built = JsonNode::Null;
+        built["config"]["port"] = JsonNode::Number(8080.into());
+        built["config"]["hosts"] = JsonNode::Array(vec![JsonNode::Null]);
+        built["config"]["hosts"][0] = JsonNode::String("c".to_string());
+        assert_eq!(built, JsonNode::from_str("{\"config\":{\"port\":8080,\"hosts\":[\"c\"]}}").unwrap());
+    }
+
+    #[test]
+    #[should_panic(expected = "out of bounds")]
+    fn test_index_mut_past_the_end() {
+        let mut node = JsonNode::Array(Vec::new());
+        node[0] = JsonNode::Null
+    }
+
+    #[test]
+    fn test_mutation() {
+        let mut node = JsonNode::Null;
+        node.insert("list", JsonNode::Null);
+        node["list"].push(JsonNode::Number(1.into()));
+        node["list"].push(JsonNode::Number(3.into()));
+        node["list"].insert_at(1, JsonNode::Number(2.into()));
+        assert_eq!(node["list"].to_string(), "[1,2,3]");
+        assert_eq!(node["list"].remove_at(0), Some(JsonNode::Number(1.into())));
+        assert_eq!(node["list"].remove_at(5), None);
+
+        *node.entry("count").or_insert(JsonNode::Number(0.into())) = JsonNode::Number(1.into());
+        assert_eq!(node.insert("count", JsonNode::Boolean(true)), Some(JsonNode::Number(1.into())));
+        assert_eq!(node.remove("count"), Some(JsonNode::Boolean(true)));
+        assert_eq!(node.remove("count"), None);
+
+        let list = node["list"].take();
+        assert_eq!(list.to_string(), "[2,3]");
+        assert_eq!(node.replace(list), JsonNode::from_str("{\"list\":null}").unwrap());
+        assert_eq!(node.to_string(), "[2,3]");
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot push onto a non-array")]
+    fn test_push_onto_object() {
+        JsonNode::from_str("{}").unwrap().push(JsonNode::Null)
+    }
+
+    #[test]
+    fn test_pointer() {
+        let mut node = JsonNode::from_str("{\"a\":{\"b/c\":[0,{\"m~n\":true}]},\"\":1}").unwrap();
+        assert_eq!(node.pointer("/a/b~1c/1/m~0n"), Some(&JsonNode::Boolean(true)));
+        assert_eq!(node.pointer("/"), Some(&JsonNode::Number(1.into())));
+        assert_eq!(node.pointer(""), Some(&node.clone()));
+        assert_eq!(node.pointer("/a/b~1c/2"), None);
+        assert_eq!(node.pointer("a"), None);
+
+        *node.pointer_mut("/a/b~1c/0").unwrap() = JsonNode::Null;
+        assert!(node["a"]["b/c"][0].is_null());
+        assert!(node.pointer_mut("/a/x").is_none());
+    }
+
+    #[test]
+    fn test_apply_patch() {
+        let mut node = JsonNode::from_str("{\"a\":[1,2]}").unwrap();
+        let patch = JsonNode::from_str("[{\"op\":\"add\",\"path\":\"/a/-\",\"value\":3},{\"op\":\"move\",\"from\":\"/a\",\"path\":\"/b\"}]").unwrap();
+        node.apply_patch(&patch).unwrap();
+        assert_eq!(node.to_string(), "{\"b\":[1,2,3]}");
+
+        let failing = JsonNode::from_str("[{\"op\":\"remove\",\"path\":\"/b/0\"},{\"op\":\"test\",\"path\":\"/b/0\",\"value\":1}]").unwrap();
+        assert_eq!(node.apply_patch(&failing).unwrap_err().index, 1);
+        assert_eq!(node.to_string(), "{\"b\":[1,2,3]}");
+        assert!(node.apply_patch(&JsonNode::from_str("[{\"op\":\"jump\"}]").unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_merge_patch() {
+        let mut config = JsonNode::from_str("{\"server\":{\"port\":80,\"tls\":true},\"debug\":true,\"tags\":[1]}").unwrap();
+        config.merge_patch(&JsonNode::from_str("{\"server\":{\"port\":8080,\"tls\":null},\"debug\":null,\"tags\":[2]}").unwrap());
+        assert_eq!(config, JsonNode::from_str("{\"server\":{\"port\":8080},\"tags\":[2]}").unwrap());
+        config.merge_patch(&JsonNode::Boolean(false));
+        assert_eq!(config, JsonNode::Boolean(false));
+    }
+
+    #[test]
+    fn test_find_all() {
+        let node = JsonNode::from_str("{\"b\":[\"long text\",{\"c\":\"tiny\"}],\"a\":\"also long\",\"n\":12}").unwrap();
+        let long_strings = node.find_all(|_, value| match value {
+            JsonNode::String(s) => s.len() > 5,
+            _ => false
+        });
+        let paths : Vec<String> = long_strings.iter().map(|(path, _)| path.to_string()).collect();
+        assert_eq!(paths, vec!["/a", "/b/0"]);
+        assert_eq!(long_strings[1].1, &JsonNode::String("long text".to_string()));
+
+        let under_b = node.find_all(|path, _| path.tokens().first().is_some_and(|token| token == "b"));
+        assert_eq!(under_b.len(), 4);
+        assert_eq!(node.find_all(|path, _| path.is_root()).len(), 1);
+    }
+
+    #[test]
+    fn test_approx_heap_bytes_of_scalars() {
+        assert_eq!(JsonNode::Null.approx_heap_bytes(), 0);
+        assert_eq!(JsonNode::Number(1.0.into()).approx_heap_bytes(), 0);
+        assert_eq!(JsonNode::String(String::with_capacity(10)).approx_heap_bytes(), 10);
+    }
+
+    #[test]
+    fn test_approx_heap_bytes_of_containers() {
+        let array = JsonNode::Array(vec![JsonNode::String(String::with_capacity(7))]);
+        assert!(array.approx_heap_bytes() >= mem::size_of::<JsonNode>() + 7);
 
-        write!(f, "}}")
+        let mut object = Object::default();
+        object.insert("key".to_string(), array);
+        let object = JsonNode::Object(object);
+        assert!(object.approx_heap_bytes() > mem::size_of::<JsonNode>() * 2 + 7 + 3);
     }
 }
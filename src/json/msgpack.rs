@@ -0,0 +1,349 @@
+// MessagePack, with the `msgpack` feature: encoding a `JsonNode` and
+// decoding one back, as CBOR does in `cbor`.
+
+use std::fmt;
+use crate::json::{maybe_grow, JsonNode, Number, Object, DEFAULT_MAX_DEPTH};
+use crate::json::cbor::base64url;
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct MsgpackError {
+    /// Where in the input the object that could not be read starts.
+    pub offset: usize,
+    pub message: &'static str
+}
+
+impl fmt::Display for MsgpackError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "byte {}: {}", self.offset, self.message)
+    }
+}
+
+/// `node` as MessagePack, every value in its shortest format: integers as
+/// the smallest that hold them, doubles as float 32 when that loses nothing,
+/// and members in key order unless the `small_objects` or `preserve_order`
+/// feature keeps their order.
+pub fn encode(node : &JsonNode) -> Vec<u8> {
+    let mut out = Vec::new();
+    encode_into(node, &mut out);
+    out
+}
+
+fn encode_into(node : &JsonNode, out : &mut Vec<u8>) {
+    maybe_grow(|| match node {
+        JsonNode::Null => out.push(0xc0),
+        JsonNode::Boolean(b) => out.push(if *b { 0xc3 } else { 0xc2 }),
+        JsonNode::Number(n) => number(*n, out),
+        JsonNode::String(s) => string(s, out),
+        JsonNode::Array(items) => {
+            length(items.len(), 0x90, 16, 0xdc, out);
+            for item in items {
+                encode_into(item, out)
+            }
+        },
+        JsonNode::Object(members) => {
+            let mut members : Vec<(&String, &JsonNode)> = members.iter().collect();
+            if !cfg!(feature = "small_objects") {
+                members.sort_by_key(|&(key, _)| key)
+            }
+            length(members.len(), 0x80, 16, 0xde, out);
+            for (key, value) in members {
+                string(key, out);
+                encode_into(value, out)
+            }
+        }
+    })
+}
+
+fn number(n : Number, out : &mut Vec<u8>) {
+    match (n.as_u64(), n.as_i64()) {
+        (Some(u), _) if u < 0x80 => out.push(u as u8),
+        (Some(u), _) if u <= 0xff => out.extend_from_slice(&[0xcc, u as u8]),
+        (Some(u), _) if u <= 0xffff => {
+            out.push(0xcd);
+            out.extend_from_slice(&(u as u16).to_be_bytes())
+        },
+        (Some(u), _) if u <= 0xffff_ffff => {
+            out.push(0xce);
+            out.extend_from_slice(&(u as u32).to_be_bytes())
+        },
+        (Some(u), _) => {
+            out.push(0xcf);
+            out.extend_from_slice(&u.to_be_bytes())
+        },
+        (None, Some(i)) if i >= -32 => out.push(i as u8),
+        (None, Some(i)) if i >= i8::MIN as i64 => out.extend_from_slice(&[0xd0, i as u8]),
+        (None, Some(i)) if i >= i16::MIN as i64 => {
+            out.push(0xd1);
+            out.extend_from_slice(&(i as i16).to_be_bytes())
+        },
+        (None, Some(i)) if i >= i32::MIN as i64 => {
+            out.push(0xd2);
+            out.extend_from_slice(&(i as i32).to_be_bytes())
+        },
+        (None, Some(i)) => {
+            out.push(0xd3);
+            out.extend_from_slice(&i.to_be_bytes())
+        },
+        (None, None) => {
+            let f = n.as_f64();
+            if (f as f32) as f64 == f || f.is_nan() {
+                out.push(0xca);
+                out.extend_from_slice(&(f as f32).to_be_bytes())
+            } else {
+                out.push(0xcb);
+                out.extend_from_slice(&f.to_be_bytes())
+            }
+        }
+    }
+}
+
+fn string(s : &str, out : &mut Vec<u8>) {
+    if s.len() < 32 {
+        out.push(0xa0 | s.len() as u8)
+    } else if s.len() <= 0xff {
+        out.extend_from_slice(&[0xd9, s.len() as u8])
+    } else {
+        length(s.len(), 0, 0, 0xda, out)
+    }
+    out.extend_from_slice(s.as_bytes())
+}
+
+// the marker and length of a container or long string: `fixed` holding the
+// length itself below `limit`, and then `wide` for 16 bits and the marker
+// after it for 32
+fn length(n : usize, fixed : u8, limit : usize, wide : u8, out : &mut Vec<u8>) {
+    if n < limit {
+        out.push(fixed | n as u8)
+    } else if n <= 0xffff {
+        out.push(wide);
+        out.extend_from_slice(&(n as u16).to_be_bytes())
+    } else {
+        out.push(wide + 1);
+        out.extend_from_slice(&(n as u32).to_be_bytes())
+    }
+}
+
+/// Reads one MessagePack object, which must take up all of `input`. As in
+/// `cbor::decode`, binary data becomes base64url text and map keys that are
+/// not strings their JSON text; timestamps become seconds since the epoch,
+/// other extension types the base64url text of their data, and non-finite
+/// floats null. Nesting deeper than `DEFAULT_MAX_DEPTH` fails.
+pub fn decode(input : &[u8]) -> Result<JsonNode, MsgpackError> {
+    let mut decoder = Decoder { input, position: 0 };
+    let node = decoder.object(0)?;
+    if decoder.position < input.len() {
+        return Err(MsgpackError { offset: decoder.position, message: "unexpected data after the object" })
+    }
+    Ok(node)
+}
+
+struct Decoder<'a> {
+    input: &'a [u8],
+    position: usize
+}
+
+impl<'a> Decoder<'a> {
+    fn take(&mut self, length : usize, start : usize) -> Result<&'a [u8], MsgpackError> {
+        let end = self.position.checked_add(length).filter(|&end| end <= self.input.len());
+        match end {
+            Some(end) => {
+                let bytes = &self.input[self.position..end];
+                self.position = end;
+                Ok(bytes)
+            },
+            None => Err(MsgpackError { offset: start, message: "the input ends inside the object" })
+        }
+    }
+
+    // a big-endian unsigned integer of `length` bytes
+    fn unsigned(&mut self, length : usize, start : usize) -> Result<u64, MsgpackError> {
+        Ok(self.take(length, start)?.iter().fold(0, |n, &b| n << 8 | b as u64))
+    }
+
+    // the same, as a length of data in the input
+    fn length(&mut self, length : usize, start : usize) -> Result<usize, MsgpackError> {
+        self.unsigned(length, start).map(|n| n as usize)
+    }
+
+    fn object(&mut self, depth : usize) -> Result<JsonNode, MsgpackError> {
+        let start = self.position;
+        let marker = self.take(1, start)?[0];
+        // `depth` containers hold this object
+        if depth == DEFAULT_MAX_DEPTH && matches!(marker, 0x80..=0x9f | 0xdc..=0xdf) {
+            return Err(MsgpackError { offset: start, message: "objects nest too deeply" })
+        }
+        let signed = |n : u64, bits : u32| JsonNode::from(((n << (64 - bits)) as i64) >> (64 - bits));
+        maybe_grow(|| Ok(match marker {
+            0x00..=0x7f => JsonNode::from(marker as u64),
+            0xe0..=0xff => JsonNode::from(marker as i8 as i64),
+            0xc0 => JsonNode::Null,
+            0xc2 => JsonNode::Boolean(false),
+            0xc3 => JsonNode::Boolean(true),
+            0xcc..=0xcf => JsonNode::from(self.unsigned(1 << (marker - 0xcc), start)?),
+            0xd0..=0xd3 => {
+                let bytes = 1 << (marker - 0xd0);
+                signed(self.unsigned(bytes, start)?, 8 * bytes as u32)
+            },
+            0xca => finite(f32::from_bits(self.unsigned(4, start)? as u32) as f64),
+            0xcb => finite(f64::from_bits(self.unsigned(8, start)?)),
+            0xa0..=0xbf => self.text((marker & 0x1f) as usize, start)?,
+            0xd9..=0xdb => {
+                let length = self.length(1 << (marker - 0xd9), start)?;
+                self.text(length, start)?
+            },
+            0xc4..=0xc6 => {
+                let length = self.length(1 << (marker - 0xc4), start)?;
+                JsonNode::String(base64url(self.take(length, start)?))
+            },
+            0x90..=0x9f => self.array((marker & 0x0f) as usize, depth)?,
+            0xdc | 0xdd => {
+                let length = self.length(2 << (marker - 0xdc), start)?;
+                self.array(length, depth)?
+            },
+            0x80..=0x8f => self.map((marker & 0x0f) as usize, depth)?,
+            0xde | 0xdf => {
+                let length = self.length(2 << (marker - 0xde), start)?;
+                self.map(length, depth)?
+            },
+            0xd4..=0xd8 => self.extension(1 << (marker - 0xd4), start)?,
+            0xc7..=0xc9 => {
+                let length = self.length(1 << (marker - 0xc7), start)?;
+                self.extension(length, start)?
+            },
+            _ => return Err(MsgpackError { offset: start, message: "0xc1 is never used" })
+        }))
+    }
+
+    fn text(&mut self, length : usize, start : usize) -> Result<JsonNode, MsgpackError> {
+        match String::from_utf8(self.take(length, start)?.to_vec()) {
+            Ok(text) => Ok(JsonNode::String(text)),
+            Err(_) => Err(MsgpackError { offset: start, message: "a string is not UTF-8" })
+        }
+    }
+
+    fn array(&mut self, length : usize, depth : usize) -> Result<JsonNode, MsgpackError> {
+        // every object takes at least a byte, which bounds what to reserve
+        let mut items = Vec::with_capacity(length.min(self.input.len() - self.position));
+        for _ in 0..length {
+            items.push(self.object(depth + 1)?)
+        }
+        Ok(JsonNode::Array(items))
+    }
+
+    fn map(&mut self, length : usize, depth : usize) -> Result<JsonNode, MsgpackError> {
+        let mut members = Object::default();
+        for _ in 0..length {
+            let mut key = self.object(depth + 1)?;
+            let key = match &mut key {
+                JsonNode::String(key) => std::mem::take(key),
+                _ => key.to_string()
+            };
+            members.insert(key, self.object(depth + 1)?);
+        }
+        Ok(JsonNode::Object(members))
+    }
+
+    // an extension type with `length` bytes of data
+    fn extension(&mut self, length : usize, start : usize) -> Result<JsonNode, MsgpackError> {
+        let kind = self.take(1, start)?[0] as i8;
+        let data = self.take(length, start)?;
+        let n = data.iter().fold(0, |n, &b| n << 8 | b as u64);
+        Ok(match (kind, length) {
+            // timestamps: seconds, or nanoseconds and seconds
+            (-1, 4) => JsonNode::from(n),
+            (-1, 8) => seconds((n & 0x3_ffff_ffff) as i64, n >> 34),
+            (-1, 12) => {
+                let nanoseconds = u32::from_be_bytes([data[0], data[1], data[2], data[3]]) as u64;
+                seconds(i64::from_be_bytes([data[4], data[5], data[6], data[7], data[8], data[9], data[10], data[11]]), nanoseconds)
+            },
+            _ => JsonNode::String(base64url(data))
+        })
+    }
+}
+
+fn seconds(seconds : i64, nanoseconds : u64) -> JsonNode {
+    if nanoseconds == 0 {
+        JsonNode::from(seconds)
+    } else {
+        JsonNode::from(seconds as f64 + nanoseconds as f64 / 1e9)
+    }
+}
+
+fn finite(f : f64) -> JsonNode {
+    if f.is_finite() { JsonNode::from(f) } else { JsonNode::Null }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn json(text : &str) -> JsonNode {
+        JsonNode::from_str(text).unwrap()
+    }
+
+    #[test]
+    fn test_numbers_take_their_shortest_format() {
+        let cases : &[(JsonNode, &[u8])] = &[
+            (JsonNode::from(0), &[0x00]),
+            (JsonNode::from(127), &[0x7f]),
+            (JsonNode::from(128), &[0xcc, 0x80]),
+            (JsonNode::from(256), &[0xcd, 0x01, 0x00]),
+            (JsonNode::from(65536), &[0xce, 0x00, 0x01, 0x00, 0x00]),
+            (JsonNode::from(u64::MAX), &[0xcf, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]),
+            (JsonNode::from(-32), &[0xe0]),
+            (JsonNode::from(-33), &[0xd0, 0xdf]),
+            (JsonNode::from(-129), &[0xd1, 0xff, 0x7f]),
+            (JsonNode::from(-32769), &[0xd2, 0xff, 0xff, 0x7f, 0xff]),
+            (JsonNode::from(i64::MIN), &[0xd3, 0x80, 0, 0, 0, 0, 0, 0, 0]),
+            (JsonNode::from(1.5), &[0xca, 0x3f, 0xc0, 0x00, 0x00]),
+            (JsonNode::from(0.1), &[0xcb, 0x3f, 0xb9, 0x99, 0x99, 0x99, 0x99, 0x99, 0x9a])
+        ];
+        for (node, bytes) in cases {
+            assert_eq!(&encode(node)[..], *bytes);
+            assert_eq!(&decode(bytes).unwrap(), node);
+            assert_eq!(decode(bytes).unwrap().to_string(), node.to_string());
+        }
+        assert_eq!(decode(&[0xd0, 0x05]).unwrap(), JsonNode::from(5));
+        assert_eq!(decode(&[0xcb, 0x7f, 0xf0, 0, 0, 0, 0, 0, 0]).unwrap(), JsonNode::Null);
+    }
+
+    #[test]
+    fn test_round_trips_strings_and_containers() {
+        let long = |n : usize| JsonNode::String("\u{e9}".repeat(n / 2));
+        for (node, marker) in [(long(30), 0xa0 | 30), (long(32), 0xd9), (long(256), 0xda), (long(65536), 0xdb)] {
+            let bytes = encode(&node);
+            assert_eq!(bytes[0], marker);
+            assert_eq!(decode(&bytes).unwrap(), node);
+        }
+        let node = json("{\"users\":[{\"name\":\"a\",\"tags\":[],\"meta\":{}},{\"name\":\"\",\"age\":null,\"ok\":true}],\"n\":[-1,2.25,1e300]}");
+        assert_eq!(decode(&encode(&node)).unwrap(), node);
+        let wide = JsonNode::Array(vec![JsonNode::from(1); 16]);
+        assert_eq!(encode(&wide)[..3], [0xdc, 0x00, 0x10]);
+        assert_eq!(decode(&encode(&wide)).unwrap(), wide);
+        assert_eq!(encode(&json("{\"a\":[false]}")), [0x81, 0xa1, b'a', 0x91, 0xc2]);
+    }
+
+    #[test]
+    fn test_decodes_what_json_lacks() {
+        // binary data, an integer key, timestamps and another extension type
+        let input = [0x85, 0xc4, 0x02, 0xfb, 0xff, 0x01, 0x07, 0xa1, b'n', 0xa1, b't', 0xd6, 0xff, 0x00, 0x00, 0x00, 0x3c,
+            0xa1, b'u', 0xd7, 0xff, 0x77, 0x35, 0x94, 0x00, 0x00, 0x00, 0x00, 0x3c, 0xa1, b'x', 0xd4, 0x05, 0x2a];
+        assert_eq!(decode(&input).unwrap(), json("{\"-_8\":1,\"7\":\"n\",\"t\":60,\"u\":60.5,\"x\":\"Kg\"}"));
+        let input = [0xc7, 0x0c, 0xff, 0x00, 0x00, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff];
+        assert_eq!(decode(&input).unwrap(), JsonNode::from(-1));
+    }
+
+    #[test]
+    fn test_reports_errors() {
+        let error = |input : &[u8]| decode(input).unwrap_err();
+        assert_eq!(error(&[0x92, 0x01]), MsgpackError { offset: 2, message: "the input ends inside the object" });
+        assert_eq!(error(&[0x01, 0x02]).message, "unexpected data after the object");
+        assert_eq!(error(&[0xa2, 0xff, 0xfe]).message, "a string is not UTF-8");
+        assert_eq!(error(&[0xc1]).message, "0xc1 is never used");
+        assert_eq!(error(&[0xdb, 0xff, 0xff, 0xff, 0xff]).offset, 0);
+        if !cfg!(feature = "grow_stack") {
+            assert_eq!(error(&[0x91; 1000]).message, "objects nest too deeply");
+        }
+    }
+}
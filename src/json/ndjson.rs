@@ -0,0 +1,189 @@
+use std::fmt;
+use std::io::{BufRead, BufReader, Read, Split};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::thread;
+use crate::json::JsonNode;
+
+/// Lines handed to a worker at a time, to keep channel traffic low.
+const BATCH : usize = 256;
+
+type Lines = Vec<(usize, Vec<u8>)>;
+type Records = Vec<Result<JsonNode, NdjsonError>>;
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct NdjsonError {
+    /// Counting from 1.
+    pub line: usize,
+    pub message: String
+}
+
+impl fmt::Display for NdjsonError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// Parses one newline-delimited JSON record; blank lines give None.
+pub fn parse_line(line : &[u8], number : usize) -> Option<Result<JsonNode, NdjsonError>> {
+    let error = |message : String| Some(Err(NdjsonError { line: number, message }));
+    if line.iter().all(|b| b" \t\r".contains(b)) {
+        return None
+    }
+    // a terminator completes a number at the end of the line
+    let mut terminated = line.to_vec();
+    terminated.push(b'\n');
+    match JsonNode::parse_prefix(&terminated) {
+        Ok(Some((json, length))) if terminated[length..].iter().all(|b| b" \t\r\n".contains(b)) => Some(Ok(json)),
+        Ok(Some(_)) => error("unexpected data after the record".to_string()),
+        Ok(None) => error("the record ends too early".to_string()),
+        Err(reason) => error(reason.to_string())
+    }
+}
+
+/// Reads newline-delimited JSON one line at a time, yielding a document per
+/// non-blank line and an error, with its line number, for each line that
+/// does not hold exactly one. A read error ends the records.
+pub struct NdjsonReader<R : Read> {
+    lines: Split<BufReader<R>>,
+    number: usize,
+    failed: bool
+}
+
+impl<R : Read> NdjsonReader<R> {
+    pub fn new(reader : R) -> NdjsonReader<R> {
+        NdjsonReader { lines: BufReader::new(reader).split(b'\n'), number: 0, failed: false }
+    }
+}
+
+impl<R : Read> Iterator for NdjsonReader<R> {
+    type Item = Result<JsonNode, NdjsonError>;
+
+    fn next(&mut self) -> Option<Result<JsonNode, NdjsonError>> {
+        while !self.failed {
+            self.number += 1;
+            match self.lines.next()? {
+                Ok(line) => if let Some(record) = parse_line(&line, self.number) {
+                    return Some(record)
+                },
+                Err(reason) => {
+                    self.failed = true;
+                    return Some(Err(NdjsonError { line: self.number, message: reason.to_string() }))
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Parses the records of `reader` on `num_threads` worker threads. Records
+/// arrive on the returned channel in input order, and the channel closes
+/// after the last one or after the first read error.
+pub fn parse_ndjson_parallel<R>(reader : R, num_threads : usize) -> Receiver<Result<JsonNode, NdjsonError>>
+    where R : Read + Send + 'static
+{
+    let workers : Vec<(SyncSender<Lines>, Receiver<Records>)> = (0..num_threads.max(1)).map(|_| {
+        let (batches, inbox) = sync_channel::<Lines>(2);
+        let (outbox, results) = sync_channel(2);
+        thread::spawn(move || {
+            for batch in inbox {
+                let parsed = batch.iter().filter_map(|(number, line)| parse_line(line, *number)).collect();
+                if outbox.send(parsed).is_err() {
+                    break
+                }
+            }
+        });
+        (batches, results)
+    }).collect();
+    let (senders, receivers) : (Vec<_>, Vec<_>) = workers.into_iter().unzip();
+
+    // batches go out round-robin and are collected in the same rotation,
+    // which keeps records in order; a read error follows the records before it
+    let (output, records) = sync_channel(BATCH);
+    let (errors, read_errors) = sync_channel::<NdjsonError>(1);
+    let (dispatch, dispatched) = sync_channel::<usize>(num_threads.max(1) * 2);
+    thread::spawn(move || {
+        let mut lines = BufReader::new(reader).split(b'\n');
+        let mut number = 0;
+        for worker in (0..senders.len()).cycle() {
+            let mut batch = Vec::with_capacity(BATCH);
+            let mut failure = None;
+            while batch.len() < BATCH {
+                match lines.next() {
+                    Some(Ok(line)) => {
+                        number += 1;
+                        batch.push((number, line))
+                    },
+                    Some(Err(reason)) => {
+                        failure = Some(NdjsonError { line: number + 1, message: reason.to_string() });
+                        break
+                    },
+                    None => break
+                }
+            }
+            let last = batch.len() < BATCH;
+            if !batch.is_empty() && (senders[worker].send(batch).is_err() || dispatch.send(worker).is_err()) {
+                return
+            }
+            if let Some(failure) = failure {
+                let _ = errors.send(failure);
+            }
+            if last {
+                return
+            }
+        }
+    });
+    thread::spawn(move || {
+        for worker in dispatched {
+            let batch = match receivers[worker].recv() {
+                Ok(batch) => batch,
+                Err(_) => return
+            };
+            for record in batch {
+                if output.send(record).is_err() {
+                    return
+                }
+            }
+        }
+        if let Ok(failure) = read_errors.recv() {
+            let _ = output.send(Err(failure));
+        }
+    });
+    records
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_reader() {
+        let records : Vec<Result<JsonNode, NdjsonError>> = NdjsonReader::new("{\"a\":1}\n\n 2\r\n[3\n4".as_bytes()).collect();
+        assert_eq!(records[0], Ok(json!({"a": 1})));
+        assert_eq!(records[1], Ok(json!(2)));
+        assert_eq!(records[2], Err(NdjsonError { line: 4, message: "the record ends too early".to_string() }));
+        assert_eq!(records[3], Ok(json!(4)));
+        assert_eq!(records.len(), 4);
+    }
+
+    #[test]
+    fn test_records_arrive_in_order() {
+        let input : String = (0..2000).map(|i| format!("{{\"n\":{}}}\n", i)).collect();
+        let records : Vec<JsonNode> = parse_ndjson_parallel(std::io::Cursor::new(input), 4).into_iter().map(Result::unwrap).collect();
+        assert_eq!(records.len(), 2000);
+        for (i, record) in records.iter().enumerate() {
+            assert_eq!(record.get_all("/n").unwrap()[0].1, &JsonNode::Number(i.into()));
+        }
+    }
+
+    #[test]
+    fn test_errors_carry_line_numbers() {
+        let input = "1\n\n[2]  \r\n{\"a\":\n\"x\" y\n";
+        let records : Vec<Result<JsonNode, NdjsonError>> = parse_ndjson_parallel(input.as_bytes(), 0).into_iter().collect();
+        assert_eq!(records[0], Ok(JsonNode::Number(1.0.into())));
+        assert_eq!(records[1], Ok(JsonNode::from_str("[2]").unwrap()));
+        assert_eq!(records[2].as_ref().unwrap_err().line, 4);
+        assert_eq!(records[3].as_ref().unwrap_err().to_string(), "line 5: unexpected data after the record");
+        assert_eq!(records.len(), 4);
+    }
+}
@@ -0,0 +1,367 @@
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::fmt;
+use std::num::ParseFloatError;
+use std::ops::Neg;
+use std::str::FromStr;
+
+/// A JSON number. Integers that fit in 64 bits are kept as integers, so
+/// that they survive a parse and print exactly; everything else is a
+/// double, except that with the `arbitrary_precision` feature a number no
+/// double holds, such as `0.1000000000000000000000001` or a 128-bit
+/// integer, is kept as the decimal it was written as, up to 38 significant
+/// digits. Numbers compare by value, whatever they are kept as, so `1`
+/// equals `1.0`.
+#[derive(Clone, Copy, Debug)]
+pub struct Number(Repr);
+
+#[derive(Clone, Copy, Debug)]
+enum Repr {
+    UInt(u64),
+    // always negative
+    Int(i64),
+    Float(f64),
+    // ±digits × 10^exponent, with no zero ending digits
+    #[cfg(feature = "arbitrary_precision")]
+    Decimal { negative: bool, digits: u128, exponent: i32 }
+}
+
+impl Number {
+    pub fn as_f64(self) -> f64 {
+        match self.0 {
+            Repr::UInt(u) => u as f64,
+            Repr::Int(i) => i as f64,
+            Repr::Float(f) => f,
+            #[cfg(feature = "arbitrary_precision")]
+            Repr::Decimal { negative, digits, exponent } => {
+                format!("{}{}e{}", if negative { "-" } else { "" }, digits, exponent).parse().expect("a decimal reads as a double")
+            }
+        }
+    }
+
+    /// The integer, if this is one kept as an integer that fits in an i64.
+    pub fn as_i64(self) -> Option<i64> {
+        match self.0 {
+            Repr::UInt(u) if u <= i64::MAX as u64 => Some(u as i64),
+            Repr::Int(i) => Some(i),
+            _ => None
+        }
+    }
+
+    /// The integer, if this is a non-negative one kept as an integer.
+    pub fn as_u64(self) -> Option<u64> {
+        match self.0 {
+            Repr::UInt(u) => Some(u),
+            _ => None
+        }
+    }
+
+    /// Whether this is kept as a double, or as a decimal, rather than as a
+    /// 64-bit integer.
+    pub fn is_f64(self) -> bool {
+        !matches!(self.0, Repr::UInt(_) | Repr::Int(_))
+    }
+
+    /// Whether this is kept as the decimal it was written as because no
+    /// double holds it, which only the `arbitrary_precision` feature does.
+    pub fn is_decimal(self) -> bool {
+        !matches!(self.0, Repr::UInt(_) | Repr::Int(_) | Repr::Float(_))
+    }
+
+    /// The number `lexeme` spells, which the float parser read as `nearest`.
+    #[cfg(not(feature = "arbitrary_precision"))]
+    pub(crate) fn from_lexeme(_lexeme : &[u8], nearest : f64) -> Number {
+        Number::from(nearest)
+    }
+
+    /// The number `lexeme` spells, which the float parser read as `nearest`:
+    /// that double if it prints as the same decimal, or else the decimal.
+    #[cfg(feature = "arbitrary_precision")]
+    pub(crate) fn from_lexeme(lexeme : &[u8], nearest : f64) -> Number {
+        let exact = std::str::from_utf8(lexeme).ok().and_then(decimal);
+        match exact {
+            Some((negative, digits, exponent)) if digits != 0 && exact != decimal(&format!("{:e}", nearest)) => {
+                Number(Repr::Decimal { negative, digits, exponent })
+            },
+            _ => Number::from(nearest)
+        }
+    }
+
+    /// The exact value of an integer kept as one.
+    pub(crate) fn as_i128(self) -> Option<i128> {
+        match self.0 {
+            Repr::UInt(u) => Some(u as i128),
+            Repr::Int(i) => Some(i as i128),
+            _ => None
+        }
+    }
+
+    fn from_i128(n : i128) -> Number {
+        if n >= 0 && n <= u64::MAX as i128 {
+            Number(Repr::UInt(n as u64))
+        } else if n < 0 && n >= i64::MIN as i128 {
+            Number(Repr::Int(n as i64))
+        } else {
+            Number::beyond_64_bits(n < 0, n.unsigned_abs())
+        }
+    }
+
+    #[cfg(not(feature = "arbitrary_precision"))]
+    fn beyond_64_bits(negative : bool, magnitude : u128) -> Number {
+        Number(Repr::Float(if negative { -(magnitude as f64) } else { magnitude as f64 }))
+    }
+
+    #[cfg(feature = "arbitrary_precision")]
+    fn beyond_64_bits(negative : bool, magnitude : u128) -> Number {
+        let (mut digits, mut exponent) = (magnitude, 0);
+        while digits % 10 == 0 {
+            digits /= 10;
+            exponent += 1
+        }
+        Number(Repr::Decimal { negative, digits, exponent })
+    }
+
+    // how self and other compare, if both are kept exactly
+    #[cfg(not(feature = "arbitrary_precision"))]
+    fn exact_cmp(self, other : Number) -> Option<Ordering> {
+        Some(self.as_i128()?.cmp(&other.as_i128()?))
+    }
+
+    // how self and other compare, exactly if either is a decimal
+    #[cfg(feature = "arbitrary_precision")]
+    fn exact_cmp(self, other : Number) -> Option<Ordering> {
+        if !self.is_decimal() && !other.is_decimal() {
+            return Some(self.as_i128()?.cmp(&other.as_i128()?))
+        }
+        let (a, b) = (self.as_decimal()?, other.as_decimal()?);
+        let sign = |(negative, digits, _) : (bool, u128, i32)| if digits == 0 { 0 } else if negative { -1 } else { 1 };
+        Some(match sign(a).cmp(&sign(b)) {
+            Ordering::Equal if sign(a) < 0 => compare_magnitudes(b, a),
+            Ordering::Equal if sign(a) > 0 => compare_magnitudes(a, b),
+            order => order
+        })
+    }
+
+    // ±digits × 10^exponent, taking a double as the shortest decimal that
+    // reads back as it, if it is finite
+    #[cfg(feature = "arbitrary_precision")]
+    fn as_decimal(self) -> Option<(bool, u128, i32)> {
+        match self.0 {
+            Repr::UInt(u) => Some((false, u as u128, 0)),
+            Repr::Int(i) => Some((true, i.unsigned_abs() as u128, 0)),
+            Repr::Float(f) => decimal(&format!("{:e}", f)),
+            Repr::Decimal { negative, digits, exponent } => Some((negative, digits, exponent))
+        }
+    }
+}
+
+// `s` as ±digits × 10^exponent with no zero ending digits, if it is a
+// decimal of at most 38 significant digits
+#[cfg(feature = "arbitrary_precision")]
+fn decimal(s : &str) -> Option<(bool, u128, i32)> {
+    let (negative, s) = match s.as_bytes().first() {
+        Some(b'-') => (true, &s[1..]),
+        Some(b'+') => (false, &s[1..]),
+        _ => (false, s)
+    };
+    let (mantissa, exponent) = match s.find(['e', 'E']) {
+        Some(i) => (&s[..i], s[i + 1..].parse::<i32>().ok()?),
+        None => (s, 0)
+    };
+    let (whole, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let all = format!("{}{}", whole, fraction);
+    if all.is_empty() || !all.bytes().all(|b| b.is_ascii_digit()) {
+        return None
+    }
+    let significant = all.trim_start_matches('0').trim_end_matches('0');
+    if significant.is_empty() {
+        return Some((negative, 0, 0))
+    }
+    let zeros = all.len() - all.trim_end_matches('0').len();
+    let exponent = exponent.checked_add(i32::try_from(zeros).ok()?)?.checked_sub(i32::try_from(fraction.len()).ok()?)?;
+    Some((negative, significant.parse().ok()?, exponent))
+}
+
+// how the nonzero magnitudes of two decimals compare
+#[cfg(feature = "arbitrary_precision")]
+fn compare_magnitudes(a : (bool, u128, i32), b : (bool, u128, i32)) -> Ordering {
+    if a.2 < b.2 {
+        return compare_magnitudes(b, a).reverse()
+    }
+    // line a's digits up with b's; if they overflow doing so, a is larger
+    let scale = u32::try_from(a.2 as i64 - b.2 as i64).ok().and_then(|shift| 10u128.checked_pow(shift));
+    match scale.and_then(|scale| a.1.checked_mul(scale)) {
+        Some(digits) => digits.cmp(&b.1),
+        None => Ordering::Greater
+    }
+}
+
+impl From<f64> for Number {
+    fn from(f : f64) -> Number {
+        Number(Repr::Float(f))
+    }
+}
+
+impl From<u64> for Number {
+    fn from(u : u64) -> Number {
+        Number(Repr::UInt(u))
+    }
+}
+
+impl From<i64> for Number {
+    fn from(i : i64) -> Number {
+        Number::from_i128(i as i128)
+    }
+}
+
+impl From<usize> for Number {
+    fn from(u : usize) -> Number {
+        Number(Repr::UInt(u as u64))
+    }
+}
+
+/// Beyond 64 bits a double, or with `arbitrary_precision` the exact integer.
+impl From<i128> for Number {
+    fn from(i : i128) -> Number {
+        Number::from_i128(i)
+    }
+}
+
+/// Beyond 64 bits a double, or with `arbitrary_precision` the exact integer.
+impl From<u128> for Number {
+    fn from(u : u128) -> Number {
+        match u64::try_from(u) {
+            Ok(u) => Number(Repr::UInt(u)),
+            Err(_) => Number::beyond_64_bits(false, u)
+        }
+    }
+}
+
+impl From<i32> for Number {
+    fn from(i : i32) -> Number {
+        Number::from_i128(i as i128)
+    }
+}
+
+impl FromStr for Number {
+    type Err = ParseFloatError;
+
+    /// Reads an integer exactly when it fits, and anything else as a double.
+    fn from_str(s : &str) -> Result<Number, ParseFloatError> {
+        if let Ok(u) = s.parse::<u64>() {
+            return Ok(u.into())
+        }
+        match s.parse::<i64>() {
+            // "-0" keeps its sign as a double
+            Ok(i) if i != 0 => Ok(i.into()),
+            _ => s.parse::<f64>().map(|nearest| Number::from_lexeme(s.as_bytes(), nearest))
+        }
+    }
+}
+
+impl PartialEq for Number {
+    fn eq(&self, other : &Number) -> bool {
+        self.partial_cmp(other) == Some(Ordering::Equal)
+    }
+}
+
+impl PartialOrd for Number {
+    fn partial_cmp(&self, other : &Number) -> Option<Ordering> {
+        self.exact_cmp(*other).or_else(|| self.as_f64().partial_cmp(&other.as_f64()))
+    }
+}
+
+impl Neg for Number {
+    type Output = Number;
+
+    fn neg(self) -> Number {
+        match (self.0, self.as_i128()) {
+            #[cfg(feature = "arbitrary_precision")]
+            (Repr::Decimal { negative, digits, exponent }, _) => Number(Repr::Decimal { negative: !negative, digits, exponent }),
+            // an integer zero has no sign to flip
+            (_, Some(0)) => Number(Repr::Float(-0.0)),
+            (_, Some(n)) => Number::from_i128(-n),
+            (_, None) => Number(Repr::Float(-self.as_f64()))
+        }
+    }
+}
+
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Repr::UInt(u) => write!(f, "{}", u),
+            Repr::Int(i) => write!(f, "{}", i),
+            Repr::Float(n) => write!(f, "{}", n),
+            // positionally unless that takes more than 21 zeros, as a double
+            // would be written
+            #[cfg(feature = "arbitrary_precision")]
+            Repr::Decimal { negative, digits, exponent } => {
+                if negative {
+                    f.write_str("-")?
+                }
+                let digits = digits.to_string();
+                // how many digits come before the point
+                let point = digits.len() as i64 + exponent as i64;
+                if (0..=21).contains(&exponent) {
+                    write!(f, "{}{}", digits, "0".repeat(exponent as usize))
+                } else if exponent < 0 && point > 0 {
+                    write!(f, "{}.{}", &digits[..point as usize], &digits[point as usize..])
+                } else if exponent < 0 && point > -6 {
+                    write!(f, "0.{}{}", "0".repeat(-point as usize), digits)
+                } else if digits.len() > 1 {
+                    write!(f, "{}.{}e{}", &digits[..1], &digits[1..], point - 1)
+                } else {
+                    write!(f, "{}e{}", digits, point - 1)
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_integers_stay_exact() {
+        let big = Number::from(9007199254740993u64);
+        assert_eq!(big.to_string(), "9007199254740993");
+        assert_ne!(big, Number::from(9007199254740992u64));
+        assert_eq!(Number::from(i64::MIN).as_i64(), Some(i64::MIN));
+        assert_eq!(Number::from(u64::MAX).as_i64(), None);
+        let expected = if cfg!(feature = "arbitrary_precision") { "-18446744073709551615" } else { "-18446744073709552000" };
+        assert_eq!((-Number::from(u64::MAX)).to_string(), expected);
+        assert_eq!((-Number::from(5)).as_i64(), Some(-5));
+    }
+
+    #[test]
+    fn test_compares_by_value() {
+        assert_eq!(Number::from(1), Number::from(1.0));
+        assert!(Number::from(-1) < Number::from(0.5));
+        assert!(Number::from(f64::NAN) != Number::from(f64::NAN));
+        assert_eq!(Number::from(2.5).to_string(), "2.5");
+        assert!((-Number::from(0)).as_f64().is_sign_negative());
+    }
+
+    #[test]
+    #[cfg(feature = "arbitrary_precision")]
+    fn test_arbitrary_precision() {
+        let round_trip = |text : &str| text.parse::<Number>().unwrap().to_string();
+        for text in ["0.1000000000000000000000001", "170141183460469231731687303715884105727", "-340282366920938463463374607431768211455",
+            "1e400", "-2.5e-400", "0.00012345678901234567890123"] {
+            assert_eq!(round_trip(text), text);
+            assert!(text.parse::<Number>().unwrap().is_decimal());
+        }
+        assert_eq!(round_trip("123456789012345678901234.5e-10"), "12345678901234.56789012345");
+        assert!(!"0.1".parse::<Number>().unwrap().is_decimal());
+        assert!(!"1e23".parse::<Number>().unwrap().is_decimal());
+
+        let n = |text : &str| text.parse::<Number>().unwrap();
+        assert!(n("0.1000000000000000000000001") > n("0.1000000000000000000000000"));
+        assert!(n("0.1000000000000000000000001") != n("0.1"));
+        assert!(n("-18446744073709551616") < n("-18446744073709551615"));
+        assert!(n("1e400") > n("170141183460469231731687303715884105727"));
+        assert_eq!(-n("-1e400"), n("10e399"));
+        assert_eq!(n("1e400").as_f64(), f64::INFINITY);
+    }
+}
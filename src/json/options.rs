@@ -0,0 +1,274 @@
+/// What the parser does with a member name that an object repeats.
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub enum DuplicateKeys {
+    /// Keeps the value of the first, ignoring the rest: the default.
+    #[default]
+    FirstWins,
+    /// Keeps the value of the last, as JavaScript's `JSON.parse` does, in
+    /// the place of the first.
+    LastWins,
+    /// Rejects the document, reporting the name and where it repeats.
+    Error
+}
+
+/// How deep containers may nest by default. The parser keeps open
+/// containers on the heap and dropping a document takes it apart without
+/// recursion, but printing, cloning and comparing recurse into each
+/// container, so without the `grow_stack` feature the stack limits how deep
+/// a document can safely go; with it there is no limit.
+#[cfg(not(feature = "grow_stack"))]
+pub const DEFAULT_MAX_DEPTH : usize = 128;
+#[cfg(feature = "grow_stack")]
+pub const DEFAULT_MAX_DEPTH : usize = usize::MAX;
+
+/// Which departures from strict RFC 8259 the parser accepts, as passed to
+/// `JsonNode::from_str_with`, what to do with repeated member names and how
+/// deep containers may nest. The default accepts none of the departures,
+/// keeps the first of repeated members and allows `DEFAULT_MAX_DEPTH`.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct ParserOptions {
+    pub trailing_commas: bool,
+    pub single_quotes: bool,
+    pub unquoted_keys: bool,
+    pub non_finite_numbers: bool,
+    pub comments: bool,
+    pub hex_numbers: bool,
+    pub plus_signs: bool,
+    pub bare_decimal_points: bool,
+    pub multiline_strings: bool,
+    pub extra_whitespace: bool,
+    pub duplicate_keys: DuplicateKeys,
+    pub max_depth: usize
+}
+
+impl Default for ParserOptions {
+    fn default() -> ParserOptions {
+        ParserOptions {
+            trailing_commas: false,
+            single_quotes: false,
+            unquoted_keys: false,
+            non_finite_numbers: false,
+            comments: false,
+            hex_numbers: false,
+            plus_signs: false,
+            bare_decimal_points: false,
+            multiline_strings: false,
+            extra_whitespace: false,
+            duplicate_keys: DuplicateKeys::FirstWins,
+            max_depth: DEFAULT_MAX_DEPTH
+        }
+    }
+}
+
+impl ParserOptions {
+    /// Strict RFC 8259.
+    pub fn new() -> ParserOptions {
+        ParserOptions::default()
+    }
+
+    /// Every lenient behavior at once, which together make up JSON5.
+    pub fn lenient() -> ParserOptions {
+        ParserOptions {
+            trailing_commas: true,
+            single_quotes: true,
+            unquoted_keys: true,
+            non_finite_numbers: true,
+            comments: true,
+            hex_numbers: true,
+            plus_signs: true,
+            bare_decimal_points: true,
+            multiline_strings: true,
+            extra_whitespace: true,
+            ..ParserOptions::default()
+        }
+    }
+
+    /// The JSON5 grammar (https://spec.json5.org), for configuration
+    /// written by hand, except that unquoted keys cannot contain `\u` escapes.
+    pub fn json5() -> ParserOptions {
+        ParserOptions::lenient()
+    }
+
+    /// JSON with comments, as in tsconfig.json and VS Code settings, which
+    /// also tolerate trailing commas.
+    pub fn jsonc() -> ParserOptions {
+        ParserOptions::new().comments(true).trailing_commas(true)
+    }
+
+    /// Allows a `,` after the last element of an array or member of an object.
+    pub fn trailing_commas(mut self, allow : bool) -> ParserOptions {
+        self.trailing_commas = allow;
+        self
+    }
+
+    /// Allows strings and keys in `'single quotes'`, in which `\'` escapes a quote.
+    pub fn single_quotes(mut self, allow : bool) -> ParserOptions {
+        self.single_quotes = allow;
+        self
+    }
+
+    /// Allows object keys written as bare identifiers, like `{name: 1}`.
+    pub fn unquoted_keys(mut self, allow : bool) -> ParserOptions {
+        self.unquoted_keys = allow;
+        self
+    }
+
+    /// Allows `NaN`, `Infinity` and `-Infinity` as numbers.
+    pub fn non_finite_numbers(mut self, allow : bool) -> ParserOptions {
+        self.non_finite_numbers = allow;
+        self
+    }
+
+    /// Allows `// line` and `/* block */` comments wherever whitespace may go.
+    pub fn comments(mut self, allow : bool) -> ParserOptions {
+        self.comments = allow;
+        self
+    }
+
+    /// Allows hexadecimal integers like `0xFF` and `-0x1f`.
+    pub fn hex_numbers(mut self, allow : bool) -> ParserOptions {
+        self.hex_numbers = allow;
+        self
+    }
+
+    /// Allows a `+` in front of a decimal number, like `+1`. A hexadecimal or
+    /// non-finite number may have one whenever it is allowed at all.
+    pub fn plus_signs(mut self, allow : bool) -> ParserOptions {
+        self.plus_signs = allow;
+        self
+    }
+
+    /// Allows a decimal point with no digits before or after it, like `.5`
+    /// and `5.`. Leading zeros, as in `05`, stay an error, as in JSON5.
+    pub fn bare_decimal_points(mut self, allow : bool) -> ParserOptions {
+        self.bare_decimal_points = allow;
+        self
+    }
+
+    /// Allows a backslash to continue a string on the next line, along with
+    /// the other JSON5 escapes: `\v`, `\0`, `\xFF` and any character but a
+    /// digit standing for itself.
+    pub fn multiline_strings(mut self, allow : bool) -> ParserOptions {
+        self.multiline_strings = allow;
+        self
+    }
+
+    /// Allows the whitespace JSON5 adds: vertical tab, form feed, no-break
+    /// space, byte order mark, line and paragraph separators and the other
+    /// Unicode spaces.
+    pub fn extra_whitespace(mut self, allow : bool) -> ParserOptions {
+        self.extra_whitespace = allow;
+        self
+    }
+
+    pub fn duplicate_keys(mut self, policy : DuplicateKeys) -> ParserOptions {
+        self.duplicate_keys = policy;
+        self
+    }
+
+    /// Fails on containers nested deeper than `depth`, counting the outermost
+    /// as 1, instead of running out of stack.
+    pub fn max_depth(mut self, depth : usize) -> ParserOptions {
+        self.max_depth = depth;
+        self
+    }
+
+    /// Whether nothing beyond RFC 8259 is allowed.
+    pub fn is_strict(&self) -> bool {
+        *self == ParserOptions { duplicate_keys: self.duplicate_keys, max_depth: self.max_depth, ..ParserOptions::default() }
+    }
+}
+
+/// How the serializer writes a number kept as a float. Integers, and the
+/// decimals the `arbitrary_precision` feature keeps, always come out exactly
+/// as they are.
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub enum NumberFormat {
+    /// The fewest digits that read back as the same double, never in
+    /// exponential notation, as `Display` for `Number` writes them: the
+    /// default, though `1e300` comes out as 301 digits.
+    #[default]
+    Plain,
+    /// The fewest digits that read back as the same double, in exponential
+    /// notation from 1e21 up and below 1e-6, as JavaScript writes them.
+    Shortest,
+    /// Rounded to this many digits after the point, so `0.1 + 0.2` with 2
+    /// comes out as `0.30`.
+    Fixed(usize)
+}
+
+/// How `JsonNode::to_string_with` and `Serializer::with_options` write a
+/// document. The default writes what `Display` does: compact, members in
+/// the order the object keeps them, only the characters JSON requires
+/// escaped, and nothing after the document.
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct SerializeOptions {
+    pub indent: Option<String>,
+    pub sort_keys: bool,
+    pub escape_non_ascii: bool,
+    pub escape_forward_slash: bool,
+    pub trailing_newline: bool,
+    pub number_format: NumberFormat,
+    pub whole_floats_as_integers: bool,
+    pub non_finite_numbers: bool
+}
+
+impl SerializeOptions {
+    pub fn new() -> SerializeOptions {
+        SerializeOptions::default()
+    }
+
+    /// Puts every array element and object member on a line of its own,
+    /// indented by `indent` once per level of nesting.
+    pub fn indent(mut self, indent : &str) -> SerializeOptions {
+        self.indent = Some(indent.to_string());
+        self
+    }
+
+    /// Writes object members in the byte order of their keys, so that equal
+    /// documents print alike.
+    pub fn sort_keys(mut self, sort : bool) -> SerializeOptions {
+        self.sort_keys = sort;
+        self
+    }
+
+    /// Escapes every character outside ASCII as `\uXXXX`.
+    pub fn escape_non_ascii(mut self, escape : bool) -> SerializeOptions {
+        self.escape_non_ascii = escape;
+        self
+    }
+
+    /// Escapes `/` as `\/`, so that `</script>` cannot end an HTML script
+    /// element the JSON is embedded in.
+    pub fn escape_forward_slash(mut self, escape : bool) -> SerializeOptions {
+        self.escape_forward_slash = escape;
+        self
+    }
+
+    /// Ends the document with a line break, as text files do.
+    pub fn trailing_newline(mut self, newline : bool) -> SerializeOptions {
+        self.trailing_newline = newline;
+        self
+    }
+
+    /// Writes floats as `format` says.
+    pub fn number_format(mut self, format : NumberFormat) -> SerializeOptions {
+        self.number_format = format;
+        self
+    }
+
+    /// Writes a float with nothing after the point, such as `2.0`, as the
+    /// integer `2` whatever the number format, if it is below 1e21.
+    pub fn whole_floats_as_integers(mut self, whole : bool) -> SerializeOptions {
+        self.whole_floats_as_integers = whole;
+        self
+    }
+
+    /// Writes NaN and the infinities as JSON5's `NaN`, `Infinity` and
+    /// `-Infinity`. Otherwise they come out as `null`, as JSON has no way
+    /// to write them.
+    pub fn non_finite_numbers(mut self, allow : bool) -> SerializeOptions {
+        self.non_finite_numbers = allow;
+        self
+    }
+}
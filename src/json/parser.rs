@@ -1,276 +1,922 @@
-use nom;
-use nom::double;
-use crate::json::JsonNode;
-use std::collections::HashMap;
+use nom::{Err, ErrorKind, IResult, Needed};
+use crate::json::{DuplicateKeys, Expected, JsonNode, MemberEntry, Number, Object, ParserOptions, Reason};
+use crate::json::hooks::{NoHooks, ParseHooks};
+use crate::json::scan::skip_whitespace;
 #[allow(unused_imports)]
 use std::io::Write;
 
-pub fn parse_json(input: &[u8]) -> Result<(&[u8], JsonNode), nom::Err<&[u8], u32>> {
-    parse_json_element(input)
+pub struct Context<'h> {
+    pub hooks: &'h mut dyn ParseHooks,
+    pub options: ParserOptions,
+    /// Why parsing failed, when it is not bad syntax.
+    pub reason: Reason
 }
 
-named!(parse_json_element<&[u8], JsonNode>,
-    alt!(
-        parse_json_null | parse_json_boolean | parse_json_number | parse_json_string | parse_json_array | parse_json_object
-    )
-);
+impl<'h> Context<'h> {
+    pub fn new(hooks: &'h mut dyn ParseHooks, options: ParserOptions) -> Context<'h> {
+        Context { hooks, options, reason: Reason::Syntax }
+    }
+}
+
+pub fn parse_json(input: &[u8]) -> IResult<&[u8], JsonNode> {
+    parse_json_with(input, &mut Context::new(&mut NoHooks, ParserOptions::default()))
+}
+
+pub fn parse_json_with<'a>(input: &'a [u8], ctx: &mut Context) -> IResult<&'a [u8], JsonNode> {
+    let (start, ()) = ws(input, &ctx.options)?;
+    let (rest, node) = parse_json_element(start, ctx)?;
+    ctx.hooks.on_document_end(input.len() - rest.len());
+    Ok((rest, node))
+}
 
-named!(parse_json_null<&[u8], JsonNode>,
+/// What the parser was looking for when it failed with `kind`.
+pub(crate) fn expected(kind: &ErrorKind) -> Expected {
+    match kind {
+        ErrorKind::Custom(1) => Expected::ValueOrEnd,
+        ErrorKind::Custom(2) => Expected::Key,
+        ErrorKind::Custom(3) => Expected::KeyOrEnd,
+        ErrorKind::Custom(4) => Expected::Colon,
+        ErrorKind::Custom(5) => Expected::CommaOrEnd(']'),
+        ErrorKind::Custom(6) => Expected::CommaOrEnd('}'),
+        _ => Expected::Value
+    }
+}
+
+fn fail<T>(input: &[u8], expected: Expected) -> IResult<&[u8], T> {
+    let code = match expected {
+        Expected::Value | Expected::End => 0,
+        Expected::ValueOrEnd => 1,
+        Expected::Key => 2,
+        Expected::KeyOrEnd => 3,
+        Expected::Colon => 4,
+        Expected::CommaOrEnd(']') => 5,
+        Expected::CommaOrEnd(_) => 6
+    };
+    Err(Err::Error(nom::Context::Code(input, ErrorKind::Custom(code))))
+}
+
+// why a string was refused, beyond the codes `fail` gives for what was expected
+const INVALID_ESCAPE: u32 = 7;
+const INVALID_UTF8: u32 = 8;
+const CONTROL_CHARACTER: u32 = 9;
+
+/// Why the parser refused input it failed on with `kind`, when that is more
+/// than not finding what it expected.
+pub(crate) fn reason(kind: &ErrorKind) -> Reason {
+    match kind {
+        ErrorKind::Custom(INVALID_ESCAPE) => Reason::InvalidEscape,
+        ErrorKind::Custom(INVALID_UTF8) => Reason::InvalidUtf8,
+        ErrorKind::Custom(CONTROL_CHARACTER) => Reason::ControlCharacter,
+        _ => Reason::Syntax
+    }
+}
+
+fn incomplete<'a, T>() -> IResult<&'a [u8], T> {
+    Err(Err::Incomplete(Needed::Size(1)))
+}
+
+/// A container still being parsed.
+struct Open<'a> {
+    // the input from its opening bracket on
+    start: &'a [u8],
+    elements: Vec<JsonNode>,
+    // for an object, the member names with where they start, one ahead of
+    // `elements` while the value of the last one is being parsed
+    keys: Option<Vec<(&'a [u8], String)>>
+}
+
+impl<'a> Open<'a> {
+    fn close_bracket(&self) -> u8 {
+        if self.keys.is_some() { b'}' } else { b']' }
+    }
+
+    fn into_node(self, ctx: &mut Context) -> Result<JsonNode, Err<&'a [u8]>> {
+        let keys = match self.keys {
+            Some(keys) => keys,
+            None => return Ok(JsonNode::Array(self.elements))
+        };
+        let mut container = Object::with_capacity_and_hasher(keys.len(), Default::default());
+        for ((at, k), v) in keys.into_iter().zip(self.elements) {
+            match (container.entry(k), ctx.options.duplicate_keys) {
+                (MemberEntry::Vacant(member), _) => { member.insert(v); },
+                (MemberEntry::Occupied(_), DuplicateKeys::FirstWins) => (),
+                (MemberEntry::Occupied(mut member), DuplicateKeys::LastWins) => { member.insert(v); },
+                (MemberEntry::Occupied(member), DuplicateKeys::Error) => {
+                    ctx.reason = Reason::DuplicateKey(member.key().clone());
+                    return fail(at, Expected::Key).map(|(_, node)| node)
+                }
+            }
+        }
+        Ok(JsonNode::Object(container))
+    }
+}
+
+/// Reads one value, keeping the containers open around the current one on
+/// a stack of its own rather than recursing, so that nesting is limited
+/// only by memory and `ParserOptions::max_depth`.
+fn parse_json_element<'a>(input: &'a [u8], ctx: &mut Context) -> IResult<&'a [u8], JsonNode> {
+    let options = ctx.options;
+    let mut open: Vec<Open<'a>> = Vec::new();
+    let mut rest = input;
+    loop {
+        // `rest` starts a value
+        let (mut after, mut node) = match rest.first() {
+            Some(b'[') | Some(b'{') if open.len() == options.max_depth => {
+                ctx.reason = Reason::TooDeep(options.max_depth);
+                return fail(rest, Expected::Value)
+            },
+            Some(b'[') | Some(b'{') => {
+                let mut container = Open { start: rest, elements: Vec::new(), keys: if rest[0] == b'{' { Some(Vec::new()) } else { None } };
+                let (inside, ()) = ws(&rest[1..], &options)?;
+                if inside.first() == Some(&container.close_bracket()) {
+                    (&inside[1..], container.into_node(ctx)?)
+                } else {
+                    rest = inside;
+                    if let Some(keys) = &mut container.keys {
+                        let (value, key) = parse_member_name(inside, ctx, Expected::KeyOrEnd)?;
+                        keys.push((inside, key));
+                        rest = value
+                    }
+                    open.push(container);
+                    continue
+                }
+            },
+            _ => match parse_json_scalar(rest, &options) {
+                // where the array could as well have ended
+                Err(Err::Error(nom::Context::Code(at, _)))
+                    if at.len() == rest.len() && open.last().is_some_and(|top| top.keys.is_none() && top.elements.is_empty()) =>
+                    return fail(rest, Expected::ValueOrEnd),
+                result => result?
+            }
+        };
+        ctx.hooks.on_value(&node, rest.len() - after.len());
+
+        // add the value to its container, closing every container it completes
+        loop {
+            let top = match open.last_mut() {
+                Some(top) => top,
+                None => return Ok((after, node))
+            };
+            top.elements.push(node);
+            let close = top.close_bracket();
+            let (next, ()) = ws(after, &options)?;
+            let closing = match next.first() {
+                Some(b',') => {
+                    let (next, ()) = ws(&next[1..], &options)?;
+                    if !(options.trailing_commas && next.first() == Some(&close)) {
+                        rest = next;
+                        if let Some(keys) = &mut top.keys {
+                            let (value, key) = parse_member_name(next, ctx, Expected::Key)?;
+                            keys.push((next, key));
+                            rest = value
+                        }
+                        break
+                    }
+                    next
+                },
+                Some(c) if *c == close => next,
+                Some(_) => return fail(next, Expected::CommaOrEnd(close as char)),
+                None => return incomplete()
+            };
+            after = &closing[1..];
+            let container = open.pop().expect("a container is open");
+            let start = container.start;
+            node = container.into_node(ctx)?;
+            ctx.hooks.on_value(&node, start.len() - after.len());
+        }
+    }
+}
+
+/// Reads any value but a container.
+fn parse_json_scalar<'a>(input: &'a [u8], options: &ParserOptions) -> IResult<&'a [u8], JsonNode> {
+    match input.first() {
+        None => incomplete(),
+        Some(b'"') | Some(b'\'') => {
+            let (rest, value) = parse_lenient_string(input, options)?;
+            Ok((rest, JsonNode::String(value)))
+        },
+        Some(b't') | Some(b'f') => parse_json_boolean(input),
+        Some(b'n') => parse_json_null(input),
+        Some(_) => parse_lenient_number(input, options)
+    }
+}
+
+/// Skips the whitespace RFC 8259 allows around structural characters, and
+/// comments and other whitespace too when `options` allow them.
+pub(crate) fn ws<'a>(input: &'a [u8], options: &ParserOptions) -> IResult<&'a [u8], ()> {
+    let mut pos = 0;
+    loop {
+        pos = skip_whitespace(input, pos);
+        let extra = if options.extra_whitespace { extra_whitespace(&input[pos..]) } else { 0 };
+        if extra > 0 {
+            pos += extra;
+            continue
+        }
+        if !options.comments || input.get(pos) != Some(&b'/') {
+            break
+        }
+        let body = pos + 2;
+        pos = match input.get(pos + 1) {
+            // a line comment may end the input
+            Some(b'/') => input[body..].iter().position(|b| *b == b'\n').map_or(input.len(), |end| body + end + 1),
+            Some(b'*') => match input[body..].windows(2).position(|pair| pair == b"*/") {
+                Some(end) => body + end + 2,
+                None => return incomplete()
+            },
+            Some(_) => break,
+            None => return incomplete()
+        }
+    }
+    Ok((&input[pos..], ()))
+}
+
+/// Length of the whitespace character starting `input` that JSON5 allows
+/// but RFC 8259 does not, or 0.
+fn extra_whitespace(input: &[u8]) -> usize {
+    match input {
+        [0x0b, ..] | [0x0c, ..] => 1,
+        // no-break space
+        [0xc2, 0xa0, ..] => 2,
+        // byte order mark
+        [0xef, 0xbb, 0xbf, ..] => 3,
+        // ogham space mark, the en quad to hair spaces, line and paragraph separators,
+        // narrow no-break, medium mathematical and ideographic spaces
+        [0xe1, 0x9a, 0x80, ..] | [0xe2, 0x80, 0x80..=0x8a, ..] | [0xe2, 0x80, 0xa8, ..] | [0xe2, 0x80, 0xa9, ..]
+            | [0xe2, 0x80, 0xaf, ..] | [0xe2, 0x81, 0x9f, ..] | [0xe3, 0x80, 0x80, ..] => 3,
+        _ => 0
+    }
+}
+
+named_attr!(#[allow(deprecated)], pub parse_json_null<&[u8], JsonNode>,
     do_parse!(
         tag_s!("null") >>
         (JsonNode::Null)
     )
 );
 
-named!(parse_json_boolean<&[u8], JsonNode>,
+named_attr!(#[allow(deprecated)], pub parse_json_boolean<&[u8], JsonNode>,
     do_parse!(
         truth: alt!(tag_s!("true") | tag_s!("false")) >>
         (JsonNode::Boolean(truth[0] == b't'))
     )
 );
 
-named!(parse_json_number<&[u8], JsonNode>,
-    do_parse!(value: double >> (JsonNode::Number(value)))
-);
-
-named!(parse_json_string<&[u8], JsonNode>,
-    do_parse!(
-        value: parse_json_escaped_string >>
-        (JsonNode::String(value))
-    )
-);
+/// Reads a number as RFC 8259 writes it, keeping plain integers that fit
+/// in 64 bits exact and taking them without going through the float parser.
+pub(crate) fn parse_json_number(input: &[u8]) -> IResult<&[u8], JsonNode> {
+    parse_decimal(input, false)
+}
 
-named!(parse_json_escaped_string<&[u8], String>,
-    do_parse!(
-        tag_s!("\"") >>
-        result: many0!(
-            alt!(
-                map!( parse_json_escaped_ascii, Vec::from )
-                | parse_json_unicode_escape
-            )
-        ) >>
-        tag_s!("\"") >>
-        (String::from_utf8(flatten(result)).unwrap())
-    )
-);
+/// Reads a decimal number, negative or not, also without digits on one side
+/// of the point if `bare_points`.
+fn parse_decimal(input: &[u8], bare_points: bool) -> IResult<&[u8], JsonNode> {
+    let end = number_length(input, bare_points)?;
+    let lexeme = &input[..end];
+    let start = if lexeme[0] == b'-' { 1 } else { 0 };
+    if !lexeme.iter().any(|b| *b == b'.' || *b == b'e' || *b == b'E') {
+        let value = lexeme[start..].iter()
+            .try_fold(0u64, |value, digit| value.checked_mul(10)?.checked_add((digit - b'0') as u64));
+        // longer integers become doubles like fractions do
+        if let Some(value) = value.map(Number::from) {
+            return Ok((&input[end..], JsonNode::Number(if lexeme[0] == b'-' { -value } else { value })))
+        }
+    }
+    // the lexeme is ASCII and in a form the float parser takes
+    let value = std::str::from_utf8(lexeme).ok().and_then(|text| text.parse().ok()).expect("a number lexeme reads as a float");
+    Ok((&input[end..], JsonNode::Number(Number::from_lexeme(lexeme, value))))
+}
 
-fn flatten(deep : Vec<Vec<u8>>) -> Vec<u8> {
-    let mut flat : Vec<u8> = Vec::new();
-    deep.iter().for_each(| inner | flat.extend(inner.iter()));
-    flat
+/// Length of the number starting `input`: a minus, an integer part without
+/// leading zeros, and optionally a fraction and an exponent, each with at
+/// least one digit, except that with `bare_points` either side of the point
+/// may go without.
+fn number_length(input: &[u8], bare_points: bool) -> Result<usize, Err<&[u8]>> {
+    let digits = |from: usize| input[from.min(input.len())..].iter().take_while(|b| b.is_ascii_digit()).count();
+    let mut end = if input.first() == Some(&b'-') { 1 } else { 0 };
+    let integer = digits(end);
+    if integer > 1 && input[end] == b'0' {
+        return fail(input, Expected::Value).map(|(_, length)| length)
+    }
+    end += integer;
+    let mut fraction = None;
+    if input.get(end) == Some(&b'.') {
+        let count = digits(end + 1);
+        end += 1 + count;
+        fraction = Some(count)
+    }
+    let well_formed = match fraction {
+        None => integer > 0,
+        Some(count) => (integer > 0 && count > 0) || (bare_points && integer + count > 0)
+    };
+    match input.get(end) {
+        Some(b'e') | Some(b'E') if well_formed => {
+            end += 1;
+            if let Some(b'-') | Some(b'+') = input.get(end) {
+                end += 1
+            }
+            let exponent = digits(end);
+            end += exponent;
+            match input.get(end) {
+                None => Err(Err::Incomplete(Needed::Size(1))),
+                Some(_) if exponent == 0 => fail(input, Expected::Value).map(|(_, length)| length),
+                Some(_) => Ok(end)
+            }
+        },
+        // the number may go on
+        None => Err(Err::Incomplete(Needed::Size(1))),
+        Some(_) if well_formed => Ok(end),
+        Some(_) => fail(input, Expected::Value).map(|(_, length)| length)
+    }
 }
 
-named!(parse_json_escaped_ascii<&[u8], &[u8]>,
-    alt!(
-        is_not!("\\\"")
-        | value!("\\".as_bytes(), tag_s!("\\\\"))
-        | value!("\"".as_bytes(), tag_s!("\\\""))
-        | value!("/".as_bytes(), tag_s!("\\/"))
-        | value!("\08".as_bytes(), tag_s!("\\b"))
-        | value!("\n".as_bytes(), tag_s!("\\n"))
-        | value!("\r".as_bytes(), tag_s!("\\r"))
-        | value!("\t".as_bytes(), tag_s!("\\t"))
-    )
-);
+/// Reads a number in any of the forms `options` allow: `NaN`, signed
+/// `Infinity`, hexadecimal integers, a leading `+` and bare decimal points
+/// besides the decimals JSON has.
+fn parse_lenient_number<'a>(input: &'a [u8], options: &ParserOptions) -> IResult<&'a [u8], JsonNode> {
+    let (negative, unsigned) = match input.first() {
+        Some(b'-') => (true, &input[1..]),
+        Some(b'+') => (false, &input[1..]),
+        _ => (false, input)
+    };
+    let (rest, value) = match unsigned {
+        [b'N', ..] | [b'I', ..] if options.non_finite_numbers => {
+            let (rest, word) = alt!(unsigned, tag!("NaN") | tag!("Infinity"))?;
+            (rest, Number::from(if word == b"NaN" { f64::NAN } else { f64::INFINITY }))
+        },
+        [b'0', b'x', ..] | [b'0', b'X', ..] if options.hex_numbers => {
+            let hex = &unsigned[2..];
+            let digits = hex.iter().take_while(|b| b.is_ascii_hexdigit()).count();
+            if digits == hex.len() {
+                return incomplete()
+            } else if digits == 0 {
+                return fail(input, Expected::Value)
+            }
+            let value = hex[..digits].iter().try_fold(0u128, |value, digit| {
+                value.checked_mul(16)?.checked_add((*digit as char).to_digit(16)? as u128)
+            });
+            // integers beyond 128 bits become doubles
+            let value = value.map_or_else(|| {
+                Number::from(hex[..digits].iter().fold(0.0, |value, digit| value * 16.0 + (*digit as char).to_digit(16).unwrap_or(0) as f64))
+            }, Number::from);
+            (&hex[digits..], value)
+        },
+        // one sign at most
+        [b'-', ..] | [b'+', ..] => return fail(input, Expected::Value),
+        _ if input[0] != b'+' => return parse_decimal(input, options.bare_decimal_points),
+        _ if options.plus_signs => return parse_decimal(unsigned, options.bare_decimal_points),
+        _ => return fail(input, Expected::Value)
+    };
+    Ok((rest, JsonNode::Number(if negative { -value } else { value })))
+}
 
-named!(parse_json_unicode_escape<&[u8], Vec<u8>>,
+named!(pub(crate) parse_json_string<&[u8], JsonNode>,
     do_parse!(
-        tag_s!("\\u") >>
-        result: map!( take!(4), codepoint_from_hex ) >>
-        (result)
+        value: parse_json_escaped_string >>
+        (JsonNode::String(value))
     )
 );
 
-#[allow(dead_code)] // used in parse_json_unicode_escape
-fn codepoint_from_hex(input: &[u8]) -> Vec<u8> {
-  let hex = String::from_utf8(input.to_vec()).unwrap();
-  let value = u32::from_str_radix(&hex, 16).unwrap();
-  let mut buffer : [u8; 4] = [0; 4];
+/// Reads a quoted string, appending unescaped runs and escapes straight into
+/// one buffer; a string without escapes is copied in a single allocation.
+pub(crate) fn parse_json_escaped_string(input: &[u8]) -> IResult<&[u8], String> {
+    parse_json_escaped_string_into(input, Vec::new())
+}
 
-  std::char::from_u32(value).unwrap().encode_utf8(&mut buffer).as_bytes().to_vec()
+/// Like `parse_json_escaped_string`, but decodes into the emptied `out`,
+/// whose allocation the returned string takes over.
+pub(crate) fn parse_json_escaped_string_into(input: &[u8], out: Vec<u8>) -> IResult<&[u8], String> {
+    parse_quoted_string_into(input, b'"', false, out)
 }
 
-named!(parse_json_array<&[u8], JsonNode>,
-    do_parse!(
-        tag_s!("[") >>
-        content: opt!(separated_list_complete!(tag_s!(","), parse_json_element)) >>
-        tag_s!("]") >>
-        (
-            match content {
-                Some(elements) => (
-                    JsonNode::Array(elements)
-                ),
-                None => (
-                    JsonNode::Array(Vec::<JsonNode>::new())
-                )
-            }
-        )
-    )
-);
+/// The UTF-16 code unit that the four hex digits of a `\u` escape spell.
+fn hex_code_unit(hex: &[u8]) -> Option<u32> {
+    hex.iter().try_fold(0, |unit, digit| Some(unit * 16 + (*digit as char).to_digit(16)?))
+}
 
-named!(parse_json_object<&[u8], JsonNode>,
-    do_parse!(
-        tag_s!("{") >>
-        content: opt!(separated_list_complete!(tag_s!(","), parse_json_pair)) >>
-        tag_s!("}") >>
-        (
-            {
-                let mut container = HashMap::<String, JsonNode>::new();
-                match content {
-                    Some(mut elements) => {
-                        while let Some((k, v)) = elements.pop() {
-                            match container.insert(k, v) { _ => () }
+/// Reads a string in either quote `options` allow, decoding the escapes they allow.
+fn parse_lenient_string<'a>(input: &'a [u8], options: &ParserOptions) -> IResult<&'a [u8], String> {
+    let quote = if options.single_quotes && input.first() == Some(&b'\'') { b'\'' } else { b'"' };
+    parse_quoted_string_into(input, quote, options.multiline_strings, Vec::new())
+}
+
+/// Reads a string between `quote`s, which a backslash also escapes, and
+/// which may not hold control characters. With `json5_escapes`, a backslash
+/// also continues the string on the next line, and escapes any character
+/// but a digit into itself, and only line breaks count as control characters.
+fn parse_quoted_string_into(input: &[u8], quote: u8, json5_escapes: bool, mut out: Vec<u8>) -> IResult<&[u8], String> {
+    // positions are offsets into `input`, which starts one byte before `body`
+    let error = |at: usize, code: u32| Err(Err::Error(nom::Context::Code(&input[at..], ErrorKind::Custom(code))));
+    match input.first() {
+        None => return incomplete(),
+        Some(q) if *q == quote => (),
+        Some(_) => return error(0, 0)
+    }
+    let body = &input[1..];
+    let control = |b: u8| if json5_escapes { b == b'\n' || b == b'\r' } else { b < 0x20 };
+    let special = |from: usize| body[from..].iter().position(|b| *b == quote || *b == b'\\' || control(*b)).map(|run| from + run);
+    let mut i = match special(0) {
+        Some(i) => i,
+        None => return incomplete()
+    };
+    out.clear();
+    out.reserve(i + if body[i] == quote { 0 } else { 16 });
+    out.extend_from_slice(&body[..i]);
+    loop {
+        match body[i] {
+            b'\\' => (),
+            b if b == quote => break,
+            _ => return error(i + 1, CONTROL_CHARACTER)
+        }
+        let escape = match body.get(i + 1) {
+            Some(escape) => *escape,
+            None => return incomplete()
+        };
+        let mut next = i + 2;
+        match escape {
+            b'"' | b'\\' | b'/' => out.push(escape),
+            _ if escape == quote => out.push(escape),
+            b'b' => out.push(0x08),
+            b'f' => out.push(0x0c),
+            b'n' => out.push(b'\n'),
+            b'r' => out.push(b'\r'),
+            b't' => out.push(b'\t'),
+            b'u' => {
+                let unit = match body.get(i + 2..i + 6) {
+                    Some(hex) => hex_code_unit(hex),
+                    None if body[i + 2..].iter().all(u8::is_ascii_hexdigit) => return incomplete(),
+                    None => return error(i + 1, INVALID_ESCAPE)
+                };
+                let code = match unit {
+                    // a high surrogate pairs with an escaped low one into a character past the BMP
+                    Some(high @ 0xd800..=0xdbff) => {
+                        let low = match body.get(i + 6..i + 12) {
+                            Some(escape) if escape.starts_with(b"\\u") => hex_code_unit(&escape[2..]),
+                            Some(_) => None,
+                            None => {
+                                let tail = &body[i + 6..];
+                                let pending = tail.iter().enumerate().all(|(k, b)| match k {
+                                    0 => *b == b'\\',
+                                    1 => *b == b'u',
+                                    _ => b.is_ascii_hexdigit()
+                                });
+                                return if pending { incomplete() } else { error(i + 1, INVALID_ESCAPE) }
+                            }
+                        };
+                        match low {
+                            Some(low @ 0xdc00..=0xdfff) => {
+                                next += 6;
+                                Some(0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00))
+                            },
+                            _ => None
                         }
                     },
-                    None => ()
+                    // from_u32 turns down a lone low surrogate
+                    unit => unit
+                };
+                match code.and_then(std::char::from_u32) {
+                    Some(c) => out.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+                    None => return error(i + 1, INVALID_ESCAPE)
                 }
-                JsonNode::Object(container)
-            }
-        )
-    )
-);
+                next += 4
+            },
+            b'v' if json5_escapes => out.push(0x0b),
+            b'0' if json5_escapes => out.push(0),
+            b'x' if json5_escapes => {
+                let hex = match body.get(i + 2..i + 4) {
+                    Some(hex) => hex,
+                    None if body[i + 2..].iter().all(u8::is_ascii_hexdigit) => return incomplete(),
+                    None => return error(i + 1, INVALID_ESCAPE)
+                };
+                match std::str::from_utf8(hex).ok().and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                    Some(b) => out.extend_from_slice((b as char).encode_utf8(&mut [0; 4]).as_bytes()),
+                    None => return error(i + 1, INVALID_ESCAPE)
+                }
+                next += 2
+            },
+            // line continuations leave nothing in the string
+            b'\n' if json5_escapes => (),
+            b'\r' if json5_escapes => if body.get(i + 2) == Some(&b'\n') { next += 1 },
+            0xe2 if json5_escapes && matches!(body.get(i + 2..i + 4), Some([0x80, 0xa8]) | Some([0x80, 0xa9])) => next += 2,
+            // the escaped character is copied with the run after it
+            _ if json5_escapes && !escape.is_ascii_digit() => next -= 1,
+            _ => return error(i + 1, INVALID_ESCAPE)
+        }
+        i = match special(next) {
+            Some(i) => i,
+            None => return incomplete()
+        };
+        out.extend_from_slice(&body[next..i]);
+    }
+    match String::from_utf8(out) {
+        Ok(value) => Ok((&body[i + 1..], value)),
+        // escapes decode to whole characters, so the culprit is in the raw text
+        Err(_) => error(1 + std::str::from_utf8(&body[..i]).err().map_or(0, |e| e.valid_up_to()), INVALID_UTF8)
+    }
+}
 
-named!(parse_json_pair<&[u8], (String, JsonNode)>,
-    do_parse!(
-        name: parse_json_escaped_string >>
-        tag_s!(":") >>
-        value: parse_json_element >>
-        ( (name, value) )
-    )
-);
+/// Reads a member name and the `:` after it, leaving the input where the
+/// value starts; fails expecting `expected` when no name starts `input`.
+fn parse_member_name<'a>(input: &'a [u8], ctx: &mut Context, expected: Expected) -> IResult<&'a [u8], String> {
+    let options = ctx.options;
+    let (rest, name) = match input.first() {
+        Some(b'"') => parse_lenient_string(input, &options)?,
+        Some(b'\'') if options.single_quotes => parse_lenient_string(input, &options)?,
+        Some(b) if options.unquoted_keys && (is_identifier_start(*b) || *b >= 0x80) => match parse_identifier(input) {
+            Err(Err::Error(_)) => return fail(input, expected),
+            result => result?
+        },
+        Some(_) => return fail(input, expected),
+        None => return incomplete()
+    };
+    ctx.hooks.on_key(&name);
+    let (rest, ()) = ws(rest, &options)?;
+    match rest.first() {
+        Some(b':') => (),
+        Some(_) => return fail(rest, Expected::Colon),
+        None => return incomplete()
+    }
+    let (rest, ()) = ws(&rest[1..], &options)?;
+    Ok((rest, name))
+}
+
+fn is_identifier_start(b: u8) -> bool {
+    b.is_ascii_alphabetic() || b == b'_' || b == b'$'
+}
+
+/// Reads an unquoted key: letters, digits, `_` and `$`, not starting with a digit.
+fn parse_identifier(input: &[u8]) -> IResult<&[u8], String> {
+    let run = input.iter().take_while(|b| is_identifier_start(**b) || b.is_ascii_digit() || **b >= 0x80).count();
+    if run == input.len() {
+        return incomplete()
+    }
+    // the run may end inside a character, or in one that cannot be in a name
+    let text = match std::str::from_utf8(&input[..run]) {
+        Ok(text) => text,
+        Err(e) => std::str::from_utf8(&input[..e.valid_up_to()]).unwrap_or_default()
+    };
+    let len = text.char_indices()
+        .find(|(_, c)| !(c.is_alphanumeric() || *c == '_' || *c == '$'))
+        .map_or(text.len(), |(at, _)| at);
+    if len == 0 {
+        return fail(input, Expected::Key)
+    }
+    Ok((&input[len..], text[..len].to_string()))
+}
 
 #[cfg(test)]
 mod tests {
     // Note this useful idiom: importing names from outer (for mod tests) scope.
     use super::*;
+    use std::str::FromStr;
+    use crate::json::hooks::ParseMetrics;
+    use crate::json::DuplicateKeys;
+    #[cfg(not(feature = "grow_stack"))]
+    use crate::json::DEFAULT_MAX_DEPTH;
 
     #[test]
     fn test_null_ok() {
-        assert_eq!(JsonNode::from_str("null"), JsonNode::Null);
+        assert_eq!(JsonNode::from_str("null").unwrap(), JsonNode::Null);
     }
 
     #[test]
     fn test_boolean_ok() {
-        assert_eq!(JsonNode::from_str("true"), JsonNode::Boolean(true));
-        assert_eq!(JsonNode::from_str("false"), JsonNode::Boolean(false));
+        assert_eq!(JsonNode::from_str("true").unwrap(), JsonNode::Boolean(true));
+        assert_eq!(JsonNode::from_str("false").unwrap(), JsonNode::Boolean(false));
     }
 
     #[test]
     fn test_number_ok() {
         // we provide an extra character to make parser realize the number is complete.
 
-        assert_eq!(JsonNode::from_str("0 "), JsonNode::Number(0.0));
-        assert_eq!(JsonNode::from_str("+0 "), JsonNode::Number(0.0));
-        assert_eq!(JsonNode::from_str("-0 "), JsonNode::Number(0.0));
+        assert_eq!(JsonNode::from_str("0 ").unwrap(), JsonNode::Number(0.0.into()));
+        assert_eq!(JsonNode::from_str("-0 ").unwrap(), JsonNode::Number(0.0.into()));
 
-        assert_eq!(JsonNode::from_str(".0 "), JsonNode::Number(0.0));
-        assert_eq!(JsonNode::from_str("0.0 "), JsonNode::Number(0.0));
-        assert_eq!(JsonNode::from_str("00.000 "), JsonNode::Number(0.0));
+        assert_eq!(JsonNode::from_str("0.0 ").unwrap(), JsonNode::Number(0.0.into()));
+        assert_eq!(JsonNode::from_str("0.000e0 ").unwrap(), JsonNode::Number(0.0.into()));
 
-        assert_eq!(JsonNode::from_str("1 "), JsonNode::Number(1.0));
-        assert_eq!(JsonNode::from_str("00012345 "), JsonNode::Number(12345.0));
-        assert_eq!(JsonNode::from_str("12.345000 "), JsonNode::Number(12.345));
-        assert_eq!(JsonNode::from_str("67e89 "), JsonNode::Number(67e89));
-        assert_eq!(JsonNode::from_str("-67e89 "), JsonNode::Number(-67e89));
-        assert_eq!(JsonNode::from_str("5.67e-89 "), JsonNode::Number(5.67e-89));
+        assert_eq!(JsonNode::from_str("1 ").unwrap(), JsonNode::Number(1.0.into()));
+        assert_eq!(JsonNode::from_str("12345 ").unwrap(), JsonNode::Number(12345.0.into()));
+        assert_eq!(JsonNode::from_str("12.345000 ").unwrap(), JsonNode::Number(12.345.into()));
+        assert_eq!(JsonNode::from_str("67e89 ").unwrap(), JsonNode::Number(67e89.into()));
+        assert_eq!(JsonNode::from_str("-67e89 ").unwrap(), JsonNode::Number((-67e89).into()));
+        assert_eq!(JsonNode::from_str("5.67e-89 ").unwrap(), JsonNode::Number(5.67e-89.into()));
+        assert_eq!(JsonNode::from_str("1E+2 ").unwrap(), JsonNode::Number(100.0.into()));
+    }
+
+    #[test]
+    fn test_number_malformed() {
+        for malformed in &["+0 ", ".0 ", "00.000 ", "00012345 ", "-01 ", "1. ", "-.5 ", "1.e5 ", "1e ", "1e+ ", "- ", "--1 ", "-+1 "] {
+            assert!(JsonNode::from_str(malformed).is_err(), "{}", malformed);
+        }
+        for malformed in &["01]", "1.]", ".5]", "+1]", "1e]"] {
+            assert!(parse_json_number(malformed.as_bytes()).is_err(), "{}", malformed);
+        }
+        assert!(matches!(parse_json_number(b"1."), Err(Err::Incomplete(_))));
+        assert!(matches!(parse_json_number(b"-"), Err(Err::Incomplete(_))));
+    }
+
+    #[test]
+    fn test_integer_fast_path() {
+        assert_eq!(parse_json_number(b"-42,").unwrap(), (&b","[..], JsonNode::Number((-42.0).into())));
+        assert_eq!(parse_json_number(b"999999999999999]").unwrap().1, JsonNode::Number(999999999999999.0.into()));
+        assert_eq!(parse_json_number(b"12345678901234567 ").unwrap().1.to_string(), "12345678901234567");
+        assert_eq!(parse_json_number(b"-9223372036854775808 ").unwrap().1.to_string(), "-9223372036854775808");
+        let beyond = if cfg!(feature = "arbitrary_precision") { "18446744073709551616" } else { "18446744073709552000" };
+        assert_eq!(parse_json_number(b"18446744073709551616 ").unwrap().1.to_string(), beyond);
+        assert_eq!(parse_json_number(b"7e2 ").unwrap().1, JsonNode::Number(700.0.into()));
+        assert!(matches!(parse_json_number(b"12"), Err(Err::Incomplete(_))));
+        if let JsonNode::Number(zero) = parse_json_number(b"-0 ").unwrap().1 {
+            assert!(zero.as_f64().is_sign_negative())
+        }
     }
 
     #[test]
     fn test_empty_string_ok() {
-        assert_eq!(JsonNode::from_str("\"\""), JsonNode::String("".to_string()));
+        assert_eq!(JsonNode::from_str("\"\"").unwrap(), JsonNode::String("".to_string()));
     }
 
    #[test]
     fn test_strings_ok() {
-        assert_eq!(JsonNode::from_str("\" \""), JsonNode::String(" ".to_string()));
-        assert_eq!(JsonNode::from_str("\"#€%&/()=\""), JsonNode::String("#€%&/()=".to_string()));
+        assert_eq!(JsonNode::from_str("\" \"").unwrap(), JsonNode::String(" ".to_string()));
+        assert_eq!(JsonNode::from_str("\"#€%&/()=\"").unwrap(), JsonNode::String("#€%&/()=".to_string()));
     }
 
    #[test]
     fn test_escaped_strings_ok() {
-        assert_eq!(JsonNode::from_str("\"\\\"\""), JsonNode::String("\"".to_string()));
-        assert_eq!(JsonNode::from_str("\"\\\\\""), JsonNode::String("\\".to_string()));
-        assert_eq!(JsonNode::from_str("\"\\/\""), JsonNode::String("/".to_string()));
-        assert_eq!(JsonNode::from_str("\"\\b\""), JsonNode::String("\08".to_string()));
-        assert_eq!(JsonNode::from_str("\"\\n\""), JsonNode::String("\n".to_string()));
-        assert_eq!(JsonNode::from_str("\"\\r\""), JsonNode::String("\r".to_string()));
-        assert_eq!(JsonNode::from_str("\"\\t\""), JsonNode::String("\t".to_string()));
-        assert_eq!(JsonNode::from_str("\"\\u211D\""), JsonNode::String("\u{211D}".to_string()));
+        assert_eq!(JsonNode::from_str("\"\\\"\"").unwrap(), JsonNode::String("\"".to_string()));
+        assert_eq!(JsonNode::from_str("\"\\\\\"").unwrap(), JsonNode::String("\\".to_string()));
+        assert_eq!(JsonNode::from_str("\"\\/\"").unwrap(), JsonNode::String("/".to_string()));
+        assert_eq!(JsonNode::from_str("\"\\b\"").unwrap(), JsonNode::String("\u{8}".to_string()));
+        assert_eq!(JsonNode::from_str("\"\\n\"").unwrap(), JsonNode::String("\n".to_string()));
+        assert_eq!(JsonNode::from_str("\"\\r\"").unwrap(), JsonNode::String("\r".to_string()));
+        assert_eq!(JsonNode::from_str("\"\\t\"").unwrap(), JsonNode::String("\t".to_string()));
+        assert_eq!(JsonNode::from_str("\"\\u211D\"").unwrap(), JsonNode::String("\u{211D}".to_string()));
+    }
+
+    #[test]
+    fn test_escapes_between_runs() {
+        assert_eq!(JsonNode::from_str("\"ab\\ncd\\u00e9\\\"\\f\"").unwrap(), JsonNode::String("ab\ncd\u{e9}\"\u{c}".to_string()));
+        assert!(matches!(parse_json_escaped_string(b"\"ab\\"), Err(Err::Incomplete(_))));
+        assert!(matches!(parse_json_escaped_string(b"\"ab\\u00"), Err(Err::Incomplete(_))));
+        assert!(matches!(parse_json_escaped_string(b"\"a\\x\""), Err(Err::Error(_))));
+        assert!(matches!(parse_json_escaped_string(b"\"\\uZZZZ\""), Err(Err::Error(_))));
+        assert_eq!(parse_json_escaped_string(b"\"x\" rest").unwrap(), (&b" rest"[..], "x".to_string()));
+    }
+
+    #[test]
+    fn test_malformed_strings_fail() {
+        let refused = |json: &[u8]| {
+            let e = JsonNode::from_bytes(json).unwrap_err();
+            (e.reason.clone(), e.offset, e.to_string())
+        };
+        assert_eq!(refused(b"[\"ab\\x41\"]"), (Reason::InvalidEscape, 4, "line 1, column 5: invalid escape sequence at \"\\x41\"]\"".to_string()));
+        assert_eq!(refused(b"\"\\u12\""), (Reason::InvalidEscape, 1, "line 1, column 2: invalid escape sequence at \"\\u12\"\"".to_string()));
+        assert_eq!(refused(b"{\"k\": \"a\xff\xfeb\"}"), (Reason::InvalidUtf8, 8, "line 1, column 9: invalid UTF-8 in a string".to_string()));
+        assert_eq!(refused(b"\"\xe2\x82\""), (Reason::InvalidUtf8, 1, "line 1, column 2: invalid UTF-8 in a string".to_string()));
+        assert_eq!(refused(b"[\"tab\there\"]"), (Reason::ControlCharacter, 5, "line 1, column 6: unescaped control character in a string".to_string()));
+        assert_eq!(refused(b"\"two\nlines\""), (Reason::ControlCharacter, 4, "line 1, column 5: unescaped control character in a string".to_string()));
+        assert_eq!(JsonNode::from_str("\"\\b\\f\\u0000\u{7f}\"").unwrap(), JsonNode::String("\u{8}\u{c}\0\u{7f}".to_string()));
+        // JSON5 strings may hold tabs, but not line breaks
+        assert_eq!(JsonNode::from_str_with("'a\tb'", ParserOptions::json5()).unwrap(), JsonNode::String("a\tb".to_string()));
+        assert_eq!(JsonNode::from_str_with("'a\nb'", ParserOptions::json5()).unwrap_err().reason, Reason::ControlCharacter);
+    }
+
+    #[test]
+    fn test_surrogate_pairs() {
+        assert_eq!(JsonNode::from_str("\"\\uD83D\\uDE00\"").unwrap(), JsonNode::String("\u{1F600}".to_string()));
+        assert_eq!(JsonNode::from_str("\"a\\ud834\\udd1eb\"").unwrap(), JsonNode::String("a\u{1D11E}b".to_string()));
+        assert_eq!(JsonNode::from_str("\"\\uDBFF\\uDFFF\"").unwrap(), JsonNode::String("\u{10FFFF}".to_string()));
+        for lone in &["\"\\uD83D\"", "\"\\uD83Dx\\uDE00\"", "\"\\uDE00\"", "\"\\uD83D\\uD83D\"", "\"\\uD83D\\n\"", "\"\\u+123\"", "\"\\u12G4\""] {
+            assert!(!JsonNode::from_str(lone).unwrap_err().unexpected_end, "{}", lone);
+        }
+        match parse_json_escaped_string(b"\"ab\\uDE00\"") {
+            Err(Err::Error(nom::Context::Code(at, _))) => assert_eq!(at, b"\\uDE00\""),
+            other => panic!("{:?}", other)
+        }
+        assert!(matches!(parse_json_escaped_string(b"\"\\uD83D\\uDE"), Err(Err::Incomplete(_))));
+        assert!(matches!(parse_json_escaped_string(b"\"\\uD83D"), Err(Err::Incomplete(_))));
+        assert!(matches!(parse_json_escaped_string(b"\"\\uD83D\""), Err(Err::Error(_))));
     }
 
     #[test]
     fn test_empty_list_ok() {
         let expected = Vec::new();
-        assert_eq!(JsonNode::from_str("[]"), JsonNode::Array(expected));
+        assert_eq!(JsonNode::from_str("[]").unwrap(), JsonNode::Array(expected));
     }
     
     #[test]
+    #[allow(clippy::vec_init_then_push)]
     fn test_lists_within_lists_ok() {
         let mut expected = Vec::new();
         expected.push(JsonNode::Array(Vec::new()));
-        assert_eq!(JsonNode::from_str("[[]]"), JsonNode::Array(expected));
+        assert_eq!(JsonNode::from_str("[[]]").unwrap(), JsonNode::Array(expected));
 
         let mut expected = Vec::new();
         expected.push(JsonNode::Array(Vec::new()));
         expected.push(JsonNode::Array(Vec::new()));
-        assert_eq!(JsonNode::from_str("[[],[]]"), JsonNode::Array(expected));
+        assert_eq!(JsonNode::from_str("[[],[]]").unwrap(), JsonNode::Array(expected));
 
         let mut expected = Vec::new();
         let mut inner = Vec::new();
         inner.push(JsonNode::Array(Vec::new()));
         expected.push(JsonNode::Array(inner));
-        assert_eq!(JsonNode::from_str("[[[]]]"), JsonNode::Array(expected));
+        assert_eq!(JsonNode::from_str("[[[]]]").unwrap(), JsonNode::Array(expected));
     }
 
     #[test]
-    #[should_panic(expected = "JSON parsing failed: Error(")]
-    fn test_list_with_a_comma_only_fails() {
-        JsonNode::from_str("[,]");
+    fn test_malformed_lists_fail() {
+        for malformed in &["[,]", "[[],]", "[,[]]"] {
+            assert!(!JsonNode::from_str(malformed).unwrap_err().unexpected_end);
+        }
     }
 
     #[test]
-    #[should_panic(expected = "JSON parsing failed: Error(")]
-    fn test_list_with_extra_comma_fails() {
-        JsonNode::from_str("[[],]");
+    fn test_empty_input_fails() {
+        assert!(JsonNode::from_str("").unwrap_err().unexpected_end);
     }
 
     #[test]
-    #[should_panic(expected = "JSON parsing failed: Error(")]
-    fn test_list_starting_with_comma_fails() {
-        JsonNode::from_str("[,[]]");
+    fn test_bad_syntax_input_fails() {
+        assert_eq!(JsonNode::from_str("x").unwrap_err().offset, 0);
+        assert_eq!("x".parse::<JsonNode>().unwrap_err().to_string(), "line 1, column 1: expected a value at \"x\"");
     }
 
+
     #[test]
-    #[should_panic(expected = "JSON parsing failed: Incomplete(Size(")]
-    fn test_empty_input_fails() {
-        JsonNode::from_str("");
+    fn test_empty_object_ok() {
+        let expected = Object::default();
+        assert_eq!(JsonNode::from_str("{}").unwrap(), JsonNode::Object(expected));
     }
 
     #[test]
-    #[should_panic(expected = "JSON parsing failed: Error(")]
-    fn test_bad_syntax_input_fails() {
-        JsonNode::from_str("x");
+    fn test_object_ok() {
+        let mut expected = Object::default();
+        expected.insert("foo".to_string(), JsonNode::Null);
+        assert_eq!(JsonNode::from_str("{\"foo\":null}").unwrap(), JsonNode::Object(expected));
+    }
+
+    #[test]
+    fn test_whitespace_around_tokens() {
+        let pretty = "\r\n {\n\t\"a\" : [ 1 , true ,[ ] ] ,\n  \"b\"\t:\t{ } , \"c\":null\n}\n";
+        assert_eq!(JsonNode::from_str(pretty).unwrap(), JsonNode::from_str("{\"a\":[1,true,[]],\"b\":{},\"c\":null}").unwrap());
+        assert_eq!(JsonNode::from_str("[ 1 ]").unwrap(), JsonNode::Array(vec![JsonNode::Number(1.0.into())]));
+        for malformed in &["[ 1 , ]", "[ , 1]", "{ \"a\" : 1 , }", "[1 2]"] {
+            assert!(JsonNode::from_str(malformed).is_err());
+        }
+        assert!(JsonNode::from_str("[ 1,\n").unwrap_err().unexpected_end);
     }
 
+    #[test]
+    fn test_lenient_options() {
+        let strict = |json: &str| JsonNode::from_str(json).unwrap();
+        let lenient = |json: &str, options: ParserOptions| JsonNode::from_str_with(json, options).unwrap();
+        assert_eq!(lenient("[1, 2, ]", ParserOptions::new().trailing_commas(true)), strict("[1,2]"));
+        assert_eq!(lenient("{\"a\": {\"b\": 1,},}", ParserOptions::new().trailing_commas(true)), strict("{\"a\":{\"b\":1}}"));
+        assert_eq!(lenient("['it\\'s', {'k': \"'\"}]", ParserOptions::new().single_quotes(true)), strict("[\"it's\",{\"k\":\"'\"}]"));
+        assert_eq!(lenient("{$id: 1, _x9 : 2}", ParserOptions::new().unquoted_keys(true)), strict("{\"$id\":1,\"_x9\":2}"));
+        let comments = "/* head */ [1, // one\n 2 /* two */] // tail";
+        assert_eq!(lenient(comments, ParserOptions::new().comments(true)), strict("[1,2]"));
+
+        let non_finite = lenient("[NaN, Infinity, -Infinity, +Infinity]", ParserOptions::new().non_finite_numbers(true));
+        let values : Vec<f64> = non_finite.as_array().unwrap().iter().filter_map(JsonNode::as_f64).collect();
+        assert!(values[0].is_nan());
+        assert_eq!(&values[1..], &[f64::INFINITY, f64::NEG_INFINITY, f64::INFINITY]);
+        assert_eq!(lenient("[-1, 2.5]", ParserOptions::new().non_finite_numbers(true)), strict("[-1,2.5]"));
+    }
 
     #[test]
-    fn test_empty_object_ok() {
-        let expected = HashMap::<String, JsonNode>::new();
-        assert_eq!(JsonNode::from_str("{}"), JsonNode::Object(expected));
+    fn test_jsonc() {
+        let tsconfig = "{\n  // compiler settings\n  \"compilerOptions\": {\n    \"target\": \"es2017\", /* or newer */\n    \"lib\": [\"dom\", /* \"webworker\", */ \"es2017\" // the rest\n    ],\n  },\n  /* paths */ \"include\": [\"src/**/*\"]\n}\n// end of file";
+        let expected = "{\"compilerOptions\":{\"target\":\"es2017\",\"lib\":[\"dom\",\"es2017\"]},\"include\":[\"src/**/*\"]}";
+        assert_eq!(JsonNode::from_str_with(tsconfig, ParserOptions::jsonc()).unwrap(), JsonNode::from_str(expected).unwrap());
+        assert_eq!(JsonNode::from_str_with("[\"// not a comment\", \"/* nor this */\"]", ParserOptions::jsonc()).unwrap().to_string(),
+            "[\"// not a comment\",\"/* nor this */\"]");
+        assert_eq!(JsonNode::from_str_with("/**/1/***/", ParserOptions::jsonc()).unwrap(), JsonNode::from(1));
+
+        let e = JsonNode::from_str_with("{\"a\": 1 /* unterminated", ParserOptions::jsonc()).unwrap_err();
+        assert!(e.unexpected_end);
+        assert!(JsonNode::from_str_with("[1, / 2]", ParserOptions::jsonc()).is_err());
+        assert!(JsonNode::from_str_with("{'a': 1}", ParserOptions::jsonc()).is_err());
     }
 
     #[test]
-    fn test_object_ok() {
-        let mut expected = HashMap::<String, JsonNode>::new();
-        expected.insert("foo".to_string(), JsonNode::Null);
-        assert_eq!(JsonNode::from_str("{\"foo\":null}"), JsonNode::Object(expected));
+    fn test_json5() {
+        let config = "\u{feff}// settings\n{\n  unquoted: 'and you can quote me on that',\n  singleQuotes: 'I can use \"double quotes\" here',\n  lineBreaks: \"Look, Mom! \\\nNo \\\\n's!\",\n  hexadecimal: 0xdecaf,\n  leadingDecimalPoint: .8675309, andTrailing: 8675309.,\n  positiveSign: +1,\n  trailingComma: 'in objects', andIn: ['arrays',],\n  \"backwardsCompatible\": \"with JSON\",\n  ключ: '\\x41\\v\\0\\q',\n}\n";
+        let node = JsonNode::from_str_with(config, ParserOptions::json5()).unwrap();
+        assert_eq!(node["unquoted"], JsonNode::from("and you can quote me on that"));
+        assert_eq!(node["singleQuotes"], JsonNode::from("I can use \"double quotes\" here"));
+        assert_eq!(node["lineBreaks"], JsonNode::from("Look, Mom! No \\n's!"));
+        assert_eq!(node["hexadecimal"], JsonNode::from(912559));
+        assert_eq!(node["leadingDecimalPoint"], JsonNode::from(0.8675309));
+        assert_eq!(node["andTrailing"], JsonNode::from(8675309));
+        assert_eq!(node["positiveSign"], JsonNode::from(1));
+        assert_eq!(node["andIn"], JsonNode::from(vec!["arrays"]));
+        assert_eq!(node["backwardsCompatible"], JsonNode::from("with JSON"));
+        assert_eq!(node["ключ"], JsonNode::from("A\u{b}\0q"));
+        assert_eq!(JsonNode::from_str_with("[-0xFF, 0x10000000000000000, \"a\\\r\nb\\\u{2028}c\"]", ParserOptions::json5()).unwrap(),
+            JsonNode::from_str("[-255, 18446744073709551616, \"abc\"]").unwrap());
+        for malformed in &["[0x]", "[0xg]", "\"\\1\"", "{1a: 2}", "{\u{a0}: 1}"] {
+            assert!(JsonNode::from_str_with(malformed, ParserOptions::json5()).is_err(), "{}", malformed);
+        }
+    }
+
+    #[test]
+    fn test_lenient_options_are_off_by_default() {
+        for lenient in &["[1,]", "{\"a\":1,}", "['a']", "{a:1}", "NaN", "[Infinity]", "[1 /* c */]", "// c\n1 ", "[0x1]", "\"\\\n\"", "[\u{a0}1]",
+                "[+1]", "[.5]", "[1.]", "[+.5e1]"] {
+            assert!(JsonNode::from_str(lenient).is_err(), "{}", lenient);
+            assert!(JsonNode::from_str_with(lenient, ParserOptions::lenient()).is_ok(), "{}", lenient);
+        }
+        // JSON5 has no leading zeros either
+        for strict in &["[01]", "[-01.5]", "[+-1]", "[.]", "[+.e1]"] {
+            assert!(JsonNode::from_str(strict).is_err(), "{}", strict);
+            assert!(JsonNode::from_str_with(strict, ParserOptions::lenient()).is_err(), "{}", strict);
+        }
+    }
+
+    #[test]
+    fn test_lenient_errors() {
+        let error = |json: &str| JsonNode::from_str_with(json, ParserOptions::lenient()).unwrap_err();
+        let e = error("{a: 1,\n b 2}");
+        assert_eq!((e.offset, e.line, e.column, e.expected), (10, 2, 4, Expected::Colon));
+        assert_eq!(error("[1 2]").expected, Expected::CommaOrEnd(']'));
+        assert_eq!(error("{-}").expected, Expected::KeyOrEnd);
+        assert!(error("[1, /* open").unexpected_end);
+        assert_eq!(error("[1,,]").offset, 3);
+    }
+
+    #[test]
+    fn test_duplicate_keys() {
+        let json = "{\"a\": 1, \"b\": {\"c\": 2, \"c\": 3}, \"a\": 4}";
+        let with = |policy| JsonNode::from_str_with(json, ParserOptions::new().duplicate_keys(policy));
+        assert_eq!(JsonNode::from_str(json).unwrap(), JsonNode::from_str("{\"a\":1,\"b\":{\"c\":2}}").unwrap());
+        assert_eq!(with(DuplicateKeys::FirstWins).unwrap(), JsonNode::from_str(json).unwrap());
+        assert_eq!(with(DuplicateKeys::LastWins).unwrap(), JsonNode::from_str("{\"a\":4,\"b\":{\"c\":3}}").unwrap());
+
+        let e = with(DuplicateKeys::Error).unwrap_err();
+        assert_eq!((e.reason.clone(), e.offset, e.column), (Reason::DuplicateKey("c".to_string()), 23, 24));
+        assert_eq!(e.to_string(), "line 1, column 24: duplicate member name \"c\"");
+        let e = JsonNode::from_str_with("{'a\\u0062': 1, ab: 2}", ParserOptions::json5().duplicate_keys(DuplicateKeys::Error)).unwrap_err();
+        assert_eq!(e.reason, Reason::DuplicateKey("ab".to_string()));
+        assert!(JsonNode::from_str_with("[{\"a\": 1}, {\"a\": 2}]", ParserOptions::new().duplicate_keys(DuplicateKeys::Error)).is_ok());
+    }
+
+    #[test]
+    fn test_depth_limit() {
+        let nested = |depth: usize| format!("{}1{}", "[{\"a\":".repeat(depth), "}]".repeat(depth));
+        let limited = |json: &str, depth| JsonNode::from_str_with(json, ParserOptions::new().max_depth(depth));
+        assert!(limited(&nested(5), 10).is_ok());
+        assert!(limited(&nested(5), 9).is_err());
+        assert!(limited("[]", 1).is_ok());
+        assert!(limited("1 ", 0).is_ok());
+
+        let e = limited("{\"a\": [[1], [[2]]]}", 3).unwrap_err();
+        assert_eq!((e.reason.clone(), e.offset), (Reason::TooDeep(3), 13));
+        assert_eq!(e.to_string(), "line 1, column 14: containers nest deeper than 3 levels");
+    }
+
+    #[test]
+    fn test_pathological_nesting_fails_cleanly() {
+        let depth = 100000;
+        // with `grow_stack` there is no limit by default
+        #[cfg(not(feature = "grow_stack"))]
+        {
+            let e = JsonNode::from_str(&"[".repeat(depth)).unwrap_err();
+            assert_eq!((e.reason, e.offset), (Reason::TooDeep(DEFAULT_MAX_DEPTH), DEFAULT_MAX_DEPTH));
+            let e = JsonNode::parse_prefix(format!("{}{}", "{\"k\":[".repeat(depth), "]}".repeat(depth)).as_bytes()).unwrap_err();
+            assert_eq!(e.reason, Reason::TooDeep(DEFAULT_MAX_DEPTH));
+        }
+        let e = JsonNode::from_str_with(&"[".repeat(depth), ParserOptions::new().max_depth(64)).unwrap_err();
+        assert_eq!(e.reason, Reason::TooDeep(64));
+    }
+
+    #[test]
+    fn test_nesting_limited_only_by_memory() {
+        let depth = 1000000;
+        let text = format!("{}[]{}", "[{\"a\":".repeat(depth), "}]".repeat(depth));
+        let node = JsonNode::from_str_with(&text, ParserOptions::new().max_depth(usize::MAX)).unwrap();
+        let mut inner = &node;
+        for _ in 0..depth {
+            inner = &inner[0]["a"]
+        }
+        assert_eq!(inner, &JsonNode::Array(Vec::new()))
+    }
+
+    #[test]
+    fn test_hooks_see_every_value_and_key() {
+        let mut metrics = ParseMetrics::default();
+        JsonNode::from_bytes_with_hooks(b"{\"a\":[1,2],\"b\":\"x\"}", &mut metrics).unwrap();
+        assert_eq!(metrics, ParseMetrics { values: 5, containers: 2, keys: 2, documents: 1, bytes: 19 });
+        let error = JsonNode::from_bytes_with_hooks(b"{\"a\":[1,}", &mut ParseMetrics::default()).unwrap_err();
+        assert_eq!(error.offset, 8);
+        assert_eq!(JsonNode::from_bytes_with_hooks(b"42", &mut ParseMetrics::default()), Ok(JsonNode::from(42)));
     }
 
     #[test]
     fn test_output_format_ok() {
         let mut output = Vec::new();
         let json_string = "[1.23,null,\"foo\"]";
-        let json = JsonNode::from_str(json_string);
+        let json = JsonNode::from_str(json_string).unwrap();
         write!(output, "{}", json).expect("write never fails");
         assert_eq!(std::str::from_utf8(&output).unwrap(), json_string);
     }
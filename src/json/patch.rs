@@ -0,0 +1,428 @@
+use std::fmt;
+use std::mem;
+use crate::json::{maybe_grow, JsonNode, Object};
+use crate::json::pointer::{array_index, JsonPointer};
+
+/// One JSON Patch (RFC 6902) operation.
+#[derive(PartialEq, Debug, Clone)]
+pub enum PatchOperation {
+    Add { path: JsonPointer, value: JsonNode },
+    Remove { path: JsonPointer },
+    Replace { path: JsonPointer, value: JsonNode },
+    Move { from: JsonPointer, path: JsonPointer },
+    Copy { from: JsonPointer, path: JsonPointer },
+    Test { path: JsonPointer, value: JsonNode }
+}
+
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct Patch {
+    pub operations: Vec<PatchOperation>
+}
+
+/// A programmatic edit for `apply_edits`.
+#[derive(PartialEq, Debug, Clone)]
+pub enum Edit {
+    /// Stores a value, creating missing parents as `JsonPointer::assign` does.
+    Set(JsonPointer, JsonNode),
+    Remove(JsonPointer)
+}
+
+#[derive(PartialEq, Debug)]
+pub enum PatchErrorKind {
+    /// The patch document itself is not valid.
+    Malformed(&'static str),
+    NoSuchPath(JsonPointer),
+    TestFailed(JsonPointer),
+    /// A move into the moved value itself, or a removal of the whole document.
+    InvalidTarget(JsonPointer)
+}
+
+/// Why a patch failed, and at which operation.
+#[derive(PartialEq, Debug)]
+pub struct PatchError {
+    pub index: usize,
+    pub kind: PatchErrorKind
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "patch operation {} ", self.index)?;
+        match &self.kind {
+            PatchErrorKind::Malformed(reason) => write!(f, "is malformed: {}", reason),
+            PatchErrorKind::NoSuchPath(path) => write!(f, "failed: no value at \"{}\"", path),
+            PatchErrorKind::TestFailed(path) => write!(f, "failed: test of \"{}\" did not match", path),
+            PatchErrorKind::InvalidTarget(path) => write!(f, "failed: cannot target \"{}\"", path)
+        }
+    }
+}
+
+impl PatchOperation {
+    pub fn path(&self) -> &JsonPointer {
+        match self {
+            PatchOperation::Add { path, .. } | PatchOperation::Remove { path } | PatchOperation::Replace { path, .. }
+            | PatchOperation::Move { path, .. } | PatchOperation::Copy { path, .. } | PatchOperation::Test { path, .. } => path
+        }
+    }
+
+    pub fn from_node(node : &JsonNode) -> Result<PatchOperation, PatchErrorKind> {
+        let members = match node {
+            JsonNode::Object(o) => o,
+            _ => return Err(PatchErrorKind::Malformed("operation is not an object"))
+        };
+        let pointer = |name : &'static str| match members.get(name) {
+            Some(JsonNode::String(s)) => JsonPointer::parse(s).map_err(|_| PatchErrorKind::Malformed("invalid pointer")),
+            _ => Err(PatchErrorKind::Malformed(if name == "from" { "missing from" } else { "missing path" }))
+        };
+        let value = || members.get("value").cloned().ok_or(PatchErrorKind::Malformed("missing value"));
+
+        match members.get("op") {
+            Some(JsonNode::String(op)) => match op.as_str() {
+                "add" => Ok(PatchOperation::Add { path: pointer("path")?, value: value()? }),
+                "remove" => Ok(PatchOperation::Remove { path: pointer("path")? }),
+                "replace" => Ok(PatchOperation::Replace { path: pointer("path")?, value: value()? }),
+                "move" => Ok(PatchOperation::Move { from: pointer("from")?, path: pointer("path")? }),
+                "copy" => Ok(PatchOperation::Copy { from: pointer("from")?, path: pointer("path")? }),
+                "test" => Ok(PatchOperation::Test { path: pointer("path")?, value: value()? }),
+                _ => Err(PatchErrorKind::Malformed("unknown op"))
+            },
+            _ => Err(PatchErrorKind::Malformed("missing op"))
+        }
+    }
+
+    pub fn to_node(&self) -> JsonNode {
+        let mut members = Object::default();
+        let text = |s : &str| JsonNode::String(s.to_string());
+        let (op, from, value) = match self {
+            PatchOperation::Add { value, .. } => ("add", None, Some(value)),
+            PatchOperation::Remove { .. } => ("remove", None, None),
+            PatchOperation::Replace { value, .. } => ("replace", None, Some(value)),
+            PatchOperation::Move { from, .. } => ("move", Some(from), None),
+            PatchOperation::Copy { from, .. } => ("copy", Some(from), None),
+            PatchOperation::Test { value, .. } => ("test", None, Some(value))
+        };
+        members.insert("op".to_string(), text(op));
+        members.insert("path".to_string(), text(&self.path().to_string()));
+        if let Some(from) = from {
+            members.insert("from".to_string(), text(&from.to_string()));
+        }
+        if let Some(value) = value {
+            members.insert("value".to_string(), value.clone());
+        }
+        JsonNode::Object(members)
+    }
+}
+
+impl Patch {
+    /// Reads a patch document: an array of operation objects.
+    pub fn from_node(node : &JsonNode) -> Result<Patch, PatchError> {
+        match node {
+            JsonNode::Array(a) => a.iter().enumerate()
+                .map(|(index, op)| PatchOperation::from_node(op).map_err(|kind| PatchError { index, kind }))
+                .collect::<Result<Vec<_>, _>>()
+                .map(|operations| Patch { operations }),
+            _ => Err(PatchError { index: 0, kind: PatchErrorKind::Malformed("patch is not an array") })
+        }
+    }
+
+    pub fn to_node(&self) -> JsonNode {
+        JsonNode::Array(self.operations.iter().map(PatchOperation::to_node).collect())
+    }
+}
+
+/// Applies every operation of `patch`, or none of them: on failure `node` is left untouched.
+pub fn apply(node : &mut JsonNode, patch : &Patch) -> Result<(), PatchError> {
+    let mut patched = node.clone();
+    for (index, op) in patch.operations.iter().enumerate() {
+        apply_operation(&mut patched, op).map_err(|kind| PatchError { index, kind })?;
+    }
+    *node = patched;
+    Ok(())
+}
+
+/// Applies every edit in order, or none of them: on failure `node` is left
+/// untouched and the error tells which edit failed.
+pub fn apply_edits(node : &mut JsonNode, edits : &[Edit]) -> Result<(), PatchError> {
+    let mut edited = node.clone();
+    for (index, edit) in edits.iter().enumerate() {
+        let result = match edit {
+            Edit::Set(path, value) => path.assign(&mut edited, value.clone()).map(mem::drop).map_err(|_| PatchErrorKind::NoSuchPath(path.clone())),
+            Edit::Remove(path) => remove(&mut edited, path).map(mem::drop)
+        };
+        result.map_err(|kind| PatchError { index, kind })?;
+    }
+    *node = edited;
+    Ok(())
+}
+
+/// Applies a single operation in place; see `apply` for all-or-nothing patching.
+pub fn apply_operation(root : &mut JsonNode, op : &PatchOperation) -> Result<(), PatchErrorKind> {
+    match op {
+        PatchOperation::Add { path, value } => add(root, path, value.clone()),
+        PatchOperation::Remove { path } => remove(root, path).map(mem::drop),
+        PatchOperation::Replace { path, value } => {
+            let slot = path.resolve_mut(root).ok_or_else(|| PatchErrorKind::NoSuchPath(path.clone()))?;
+            *slot = value.clone();
+            Ok(())
+        },
+        PatchOperation::Move { from, path } => {
+            if path.starts_with(from) && path != from {
+                return Err(PatchErrorKind::InvalidTarget(path.clone()))
+            }
+            let value = remove(root, from)?;
+            add(root, path, value)
+        },
+        PatchOperation::Copy { from, path } => {
+            let value = from.resolve(root).cloned().ok_or_else(|| PatchErrorKind::NoSuchPath(from.clone()))?;
+            add(root, path, value)
+        },
+        PatchOperation::Test { path, value } => match path.resolve(root) {
+            Some(actual) if actual == value => Ok(()),
+            Some(_) => Err(PatchErrorKind::TestFailed(path.clone())),
+            None => Err(PatchErrorKind::NoSuchPath(path.clone()))
+        }
+    }
+}
+
+fn add(root : &mut JsonNode, path : &JsonPointer, value : JsonNode) -> Result<(), PatchErrorKind> {
+    let (parent, last) = match (path.parent(), path.last()) {
+        (Some(parent), Some(last)) => (parent, last),
+        _ => {
+            *root = value;
+            return Ok(())
+        }
+    };
+    match parent.resolve_mut(root) {
+        Some(JsonNode::Object(o)) => {
+            o.insert(last.to_string(), value);
+            Ok(())
+        },
+        Some(JsonNode::Array(a)) => {
+            let index = if last == "-" { Some(a.len()) } else { array_index(last) };
+            match index {
+                Some(index) if index <= a.len() => {
+                    a.insert(index, value);
+                    Ok(())
+                },
+                _ => Err(PatchErrorKind::NoSuchPath(path.clone()))
+            }
+        },
+        _ => Err(PatchErrorKind::NoSuchPath(path.clone()))
+    }
+}
+
+fn remove(root : &mut JsonNode, path : &JsonPointer) -> Result<JsonNode, PatchErrorKind> {
+    let (parent, last) = match (path.parent(), path.last()) {
+        (Some(parent), Some(last)) => (parent, last),
+        _ => return Err(PatchErrorKind::InvalidTarget(path.clone()))
+    };
+    let removed = match parent.resolve_mut(root) {
+        Some(JsonNode::Object(o)) => o.remove(last),
+        Some(JsonNode::Array(a)) => match array_index(last) {
+            Some(index) if index < a.len() => Some(a.remove(index)),
+            _ => None
+        },
+        _ => None
+    };
+    removed.ok_or_else(|| PatchErrorKind::NoSuchPath(path.clone()))
+}
+
+/// A patch turning `a` into `b`. Object members are compared key by key and
+/// arrays are aligned with the fewest element removals, insertions and
+/// in-place changes, so only the parts that differ are touched.
+pub fn diff(a : &JsonNode, b : &JsonNode) -> Patch {
+    let mut patch = Patch::default();
+    diff_at(&mut JsonPointer::root(), a, b, &mut patch.operations);
+    patch
+}
+
+fn diff_at(path : &mut JsonPointer, a : &JsonNode, b : &JsonNode, out : &mut Vec<PatchOperation>) {
+    if a == b {
+        return
+    }
+    maybe_grow(|| match (a, b) {
+        (JsonNode::Object(a), JsonNode::Object(b)) => {
+            let mut removed : Vec<&String> = a.keys().filter(|k| !b.contains_key(*k)).collect();
+            removed.sort();
+            for key in removed {
+                out.push(PatchOperation::Remove { path: path.child(key.as_str()) })
+            }
+            let mut keys : Vec<&String> = b.keys().collect();
+            keys.sort();
+            for key in keys {
+                match a.get(key) {
+                    Some(old) => {
+                        path.push(key.as_str());
+                        diff_at(path, old, &b[key], out);
+                        path.pop();
+                    },
+                    None => out.push(PatchOperation::Add { path: path.child(key.as_str()), value: b[key].clone() })
+                }
+            }
+        },
+        (JsonNode::Array(a), JsonNode::Array(b)) => {
+            let prefix = a.iter().zip(b.iter()).take_while(|(x, y)| x == y).count();
+            let suffix = a[prefix..].iter().rev().zip(b[prefix..].iter().rev()).take_while(|(x, y)| x == y).count();
+            let (middle_a, middle_b) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+            let mut index = prefix;
+            for step in align(middle_a, middle_b) {
+                match step {
+                    Step::Keep => index += 1,
+                    Step::Change(i, j) => {
+                        path.push(index.to_string());
+                        diff_at(path, &middle_a[i], &middle_b[j], out);
+                        path.pop();
+                        index += 1
+                    },
+                    Step::Remove => out.push(PatchOperation::Remove { path: path.child(index.to_string()) }),
+                    Step::Insert(j) => {
+                        out.push(PatchOperation::Add { path: path.child(index.to_string()), value: middle_b[j].clone() });
+                        index += 1
+                    }
+                }
+            }
+        },
+        _ => out.push(PatchOperation::Replace { path: path.clone(), value: b.clone() })
+    })
+}
+
+enum Step {
+    Keep,
+    Change(usize, usize),
+    Remove,
+    Insert(usize)
+}
+
+// Cheapest edit script between two arrays (Levenshtein distance, where changing
+// an element in place costs the same as removing or inserting one). Very large
+// inputs fall back to changing elements pairwise.
+fn align(a : &[JsonNode], b : &[JsonNode]) -> Vec<Step> {
+    const MAX_CELLS : usize = 1 << 20;
+    let (n, m) = (a.len(), b.len());
+    if (n + 1) * (m + 1) > MAX_CELLS {
+        let common = n.min(m);
+        let mut steps : Vec<Step> = (0..common).map(|i| Step::Change(i, i)).collect();
+        steps.extend((common..n).map(|_| Step::Remove));
+        steps.extend((common..m).map(Step::Insert));
+        return steps
+    }
+
+    let mut cost = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..=n).rev() {
+        for j in (0..=m).rev() {
+            cost[i][j] = if i == n {
+                m - j
+            } else if j == m {
+                n - i
+            } else if a[i] == b[j] {
+                cost[i + 1][j + 1]
+            } else {
+                1 + cost[i + 1][j + 1].min(cost[i + 1][j]).min(cost[i][j + 1])
+            }
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut steps = Vec::new();
+    while i < n || j < m {
+        if i < n && j < m && a[i] == b[j] {
+            steps.push(Step::Keep);
+            i += 1;
+            j += 1
+        } else if i < n && j < m && cost[i][j] == 1 + cost[i + 1][j + 1] {
+            steps.push(Step::Change(i, j));
+            i += 1;
+            j += 1
+        } else if i < n && (j == m || cost[i][j] == 1 + cost[i + 1][j]) {
+            steps.push(Step::Remove);
+            i += 1
+        } else {
+            steps.push(Step::Insert(j));
+            j += 1
+        }
+    }
+    steps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn patch(text : &str) -> Patch {
+        Patch::from_node(&JsonNode::from_str(text).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_all_operations() {
+        let mut doc = JsonNode::from_str("{\"a\":{\"b\":[1,2]},\"c\":\"x\"}").unwrap();
+        apply(&mut doc, &patch("[\
+            {\"op\":\"add\",\"path\":\"/a/b/1\",\"value\":9},\
+            {\"op\":\"remove\",\"path\":\"/a/b/0\"},\
+            {\"op\":\"replace\",\"path\":\"/c\",\"value\":\"y\"},\
+            {\"op\":\"move\",\"from\":\"/c\",\"path\":\"/d\"},\
+            {\"op\":\"copy\",\"from\":\"/a/b\",\"path\":\"/e\"},\
+            {\"op\":\"test\",\"path\":\"/e/-\",\"value\":null}]")).unwrap_err();
+        assert_eq!(doc, JsonNode::from_str("{\"a\":{\"b\":[1,2]},\"c\":\"x\"}").unwrap());
+
+        apply(&mut doc, &patch("[\
+            {\"op\":\"add\",\"path\":\"/a/b/1\",\"value\":9},\
+            {\"op\":\"remove\",\"path\":\"/a/b/0\"},\
+            {\"op\":\"replace\",\"path\":\"/c\",\"value\":\"y\"},\
+            {\"op\":\"move\",\"from\":\"/c\",\"path\":\"/d\"},\
+            {\"op\":\"copy\",\"from\":\"/a/b\",\"path\":\"/e\"},\
+            {\"op\":\"test\",\"path\":\"/e\",\"value\":[9,2]}]")).unwrap();
+        assert_eq!(doc, JsonNode::from_str("{\"a\":{\"b\":[9,2]},\"d\":\"y\",\"e\":[9,2]}").unwrap());
+    }
+
+    #[test]
+    fn test_failures_report_the_operation() {
+        let mut doc = JsonNode::from_str("{\"a\":1}").unwrap();
+        let failed = apply(&mut doc, &patch("[{\"op\":\"add\",\"path\":\"/b\",\"value\":2},{\"op\":\"test\",\"path\":\"/a\",\"value\":2}]"));
+        assert_eq!(failed, Err(PatchError { index: 1, kind: PatchErrorKind::TestFailed(JsonPointer::parse("/a").unwrap()) }));
+        assert_eq!(doc, JsonNode::from_str("{\"a\":1}").unwrap());
+
+        let into_itself = apply(&mut doc, &patch("[{\"op\":\"move\",\"from\":\"\",\"path\":\"/a/b\"}]"));
+        assert_eq!(into_itself.unwrap_err().kind, PatchErrorKind::InvalidTarget(JsonPointer::parse("/a/b").unwrap()));
+
+        let malformed = Patch::from_node(&JsonNode::from_str("[{\"op\":\"add\",\"path\":\"/a\"}]").unwrap());
+        assert_eq!(malformed, Err(PatchError { index: 0, kind: PatchErrorKind::Malformed("missing value") }));
+    }
+
+    #[test]
+    fn test_diff_produces_applicable_minimal_patch() {
+        let a = JsonNode::from_str("{\"keep\":[1,2,3,4],\"gone\":true,\"deep\":{\"x\":1}}").unwrap();
+        let b = JsonNode::from_str("{\"keep\":[1,3,4,5],\"new\":null,\"deep\":{\"x\":2}}").unwrap();
+        let patch = diff(&a, &b);
+        assert_eq!(patch.to_node().to_string().matches("\"op\"").count(), 5);
+
+        let mut patched = a.clone();
+        apply(&mut patched, &patch).unwrap();
+        assert_eq!(patched, b);
+        assert_eq!(diff(&a, &a), Patch::default());
+    }
+
+    #[test]
+    fn test_patch_round_trips_through_json() {
+        let original = patch("[{\"op\":\"copy\",\"from\":\"/a~1b\",\"path\":\"/c\"}]");
+        assert_eq!(Patch::from_node(&original.to_node()).unwrap(), original);
+    }
+
+    #[test]
+    fn test_edits_are_all_or_nothing() {
+        let pointer = |text : &str| JsonPointer::parse(text).unwrap();
+        let mut config = JsonNode::from_str("{\"db\":{\"host\":\"a\",\"port\":1},\"debug\":true}").unwrap();
+        let edits = vec![
+            Edit::Set(pointer("/db/host"), JsonNode::String("b".to_string())),
+            Edit::Remove(pointer("/debug")),
+            Edit::Remove(pointer("/debug"))
+        ];
+        let error = apply_edits(&mut config, &edits).unwrap_err();
+        assert_eq!(error, PatchError { index: 2, kind: PatchErrorKind::NoSuchPath(pointer("/debug")) });
+        assert_eq!(config, JsonNode::from_str("{\"db\":{\"host\":\"a\",\"port\":1},\"debug\":true}").unwrap());
+
+        apply_edits(&mut config, &edits[..2]).unwrap();
+        apply_edits(&mut config, &[Edit::Set(pointer("/cache/ttl"), JsonNode::Number(60.0.into()))]).unwrap();
+        assert_eq!(config, JsonNode::from_str("{\"db\":{\"host\":\"b\",\"port\":1},\"cache\":{\"ttl\":60}}").unwrap());
+        assert!(apply_edits(&mut config, &[Edit::Set(pointer("/db/port/x"), JsonNode::Null)]).is_err());
+    }
+}
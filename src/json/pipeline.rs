@@ -0,0 +1,161 @@
+use crate::json::{maybe_grow, JsonNode, Object};
+use crate::json::pointer::JsonPointer;
+
+type Rename = Box<dyn Fn(&str) -> Option<String>>;
+type MapValue = Box<dyn Fn(&JsonPointer, JsonNode) -> JsonNode>;
+
+/// A composition of tree transforms, all applied in a single traversal.
+/// Paths given to the steps are glob patterns (see `JsonPointer::select_all`)
+/// and always refer to the input document, before any key is renamed or any
+/// array element removed.
+#[derive(Default)]
+pub struct Pipeline {
+    renames: Vec<Rename>,
+    keep: Vec<JsonPointer>,
+    drop: Vec<JsonPointer>,
+    maps: Vec<MapValue>,
+    prune: bool
+}
+
+impl Pipeline {
+    pub fn new() -> Pipeline {
+        Pipeline::default()
+    }
+
+    /// Renames every object member called `from`.
+    pub fn rename_key(self, from : &str, to : &str) -> Pipeline {
+        let (from, to) = (from.to_string(), to.to_string());
+        self.rename_keys(move |key| if key == from { Some(to.clone()) } else { None })
+    }
+
+    /// Renames the object members for which `rename` returns a new name.
+    pub fn rename_keys<F>(mut self, rename : F) -> Pipeline
+        where F : Fn(&str) -> Option<String> + 'static
+    {
+        self.renames.push(Box::new(rename));
+        self
+    }
+
+    /// Keeps only the values matching `pattern` (or another kept pattern) and
+    /// the containers on the way to them.
+    pub fn keep_paths(mut self, pattern : JsonPointer) -> Pipeline {
+        self.keep.push(pattern);
+        self
+    }
+
+    /// Removes the values matching `pattern`.
+    pub fn drop_paths(mut self, pattern : JsonPointer) -> Pipeline {
+        self.drop.push(pattern);
+        self
+    }
+
+    /// Replaces every scalar with what `map` makes of it and its path.
+    pub fn map_values<F>(mut self, map : F) -> Pipeline
+        where F : Fn(&JsonPointer, JsonNode) -> JsonNode + 'static
+    {
+        self.maps.push(Box::new(map));
+        self
+    }
+
+    /// Removes nulls, and the arrays and objects the other steps left empty.
+    pub fn prune(mut self) -> Pipeline {
+        self.prune = true;
+        self
+    }
+
+    pub fn apply(&self, node : JsonNode) -> JsonNode {
+        self.walk(node, &mut JsonPointer::root(), self.keep.is_empty()).unwrap_or(JsonNode::Null)
+    }
+
+    // `kept` tells whether an ancestor already matched a keep pattern
+    fn walk(&self, mut node : JsonNode, path : &mut JsonPointer, kept : bool) -> Option<JsonNode> {
+        maybe_grow(|| {
+            if self.drop.iter().any(|pattern| pattern.glob_matches(path)) {
+                return None
+            }
+            let kept = kept || self.keep.iter().any(|pattern| pattern.glob_matches(path));
+            if !kept && !self.keep.iter().any(|pattern| pattern.glob_matches_below(path)) {
+                return None
+            }
+            let node = match node {
+                JsonNode::Array(ref mut a) => {
+                    let mut out = Vec::with_capacity(a.len());
+                    for (i, item) in std::mem::take(a).into_iter().enumerate() {
+                        path.push(i.to_string());
+                        out.extend(self.walk(item, path, kept));
+                        path.pop();
+                    }
+                    JsonNode::Array(out)
+                },
+                JsonNode::Object(ref mut o) => {
+                    let mut out = Object::with_capacity_and_hasher(o.len(), Default::default());
+                    for (key, value) in std::mem::take(o) {
+                        path.push(key.as_str());
+                        if let Some(value) = self.walk(value, path, kept) {
+                            out.insert(self.rename(key), value);
+                        }
+                        path.pop();
+                    }
+                    JsonNode::Object(out)
+                },
+                _ => self.maps.iter().fold(node, |value, map| map(path, value))
+            };
+            let empty = match &node {
+                JsonNode::Null => true,
+                JsonNode::Array(a) => a.is_empty(),
+                JsonNode::Object(o) => o.is_empty(),
+                _ => false
+            };
+            if self.prune && empty { None } else { Some(node) }
+        })
+    }
+
+    fn rename(&self, key : String) -> String {
+        self.renames.iter().fold(key, |key, rename| rename(&key).unwrap_or(key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn pointer(text : &str) -> JsonPointer {
+        JsonPointer::parse(text).unwrap()
+    }
+
+    const USERS : &str = "{\"users\":[\
+        {\"name\":\"ann\",\"mail\":\"A@X\",\"password\":\"x\"},\
+        {\"name\":\"bob\",\"mail\":null,\"password\":\"y\"}],\"total\":2}";
+
+    #[test]
+    fn test_steps_compose_in_one_pass() {
+        let result = Pipeline::new()
+            .keep_paths(pointer("/users"))
+            .drop_paths(pointer("/users/*/password"))
+            .rename_key("mail", "email")
+            .map_values(|path, value| match value {
+                JsonNode::String(ref s) if path.last() == Some("mail") => JsonNode::String(s.to_lowercase()),
+                other => other
+            })
+            .prune()
+            .apply(JsonNode::from_str(USERS).unwrap());
+        assert_eq!(result, JsonNode::from_str("{\"users\":[{\"name\":\"ann\",\"email\":\"a@x\"},{\"name\":\"bob\"}]}").unwrap());
+    }
+
+    #[test]
+    fn test_keep_selects_leaves_and_their_containers() {
+        let result = Pipeline::new().keep_paths(pointer("/users/*/name")).keep_paths(pointer("/total")).apply(JsonNode::from_str(USERS).unwrap());
+        assert_eq!(result, JsonNode::from_str("{\"users\":[{\"name\":\"ann\"},{\"name\":\"bob\"}],\"total\":2}").unwrap());
+        assert_eq!(Pipeline::new().drop_paths(pointer("")).apply(JsonNode::from_str(USERS).unwrap()), JsonNode::Null);
+    }
+
+    #[test]
+    fn test_renames_apply_in_order() {
+        let result = Pipeline::new()
+            .rename_keys(|key| Some(key.to_uppercase()))
+            .rename_key("NAME", "label")
+            .apply(JsonNode::from_str("{\"name\":1,\"x\":{\"name\":2}}").unwrap());
+        assert_eq!(result, JsonNode::from_str("{\"label\":1,\"X\":{\"label\":2}}").unwrap());
+    }
+}
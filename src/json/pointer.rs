@@ -0,0 +1,524 @@
+use std::fmt;
+use std::mem;
+use std::str::FromStr;
+use crate::json::{maybe_grow, JsonNode, Object};
+
+/// A parsed JSON Pointer (RFC 6901): a sequence of unescaped reference tokens.
+/// The empty pointer refers to the whole document.
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Default)]
+pub struct JsonPointer {
+    tokens: Vec<String>
+}
+
+#[derive(PartialEq, Debug)]
+pub enum PointerError {
+    /// The text is not a pointer: it does not start with `/` or has a stray `~`.
+    Syntax(String),
+    /// A token on the way goes through a value that is not an object or array.
+    NotAContainer(String),
+    /// An array token is not an index, or is past the end of the array.
+    BadIndex(String)
+}
+
+impl fmt::Display for PointerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PointerError::Syntax(pointer) => write!(f, "invalid JSON pointer \"{}\"", pointer),
+            PointerError::NotAContainer(pointer) => write!(f, "no object or array at \"{}\"", pointer),
+            PointerError::BadIndex(pointer) => write!(f, "bad array index at \"{}\"", pointer)
+        }
+    }
+}
+
+/// Escapes one reference token: `~` becomes `~0` and `/` becomes `~1`.
+pub fn escape_token(token : &str) -> String {
+    token.replace('~', "~0").replace('/', "~1")
+}
+
+/// The array index a token denotes: decimal digits without leading zeros.
+pub fn array_index(token : &str) -> Option<usize> {
+    let digits = !token.is_empty() && token.bytes().all(|b| b.is_ascii_digit());
+    if digits && (token == "0" || !token.starts_with('0')) {
+        token.parse().ok()
+    } else {
+        None
+    }
+}
+
+impl JsonPointer {
+    pub fn root() -> JsonPointer {
+        JsonPointer { tokens: Vec::new() }
+    }
+
+    pub fn parse(text : &str) -> Result<JsonPointer, PointerError> {
+        if text.is_empty() {
+            return Ok(JsonPointer::root())
+        }
+        if !text.starts_with('/') {
+            return Err(PointerError::Syntax(text.to_string()))
+        }
+        let mut tokens = Vec::new();
+        for raw in text[1..].split('/') {
+            let mut token = String::with_capacity(raw.len());
+            let mut chars = raw.chars();
+            while let Some(c) = chars.next() {
+                if c != '~' {
+                    token.push(c);
+                    continue
+                }
+                match chars.next() {
+                    Some('0') => token.push('~'),
+                    Some('1') => token.push('/'),
+                    _ => return Err(PointerError::Syntax(text.to_string()))
+                }
+            }
+            tokens.push(token)
+        }
+        Ok(JsonPointer { tokens })
+    }
+
+    pub fn from_tokens<I, T>(tokens : I) -> JsonPointer
+        where I : IntoIterator<Item = T>, T : Into<String>
+    {
+        JsonPointer { tokens: tokens.into_iter().map(Into::into).collect() }
+    }
+
+    pub fn tokens(&self) -> &[String] {
+        &self.tokens
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    pub fn push<T : Into<String>>(&mut self, token : T) {
+        self.tokens.push(token.into())
+    }
+
+    pub fn pop(&mut self) -> Option<String> {
+        self.tokens.pop()
+    }
+
+    pub fn last(&self) -> Option<&str> {
+        self.tokens.last().map(String::as_str)
+    }
+
+    /// The pointer to the parent container, or None for the root.
+    pub fn parent(&self) -> Option<JsonPointer> {
+        if self.is_root() {
+            None
+        } else {
+            Some(JsonPointer { tokens: self.tokens[..self.tokens.len() - 1].to_vec() })
+        }
+    }
+
+    pub fn child<T : Into<String>>(&self, token : T) -> JsonPointer {
+        let mut child = self.clone();
+        child.push(token);
+        child
+    }
+
+    /// True when `self` refers to `other` or something inside it.
+    pub fn starts_with(&self, other : &JsonPointer) -> bool {
+        self.tokens.starts_with(&other.tokens)
+    }
+
+    pub fn resolve<'a>(&self, root : &'a JsonNode) -> Option<&'a JsonNode> {
+        self.tokens.iter().try_fold(root, |node, token| match node {
+            JsonNode::Object(o) => o.get(token),
+            JsonNode::Array(a) => array_index(token).and_then(|i| a.get(i)),
+            _ => None
+        })
+    }
+
+    pub fn resolve_mut<'a>(&self, root : &'a mut JsonNode) -> Option<&'a mut JsonNode> {
+        self.tokens.iter().try_fold(root, |node, token| match node {
+            JsonNode::Object(o) => o.get_mut(token),
+            JsonNode::Array(a) => array_index(token).and_then(move |i| a.get_mut(i)),
+            _ => None
+        })
+    }
+
+    /// Stores `value` at this pointer and returns whatever it replaced.
+    /// Missing or null intermediate values become arrays when the next token
+    /// is `-` or `0` and objects otherwise; `-` or the length of an array appends.
+    pub fn assign(&self, root : &mut JsonNode, value : JsonNode) -> Result<Option<JsonNode>, PointerError> {
+        let (last, parents) = match self.tokens.split_last() {
+            Some(split) => split,
+            None => return Ok(Some(mem::replace(root, value)))
+        };
+
+        let mut node = root;
+        for (depth, token) in parents.iter().enumerate() {
+            node = match self.container(node, depth)? {
+                JsonNode::Object(o) => o.entry(token.clone()).or_insert(JsonNode::Null),
+                JsonNode::Array(a) => {
+                    let index = self.index(a, depth)?;
+                    if index == a.len() {
+                        a.push(JsonNode::Null)
+                    }
+                    &mut a[index]
+                },
+                _ => unreachable!("container() only returns objects and arrays")
+            }
+        }
+
+        match self.container(node, parents.len())? {
+            JsonNode::Object(o) => Ok(o.insert(last.clone(), value)),
+            JsonNode::Array(a) => {
+                let index = self.index(a, parents.len())?;
+                if index == a.len() {
+                    a.push(value);
+                    Ok(None)
+                } else {
+                    Ok(Some(mem::replace(&mut a[index], value)))
+                }
+            },
+            _ => unreachable!("container() only returns objects and arrays")
+        }
+    }
+
+    // The container that token `depth` looks into, creating it in place of a null.
+    fn container<'a>(&self, node : &'a mut JsonNode, depth : usize) -> Result<&'a mut JsonNode, PointerError> {
+        if *node == JsonNode::Null {
+            let token = &self.tokens[depth];
+            *node = if token == "-" || token == "0" { JsonNode::Array(Vec::new()) } else { JsonNode::Object(Object::default()) }
+        }
+        match node {
+            JsonNode::Object(_) | JsonNode::Array(_) => Ok(node),
+            _ => Err(PointerError::NotAContainer(self.prefix(depth)))
+        }
+    }
+
+    fn index(&self, array : &[JsonNode], depth : usize) -> Result<usize, PointerError> {
+        let token = &self.tokens[depth];
+        match if token == "-" { Some(array.len()) } else { array_index(token) } {
+            Some(index) if index <= array.len() => Ok(index),
+            _ => Err(PointerError::BadIndex(self.prefix(depth)))
+        }
+    }
+
+    fn prefix(&self, depth : usize) -> String {
+        JsonPointer { tokens: self.tokens[..=depth].to_vec() }.to_string()
+    }
+
+    /// Every value whose path matches this pointer read as a glob pattern:
+    /// within a token `*` matches any run of characters and `?` any single
+    /// one, and a whole `**` token matches any number of levels. Matches come
+    /// parents first, with object members in key order.
+    pub fn select_all<'a>(&self, root : &'a JsonNode) -> Vec<(JsonPointer, &'a JsonNode)> {
+        let mut out = Vec::new();
+        let states = self.closure(vec![0]);
+        self.select_into(root, &mut JsonPointer::root(), states, &mut out);
+        out
+    }
+
+    // `states` are the pattern positions reached at `node`, like an NFA's
+    fn select_into<'a>(&self, node : &'a JsonNode, path : &mut JsonPointer, states : Vec<usize>, out : &mut Vec<(JsonPointer, &'a JsonNode)>) {
+        maybe_grow(|| {
+            if states.contains(&self.tokens.len()) {
+                out.push((path.clone(), node))
+            }
+            let children : Vec<(String, &JsonNode)> = match node {
+                JsonNode::Array(a) => a.iter().enumerate().map(|(i, child)| (i.to_string(), child)).collect(),
+                JsonNode::Object(o) => {
+                    let mut members : Vec<(String, &JsonNode)> = o.iter().map(|(k, v)| (k.clone(), v)).collect();
+                    members.sort_by(|a, b| a.0.cmp(&b.0));
+                    members
+                },
+                _ => return
+            };
+            for (token, child) in children {
+                let next = self.advance(&states, &token);
+                if !next.is_empty() {
+                    path.push(token);
+                    self.select_into(child, path, next, out);
+                    path.pop();
+                }
+            }
+        })
+    }
+
+    /// True when `path` matches this pointer read as a glob pattern, as in `select_all`.
+    pub fn glob_matches(&self, path : &JsonPointer) -> bool {
+        self.glob_states(path).contains(&self.tokens.len())
+    }
+
+    /// True when this glob pattern could match something strictly inside `path`.
+    pub fn glob_matches_below(&self, path : &JsonPointer) -> bool {
+        self.glob_states(path).iter().any(|s| *s < self.tokens.len())
+    }
+
+    fn glob_states(&self, path : &JsonPointer) -> Vec<usize> {
+        path.tokens.iter().fold(self.closure(vec![0]), |states, token| self.advance(&states, token))
+    }
+
+    fn advance(&self, states : &[usize], token : &str) -> Vec<usize> {
+        let next = states.iter().filter(|s| **s < self.tokens.len()).filter_map(|s| {
+            let pattern = &self.tokens[*s];
+            if pattern == "**" {
+                Some(*s)
+            } else if glob_matches(pattern, token) {
+                Some(s + 1)
+            } else {
+                None
+            }
+        }).collect();
+        self.closure(next)
+    }
+
+    // adds the positions after each `**`, which may match no levels at all
+    fn closure(&self, mut states : Vec<usize>) -> Vec<usize> {
+        let mut i = 0;
+        while i < states.len() {
+            let s = states[i];
+            if self.tokens.get(s).map(String::as_str) == Some("**") {
+                states.push(s + 1)
+            }
+            i += 1
+        }
+        states.sort_unstable();
+        states.dedup();
+        states
+    }
+}
+
+fn glob_matches(pattern : &str, token : &str) -> bool {
+    let pattern : Vec<char> = pattern.chars().collect();
+    let token : Vec<char> = token.chars().collect();
+    // positions to resume from after the last `*`
+    let (mut p, mut t, mut star) = (0, 0, None);
+    while t < token.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == token[t]) {
+            p += 1;
+            t += 1
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1
+        } else if let Some((star_p, star_t)) = star {
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1))
+        } else {
+            return false
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// A relative JSON Pointer: walk `up` levels from a current location, optionally
+/// shift the array index there, then either follow `pointer` or (with `#`)
+/// take the key or index under which the value sits.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct RelativePointer {
+    up: usize,
+    shift: isize,
+    name_only: bool,
+    pointer: JsonPointer
+}
+
+/// What a relative pointer evaluates to.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum RelativeValue<'a> {
+    Node(&'a JsonNode),
+    Key(&'a str),
+    Index(usize)
+}
+
+impl RelativePointer {
+    /// Parses forms like `0`, `1/foo/0`, `2#` and `0+1/name`.
+    pub fn parse(text : &str) -> Result<RelativePointer, PointerError> {
+        let syntax = || PointerError::Syntax(text.to_string());
+        let digits = text.bytes().take_while(u8::is_ascii_digit).count();
+        let up = array_index(&text[..digits]).ok_or_else(syntax)?;
+        let mut rest = &text[digits..];
+
+        let mut shift = 0;
+        if rest.starts_with('+') || rest.starts_with('-') {
+            let length = 1 + rest[1..].bytes().take_while(u8::is_ascii_digit).count();
+            let amount = array_index(&rest[1..length]).ok_or_else(syntax)? as isize;
+            shift = if rest.starts_with('-') { -amount } else { amount };
+            rest = &rest[length..];
+        }
+
+        if rest == "#" {
+            Ok(RelativePointer { up, shift, name_only: true, pointer: JsonPointer::root() })
+        } else {
+            let pointer = JsonPointer::parse(rest).map_err(|_| syntax())?;
+            Ok(RelativePointer { up, shift, name_only: false, pointer })
+        }
+    }
+
+    /// The absolute location this refers to when evaluated at `from`, if it exists in the tree shape.
+    pub fn target(&self, from : &JsonPointer) -> Option<JsonPointer> {
+        if self.up > from.tokens.len() {
+            return None
+        }
+        let mut location = JsonPointer { tokens: from.tokens[..from.tokens.len() - self.up].to_vec() };
+        if self.shift != 0 {
+            let index = array_index(location.last()?)? as isize + self.shift;
+            if index < 0 {
+                return None
+            }
+            location.pop();
+            location.push(index.to_string());
+        }
+        location.tokens.extend(self.pointer.tokens.iter().cloned());
+        Some(location)
+    }
+
+    pub fn resolve<'a>(&self, root : &'a JsonNode, from : &JsonPointer) -> Option<RelativeValue<'a>> {
+        let location = self.target(from)?;
+        let node = location.resolve(root)?;
+        if !self.name_only {
+            return Some(RelativeValue::Node(node))
+        }
+        let parent = location.parent()?.resolve(root)?;
+        match parent {
+            JsonNode::Array(_) => array_index(location.last()?).map(RelativeValue::Index),
+            JsonNode::Object(o) => o.keys().find(|k| Some(k.as_str()) == location.last()).map(|k| RelativeValue::Key(k)),
+            _ => None
+        }
+    }
+}
+
+impl FromStr for RelativePointer {
+    type Err = PointerError;
+
+    fn from_str(text : &str) -> Result<RelativePointer, PointerError> {
+        RelativePointer::parse(text)
+    }
+}
+
+impl fmt::Display for RelativePointer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.up)?;
+        if self.shift != 0 {
+            write!(f, "{:+}", self.shift)?;
+        }
+        if self.name_only {
+            write!(f, "#")
+        } else {
+            write!(f, "{}", self.pointer)
+        }
+    }
+}
+
+impl FromStr for JsonPointer {
+    type Err = PointerError;
+
+    fn from_str(text : &str) -> Result<JsonPointer, PointerError> {
+        JsonPointer::parse(text)
+    }
+}
+
+impl fmt::Display for JsonPointer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for token in &self.tokens {
+            write!(f, "/{}", escape_token(token))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_display_round_trip() {
+        let pointer = JsonPointer::parse("/a~1b/~0/0").unwrap();
+        assert_eq!(pointer.tokens(), &["a/b".to_string(), "~".to_string(), "0".to_string()]);
+        assert_eq!(pointer.to_string(), "/a~1b/~0/0");
+        assert_eq!(JsonPointer::parse("").unwrap(), JsonPointer::root());
+        assert_eq!(JsonPointer::parse("/").unwrap().tokens(), &["".to_string()]);
+    }
+
+    #[test]
+    fn test_invalid_pointers() {
+        assert_eq!(JsonPointer::parse("a"), Err(PointerError::Syntax("a".to_string())));
+        assert_eq!(JsonPointer::parse("/~2"), Err(PointerError::Syntax("/~2".to_string())));
+        assert_eq!(JsonPointer::parse("/~"), Err(PointerError::Syntax("/~".to_string())));
+    }
+
+    #[test]
+    fn test_resolve() {
+        let doc = JsonNode::from_str("{\"a\":[{\"b\":true}],\"\":1,\"m~n\":null}").unwrap();
+        let at = |p : &str| JsonPointer::parse(p).unwrap().resolve(&doc).cloned();
+        assert_eq!(at("/a/0/b"), Some(JsonNode::Boolean(true)));
+        assert_eq!(at("/"), Some(JsonNode::Number(1.0.into())));
+        assert_eq!(at("/m~0n"), Some(JsonNode::Null));
+        assert_eq!(at("/a/00"), None);
+        assert_eq!(at("/a/1"), None);
+        assert_eq!(at("/a/0/b/c"), None);
+    }
+
+    #[test]
+    fn test_resolve_mut() {
+        let mut doc = JsonNode::from_str("[1,[2]]").unwrap();
+        *JsonPointer::parse("/1/0").unwrap().resolve_mut(&mut doc).unwrap() = JsonNode::Null;
+        assert_eq!(doc, JsonNode::from_str("[1,[null]]").unwrap());
+    }
+
+    #[test]
+    fn test_relative_pointers() {
+        let doc = JsonNode::from_str("{\"foo\":[\"bar\",\"baz\"],\"highly\":{\"nested\":{\"objects\":true}}}").unwrap();
+        let here = JsonPointer::parse("/foo/1").unwrap();
+        let at = |p : &str| RelativePointer::parse(p).unwrap().resolve(&doc, &here);
+        assert_eq!(at("0"), Some(RelativeValue::Node(&JsonNode::String("baz".to_string()))));
+        assert_eq!(at("1/0"), Some(RelativeValue::Node(&JsonNode::String("bar".to_string()))));
+        assert_eq!(at("0-1"), Some(RelativeValue::Node(&JsonNode::String("bar".to_string()))));
+        assert_eq!(at("2/highly/nested/objects"), Some(RelativeValue::Node(&JsonNode::Boolean(true))));
+        assert_eq!(at("0#"), Some(RelativeValue::Index(1)));
+        assert_eq!(at("1#"), Some(RelativeValue::Key("foo")));
+        assert_eq!(at("2#"), None);
+        assert_eq!(at("3"), None);
+        assert_eq!(at("0+1"), None);
+    }
+
+    #[test]
+    fn test_relative_pointer_syntax() {
+        assert_eq!(RelativePointer::parse("0+2/a~1b").unwrap().to_string(), "0+2/a~1b");
+        assert_eq!(RelativePointer::parse("1#").unwrap().to_string(), "1#");
+        assert!(RelativePointer::parse("").is_err());
+        assert!(RelativePointer::parse("01").is_err());
+        assert!(RelativePointer::parse("0x").is_err());
+        assert!(RelativePointer::parse("/a").is_err());
+    }
+
+    #[test]
+    fn test_assign_creates_intermediates() {
+        let mut doc = JsonNode::Null;
+        let assign = |doc : &mut JsonNode, p : &str, v| JsonPointer::parse(p).unwrap().assign(doc, v);
+        assert_eq!(assign(&mut doc, "/a/b", JsonNode::Boolean(true)), Ok(None));
+        assert_eq!(assign(&mut doc, "/list/-/x", JsonNode::Null), Ok(None));
+        assert_eq!(assign(&mut doc, "/list/1", JsonNode::Number(2.0.into())), Ok(None));
+        assert_eq!(assign(&mut doc, "/a/b", JsonNode::Boolean(false)), Ok(Some(JsonNode::Boolean(true))));
+        assert_eq!(doc, JsonNode::from_str("{\"a\":{\"b\":false},\"list\":[{\"x\":null},2]}").unwrap());
+
+        assert_eq!(assign(&mut doc, "/list/5", JsonNode::Null), Err(PointerError::BadIndex("/list/5".to_string())));
+        assert_eq!(assign(&mut doc, "/a/b/c", JsonNode::Null), Err(PointerError::NotAContainer("/a/b/c".to_string())));
+    }
+
+    #[test]
+    fn test_select_all_globs() {
+        let doc = JsonNode::from_str("{\"users\":[{\"email\":\"a@x\",\"work_email\":\"a@w\"},{\"email\":\"b@x\",\"boss\":{\"email\":\"c@x\"}}]}").unwrap();
+        let paths = |pattern : &str| -> Vec<String> {
+            doc.get_all(pattern).unwrap().iter().map(|(path, _)| path.to_string()).collect()
+        };
+        assert_eq!(paths("/users/*/email"), vec!["/users/0/email", "/users/1/email"]);
+        assert_eq!(paths("/users/0/*email"), vec!["/users/0/email", "/users/0/work_email"]);
+        assert_eq!(paths("/users/?"), vec!["/users/0", "/users/1"]);
+        assert_eq!(paths("/**/email"), vec!["/users/0/email", "/users/1/boss/email", "/users/1/email"]);
+        assert_eq!(paths("/**/**/boss"), vec!["/users/1/boss"]);
+        assert_eq!(paths("/users/*/missing"), Vec::<String>::new());
+        assert_eq!(doc.get_all("/users/1/boss/email").unwrap()[0].1, &JsonNode::String("c@x".to_string()));
+        assert!(doc.get_all("users").is_err());
+
+        let pattern = JsonPointer::parse("/users/*/email").unwrap();
+        assert!(pattern.glob_matches(&JsonPointer::parse("/users/3/email").unwrap()));
+        assert!(!pattern.glob_matches(&JsonPointer::parse("/users/3").unwrap()));
+        assert!(pattern.glob_matches_below(&JsonPointer::parse("/users/3").unwrap()));
+        assert!(!pattern.glob_matches_below(&JsonPointer::parse("/users/3/email").unwrap()));
+    }
+}
@@ -0,0 +1,299 @@
+use nom::IResult;
+use crate::json::{JsonNode, Object, ObjectEntry, DEFAULT_MAX_DEPTH};
+use crate::json::events::EventError;
+use crate::json::parser::{parse_json_boolean, parse_json_escaped_string_into, parse_json_null, parse_json_number};
+use crate::json::scan::skip_whitespace;
+
+/// At most this many arrays, objects and strings each are kept for reuse.
+pub const POOL_LIMIT : usize = 1024;
+
+// a container being filled, with the key its next member goes under
+enum Open {
+    Array(Vec<JsonNode>),
+    Object(Object, Option<String>)
+}
+
+/// Parses one document after another, keeping its scratch space between
+/// calls. Documents handed back with `recycle` leave their arrays, objects
+/// and strings to the documents parsed after them, so that parsing many
+/// small payloads settles into allocating next to nothing. Containers may
+/// nest `DEFAULT_MAX_DEPTH` deep unless `max_depth` says otherwise.
+pub struct Parser {
+    max_depth: usize,
+    open: Vec<Open>,
+    pending: Vec<JsonNode>,
+    scratch: Vec<u8>,
+    arrays: Vec<Vec<JsonNode>>,
+    objects: Vec<Object>,
+    strings: Vec<String>
+}
+
+impl Default for Parser {
+    fn default() -> Parser {
+        Parser {
+            max_depth: DEFAULT_MAX_DEPTH,
+            open: Vec::new(),
+            pending: Vec::new(),
+            scratch: Vec::new(),
+            arrays: Vec::new(),
+            objects: Vec::new(),
+            strings: Vec::new()
+        }
+    }
+}
+
+impl Parser {
+    pub fn new() -> Parser {
+        Parser::default()
+    }
+
+    /// Fails documents whose containers nest deeper than `depth`, as
+    /// `ParserOptions::max_depth` does.
+    pub fn max_depth(mut self, depth : usize) -> Parser {
+        self.max_depth = depth;
+        self
+    }
+
+    /// Parses the single document in `input`, which may have whitespace
+    /// around it but nothing else.
+    pub fn parse(&mut self, input : &[u8]) -> Result<JsonNode, EventError> {
+        self.open.clear();
+        let result = self.parse_open(input);
+        // an error leaves containers open; their storage is not lost
+        while let Some(open) = self.open.pop() {
+            let node = match open {
+                Open::Array(a) => JsonNode::Array(a),
+                Open::Object(o, key) => {
+                    if let Some(key) = key {
+                        self.keep_string(key)
+                    }
+                    JsonNode::Object(o)
+                }
+            };
+            self.recycle(node)
+        }
+        result
+    }
+
+    /// Takes back a document that is no longer needed, keeping its storage
+    /// for the documents parsed next.
+    pub fn recycle(&mut self, node : JsonNode) {
+        self.pending.push(node);
+        while let Some(mut node) = self.pending.pop() {
+            match &mut node {
+                JsonNode::String(s) => self.keep_string(std::mem::take(s)),
+                JsonNode::Array(a) => {
+                    let mut a = std::mem::take(a);
+                    self.pending.append(&mut a);
+                    if self.arrays.len() < POOL_LIMIT {
+                        self.arrays.push(a)
+                    }
+                },
+                JsonNode::Object(o) => {
+                    let mut o = std::mem::take(o);
+                    for (key, value) in o.drain() {
+                        self.keep_string(key);
+                        self.pending.push(value)
+                    }
+                    if self.objects.len() < POOL_LIMIT {
+                        self.objects.push(o)
+                    }
+                },
+                _ => ()
+            }
+        }
+    }
+
+    /// How many arrays, objects and strings are waiting to be reused.
+    pub fn pooled(&self) -> usize {
+        self.arrays.len() + self.objects.len() + self.strings.len()
+    }
+
+    fn keep_string(&mut self, mut s : String) {
+        if self.strings.len() < POOL_LIMIT {
+            s.clear();
+            self.strings.push(s)
+        }
+    }
+
+    fn parse_open(&mut self, input : &[u8]) -> Result<JsonNode, EventError> {
+        let mut pos = skip_whitespace(input, 0);
+        loop {
+            let mut value = match *input.get(pos).ok_or(EventError::UnexpectedEnd(pos))? {
+                b'[' | b'{' if self.open.len() == self.max_depth => {
+                    return Err(EventError::TooDeep { limit: self.max_depth, offset: pos })
+                },
+                b'[' => {
+                    let array = self.arrays.pop().unwrap_or_default();
+                    self.open.push(Open::Array(array));
+                    pos = skip_whitespace(input, pos + 1);
+                    if input.get(pos) != Some(&b']') {
+                        continue
+                    }
+                    pos += 1;
+                    self.close()
+                },
+                b'{' => {
+                    let object = self.objects.pop().unwrap_or_default();
+                    pos = skip_whitespace(input, pos + 1);
+                    if input.get(pos) == Some(&b'}') {
+                        pos += 1;
+                        JsonNode::Object(object)
+                    } else {
+                        let (key, next) = self.key(input, pos)?;
+                        self.open.push(Open::Object(object, Some(key)));
+                        pos = next;
+                        continue
+                    }
+                },
+                b'"' => {
+                    let (value, next) = self.string(input, pos)?;
+                    pos = next;
+                    JsonNode::String(value)
+                },
+                b't' | b'f' => self.token(input, &mut pos, parse_json_boolean)?,
+                b'n' => self.token(input, &mut pos, parse_json_null)?,
+                _ => self.token(input, &mut pos, parse_json_number)?
+            };
+
+            // hand the value to its container, closing those that end here
+            loop {
+                pos = skip_whitespace(input, pos);
+                let next = input.get(pos).cloned();
+                let duplicate = match self.open.last_mut() {
+                    None if next.is_none() => return Ok(value),
+                    None => return Err(EventError::Syntax(pos)),
+                    Some(Open::Array(a)) => { a.push(value); None },
+                    // the first of duplicate keys wins, as in JsonNode::from_str
+                    Some(Open::Object(o, key)) => match o.entry(key.take().unwrap_or_default()) {
+                        ObjectEntry::Occupied(_) => Some(value),
+                        ObjectEntry::Vacant(member) => { member.insert(value); None }
+                    }
+                };
+                if let Some(duplicate) = duplicate {
+                    self.recycle(duplicate)
+                }
+                let object = matches!(self.open.last(), Some(Open::Object(..)));
+                match (next, object) {
+                    (Some(b','), false) => {
+                        pos = skip_whitespace(input, pos + 1);
+                        break
+                    },
+                    (Some(b','), true) => {
+                        let (key, next) = self.key(input, skip_whitespace(input, pos + 1))?;
+                        if let Some(Open::Object(_, pending)) = self.open.last_mut() {
+                            *pending = Some(key)
+                        }
+                        pos = next;
+                        break
+                    },
+                    (Some(b']'), false) | (Some(b'}'), true) => {
+                        pos += 1;
+                        value = self.close()
+                    },
+                    (None, _) => return Err(EventError::UnexpectedEnd(pos)),
+                    _ => return Err(EventError::Syntax(pos))
+                }
+            }
+        }
+    }
+
+    fn close(&mut self) -> JsonNode {
+        match self.open.pop() {
+            Some(Open::Array(a)) => JsonNode::Array(a),
+            Some(Open::Object(o, _)) => JsonNode::Object(o),
+            None => unreachable!("only open containers are closed")
+        }
+    }
+
+    // a member name and its colon, returning where the value starts
+    fn key(&mut self, input : &[u8], pos : usize) -> Result<(String, usize), EventError> {
+        let (key, next) = self.string(input, pos)?;
+        let colon = skip_whitespace(input, next);
+        match input.get(colon) {
+            Some(b':') => Ok((key, skip_whitespace(input, colon + 1))),
+            Some(_) => Err(EventError::Syntax(colon)),
+            None => Err(EventError::UnexpectedEnd(colon))
+        }
+    }
+
+    fn string(&mut self, input : &[u8], pos : usize) -> Result<(String, usize), EventError> {
+        let buffer = self.strings.pop().unwrap_or_default().into_bytes();
+        match parse_json_escaped_string_into(&input[pos..], buffer) {
+            Ok((rest, value)) => Ok((value, input.len() - rest.len())),
+            Err(nom::Err::Incomplete(_)) => Err(EventError::UnexpectedEnd(input.len())),
+            Err(_) => Err(EventError::Syntax(pos))
+        }
+    }
+
+    fn token(&mut self, input : &[u8], pos : &mut usize, parser : fn(&[u8]) -> IResult<&[u8], JsonNode>) -> Result<JsonNode, EventError> {
+        match parser(&input[*pos..]) {
+            Ok((rest, value)) => {
+                *pos = input.len() - rest.len();
+                return Ok(value)
+            },
+            Err(nom::Err::Incomplete(_)) => (),
+            Err(_) => return Err(EventError::Syntax(*pos))
+        }
+        // at the end of input a number is only complete when given a terminator
+        self.scratch.clear();
+        self.scratch.extend_from_slice(&input[*pos..]);
+        self.scratch.push(b' ');
+        match parser(&self.scratch) {
+            Ok((rest, value)) if rest.len() == 1 => {
+                *pos = input.len();
+                Ok(value)
+            },
+            _ => Err(EventError::UnexpectedEnd(input.len()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_parses_like_from_str() {
+        let mut parser = Parser::new();
+        for text in &["{\"a\":[1,{\"b\":null}],\"c\":\"x\\ny\",\"d\":{}}", "[true,false,[],-1.5e3]", "42", "\"\"", "{\"k\":1,\"k\":2}"] {
+            assert_eq!(parser.parse(text.as_bytes()).unwrap(), JsonNode::from_str(&format!("{} ", text)).unwrap());
+        }
+        assert_eq!(parser.parse(b" { \"a\" : [ 1 , { } ] }\n").unwrap(), JsonNode::from_str("{\"a\":[1,{}]}").unwrap());
+        assert!(matches!(parser.parse(b"[1,]"), Err(EventError::Syntax(3))));
+        assert!(matches!(parser.parse(b"{\"a\" 1}"), Err(EventError::Syntax(5))));
+        assert!(matches!(parser.parse(b"[1] x"), Err(EventError::Syntax(4))));
+        assert!(matches!(parser.parse(b"{\"a\":[1,"), Err(EventError::UnexpectedEnd(8))));
+        assert!(matches!(parser.parse(b"\"abc"), Err(EventError::UnexpectedEnd(4))));
+    }
+
+    #[test]
+    fn test_reuses_recycled_storage() {
+        let mut parser = Parser::new();
+        let document = parser.parse(b"{\"names\":[\"ann\",\"bob\"],\"id\":7}").unwrap();
+        parser.recycle(document);
+        // one object, one array, and the strings "names", "ann", "bob" and "id"
+        assert_eq!(parser.pooled(), 6);
+        let document = parser.parse(b"[\"x\",{\"y\":[]}]").unwrap();
+        assert_eq!(document, JsonNode::from_str("[\"x\",{\"y\":[]}]").unwrap());
+        assert_eq!(parser.pooled(), 2);
+        // the containers left open by an error are kept too
+        assert!(parser.parse(b"[[[\"cut").is_err());
+        assert_eq!(parser.pooled(), 4);
+    }
+
+    #[test]
+    fn test_depth_limit() {
+        let mut parser = Parser::new().max_depth(3);
+        assert_eq!(parser.parse(b"[{\"a\":[]}]").unwrap(), JsonNode::from_str("[{\"a\":[]}]").unwrap());
+        assert!(matches!(parser.parse(b"[{\"a\":[{}]}]"), Err(EventError::TooDeep { limit: 3, offset: 7 })));
+        let deep = format!("{}{}", "[".repeat(300000), "]".repeat(300000));
+        let result = Parser::new().parse(deep.as_bytes());
+        if cfg!(feature = "grow_stack") {
+            assert!(result.is_ok())
+        } else {
+            assert!(matches!(result, Err(EventError::TooDeep { limit: DEFAULT_MAX_DEPTH, offset: DEFAULT_MAX_DEPTH })))
+        }
+    }
+}
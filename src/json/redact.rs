@@ -0,0 +1,131 @@
+use crate::json::{maybe_grow, JsonNode};
+use crate::json::flatten::{flatten, Separator};
+use crate::json::pointer::JsonPointer;
+use crate::json::sha256::hmac_sha256;
+
+pub const MASK : &str = "***";
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum Strategy {
+    /// Replace the value with the string `MASK`.
+    Mask,
+    /// Remove the member or element altogether.
+    Remove,
+    /// Replace the value with its HMAC-SHA256 under `key`, so equal secrets
+    /// stay recognizably equal wherever the same key is used, while no one
+    /// without the key can test a guess against them. Values of different
+    /// types never hash alike, so `"1"` and `1` stay apart.
+    Hash { key: Vec<u8> }
+}
+
+/// Scrubs every value matching one of the glob `patterns` (see
+/// `JsonPointer::select_all`). Patterns refer to the document as given, so
+/// removing an array element does not shift what later patterns match.
+pub fn redact(node : &mut JsonNode, patterns : &[JsonPointer], strategy : Strategy) {
+    if !walk(node, &mut JsonPointer::root(), patterns, &strategy) {
+        *node = JsonNode::Null
+    }
+}
+
+// returns whether the node stays
+fn walk(node : &mut JsonNode, path : &mut JsonPointer, patterns : &[JsonPointer], strategy : &Strategy) -> bool {
+    maybe_grow(|| {
+        if patterns.iter().any(|pattern| pattern.glob_matches(path)) {
+            match strategy {
+                Strategy::Mask => *node = JsonNode::String(MASK.to_string()),
+                Strategy::Remove => return false,
+                Strategy::Hash { key } => *node = JsonNode::String(hash(node, key))
+            }
+            return true
+        }
+        if !patterns.iter().any(|pattern| pattern.glob_matches_below(path)) {
+            return true
+        }
+        match node {
+            JsonNode::Array(a) => {
+                let mut i = 0;
+                a.retain_mut(|item| {
+                    path.push(i.to_string());
+                    i += 1;
+                    let stays = walk(item, path, patterns, strategy);
+                    path.pop();
+                    stays
+                })
+            },
+            JsonNode::Object(o) => o.retain(|key, value| {
+                path.push(key.as_str());
+                let stays = walk(value, path, patterns, strategy);
+                path.pop();
+                stays
+            }),
+            _ => ()
+        }
+        true
+    })
+}
+
+// HMAC-SHA256 over the type of the value and a key-order independent rendering of it
+fn hash(node : &JsonNode, key : &[u8]) -> String {
+    let (kind, text) = match node {
+        JsonNode::String(s) => ("string", s.clone()),
+        JsonNode::Array(_) | JsonNode::Object(_) => (if node.is_array() { "array" } else { "object" }, flatten(node, Separator::Pointer).iter()
+            .map(|(path, value)| format!("{}={}\n", path, value)).collect()),
+        JsonNode::Number(_) => ("number", node.to_string()),
+        JsonNode::Boolean(_) => ("boolean", node.to_string()),
+        JsonNode::Null => ("null", String::new())
+    };
+    let digest = hmac_sha256(key, format!("{}:{}", kind, text).as_bytes());
+    format!("hmac-sha256:{}", digest.iter().map(|b| format!("{:02x}", b)).collect::<String>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    const USERS : &str = "{\"users\":[{\"name\":\"ann\",\"token\":\"s3cret\",\"card\":{\"pan\":\"4111\"}},\
+        {\"name\":\"bob\",\"token\":\"s3cret\"}],\"admin\":{\"token\":\"t\"}}";
+
+    fn patterns(texts : &[&str]) -> Vec<JsonPointer> {
+        texts.iter().map(|text| JsonPointer::parse(text).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_mask_and_remove() {
+        let mut node = JsonNode::from_str(USERS).unwrap();
+        redact(&mut node, &patterns(&["/**/token", "/users/*/card"]), Strategy::Mask);
+        assert_eq!(node, JsonNode::from_str("{\"users\":[{\"name\":\"ann\",\"token\":\"***\",\"card\":\"***\"},\
+            {\"name\":\"bob\",\"token\":\"***\"}],\"admin\":{\"token\":\"***\"}}").unwrap());
+
+        let mut node = JsonNode::from_str(USERS).unwrap();
+        redact(&mut node, &patterns(&["/users/0", "/users/1/token", "/admin"]), Strategy::Remove);
+        assert_eq!(node, JsonNode::from_str("{\"users\":[{\"name\":\"bob\"}]}").unwrap());
+        redact(&mut node, &patterns(&[""]), Strategy::Remove);
+        assert_eq!(node, JsonNode::Null);
+    }
+
+    #[test]
+    fn test_hash_is_stable() {
+        let key = b"k3y".to_vec();
+        let mut node = JsonNode::from_str(USERS).unwrap();
+        redact(&mut node, &patterns(&["/users/*/token", "/users/0/card"]), Strategy::Hash { key: key.clone() });
+        let token = |i : &str| node.get_all(&format!("/users/{}/token", i)).unwrap()[0].1.clone();
+        assert_eq!(token("0"), token("1"));
+        assert_eq!(token("0"), JsonNode::String(hash(&JsonNode::String("s3cret".to_string()), &key)));
+        assert_ne!(token("0"), JsonNode::String("s3cret".to_string()));
+        let parse = |text : &str| JsonNode::from_str(text).unwrap();
+        assert_eq!(hash(&parse("{\"a\":1,\"b\":2}"), &key), hash(&parse("{\"b\":2,\"a\":1}"), &key));
+    }
+
+    #[test]
+    fn test_hash_depends_on_key_and_type() {
+        let parse = |text : &str| JsonNode::from_str(text).unwrap();
+        assert_ne!(hash(&parse("\"s3cret\""), b"one"), hash(&parse("\"s3cret\""), b"two"));
+        assert_ne!(hash(&parse("\"1\""), b"k"), hash(&parse("1"), b"k"));
+        assert_ne!(hash(&parse("\"true\""), b"k"), hash(&parse("true"), b"k"));
+        assert_ne!(hash(&parse("\"\""), b"k"), hash(&parse("null"), b"k"));
+        assert_ne!(hash(&parse("[]"), b"k"), hash(&parse("{}"), b"k"));
+        // HMAC-SHA256("k3y", "string:s3cret")
+        assert_eq!(hash(&parse("\"s3cret\""), b"k3y"), "hmac-sha256:0e9107ba18ccf443f8e5515a0d45afe728467af89710a170b6e7dfedc7e5c226");
+    }
+}
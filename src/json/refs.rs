@@ -0,0 +1,107 @@
+use std::fmt;
+use crate::json::{maybe_grow, JsonNode, Object};
+use crate::json::pointer::JsonPointer;
+use crate::json::schema::percent_decode;
+
+#[derive(PartialEq, Debug)]
+pub struct RefError {
+    /// Where the failing `$ref` object is in the original document.
+    pub path: JsonPointer,
+    pub reference: String,
+    pub message: &'static str
+}
+
+impl fmt::Display for RefError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "cannot resolve \"{}\" at \"{}\": {}", self.reference, self.path, self.message)
+    }
+}
+
+/// Replaces every `{"$ref": "#/..."}` object, siblings and all, with a copy
+/// of the subtree its URI fragment points to, itself resolved. References
+/// to other documents are left in place. On failure, such as a reference
+/// that leads back to itself, `node` is left untouched.
+pub fn resolve_refs(node : &mut JsonNode) -> Result<(), RefError> {
+    let resolved = expand(node, node, &mut JsonPointer::root(), &mut Vec::new())?;
+    *node = resolved;
+    Ok(())
+}
+
+fn local_target(node : &JsonNode) -> Option<&str> {
+    match node {
+        JsonNode::Object(o) => match o.get("$ref") {
+            Some(JsonNode::String(reference)) if reference.starts_with('#') => Some(reference),
+            _ => None
+        },
+        _ => None
+    }
+}
+
+// `expanding` holds the references being expanded, to catch cycles
+fn expand(node : &JsonNode, root : &JsonNode, path : &mut JsonPointer, expanding : &mut Vec<String>) -> Result<JsonNode, RefError> {
+    if let Some(reference) = local_target(node) {
+        let error = |message| RefError { path: path.clone(), reference: reference.to_string(), message };
+        if expanding.iter().any(|r| r == reference) {
+            return Err(error("the reference is circular"))
+        }
+        let mut pointer = JsonPointer::parse(&percent_decode(&reference[1..])).map_err(|_| error("the fragment is not a JSON pointer"))?;
+        let target = pointer.resolve(root).ok_or_else(|| error("nothing is there"))?;
+        expanding.push(reference.to_string());
+        let resolved = expand(target, root, &mut pointer, expanding);
+        expanding.pop();
+        return resolved
+    }
+    maybe_grow(|| match node {
+        JsonNode::Array(a) => {
+            let mut out = Vec::with_capacity(a.len());
+            for (i, item) in a.iter().enumerate() {
+                path.push(i.to_string());
+                out.push(expand(item, root, path, expanding)?);
+                path.pop();
+            }
+            Ok(JsonNode::Array(out))
+        },
+        JsonNode::Object(o) => {
+            let mut keys : Vec<&String> = o.keys().collect();
+            keys.sort();
+            let mut out = Object::with_capacity_and_hasher(o.len(), Default::default());
+            for key in keys {
+                path.push(key.as_str());
+                out.insert(key.clone(), expand(&o[key], root, path, expanding)?);
+                path.pop();
+            }
+            Ok(JsonNode::Object(out))
+        },
+        scalar => Ok(scalar.clone())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_resolve_refs() {
+        let mut node = JsonNode::from_str("{\"definitions\":{\"id\":{\"type\":\"integer\"},\"user\":{\"properties\":{\"id\":{\"$ref\":\"#/definitions/id\"}}},\"a b\":true},\
+            \"items\":[{\"$ref\":\"#/definitions/user\"},{\"$ref\":\"#/definitions/a%20b\"},{\"$ref\":\"other.json#/x\"}]}").unwrap();
+        resolve_refs(&mut node).unwrap();
+        let items = node.get_all("/items").unwrap()[0].1.clone();
+        assert_eq!(items, JsonNode::from_str("[{\"properties\":{\"id\":{\"type\":\"integer\"}}},true,{\"$ref\":\"other.json#/x\"}]").unwrap());
+        let mut whole = JsonNode::from_str("{\"a\":{\"$ref\":\"#\"}}").unwrap();
+        assert!(resolve_refs(&mut whole).is_err());
+    }
+
+    #[test]
+    fn test_unresolvable_refs() {
+        let original = JsonNode::from_str("{\"a\":{\"b\":{\"$ref\":\"#/c\"}},\"c\":[{\"$ref\":\"#/a\"}]}").unwrap();
+        let mut node = original.clone();
+        let error = resolve_refs(&mut node).unwrap_err();
+        assert_eq!(error.message, "the reference is circular");
+        assert_eq!(error.path.to_string(), "/a/b");
+        assert_eq!(node, original);
+
+        let mut node = JsonNode::from_str("[{\"$ref\":\"#/9\"}]").unwrap();
+        assert_eq!(resolve_refs(&mut node).unwrap_err().to_string(), "cannot resolve \"#/9\" at \"/0\": nothing is there");
+    }
+}
@@ -0,0 +1,434 @@
+// A small regular expression engine covering the ECMA 262 subset that JSON
+// Schema patterns use in practice: literals, `.`, classes, the \d \w \s
+// escapes, anchors, groups, alternation and greedy/lazy quantifiers. The
+// pattern compiles to a program that a Pike VM runs over the text in one
+// pass, keeping every live thread in step, so matching takes time linear in
+// the text and no stack however long it is.
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct Regex {
+    program: Vec<Inst>
+}
+
+#[derive(PartialEq, Debug, Clone)]
+enum Node {
+    Char(char),
+    Any,
+    Class(Vec<ClassItem>, bool),
+    Start,
+    End,
+    WordBoundary(bool),
+    Group(Vec<Vec<Node>>),
+    Repeat(Box<Node>, usize, usize, bool)
+}
+
+#[derive(PartialEq, Debug, Clone)]
+enum ClassItem {
+    Range(char, char),
+    Digit(bool),
+    Word(bool),
+    Space(bool)
+}
+
+// an instruction of the compiled program; the first three consume a
+// character, the assertions and jumps none
+#[derive(PartialEq, Debug, Clone)]
+enum Inst {
+    Char(char),
+    Any,
+    Class(Vec<ClassItem>, bool),
+    Start,
+    End,
+    WordBoundary(bool),
+    // continue at both, the first preferred
+    Split(usize, usize),
+    Jump(usize),
+    Match
+}
+
+impl Regex {
+    pub fn new(pattern : &str) -> Result<Regex, String> {
+        let mut parser = Parser { chars: pattern.chars().collect(), pos: 0 };
+        let alternatives = parser.alternatives()?;
+        if parser.pos < parser.chars.len() {
+            return Err(format!("unbalanced ) in pattern \"{}\"", pattern))
+        }
+        let mut program = Vec::new();
+        compile(&alternatives, &mut program);
+        program.push(Inst::Match);
+        Ok(Regex { program })
+    }
+
+    /// True when the pattern matches anywhere in `text`.
+    pub fn is_match(&self, text : &str) -> bool {
+        let text : Vec<char> = text.chars().collect();
+        let mut current = Threads::new(self.program.len());
+        let mut next = Threads::new(self.program.len());
+        for i in 0..=text.len() {
+            // a match may start anywhere
+            if self.add(&mut current, 0, i, &text) {
+                return true
+            }
+            let c = match text.get(i) {
+                Some(c) => *c,
+                None => break
+            };
+            for &pc in &current.list {
+                let steps = match &self.program[pc] {
+                    Inst::Char(expected) => *expected == c,
+                    Inst::Any => c != '\n' && c != '\r',
+                    Inst::Class(items, negated) => class_matches(items, c) != *negated,
+                    _ => false
+                };
+                if steps && self.add(&mut next, pc + 1, i + 1, &text) {
+                    return true
+                }
+            }
+            std::mem::swap(&mut current, &mut next);
+            next.clear()
+        }
+        false
+    }
+
+    // adds the thread at `pc` and every thread it reaches without consuming
+    // a character at `i`; true if one of them is a match
+    fn add(&self, threads : &mut Threads, pc : usize, i : usize, text : &[char]) -> bool {
+        let mut pending = vec![pc];
+        while let Some(pc) = pending.pop() {
+            if threads.seen[pc] {
+                continue
+            }
+            threads.seen[pc] = true;
+            match &self.program[pc] {
+                Inst::Match => return true,
+                Inst::Jump(to) => pending.push(*to),
+                Inst::Split(first, second) => {
+                    pending.push(*second);
+                    pending.push(*first)
+                },
+                Inst::Start => if i == 0 {
+                    pending.push(pc + 1)
+                },
+                Inst::End => if i == text.len() {
+                    pending.push(pc + 1)
+                },
+                Inst::WordBoundary(expected) => {
+                    let before = i > 0 && is_word(text[i - 1]);
+                    let after = i < text.len() && is_word(text[i]);
+                    if (before != after) == *expected {
+                        pending.push(pc + 1)
+                    }
+                },
+                _ => threads.list.push(pc)
+            }
+        }
+        false
+    }
+}
+
+// the threads at one position of the text, each program counter once
+struct Threads {
+    list: Vec<usize>,
+    seen: Vec<bool>
+}
+
+impl Threads {
+    fn new(size : usize) -> Threads {
+        Threads { list: Vec::new(), seen: vec![false; size] }
+    }
+
+    fn clear(&mut self) {
+        self.list.clear();
+        for seen in self.seen.iter_mut() {
+            *seen = false
+        }
+    }
+}
+
+fn compile(alternatives : &[Vec<Node>], program : &mut Vec<Inst>) {
+    let mut exits = Vec::new();
+    for (n, sequence) in alternatives.iter().enumerate() {
+        let last = n + 1 == alternatives.len();
+        let split = program.len();
+        // a placeholder until the split's targets are known
+        if !last {
+            program.push(Inst::Match)
+        }
+        for node in sequence {
+            compile_node(node, program)
+        }
+        if !last {
+            exits.push(program.len());
+            program.push(Inst::Match);
+            program[split] = Inst::Split(split + 1, program.len())
+        }
+    }
+    let end = program.len();
+    for exit in exits {
+        program[exit] = Inst::Jump(end)
+    }
+}
+
+fn compile_node(node : &Node, program : &mut Vec<Inst>) {
+    let split = |body : usize, skip : usize, greedy : bool| if greedy { Inst::Split(body, skip) } else { Inst::Split(skip, body) };
+    match node {
+        Node::Char(c) => program.push(Inst::Char(*c)),
+        Node::Any => program.push(Inst::Any),
+        Node::Class(items, negated) => program.push(Inst::Class(items.clone(), *negated)),
+        Node::Start => program.push(Inst::Start),
+        Node::End => program.push(Inst::End),
+        Node::WordBoundary(expected) => program.push(Inst::WordBoundary(*expected)),
+        Node::Group(alternatives) => compile(alternatives, program),
+        Node::Repeat(inner, min, max, greedy) => {
+            for _ in 0..*min {
+                compile_node(inner, program)
+            }
+            if *max == usize::MAX {
+                let start = program.len();
+                program.push(Inst::Match);
+                compile_node(inner, program);
+                program.push(Inst::Jump(start));
+                program[start] = split(start + 1, program.len(), *greedy)
+            } else {
+                let mut optional = Vec::new();
+                for _ in *min..*max {
+                    optional.push(program.len());
+                    program.push(Inst::Match);
+                    compile_node(inner, program)
+                }
+                let end = program.len();
+                for start in optional {
+                    program[start] = split(start + 1, end, *greedy)
+                }
+            }
+        }
+    }
+}
+
+fn is_word(c : char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+fn class_matches(items : &[ClassItem], c : char) -> bool {
+    items.iter().any(|item| match item {
+        ClassItem::Range(low, high) => *low <= c && c <= *high,
+        ClassItem::Digit(positive) => c.is_ascii_digit() == *positive,
+        ClassItem::Word(positive) => is_word(c) == *positive,
+        ClassItem::Space(positive) => c.is_whitespace() == *positive
+    })
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).cloned()
+    }
+
+    fn next(&mut self) -> Result<char, String> {
+        let c = self.peek().ok_or_else(|| "unexpected end of pattern".to_string())?;
+        self.pos += 1;
+        Ok(c)
+    }
+
+    fn alternatives(&mut self) -> Result<Vec<Vec<Node>>, String> {
+        let mut alternatives = vec![self.sequence()?];
+        while self.peek() == Some('|') {
+            self.pos += 1;
+            alternatives.push(self.sequence()?)
+        }
+        Ok(alternatives)
+    }
+
+    fn sequence(&mut self) -> Result<Vec<Node>, String> {
+        let mut nodes = Vec::new();
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break
+            }
+            let atom = self.atom()?;
+            nodes.push(self.quantified(atom)?)
+        }
+        Ok(nodes)
+    }
+
+    fn atom(&mut self) -> Result<Node, String> {
+        Ok(match self.next()? {
+            '.' => Node::Any,
+            '^' => Node::Start,
+            '$' => Node::End,
+            '(' => {
+                if self.chars[self.pos..].starts_with(&['?', ':']) {
+                    self.pos += 2
+                }
+                let alternatives = self.alternatives()?;
+                if self.next()? != ')' {
+                    return Err("missing )".to_string())
+                }
+                Node::Group(alternatives)
+            },
+            '[' => self.class()?,
+            '\\' => match self.escape()? {
+                Escaped::Char(c) => Node::Char(c),
+                Escaped::Class(item) => Node::Class(vec![item], false),
+                Escaped::Boundary(expected) => Node::WordBoundary(expected)
+            },
+            '*' | '+' | '?' => return Err("nothing to repeat".to_string()),
+            c => Node::Char(c)
+        })
+    }
+
+    fn quantified(&mut self, atom : Node) -> Result<Node, String> {
+        let (min, max) = match self.peek() {
+            Some('*') => (0, usize::MAX),
+            Some('+') => (1, usize::MAX),
+            Some('?') => (0, 1),
+            Some('{') => match self.bounds() {
+                Some(bounds) => bounds,
+                None => return Ok(atom)
+            },
+            _ => return Ok(atom)
+        };
+        self.pos += 1;
+        let greedy = self.peek() != Some('?');
+        if !greedy {
+            self.pos += 1
+        }
+        Ok(Node::Repeat(Box::new(atom), min, max, greedy))
+    }
+
+    // `{n}`, `{n,}` or `{n,m}`; leaves the position on the closing brace
+    fn bounds(&mut self) -> Option<(usize, usize)> {
+        let close = self.chars[self.pos..].iter().position(|c| *c == '}')? + self.pos;
+        let inside : String = self.chars[self.pos + 1..close].iter().collect();
+        let mut parts = inside.splitn(2, ',');
+        let min = parts.next()?.parse().ok()?;
+        let max = match parts.next() {
+            None => min,
+            Some("") => usize::MAX,
+            Some(max) => max.parse().ok()?
+        };
+        self.pos = close;
+        Some((min, max))
+    }
+
+    fn class(&mut self) -> Result<Node, String> {
+        let negated = self.peek() == Some('^');
+        if negated {
+            self.pos += 1
+        }
+        let mut items = Vec::new();
+        let mut first = true;
+        loop {
+            let c = self.next()?;
+            if c == ']' && !first {
+                return Ok(Node::Class(items, negated))
+            }
+            first = false;
+            let low = match c {
+                '\\' => match self.escape()? {
+                    Escaped::Char(c) => c,
+                    Escaped::Class(item) => {
+                        items.push(item);
+                        continue
+                    },
+                    Escaped::Boundary(_) => '\u{8}'
+                },
+                c => c
+            };
+            if self.peek() == Some('-') && self.chars.get(self.pos + 1).is_some_and(|c| *c != ']') {
+                self.pos += 1;
+                let high = match self.next()? {
+                    '\\' => match self.escape()? {
+                        Escaped::Char(c) => c,
+                        _ => return Err("invalid class range".to_string())
+                    },
+                    c => c
+                };
+                items.push(ClassItem::Range(low, high))
+            } else {
+                items.push(ClassItem::Range(low, low))
+            }
+        }
+    }
+
+    fn escape(&mut self) -> Result<Escaped, String> {
+        Ok(match self.next()? {
+            'd' => Escaped::Class(ClassItem::Digit(true)),
+            'D' => Escaped::Class(ClassItem::Digit(false)),
+            'w' => Escaped::Class(ClassItem::Word(true)),
+            'W' => Escaped::Class(ClassItem::Word(false)),
+            's' => Escaped::Class(ClassItem::Space(true)),
+            'S' => Escaped::Class(ClassItem::Space(false)),
+            'b' => Escaped::Boundary(true),
+            'B' => Escaped::Boundary(false),
+            'n' => Escaped::Char('\n'),
+            'r' => Escaped::Char('\r'),
+            't' => Escaped::Char('\t'),
+            'f' => Escaped::Char('\u{c}'),
+            'v' => Escaped::Char('\u{b}'),
+            '0' => Escaped::Char('\0'),
+            'u' => Escaped::Char(self.hex(4)?),
+            'x' => Escaped::Char(self.hex(2)?),
+            c => Escaped::Char(c)
+        })
+    }
+
+    fn hex(&mut self, digits : usize) -> Result<char, String> {
+        let hex : String = self.chars.iter().skip(self.pos).take(digits).collect();
+        self.pos += digits;
+        u32::from_str_radix(&hex, 16).ok().and_then(std::char::from_u32).ok_or_else(|| "invalid hex escape".to_string())
+    }
+}
+
+enum Escaped {
+    Char(char),
+    Class(ClassItem),
+    Boundary(bool)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern : &str, text : &str) -> bool {
+        Regex::new(pattern).unwrap().is_match(text)
+    }
+
+    #[test]
+    fn test_patterns() {
+        assert!(matches("^[a-z]+$", "abc"));
+        assert!(!matches("^[a-z]+$", "abC"));
+        assert!(matches("b", "abc"));
+        assert!(matches("^(\\([0-9]{3}\\))?[0-9]{3}-[0-9]{4}$", "(888)555-1212"));
+        assert!(!matches("^(\\([0-9]{3}\\))?[0-9]{3}-[0-9]{4}$", "(800)FLOWERS"));
+        assert!(matches("^(cat|dog)s?$", "dogs"));
+        assert!(matches("^a.*?c$", "abbbc"));
+        assert!(matches("^\\d{2,}\\s\\w$", "123 x"));
+        assert!(!matches("^\\d{2,3}$", "1234"));
+        assert!(matches("^[^-\\d]$", "x"));
+        assert!(matches("\\bword\\b", "a word here"));
+        assert!(matches("^(a*)*$", "aaaa"));
+        assert!(matches("^x{1}$", "x"));
+    }
+
+    #[test]
+    fn test_long_text() {
+        let long = "a".repeat(1 << 20);
+        assert!(matches("^[a-z]+$", &long));
+        assert!(!matches("^[a-z]+$", &(long.clone() + "1")));
+        assert!(matches("^(a|b)*?$", &long));
+        assert!(!matches("^(a*)*b$", &long));
+    }
+
+    #[test]
+    fn test_bad_patterns() {
+        assert!(Regex::new("(a").is_err());
+        assert!(Regex::new("a)").is_err());
+        assert!(Regex::new("*a").is_err());
+        assert!(Regex::new("[a").is_err());
+    }
+}
@@ -0,0 +1,253 @@
+use std::io::{BufWriter, Read, Write};
+use crate::json::events::{EventError, EventReader, JsonEvent};
+use crate::json::pointer::JsonPointer;
+use crate::json::serialize;
+use crate::json::{SerializeOptions, DEFAULT_MAX_DEPTH};
+
+/// Copies a document from a reader to a writer as compact JSON, keeping or
+/// dropping subtrees by glob pattern like `Pipeline::keep_paths` and
+/// `Pipeline::drop_paths` do, without ever building a tree or recursing:
+/// memory use grows with nesting depth only, not with document size.
+/// Containers may nest `DEFAULT_MAX_DEPTH` deep unless `max_depth` says
+/// otherwise.
+#[derive(PartialEq, Debug, Clone)]
+pub struct StreamFilter {
+    keep: Vec<JsonPointer>,
+    drop: Vec<JsonPointer>,
+    max_depth: usize
+}
+
+impl Default for StreamFilter {
+    fn default() -> StreamFilter {
+        StreamFilter { keep: Vec::new(), drop: Vec::new(), max_depth: DEFAULT_MAX_DEPTH }
+    }
+}
+
+impl StreamFilter {
+    pub fn new() -> StreamFilter {
+        StreamFilter::default()
+    }
+
+    /// Fails documents whose containers nest deeper than `depth`.
+    pub fn max_depth(mut self, depth : usize) -> StreamFilter {
+        self.max_depth = depth;
+        self
+    }
+
+    /// Keeps only the values matching `pattern` (or another kept pattern) and
+    /// the containers on the way to them.
+    pub fn keep_paths(mut self, pattern : JsonPointer) -> StreamFilter {
+        self.keep.push(pattern);
+        self
+    }
+
+    /// Leaves out the values matching `pattern`.
+    pub fn drop_paths(mut self, pattern : JsonPointer) -> StreamFilter {
+        self.drop.push(pattern);
+        self
+    }
+
+    /// Filters the document in `source` into `sink`, writing `null` when
+    /// nothing of it is kept.
+    pub fn run<R : Read, W : Write>(&self, source : R, sink : W) -> Result<(), EventError> {
+        let mut reader = EventReader::new(source).max_depth(self.max_depth);
+        let mut out = BufWriter::new(sink);
+        let mut path = JsonPointer::root();
+        if let Some(event) = reader.next_event()? {
+            match self.decide(&path, self.keep.is_empty()) {
+                Some(kept) => self.copy(&mut reader, &mut out, &mut path, event, kept)?,
+                None => {
+                    reader.skip_value(event)?;
+                    write(&mut out, b"null")?
+                }
+            }
+        }
+        out.flush().map_err(EventError::Io)
+    }
+
+    // whether the value at `path` goes out, and if so whether it is kept whole
+    fn decide(&self, path : &JsonPointer, kept : bool) -> Option<bool> {
+        if self.drop.iter().any(|pattern| pattern.glob_matches(path)) {
+            return None
+        }
+        let kept = kept || self.keep.iter().any(|pattern| pattern.glob_matches(path));
+        if !kept && !self.keep.iter().any(|pattern| pattern.glob_matches_below(path)) {
+            return None
+        }
+        Some(kept)
+    }
+
+    // copies the value `event` starts, keeping the containers open around
+    // the current value on a stack of its own rather than recursing
+    fn copy<R : Read, W : Write>(&self, reader : &mut EventReader<R>, out : &mut W, path : &mut JsonPointer, event : JsonEvent, kept : bool) -> Result<(), EventError> {
+        let mut open : Vec<Open> = Vec::new();
+        let (mut event, mut kept) = (event, kept);
+        loop {
+            match event {
+                JsonEvent::StartArray => {
+                    write(out, b"[")?;
+                    open.push(Open { kept, first: true, index: Some(0) })
+                },
+                JsonEvent::StartObject => {
+                    write(out, b"{")?;
+                    open.push(Open { kept, first: true, index: None })
+                },
+                JsonEvent::Key(_) | JsonEvent::EndArray | JsonEvent::EndObject => return Err(EventError::Syntax(reader.offset())),
+                scalar => {
+                    match scalar {
+                        JsonEvent::String(s) => serialize::write_string(&s, &SerializeOptions::default(), &mut |piece| write(out, piece.as_bytes()))?,
+                        JsonEvent::Number(n) => write(out, n.to_string().as_bytes())?,
+                        JsonEvent::Boolean(t) => write(out, if t { b"true" } else { b"false" })?,
+                        _ => write(out, b"null")?
+                    }
+                    if !open.is_empty() {
+                        path.pop();
+                    }
+                }
+            }
+            // on to the next value that goes out, closing the containers that end first
+            (event, kept) = loop {
+                let top = match open.last_mut() {
+                    Some(top) => top,
+                    None => return Ok(())
+                };
+                let (member, key) = match (next(reader)?, top.index) {
+                    (JsonEvent::EndArray, Some(_)) | (JsonEvent::EndObject, None) => {
+                        write(out, if top.index.is_some() { b"]" } else { b"}" })?;
+                        open.pop();
+                        if !open.is_empty() {
+                            path.pop();
+                        }
+                        continue
+                    },
+                    (JsonEvent::Key(key), None) => {
+                        path.push(key.as_str());
+                        (next(reader)?, Some(key))
+                    },
+                    (member, Some(i)) => {
+                        top.index = Some(i + 1);
+                        path.push(i.to_string());
+                        (member, None)
+                    },
+                    _ => return Err(EventError::Syntax(reader.offset()))
+                };
+                match self.decide(path, top.kept) {
+                    Some(kept) => {
+                        if !top.first {
+                            write(out, b",")?
+                        }
+                        top.first = false;
+                        if let Some(key) = key {
+                            serialize::write_string(&key, &SerializeOptions::default(), &mut |piece| write(out, piece.as_bytes()))?;
+                            write(out, b":")?
+                        }
+                        break (member, kept)
+                    },
+                    None => {
+                        reader.skip_value(member)?;
+                        path.pop();
+                    }
+                }
+            }
+        }
+    }
+}
+
+// a container being copied: whether it is kept whole, whether nothing of it
+// has gone out yet, and for an array the index of its next element
+struct Open {
+    kept: bool,
+    first: bool,
+    index: Option<usize>
+}
+
+fn next<R : Read>(reader : &mut EventReader<R>) -> Result<JsonEvent, EventError> {
+    let offset = reader.offset();
+    reader.next_event()?.ok_or(EventError::UnexpectedEnd(offset))
+}
+
+fn write<W : Write>(out : &mut W, bytes : &[u8]) -> Result<(), EventError> {
+    out.write_all(bytes).map_err(EventError::Io)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+    use crate::json::JsonNode;
+    use crate::json::pipeline::Pipeline;
+    use crate::json::scan;
+
+    const USERS : &str = "{\"users\":[\
+        {\"name\":\"ann \\\"a\\\"\",\"mail\":\"a@x\",\"password\":\"x\"},\
+        {\"name\":\"bob\",\"mail\":null,\"password\":\"y\",\"tags\":[1,2.5,true]}],\"total\":2}";
+
+    fn pointer(text : &str) -> JsonPointer {
+        JsonPointer::parse(text).unwrap()
+    }
+
+    fn filtered(filter : &StreamFilter, input : &str) -> JsonNode {
+        let mut out = Vec::new();
+        filter.run(input.as_bytes(), &mut out).unwrap();
+        JsonNode::from_bytes(&out).unwrap()
+    }
+
+    #[test]
+    fn test_matches_pipeline() {
+        let cases = vec![
+            (vec!["/users/*/name", "/total"], vec![]),
+            (vec![], vec!["/users/*/password", "/total"]),
+            (vec!["/users"], vec!["/**/tags/1"]),
+            (vec![], vec![""])
+        ];
+        for (keep, drop) in cases {
+            let mut filter = StreamFilter::new();
+            let mut pipeline = Pipeline::new();
+            for pattern in keep {
+                filter = filter.keep_paths(pointer(pattern));
+                pipeline = pipeline.keep_paths(pointer(pattern));
+            }
+            for pattern in drop {
+                filter = filter.drop_paths(pointer(pattern));
+                pipeline = pipeline.drop_paths(pointer(pattern));
+            }
+            assert_eq!(filtered(&filter, USERS), pipeline.apply(JsonNode::from_str(USERS).unwrap()));
+        }
+    }
+
+    #[test]
+    fn test_copies_compactly_and_reports_errors() {
+        let mut out = Vec::new();
+        StreamFilter::new().run(" [ \"a\\n\" , { } , -1 ] ".as_bytes(), &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "[\"a\\n\",{},-1]");
+        assert!(matches!(StreamFilter::new().run("[1,".as_bytes(), &mut Vec::new()), Err(EventError::UnexpectedEnd(_))));
+    }
+
+    #[test]
+    fn test_deep_nesting() {
+        let depth = 500000;
+        let text = format!("{}1{}", "[{\"a\":".repeat(depth / 2), "}]".repeat(depth / 2));
+        let error = StreamFilter::new().max_depth(128).run(text.as_bytes(), &mut Vec::new()).unwrap_err();
+        assert!(matches!(error, EventError::TooDeep { limit: 128, offset: 384 }));
+        let mut out = Vec::new();
+        let filter = StreamFilter::new().max_depth(usize::MAX).drop_paths(pointer("/0/a/0/a"));
+        filter.run(text.as_bytes(), &mut out).unwrap();
+        assert_eq!(out, b"[{\"a\":[{}]}]");
+        let mut out = Vec::new();
+        StreamFilter::new().max_depth(usize::MAX).run(text.as_bytes(), &mut out).unwrap();
+        assert_eq!(out, text.as_bytes());
+    }
+
+    #[test]
+    fn test_escapes_long_strings() {
+        let text = format!("{}\"{}\u{1}é\\{}\n", "a".repeat(40), "b".repeat(15), "c".repeat(17));
+        let mut out = Vec::new();
+        serialize::write_string(&text, &SerializeOptions::default(), &mut |piece| write(&mut out, piece.as_bytes())).unwrap();
+        let expected = format!("\"{}\\\"{}\\u0001é\\\\{}\\n\"", "a".repeat(40), "b".repeat(15), "c".repeat(17));
+        assert_eq!(String::from_utf8(out).unwrap(), expected);
+        let bytes = text.as_bytes();
+        let naive = |pos : usize| pos + bytes[pos..].iter().position(|b| *b < 0x20 || *b == b'"' || *b == b'\\').unwrap_or(bytes.len() - pos);
+        assert!((0..=bytes.len()).all(|pos| scan::escape_position(bytes, pos) == naive(pos)));
+    }
+}
@@ -0,0 +1,197 @@
+use crate::json::{maybe_grow, JsonNode, Number};
+use crate::json::nfc::nfc;
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Pass {
+    /// Remove object members whose value is null.
+    DropNullKeys,
+    /// Remove members and elements that are (or became) empty objects or arrays.
+    DropEmpty,
+    /// Strip leading and trailing whitespace from strings.
+    TrimStrings,
+    /// Cut strings down to at most this many characters.
+    TruncateStrings(usize),
+    /// Turn numbers into strings, so no transport can round them.
+    StringifyNumbers,
+    /// Turn strings that are valid JSON numbers into numbers, unless they are
+    /// too large to hold.
+    NumifyStrings,
+    /// Normalize strings to Unicode Normalization Form C.
+    NfcStrings
+}
+
+/// A composition of cleanup passes, all applied in a single bottom-up traversal.
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct Sanitizer {
+    passes: Vec<Pass>
+}
+
+impl Sanitizer {
+    pub fn new() -> Sanitizer {
+        Sanitizer { passes: Vec::new() }
+    }
+
+    pub fn with(mut self, pass : Pass) -> Sanitizer {
+        self.passes.push(pass);
+        self
+    }
+
+    pub fn drop_null_keys(self) -> Sanitizer {
+        self.with(Pass::DropNullKeys)
+    }
+
+    pub fn drop_empty(self) -> Sanitizer {
+        self.with(Pass::DropEmpty)
+    }
+
+    pub fn trim_strings(self) -> Sanitizer {
+        self.with(Pass::TrimStrings)
+    }
+
+    pub fn truncate_strings(self, max_chars : usize) -> Sanitizer {
+        self.with(Pass::TruncateStrings(max_chars))
+    }
+
+    pub fn stringify_numbers(self) -> Sanitizer {
+        self.with(Pass::StringifyNumbers)
+    }
+
+    pub fn numify_strings(self) -> Sanitizer {
+        self.with(Pass::NumifyStrings)
+    }
+
+    pub fn nfc_strings(self) -> Sanitizer {
+        self.with(Pass::NfcStrings)
+    }
+
+    pub fn apply(&self, mut node : JsonNode) -> JsonNode {
+        maybe_grow(|| match node {
+            JsonNode::Array(ref mut a) => JsonNode::Array(
+                std::mem::take(a).into_iter().map(|v| self.apply(v)).filter(|v| self.keeps_element(v)).collect()
+            ),
+            JsonNode::Object(ref mut o) => JsonNode::Object(
+                std::mem::take(o).into_iter().map(|(k, v)| (k, self.apply(v))).filter(|(_, v)| self.keeps_member(v)).collect()
+            ),
+            _ => self.clean_scalar(node)
+        })
+    }
+
+    fn clean_scalar(&self, mut node : JsonNode) -> JsonNode {
+        for pass in &self.passes {
+            let cleaned = match (pass, &mut node) {
+                (Pass::TrimStrings, JsonNode::String(s)) if s.trim().len() != s.len() => JsonNode::String(s.trim().to_string()),
+                (Pass::TruncateStrings(max_chars), JsonNode::String(s)) => {
+                    if let Some((end, _)) = s.char_indices().nth(*max_chars) {
+                        s.truncate(end)
+                    }
+                    continue
+                },
+                (Pass::StringifyNumbers, JsonNode::Number(n)) => JsonNode::String(n.to_string()),
+                (Pass::NumifyStrings, JsonNode::String(s)) if is_json_number(s) => match s.parse::<Number>() {
+                    // beyond the range of a double unless kept as a decimal
+                    Ok(n) if n.is_decimal() || n.as_f64().is_finite() => JsonNode::Number(n),
+                    _ => continue
+                },
+                (Pass::NfcStrings, JsonNode::String(s)) => JsonNode::String(nfc(s)),
+                _ => continue
+            };
+            node = cleaned
+        }
+        node
+    }
+
+    fn keeps_element(&self, value : &JsonNode) -> bool {
+        !(self.passes.contains(&Pass::DropEmpty) && is_empty_container(value))
+    }
+
+    fn keeps_member(&self, value : &JsonNode) -> bool {
+        self.keeps_element(value) && !(self.passes.contains(&Pass::DropNullKeys) && *value == JsonNode::Null)
+    }
+}
+
+// the number grammar of RFC 8259, which is stricter than f64's FromStr
+fn is_json_number(s : &str) -> bool {
+    let bytes = s.as_bytes();
+    let mut i = 0;
+    let digits = |i : &mut usize| {
+        let start = *i;
+        while bytes.get(*i).is_some_and(u8::is_ascii_digit) {
+            *i += 1
+        }
+        *i - start
+    };
+    if bytes.first() == Some(&b'-') {
+        i += 1
+    }
+    match digits(&mut i) {
+        0 => return false,
+        n if n > 1 && bytes[i - n] == b'0' => return false,
+        _ => ()
+    }
+    if bytes.get(i) == Some(&b'.') {
+        i += 1;
+        if digits(&mut i) == 0 {
+            return false
+        }
+    }
+    if matches!(bytes.get(i), Some(b'e') | Some(b'E')) {
+        i += 1;
+        if matches!(bytes.get(i), Some(b'+') | Some(b'-')) {
+            i += 1
+        }
+        if digits(&mut i) == 0 {
+            return false
+        }
+    }
+    i == bytes.len()
+}
+
+fn is_empty_container(value : &JsonNode) -> bool {
+    match value {
+        JsonNode::Array(a) => a.is_empty(),
+        JsonNode::Object(o) => o.is_empty(),
+        _ => false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_passes_compose() {
+        let input = JsonNode::from_str("{\"a\":null,\"b\":{\"c\":null},\"d\":[[],\" x \"],\"e\":\"long text\"}").unwrap();
+        let cleaned = Sanitizer::new().drop_null_keys().drop_empty().trim_strings().truncate_strings(4).apply(input);
+        assert_eq!(cleaned, JsonNode::from_str("{\"d\":[\"x\"],\"e\":\"long\"}").unwrap());
+    }
+
+    #[test]
+    fn test_nulls_in_arrays_are_kept() {
+        let input = JsonNode::from_str("[null,{\"k\":null}]").unwrap();
+        assert_eq!(Sanitizer::new().drop_null_keys().apply(input), JsonNode::from_str("[null,{}]").unwrap());
+    }
+
+    #[test]
+    fn test_truncation_respects_characters() {
+        let input = JsonNode::String("äöü".to_string());
+        assert_eq!(Sanitizer::new().truncate_strings(2).apply(input), JsonNode::String("äö".to_string()));
+    }
+
+    #[test]
+    fn test_number_and_string_normalizers() {
+        let input = JsonNode::from_str("{\"a\":2.5,\"b\":[\"-1e3\",\"007\",\" 1\",\"1.\",\"0.5\"]}").unwrap();
+        let numified = Sanitizer::new().numify_strings().apply(input.clone());
+        assert_eq!(numified, JsonNode::from_str("{\"a\":2.5,\"b\":[-1000,\"007\",\" 1\",\"1.\",0.5]}").unwrap());
+        let stringified = Sanitizer::new().stringify_numbers().apply(numified);
+        assert_eq!(stringified, JsonNode::from_str("{\"a\":\"2.5\",\"b\":[\"-1000\",\"007\",\" 1\",\"1.\",\"0.5\"]}").unwrap());
+        let huge = Sanitizer::new().numify_strings().apply(JsonNode::from_str("[\"1e400\",\"-1e400\",\"1e300\"]").unwrap());
+        if cfg!(feature = "arbitrary_precision") {
+            assert!(huge[0].is_number())
+        } else {
+            assert_eq!(huge, JsonNode::from_str("[\"1e400\",\"-1e400\",1e300]").unwrap())
+        }
+        let input = JsonNode::String("Cafe\u{301} \u{212B}".to_string());
+        assert_eq!(Sanitizer::new().nfc_strings().apply(input), JsonNode::String("Caf\u{e9} \u{c5}".to_string()));
+    }
+}
@@ -0,0 +1,132 @@
+// Structural scanning over raw bytes: finds where values start and end
+// without decoding them or building any nodes.
+
+pub fn is_whitespace(byte : u8) -> bool {
+    byte == b' ' || byte == b'\t' || byte == b'\n' || byte == b'\r'
+}
+
+pub fn skip_whitespace(input : &[u8], mut pos : usize) -> usize {
+    while pos < input.len() && is_whitespace(input[pos]) {
+        pos += 1
+    }
+    pos
+}
+
+/// Offset just past the value starting at `pos`, or None if it is malformed or truncated.
+pub fn value_end(input : &[u8], pos : usize) -> Option<usize> {
+    match *input.get(pos)? {
+        b'"' => string_end(input, pos),
+        b'[' | b'{' => container_end(input, pos),
+        b't' => literal_end(input, pos, b"true"),
+        b'f' => literal_end(input, pos, b"false"),
+        b'n' => literal_end(input, pos, b"null"),
+        b'-' | b'+' | b'.' | b'0'..=b'9' => {
+            let mut end = pos;
+            while end < input.len() && is_number_byte(input[end]) {
+                end += 1
+            }
+            Some(end)
+        },
+        _ => None
+    }
+}
+
+/// Offset just past the closing quote of the string starting at `pos`.
+pub fn string_end(input : &[u8], pos : usize) -> Option<usize> {
+    let mut i = pos + 1;
+    while i < input.len() {
+        match input[i] {
+            b'\\' => i += 2,
+            b'"' => return Some(i + 1),
+            _ => i += 1
+        }
+    }
+    None
+}
+
+fn container_end(input : &[u8], pos : usize) -> Option<usize> {
+    let mut depth = 0;
+    let mut i = pos;
+    while i < input.len() {
+        match input[i] {
+            b'"' => {
+                i = string_end(input, i)?;
+                continue
+            },
+            b'[' | b'{' => depth += 1,
+            b']' | b'}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i + 1)
+                }
+            },
+            _ => ()
+        }
+        i += 1
+    }
+    None
+}
+
+fn literal_end(input : &[u8], pos : usize, literal : &[u8]) -> Option<usize> {
+    if input[pos..].starts_with(literal) {
+        Some(pos + literal.len())
+    } else {
+        None
+    }
+}
+
+fn is_number_byte(byte : u8) -> bool {
+    byte.is_ascii_digit() || byte == b'-' || byte == b'+' || byte == b'.' || byte == b'e' || byte == b'E'
+}
+
+fn needs_escape(byte : u8) -> bool {
+    byte < 0x20 || byte == b'"' || byte == b'\\'
+}
+
+/// Offset of the first byte from `pos` on that a JSON string cannot hold
+/// as it is (a quote, a backslash or a control character), or the length of
+/// `input` if there is none. Clean runs are checked 16 bytes at a time.
+#[cfg(target_arch = "x86_64")]
+pub fn escape_position(input : &[u8], mut pos : usize) -> usize {
+    use std::arch::x86_64::*;
+    // SSE2 is part of every x86_64 target
+    unsafe {
+        let quote = _mm_set1_epi8(b'"' as i8);
+        let backslash = _mm_set1_epi8(b'\\' as i8);
+        let control = _mm_set1_epi8(0x1f);
+        while pos + 16 <= input.len() {
+            let chunk = _mm_loadu_si128(input.as_ptr().add(pos) as *const __m128i);
+            let found = _mm_or_si128(
+                _mm_or_si128(_mm_cmpeq_epi8(chunk, quote), _mm_cmpeq_epi8(chunk, backslash)),
+                // unsigned chunk <= 0x1f
+                _mm_cmpeq_epi8(_mm_max_epu8(chunk, control), control));
+            let mask = _mm_movemask_epi8(found);
+            if mask != 0 {
+                return pos + mask.trailing_zeros() as usize
+            }
+            pos += 16
+        }
+    }
+    pos + input[pos..].iter().position(|b| needs_escape(*b)).unwrap_or(input.len() - pos)
+}
+
+/// Offset of the first byte from `pos` on that a JSON string cannot hold
+/// as it is (a quote, a backslash or a control character), or the length of
+/// `input` if there is none. Clean runs are checked a word at a time.
+#[cfg(not(target_arch = "x86_64"))]
+pub fn escape_position(input : &[u8], mut pos : usize) -> usize {
+    const ONES : u64 = 0x0101_0101_0101_0101;
+    const HIGHS : u64 = 0x8080_8080_8080_8080;
+    let has_zero = |word : u64| word.wrapping_sub(ONES) & !word & HIGHS != 0;
+    while pos + 8 <= input.len() {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(&input[pos..pos + 8]);
+        let word = u64::from_le_bytes(bytes);
+        let control = word.wrapping_sub(ONES * 0x20) & !word & HIGHS != 0;
+        if control || has_zero(word ^ (ONES * b'"' as u64)) || has_zero(word ^ (ONES * b'\\' as u64)) {
+            break
+        }
+        pos += 8
+    }
+    pos + input[pos..].iter().position(|b| needs_escape(*b)).unwrap_or(input.len() - pos)
+}
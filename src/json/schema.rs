@@ -0,0 +1,1051 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use std::rc::Rc;
+use crate::json::{maybe_grow, JsonNode, Number, Object};
+use crate::json::parser::parse_json;
+use crate::json::pointer::JsonPointer;
+use crate::json::regex::Regex;
+
+/// One way an instance fails its schema: where in the instance, which
+/// schema keyword rejected it, and why. `schema_uri` names the document the
+/// keyword lives in; it is empty for a root schema without an `$id`.
+#[derive(PartialEq, Debug, Clone)]
+pub struct ValidationError {
+    pub instance_path: JsonPointer,
+    pub schema_uri: String,
+    pub schema_path: JsonPointer,
+    pub message: String
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "\"{}\": {} (schema \"{}#{}\")", self.instance_path, self.message, self.schema_uri, self.schema_path)
+    }
+}
+
+/// A schema document that cannot be compiled.
+#[derive(PartialEq, Debug, Clone)]
+pub struct SchemaError {
+    pub schema_path: JsonPointer,
+    pub message: String
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid schema at \"{}\": {}", self.schema_path, self.message)
+    }
+}
+
+/// The JSON Schema drafts the validator understands. A schema picks its
+/// dialect with `$schema`; the one given to `compile_with` applies otherwise.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Dialect {
+    Draft7,
+    Draft202012
+}
+
+impl Dialect {
+    fn from_uri(uri : &str) -> Option<Dialect> {
+        if uri.contains("draft-07") || uri.contains("draft-06") || uri.contains("draft-04") {
+            Some(Dialect::Draft7)
+        } else if uri.contains("2020-12") || uri.contains("2019-09") {
+            Some(Dialect::Draft202012)
+        } else {
+            None
+        }
+    }
+}
+
+/// Loads the schema documents that `$ref`s point to outside of the one
+/// being compiled. `uri` is absolute as far as the referring `$id`s allow.
+pub trait SchemaResolver {
+    fn load(&mut self, uri : &str) -> Result<JsonNode, String>;
+}
+
+/// Refuses every external document.
+pub struct NoResolver;
+
+impl SchemaResolver for NoResolver {
+    fn load(&mut self, uri : &str) -> Result<JsonNode, String> {
+        Err(format!("no resolver for \"{}\"", uri))
+    }
+}
+
+/// Documents known up front, by URI.
+impl SchemaResolver for HashMap<String, JsonNode> {
+    fn load(&mut self, uri : &str) -> Result<JsonNode, String> {
+        self.get(uri).cloned().ok_or_else(|| format!("unknown schema \"{}\"", uri))
+    }
+}
+
+/// Reads relative and `file://` URIs as files under a directory.
+pub struct FileResolver {
+    directory: PathBuf
+}
+
+impl FileResolver {
+    pub fn new<P : Into<PathBuf>>(directory : P) -> FileResolver {
+        FileResolver { directory: directory.into() }
+    }
+}
+
+impl SchemaResolver for FileResolver {
+    fn load(&mut self, uri : &str) -> Result<JsonNode, String> {
+        let path = match uri.strip_prefix("file://") {
+            Some(path) => PathBuf::from(path),
+            None if has_scheme(uri) => return Err(format!("cannot load \"{}\" from the file system", uri)),
+            None => self.directory.join(uri)
+        };
+        let text = fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        match parse_json(text.trim().as_bytes()) {
+            Ok((&[], schema)) => Ok(schema),
+            Ok(_) => Err(format!("{}: trailing characters after the schema", path.display())),
+            Err(reason) => Err(format!("{}: {}", path.display(), reason))
+        }
+    }
+}
+
+/// A compiled JSON Schema, draft-07 or 2020-12. `$ref` may point inside the
+/// schema, at an `$id` or anchor, or at external documents fetched through a
+/// `SchemaResolver`. `format` is treated as an annotation only. Schemas that
+/// need annotation tracking or a dynamic scope, through `unevaluatedItems`,
+/// `unevaluatedProperties` or a `$dynamicRef` to a `$dynamicAnchor`, are
+/// refused when compiled rather than checked partially.
+#[derive(Debug)]
+pub struct Schema {
+    nodes: Vec<Compiled>,
+    uris: Vec<String>
+}
+
+#[derive(Debug)]
+enum Compiled {
+    Always,
+    Never(usize, JsonPointer),
+    Rules(Vec<Rule>)
+}
+
+#[derive(Debug)]
+struct Rule {
+    document: usize,
+    path: JsonPointer,
+    keyword: Keyword
+}
+
+#[derive(Debug)]
+enum Keyword {
+    Type(Vec<String>),
+    Enum(Vec<JsonNode>),
+    Const(JsonNode),
+    MultipleOf(Number),
+    Maximum(Number, bool),
+    Minimum(Number, bool),
+    MaxLength(usize),
+    MinLength(usize),
+    Pattern(Regex, String),
+    Items(usize),
+    TupleItems(Vec<usize>),
+    AdditionalItems(usize, usize),
+    MaxItems(usize),
+    MinItems(usize),
+    UniqueItems,
+    Contains(usize, usize, usize),
+    MaxProperties(usize),
+    MinProperties(usize),
+    Required(Vec<String>),
+    Properties(Vec<(String, usize)>),
+    PatternProperties(Vec<(Regex, usize)>),
+    AdditionalProperties(usize, Vec<String>, Vec<Regex>),
+    Dependencies(Vec<(String, Dependency)>),
+    PropertyNames(usize),
+    Conditional(usize, Option<usize>, Option<usize>),
+    AllOf(Vec<usize>),
+    AnyOf(Vec<usize>),
+    OneOf(Vec<usize>),
+    Not(usize),
+    Ref(usize)
+}
+
+#[derive(Debug)]
+enum Dependency {
+    Properties(Vec<String>),
+    Schema(usize)
+}
+
+impl Schema {
+    /// Compiles a self-contained schema, draft-07 unless `$schema` says otherwise.
+    pub fn compile(schema : &JsonNode) -> Result<Schema, SchemaError> {
+        Schema::compile_with(schema, Dialect::Draft7, &mut NoResolver)
+    }
+
+    pub fn compile_with(schema : &JsonNode, dialect : Dialect, resolver : &mut dyn SchemaResolver) -> Result<Schema, SchemaError> {
+        let mut compiler = Compiler {
+            documents: Vec::new(),
+            uris: Vec::new(),
+            scopes: HashMap::new(),
+            resources: HashMap::new(),
+            anchors: HashMap::new(),
+            dynamic_anchors: HashSet::new(),
+            nodes: Vec::new(),
+            compiled: HashMap::new(),
+            scope: Scope { base: String::new(), dialect },
+            document: 0,
+            resolver
+        };
+        let root = compiler.add_document(String::new(), schema.clone());
+        compiler.scope = compiler.scopes[&(root, JsonPointer::root())].clone();
+        compiler.compile(schema, JsonPointer::root())?;
+        if let Some(path) = in_place_cycle(&compiler.nodes) {
+            return Err(invalid(path, "the reference leads back to itself without descending into the instance"))
+        }
+        Ok(Schema { nodes: compiler.nodes, uris: compiler.uris })
+    }
+
+    /// Checks `instance`, collecting every violation rather than stopping at the first.
+    pub fn validate(&self, instance : &JsonNode) -> Result<(), Vec<ValidationError>> {
+        let mut errors = Vec::new();
+        self.check(0, instance, &mut JsonPointer::root(), &mut errors);
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+
+    pub fn is_valid(&self, instance : &JsonNode) -> bool {
+        self.accepts(0, instance)
+    }
+
+    fn accepts(&self, index : usize, instance : &JsonNode) -> bool {
+        let mut errors = Vec::new();
+        self.check(index, instance, &mut JsonPointer::root(), &mut errors);
+        errors.is_empty()
+    }
+
+    fn check(&self, index : usize, instance : &JsonNode, at : &mut JsonPointer, errors : &mut Vec<ValidationError>) {
+        maybe_grow(|| {
+            let rules = match &self.nodes[index] {
+                Compiled::Always => return,
+                Compiled::Never(document, path) => {
+                    errors.push(self.error(at, *document, path, "no value is allowed here".to_string()));
+                    return
+                },
+                Compiled::Rules(rules) => rules
+            };
+            for rule in rules {
+                if let Some(message) = self.check_rule(&rule.keyword, instance, at, errors) {
+                    errors.push(self.error(at, rule.document, &rule.path, message));
+                }
+            }
+        })
+    }
+
+    fn error(&self, at : &JsonPointer, document : usize, path : &JsonPointer, message : String) -> ValidationError {
+        ValidationError { instance_path: at.clone(), schema_uri: self.uris[document].clone(), schema_path: path.clone(), message }
+    }
+
+    // Subschema violations go straight into `errors`; a violation of the keyword itself is returned.
+    fn check_rule(&self, keyword : &Keyword, instance : &JsonNode, at : &mut JsonPointer, errors : &mut Vec<ValidationError>) -> Option<String> {
+        match (keyword, instance) {
+            (Keyword::Type(types), _) => {
+                if types.iter().any(|t| has_type(instance, t)) {
+                    return None
+                }
+                Some(format!("expected {}, found {}", types.join(" or "), type_name(instance)))
+            },
+            (Keyword::Enum(values), _) => fails(!values.contains(instance), || "value is not one of the allowed values".to_string()),
+            (Keyword::Const(value), _) => fails(value != instance, || format!("value must be {}", value)),
+            (Keyword::MultipleOf(divisor), JsonNode::Number(n)) => {
+                // integers divide exactly; anything else within a rounding error
+                let multiple = match (n.as_i128(), divisor.as_i128()) {
+                    (Some(n), Some(divisor)) => n % divisor == 0,
+                    _ => {
+                        let quotient = n.as_f64() / divisor.as_f64();
+                        (quotient - quotient.round()).abs() <= 1e-9
+                    }
+                };
+                fails(!multiple, || format!("{} is not a multiple of {}", n, divisor))
+            },
+            (Keyword::Maximum(limit, exclusive), JsonNode::Number(n)) => fails(if *exclusive { n >= limit } else { n > limit },
+                || format!("{} is above the {}maximum {}", n, if *exclusive { "exclusive " } else { "" }, limit)),
+            (Keyword::Minimum(limit, exclusive), JsonNode::Number(n)) => fails(if *exclusive { n <= limit } else { n < limit },
+                || format!("{} is below the {}minimum {}", n, if *exclusive { "exclusive " } else { "" }, limit)),
+            (Keyword::MaxLength(max), JsonNode::String(s)) => fails(s.chars().count() > *max, || format!("string is longer than {} characters", max)),
+            (Keyword::MinLength(min), JsonNode::String(s)) => fails(s.chars().count() < *min, || format!("string is shorter than {} characters", min)),
+            (Keyword::Pattern(regex, pattern), JsonNode::String(s)) => fails(!regex.is_match(s), || format!("string does not match \"{}\"", pattern)),
+            (Keyword::Items(schema), JsonNode::Array(a)) => {
+                self.check_elements(a.iter().enumerate(), *schema, at, errors);
+                None
+            },
+            (Keyword::TupleItems(schemas), JsonNode::Array(a)) => {
+                for (i, (item, schema)) in a.iter().zip(schemas).enumerate() {
+                    self.check_child(*schema, item, i.to_string(), at, errors)
+                }
+                None
+            },
+            (Keyword::AdditionalItems(from, schema), JsonNode::Array(a)) => {
+                self.check_elements(a.iter().enumerate().skip(*from), *schema, at, errors);
+                None
+            },
+            (Keyword::MaxItems(max), JsonNode::Array(a)) => fails(a.len() > *max, || format!("array has more than {} items", max)),
+            (Keyword::MinItems(min), JsonNode::Array(a)) => fails(a.len() < *min, || format!("array has fewer than {} items", min)),
+            (Keyword::UniqueItems, JsonNode::Array(a)) => {
+                let duplicate = a.iter().enumerate().any(|(i, item)| a[..i].contains(item));
+                fails(duplicate, || "array items are not unique".to_string())
+            },
+            (Keyword::Contains(schema, min, max), JsonNode::Array(a)) => {
+                let matched = a.iter().filter(|item| self.accepts(*schema, item)).count();
+                if matched < *min && *min == 1 {
+                    return Some("no array item matches the contains schema".to_string())
+                }
+                fails(matched < *min || matched > *max, || format!("{} array items match the contains schema", matched))
+            },
+            (Keyword::MaxProperties(max), JsonNode::Object(o)) => fails(o.len() > *max, || format!("object has more than {} properties", max)),
+            (Keyword::MinProperties(min), JsonNode::Object(o)) => fails(o.len() < *min, || format!("object has fewer than {} properties", min)),
+            (Keyword::Required(names), JsonNode::Object(o)) => missing(names, o),
+            (Keyword::Properties(properties), JsonNode::Object(o)) => {
+                for (name, schema) in properties {
+                    if let Some(value) = o.get(name) {
+                        self.check_child(*schema, value, name.clone(), at, errors)
+                    }
+                }
+                None
+            },
+            (Keyword::PatternProperties(patterns), JsonNode::Object(o)) => {
+                for (name, value) in sorted(o) {
+                    for (_, schema) in patterns.iter().filter(|(regex, _)| regex.is_match(name)) {
+                        self.check_child(*schema, value, name.clone(), at, errors)
+                    }
+                }
+                None
+            },
+            (Keyword::AdditionalProperties(schema, known, patterns), JsonNode::Object(o)) => {
+                for (name, value) in sorted(o) {
+                    if !known.contains(name) && !patterns.iter().any(|regex| regex.is_match(name)) {
+                        self.check_child(*schema, value, name.clone(), at, errors)
+                    }
+                }
+                None
+            },
+            (Keyword::Dependencies(dependencies), JsonNode::Object(o)) => {
+                let mut messages = Vec::new();
+                for (name, dependency) in dependencies.iter().filter(|(name, _)| o.contains_key(name)) {
+                    match dependency {
+                        Dependency::Properties(names) => if let Some(message) = missing(names, o) {
+                            messages.push(format!("\"{}\" requires {}", name, message))
+                        },
+                        Dependency::Schema(schema) => self.check(*schema, instance, at, errors)
+                    }
+                }
+                if messages.is_empty() { None } else { Some(messages.join("; ")) }
+            },
+            (Keyword::PropertyNames(schema), JsonNode::Object(o)) => {
+                let invalid : Vec<&String> = sorted(o).into_iter().map(|(name, _)| name)
+                    .filter(|name| !self.accepts(*schema, &JsonNode::String(name.to_string()))).collect();
+                fails(!invalid.is_empty(), || format!("invalid property names {:?}", invalid))
+            },
+            (Keyword::Conditional(condition, then, otherwise), _) => {
+                let branch = if self.accepts(*condition, instance) { then } else { otherwise };
+                if let Some(branch) = branch {
+                    self.check(*branch, instance, at, errors)
+                }
+                None
+            },
+            (Keyword::AllOf(schemas), _) => {
+                for schema in schemas {
+                    self.check(*schema, instance, at, errors)
+                }
+                None
+            },
+            (Keyword::AnyOf(schemas), _) => fails(!schemas.iter().any(|schema| self.accepts(*schema, instance)),
+                || "value matches none of the anyOf schemas".to_string()),
+            (Keyword::OneOf(schemas), _) => {
+                let matched = schemas.iter().filter(|schema| self.accepts(**schema, instance)).count();
+                fails(matched != 1, || format!("value matches {} of the oneOf schemas instead of exactly one", matched))
+            },
+            (Keyword::Not(schema), _) => fails(self.accepts(*schema, instance), || "value matches the not schema".to_string()),
+            (Keyword::Ref(schema), _) => {
+                self.check(*schema, instance, at, errors);
+                None
+            },
+            _ => None
+        }
+    }
+
+    fn check_elements<'a, I>(&self, items : I, schema : usize, at : &mut JsonPointer, errors : &mut Vec<ValidationError>)
+        where I : Iterator<Item = (usize, &'a JsonNode)>
+    {
+        for (i, item) in items {
+            self.check_child(schema, item, i.to_string(), at, errors)
+        }
+    }
+
+    fn check_child(&self, schema : usize, value : &JsonNode, token : String, at : &mut JsonPointer, errors : &mut Vec<ValidationError>) {
+        at.push(token);
+        self.check(schema, value, at, errors);
+        at.pop();
+    }
+}
+
+fn fails<F : FnOnce() -> String>(failed : bool, message : F) -> Option<String> {
+    if failed { Some(message()) } else { None }
+}
+
+fn missing(names : &[String], o : &Object) -> Option<String> {
+    let absent : Vec<&String> = names.iter().filter(|name| !o.contains_key(*name)).collect();
+    fails(!absent.is_empty(), || format!("missing required properties {:?}", absent))
+}
+
+// object members in key order, so errors come out in a stable order
+fn sorted(o : &Object) -> Vec<(&String, &JsonNode)> {
+    let mut members : Vec<(&String, &JsonNode)> = o.iter().collect();
+    members.sort_by(|a, b| a.0.cmp(b.0));
+    members
+}
+
+fn has_type(instance : &JsonNode, name : &str) -> bool {
+    match (name, instance) {
+        ("integer", JsonNode::Number(n)) => n.as_f64().fract() == 0.0,
+        _ => type_name(instance) == name
+    }
+}
+
+fn type_name(instance : &JsonNode) -> &'static str {
+    match instance {
+        JsonNode::Null => "null",
+        JsonNode::Boolean(_) => "boolean",
+        JsonNode::Number(_) => "number",
+        JsonNode::String(_) => "string",
+        JsonNode::Array(_) => "array",
+        JsonNode::Object(_) => "object"
+    }
+}
+
+// The base URI and dialect in effect inside a schema resource.
+#[derive(Debug, Clone)]
+struct Scope {
+    base: String,
+    dialect: Dialect
+}
+
+type Location = (usize, JsonPointer);
+
+struct Compiler<'r> {
+    documents: Vec<Rc<JsonNode>>,
+    uris: Vec<String>,
+    // resource roots: where an `$id` or `$schema` sets a new scope
+    scopes: HashMap<Location, Scope>,
+    resources: HashMap<String, Location>,
+    anchors: HashMap<String, Location>,
+    dynamic_anchors: HashSet<String>,
+    nodes: Vec<Compiled>,
+    compiled: HashMap<Location, usize>,
+    scope: Scope,
+    document: usize,
+    resolver: &'r mut dyn SchemaResolver
+}
+
+impl<'r> Compiler<'r> {
+    fn add_document(&mut self, uri : String, schema : JsonNode) -> usize {
+        let document = self.documents.len();
+        let schema = Rc::new(schema);
+        self.documents.push(schema.clone());
+        self.uris.push(uri.clone());
+        self.resources.insert(uri.clone(), (document, JsonPointer::root()));
+        let scope = Scope { base: uri, dialect: self.scope.dialect };
+        self.scan(document, &schema, &mut JsonPointer::root(), scope, true);
+        document
+    }
+
+    // Records every `$id` and anchor in a document before anything is compiled,
+    // so references may point forwards and into sibling resources.
+    fn scan(&mut self, document : usize, schema : &JsonNode, path : &mut JsonPointer, mut scope : Scope, root : bool) {
+        maybe_grow(|| match schema {
+            JsonNode::Object(o) => {
+                let mut changed = root;
+                if let Some(dialect) = string(o.get("$schema")).and_then(Dialect::from_uri) {
+                    scope.dialect = dialect;
+                    changed = true
+                }
+                let mut anchors : Vec<&str> = Vec::new();
+                match string(o.get("$id")) {
+                    Some(id) if scope.dialect == Dialect::Draft7 && id.starts_with('#') => anchors.push(&id[1..]),
+                    Some(id) => {
+                        scope.base = join_uri(&scope.base, id.split('#').next().unwrap_or_default());
+                        self.resources.insert(scope.base.clone(), (document, path.clone()));
+                        changed = true
+                    },
+                    None => ()
+                }
+                if scope.dialect == Dialect::Draft202012 {
+                    anchors.extend(string(o.get("$anchor")));
+                    if let Some(anchor) = string(o.get("$dynamicAnchor")) {
+                        self.dynamic_anchors.insert(format!("{}#{}", scope.base, anchor));
+                        anchors.push(anchor)
+                    }
+                }
+                for anchor in anchors {
+                    self.anchors.insert(format!("{}#{}", scope.base, anchor), (document, path.clone()));
+                }
+                if changed {
+                    self.scopes.insert((document, path.clone()), scope.clone());
+                }
+                for (name, value) in o {
+                    // these hold instance data, not schemas
+                    if name != "enum" && name != "const" && name != "default" && name != "examples" {
+                        path.push(name.as_str());
+                        self.scan(document, value, path, scope.clone(), false);
+                        path.pop();
+                    }
+                }
+            },
+            JsonNode::Array(a) => for (i, value) in a.iter().enumerate() {
+                path.push(i.to_string());
+                self.scan(document, value, path, scope.clone(), false);
+                path.pop();
+            },
+            _ => ()
+        })
+    }
+
+    // Each subschema is compiled once per location, so recursive `$ref`s terminate.
+    fn compile(&mut self, schema : &JsonNode, path : JsonPointer) -> Result<usize, SchemaError> {
+        let location = (self.document, path);
+        if let Some(index) = self.compiled.get(&location) {
+            return Ok(*index)
+        }
+        let index = self.nodes.len();
+        self.nodes.push(Compiled::Always);
+        self.compiled.insert(location.clone(), index);
+        let outer = match self.scopes.get(&location) {
+            Some(scope) => Some(std::mem::replace(&mut self.scope, scope.clone())),
+            None => None
+        };
+        let path = location.1;
+        let compiled = maybe_grow(|| match schema {
+            JsonNode::Boolean(true) => Ok(Compiled::Always),
+            JsonNode::Boolean(false) => Ok(Compiled::Never(self.document, path)),
+            JsonNode::Object(o) => self.rules(o, &path).map(Compiled::Rules),
+            _ => Err(invalid(&path, "a schema must be an object or a boolean"))
+        });
+        if let Some(outer) = outer {
+            self.scope = outer
+        }
+        self.nodes[index] = compiled?;
+        Ok(index)
+    }
+
+    fn rules(&mut self, o : &Object, path : &JsonPointer) -> Result<Vec<Rule>, SchemaError> {
+        // in draft-07 a $ref replaces all of its sibling keywords
+        if let (Some(reference), Dialect::Draft7) = (o.get("$ref"), self.scope.dialect) {
+            let keyword = Keyword::Ref(self.reference(reference, &path.child("$ref"))?);
+            return Ok(vec![Rule { document: self.document, path: path.child("$ref"), keyword }])
+        }
+        let mut rules = Vec::new();
+        for (name, value) in sorted(o) {
+            let at = path.child(name.as_str());
+            if let Some(keyword) = self.keyword(name, value, o, &at)? {
+                rules.push(Rule { document: self.document, path: at, keyword })
+            }
+        }
+        Ok(rules)
+    }
+
+    fn keyword(&mut self, name : &str, value : &JsonNode, o : &Object, at : &JsonPointer) -> Result<Option<Keyword>, SchemaError> {
+        let draft7 = self.scope.dialect == Dialect::Draft7;
+        Ok(Some(match name {
+            "type" => Keyword::Type(match value {
+                JsonNode::String(t) => vec![type_keyword(t, at)?],
+                JsonNode::Array(types) => types.iter().map(|t| match t {
+                    JsonNode::String(t) => type_keyword(t, at),
+                    _ => Err(invalid(at, "types must be strings"))
+                }).collect::<Result<_, _>>()?,
+                _ => return Err(invalid(at, "type must be a string or an array"))
+            }),
+            "enum" => match value {
+                JsonNode::Array(values) => Keyword::Enum(values.clone()),
+                _ => return Err(invalid(at, "enum must be an array"))
+            },
+            "const" => Keyword::Const(value.clone()),
+            "multipleOf" => match number(value, at)? {
+                divisor if divisor.as_f64() > 0.0 => Keyword::MultipleOf(divisor),
+                _ => return Err(invalid(at, "multipleOf must be positive"))
+            },
+            "maximum" => Keyword::Maximum(number(value, at)?, false),
+            "exclusiveMaximum" => Keyword::Maximum(number(value, at)?, true),
+            "minimum" => Keyword::Minimum(number(value, at)?, false),
+            "exclusiveMinimum" => Keyword::Minimum(number(value, at)?, true),
+            "maxLength" => Keyword::MaxLength(count(value, at)?),
+            "minLength" => Keyword::MinLength(count(value, at)?),
+            "pattern" => match value {
+                JsonNode::String(pattern) => Keyword::Pattern(regex(pattern, at)?, pattern.clone()),
+                _ => return Err(invalid(at, "pattern must be a string"))
+            },
+            "items" => match (value, o.get("prefixItems")) {
+                (JsonNode::Array(schemas), _) if draft7 => Keyword::TupleItems(self.each(schemas, at)?),
+                (_, Some(JsonNode::Array(prefix))) if !draft7 => Keyword::AdditionalItems(prefix.len(), self.compile(value, at.clone())?),
+                _ => Keyword::Items(self.compile(value, at.clone())?)
+            },
+            "prefixItems" if !draft7 => Keyword::TupleItems(self.non_empty(value, at)?),
+            "additionalItems" if draft7 => match o.get("items") {
+                Some(JsonNode::Array(tuple)) => Keyword::AdditionalItems(tuple.len(), self.compile(value, at.clone())?),
+                _ => return Ok(None)
+            },
+            "maxItems" => Keyword::MaxItems(count(value, at)?),
+            "minItems" => Keyword::MinItems(count(value, at)?),
+            "uniqueItems" => match value {
+                JsonNode::Boolean(true) => Keyword::UniqueItems,
+                JsonNode::Boolean(false) => return Ok(None),
+                _ => return Err(invalid(at, "uniqueItems must be a boolean"))
+            },
+            "contains" => {
+                let parent = at.parent().unwrap_or_default();
+                let bound = |name : &str, default| match o.get(name) {
+                    Some(value) if !draft7 => count(value, &parent.child(name)),
+                    _ => Ok(default)
+                };
+                let (min, max) = (bound("minContains", 1)?, bound("maxContains", usize::MAX)?);
+                Keyword::Contains(self.compile(value, at.clone())?, min, max)
+            },
+            "maxProperties" => Keyword::MaxProperties(count(value, at)?),
+            "minProperties" => Keyword::MinProperties(count(value, at)?),
+            "required" => Keyword::Required(strings(value, at)?),
+            "properties" => Keyword::Properties(self.members(value, at)?),
+            "patternProperties" => Keyword::PatternProperties(self.members(value, at)?.into_iter()
+                .map(|(pattern, schema)| Ok((regex(&pattern, &at.child(pattern.as_str()))?, schema)))
+                .collect::<Result<_, _>>()?),
+            "additionalProperties" => {
+                let known = match o.get("properties") {
+                    Some(JsonNode::Object(properties)) => properties.keys().cloned().collect(),
+                    _ => Vec::new()
+                };
+                let patterns = match o.get("patternProperties") {
+                    Some(JsonNode::Object(patterns)) => patterns.keys()
+                        .map(|pattern| regex(pattern, at)).collect::<Result<_, _>>()?,
+                    _ => Vec::new()
+                };
+                Keyword::AdditionalProperties(self.compile(value, at.clone())?, known, patterns)
+            },
+            "dependencies" if draft7 => Keyword::Dependencies(self.dependencies(value, at, true, true)?),
+            "dependentRequired" if !draft7 => Keyword::Dependencies(self.dependencies(value, at, true, false)?),
+            "dependentSchemas" if !draft7 => Keyword::Dependencies(self.dependencies(value, at, false, true)?),
+            "propertyNames" => Keyword::PropertyNames(self.compile(value, at.clone())?),
+            "if" => {
+                let parent = at.parent().unwrap_or_default();
+                let then = o.get("then").map(|then| self.compile(then, parent.child("then"))).transpose()?;
+                let otherwise = o.get("else").map(|otherwise| self.compile(otherwise, parent.child("else"))).transpose()?;
+                Keyword::Conditional(self.compile(value, at.clone())?, then, otherwise)
+            },
+            "allOf" => Keyword::AllOf(self.non_empty(value, at)?),
+            "anyOf" => Keyword::AnyOf(self.non_empty(value, at)?),
+            "oneOf" => Keyword::OneOf(self.non_empty(value, at)?),
+            "not" => Keyword::Not(self.compile(value, at.clone())?),
+            "$ref" if !draft7 => Keyword::Ref(self.reference(value, at)?),
+            // a target without a `$dynamicAnchor` resolves the same statically
+            "$dynamicRef" if !draft7 => {
+                let target = self.reference(value, at)?;
+                if string(Some(value)).is_some_and(|r| self.dynamic_anchors.contains(&join_uri(&self.scope.base, r))) {
+                    return Err(invalid(at, "$dynamicRef to a $dynamicAnchor is not supported"))
+                }
+                Keyword::Ref(target)
+            },
+            "unevaluatedItems" | "unevaluatedProperties" if !draft7 => match value {
+                JsonNode::Boolean(true) => return Ok(None),
+                JsonNode::Object(o) if o.is_empty() => return Ok(None),
+                _ => return Err(invalid(at, &format!("{} is not supported", name)))
+            },
+            _ => return Ok(None)
+        }))
+    }
+
+    fn dependencies(&mut self, value : &JsonNode, at : &JsonPointer, properties : bool, schemas : bool) -> Result<Vec<(String, Dependency)>, SchemaError> {
+        let dependencies = match value {
+            JsonNode::Object(dependencies) => dependencies,
+            _ => return Err(invalid(at, "expected an object of dependencies"))
+        };
+        sorted(dependencies).into_iter().map(|(name, dependency)| {
+            let at = at.child(name.as_str());
+            Ok((name.clone(), match dependency {
+                JsonNode::Array(_) if properties => Dependency::Properties(strings(dependency, &at)?),
+                _ if schemas => Dependency::Schema(self.compile(dependency, at)?),
+                _ => return Err(invalid(&at, "expected an array of strings"))
+            }))
+        }).collect()
+    }
+
+    fn reference(&mut self, reference : &JsonNode, at : &JsonPointer) -> Result<usize, SchemaError> {
+        let reference = match reference {
+            JsonNode::String(r) => join_uri(&self.scope.base, r),
+            _ => return Err(invalid(at, "$ref must be a string"))
+        };
+        let (uri, fragment) = match reference.find('#') {
+            Some(hash) => (&reference[..hash], percent_decode(&reference[hash + 1..])),
+            None => (&reference[..], String::new())
+        };
+        if !self.resources.contains_key(uri) {
+            let schema = self.resolver.load(uri).map_err(|e| invalid(at, &format!("cannot load \"{}\": {}", uri, e)))?;
+            self.add_document(uri.to_string(), schema);
+        }
+        let target = if fragment.is_empty() || fragment.starts_with('/') {
+            let (document, resource) = self.resources[uri].clone();
+            let pointer = JsonPointer::parse(&fragment).map_err(|e| invalid(at, &e.to_string()))?;
+            Some((document, JsonPointer::from_tokens(resource.tokens().iter().chain(pointer.tokens()))))
+        } else {
+            self.anchors.get(&reference).cloned()
+        };
+        let documents = self.documents.clone();
+        let (schema, document, path) = match target.and_then(|(d, path)| Some((path.resolve(&documents[d])?, d, path))) {
+            Some(found) => found,
+            None => return Err(invalid(at, &format!("unresolved reference \"{}\"", reference)))
+        };
+        // the target compiles in the scope of the resource around it, not the one referring to it
+        let scope = (0..=path.tokens().len()).rev()
+            .find_map(|n| self.scopes.get(&(document, JsonPointer::from_tokens(&path.tokens()[..n]))))
+            .cloned().unwrap_or_else(|| self.scope.clone());
+        let outer = (std::mem::replace(&mut self.document, document), std::mem::replace(&mut self.scope, scope));
+        let compiled = self.compile(schema, path);
+        self.document = outer.0;
+        self.scope = outer.1;
+        compiled
+    }
+
+    fn each(&mut self, schemas : &[JsonNode], at : &JsonPointer) -> Result<Vec<usize>, SchemaError> {
+        schemas.iter().enumerate().map(|(i, schema)| self.compile(schema, at.child(i.to_string()))).collect()
+    }
+
+    fn non_empty(&mut self, value : &JsonNode, at : &JsonPointer) -> Result<Vec<usize>, SchemaError> {
+        match value {
+            JsonNode::Array(schemas) if !schemas.is_empty() => self.each(schemas, at),
+            _ => Err(invalid(at, "expected a non-empty array of schemas"))
+        }
+    }
+
+    fn members(&mut self, value : &JsonNode, at : &JsonPointer) -> Result<Vec<(String, usize)>, SchemaError> {
+        match value {
+            JsonNode::Object(members) => sorted(members).into_iter()
+                .map(|(name, schema)| Ok((name.clone(), self.compile(schema, at.child(name.as_str()))?)))
+                .collect(),
+            _ => Err(invalid(at, "expected an object of schemas"))
+        }
+    }
+}
+
+fn string(value : Option<&JsonNode>) -> Option<&str> {
+    match value {
+        Some(JsonNode::String(s)) => Some(s),
+        _ => None
+    }
+}
+
+fn has_scheme(uri : &str) -> bool {
+    match uri.find(':') {
+        Some(colon) => colon > 0 && uri[..colon].chars().all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c)),
+        None => false
+    }
+}
+
+// Resolves `reference` against `base` as RFC 3986 does for the URIs schemas
+// use: absolute, host-relative, path-relative or fragment only.
+fn join_uri(base : &str, reference : &str) -> String {
+    if has_scheme(reference) {
+        return reference.to_string()
+    }
+    let base = base.split('#').next().unwrap_or_default();
+    if reference.is_empty() || reference.starts_with('#') {
+        return format!("{}{}", base, reference)
+    }
+    let authority_end = match base.find("://") {
+        Some(scheme_end) => base[scheme_end + 3..].find('/').map_or(base.len(), |slash| scheme_end + 3 + slash),
+        None => base.find(':').map_or(0, |colon| colon + 1)
+    };
+    let (prefix, directory) = if reference.starts_with('/') {
+        (&base[..authority_end], "")
+    } else {
+        let path = &base[authority_end..];
+        (&base[..authority_end], &path[..path.rfind('/').map_or(0, |slash| slash + 1)])
+    };
+    let joined = format!("{}{}", directory, reference);
+    let (path, fragment) = match joined.find('#') {
+        Some(hash) => (&joined[..hash], &joined[hash..]),
+        None => (&joined[..], "")
+    };
+    let mut segments : Vec<&str> = Vec::new();
+    for segment in path.split('/') {
+        match segment {
+            "." => (),
+            ".." => {
+                if segments.last().is_some_and(|last| !last.is_empty()) {
+                    segments.pop();
+                }
+            },
+            _ => segments.push(segment)
+        }
+    }
+    format!("{}{}{}", prefix, segments.join("/"), fragment)
+}
+
+// The subschemas a keyword applies to the instance itself rather than to a
+// part of it.
+fn in_place(keyword : &Keyword) -> Vec<usize> {
+    match keyword {
+        Keyword::Ref(schema) | Keyword::Not(schema) => vec![*schema],
+        Keyword::AllOf(schemas) | Keyword::AnyOf(schemas) | Keyword::OneOf(schemas) => schemas.clone(),
+        Keyword::Conditional(condition, then, otherwise) => Some(*condition).into_iter().chain(*then).chain(*otherwise).collect(),
+        Keyword::Dependencies(dependencies) => dependencies.iter().filter_map(|(_, dependency)| match dependency {
+            Dependency::Schema(schema) => Some(*schema),
+            Dependency::Properties(_) => None
+        }).collect(),
+        _ => Vec::new()
+    }
+}
+
+// The keyword closing a cycle of subschemas applied in place, such as
+// `{"$ref":"#"}`, which validation would follow forever.
+fn in_place_cycle(nodes : &[Compiled]) -> Option<&JsonPointer> {
+    let edges = |index : usize| -> Vec<(usize, &JsonPointer)> {
+        match &nodes[index] {
+            Compiled::Rules(rules) => rules.iter()
+                .flat_map(|rule| in_place(&rule.keyword).into_iter().map(move |to| (to, &rule.path)))
+                .collect(),
+            _ => Vec::new()
+        }
+    };
+    #[derive(PartialEq, Clone, Copy)]
+    enum Visit { New, Open, Done }
+    let mut visits = vec![Visit::New; nodes.len()];
+    for start in 0..nodes.len() {
+        if visits[start] != Visit::New {
+            continue
+        }
+        visits[start] = Visit::Open;
+        let mut stack = vec![(start, edges(start), 0)];
+        while let Some((node, out, next)) = stack.last_mut() {
+            let (to, path) = match out.get(*next) {
+                Some(edge) => *edge,
+                None => {
+                    visits[*node] = Visit::Done;
+                    stack.pop();
+                    continue
+                }
+            };
+            *next += 1;
+            match visits[to] {
+                Visit::Open => return Some(path),
+                Visit::New => {
+                    visits[to] = Visit::Open;
+                    stack.push((to, edges(to), 0))
+                },
+                Visit::Done => ()
+            }
+        }
+    }
+    None
+}
+
+fn invalid(at : &JsonPointer, message : &str) -> SchemaError {
+    SchemaError { schema_path: at.clone(), message: message.to_string() }
+}
+
+fn type_keyword(name : &str, at : &JsonPointer) -> Result<String, SchemaError> {
+    match name {
+        "null" | "boolean" | "number" | "integer" | "string" | "array" | "object" => Ok(name.to_string()),
+        _ => Err(invalid(at, &format!("unknown type \"{}\"", name)))
+    }
+}
+
+fn number(value : &JsonNode, at : &JsonPointer) -> Result<Number, SchemaError> {
+    match value {
+        JsonNode::Number(n) => Ok(*n),
+        _ => Err(invalid(at, "expected a number"))
+    }
+}
+
+fn count(value : &JsonNode, at : &JsonPointer) -> Result<usize, SchemaError> {
+    match number(value, at).map(Number::as_f64) {
+        Ok(n) if n >= 0.0 && n.fract() == 0.0 => Ok(n as usize),
+        _ => Err(invalid(at, "expected a non-negative integer"))
+    }
+}
+
+fn strings(value : &JsonNode, at : &JsonPointer) -> Result<Vec<String>, SchemaError> {
+    match value {
+        JsonNode::Array(items) => items.iter().map(|item| match item {
+            JsonNode::String(s) => Ok(s.clone()),
+            _ => Err(invalid(at, "expected an array of strings"))
+        }).collect(),
+        _ => Err(invalid(at, "expected an array of strings"))
+    }
+}
+
+fn regex(pattern : &str, at : &JsonPointer) -> Result<Regex, SchemaError> {
+    Regex::new(pattern).map_err(|message| invalid(at, &message))
+}
+
+// `$ref` fragments are URI-encoded JSON pointers
+pub(crate) fn percent_decode(fragment : &str) -> String {
+    let bytes = fragment.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = fragment.get(i + 1..i + 3).and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3
+            },
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn schema(text : &str) -> Schema {
+        Schema::compile(&JsonNode::from_str(text).unwrap()).unwrap()
+    }
+
+    fn paths(errors : &[ValidationError]) -> Vec<(String, String)> {
+        errors.iter().map(|e| (e.instance_path.to_string(), e.schema_path.to_string())).collect()
+    }
+
+    #[test]
+    fn test_reports_every_violation_with_paths() {
+        let config = schema("{\"type\":\"object\",\"required\":[\"name\",\"port\"],\"properties\":{\"name\":{\"type\":\"string\",\"minLength\":1},\"port\":{\"type\":\"integer\",\"maximum\":65535},\"tags\":{\"type\":\"array\",\"items\":{\"pattern\":\"^[a-z]+$\"},\"uniqueItems\":true}},\"additionalProperties\":false}");
+        assert!(config.validate(&JsonNode::from_str("{\"name\":\"web\",\"port\":80,\"tags\":[\"a\",\"b\"]}").unwrap()).is_ok());
+
+        let errors = config.validate(&JsonNode::from_str("{\"name\":\"\",\"port\":8e4,\"tags\":[\"ok\",\"No\",\"ok\"],\"extra\":null}").unwrap()).unwrap_err();
+        assert_eq!(paths(&errors), vec![
+            ("/extra".to_string(), "/additionalProperties".to_string()),
+            ("/name".to_string(), "/properties/name/minLength".to_string()),
+            ("/port".to_string(), "/properties/port/maximum".to_string()),
+            ("/tags/1".to_string(), "/properties/tags/items/pattern".to_string()),
+            ("/tags".to_string(), "/properties/tags/uniqueItems".to_string())
+        ]);
+
+        let errors = config.validate(&JsonNode::from_str("[]").unwrap()).unwrap_err();
+        assert_eq!(errors[0].message, "expected object, found array");
+    }
+
+    #[test]
+    fn test_combinators_and_conditionals() {
+        let s = schema("{\"if\":{\"required\":[\"kind\"],\"properties\":{\"kind\":{\"const\":\"circle\"}}},\"then\":{\"required\":[\"radius\"]},\"else\":{\"required\":[\"width\"]},\"oneOf\":[{\"type\":\"object\"},{\"type\":\"array\"}],\"not\":{\"required\":[\"forbidden\"]},\"dependencies\":{\"width\":[\"height\"]}}");
+        assert!(s.is_valid(&JsonNode::from_str("{\"kind\":\"circle\",\"radius\":1}").unwrap()));
+        assert!(s.is_valid(&JsonNode::from_str("{\"width\":1,\"height\":2}").unwrap()));
+        let errors = s.validate(&JsonNode::from_str("{\"kind\":\"circle\",\"width\":1,\"forbidden\":true}").unwrap()).unwrap_err();
+        assert_eq!(paths(&errors), vec![
+            ("".to_string(), "/dependencies".to_string()),
+            ("".to_string(), "/then/required".to_string()),
+            ("".to_string(), "/not".to_string())
+        ]);
+        assert!(!s.is_valid(&JsonNode::from_str("\"neither\"").unwrap()));
+    }
+
+    #[test]
+    fn test_recursive_references() {
+        let tree = schema("{\"definitions\":{\"node\":{\"type\":\"object\",\"properties\":{\"value\":{\"type\":\"number\"},\"children\":{\"type\":\"array\",\"items\":{\"$ref\":\"#/definitions/node\"}}}}},\"$ref\":\"#/definitions/node\"}");
+        assert!(tree.is_valid(&JsonNode::from_str("{\"value\":1,\"children\":[{\"value\":2,\"children\":[]}]}").unwrap()));
+        let errors = tree.validate(&JsonNode::from_str("{\"children\":[{\"value\":\"x\"}]}").unwrap()).unwrap_err();
+        assert_eq!(paths(&errors), vec![("/children/0/value".to_string(), "/definitions/node/properties/value/type".to_string())]);
+    }
+
+    #[test]
+    fn test_integer_limits_compare_exactly() {
+        let s = schema("{\"maximum\":9007199254740992,\"exclusiveMinimum\":-9007199254740992}");
+        assert!(s.is_valid(&JsonNode::from_str("9007199254740992").unwrap()));
+        assert!(!s.is_valid(&JsonNode::from_str("9007199254740993").unwrap()));
+        assert!(!s.is_valid(&JsonNode::from_str("-9007199254740992").unwrap()));
+        assert!(s.is_valid(&JsonNode::from_str("-9007199254740991").unwrap()));
+        assert!(s.is_valid(&JsonNode::from_str("1.5").unwrap()));
+
+        let s = schema("{\"multipleOf\":3}");
+        assert!(s.is_valid(&JsonNode::from_str("9007199254740993").unwrap()));
+        assert!(!s.is_valid(&JsonNode::from_str("9007199254740992").unwrap()));
+        assert!(s.is_valid(&JsonNode::from_str("6.0").unwrap()));
+        assert!(schema("{\"multipleOf\":0.1}").is_valid(&JsonNode::from_str("0.3").unwrap()));
+    }
+
+    #[test]
+    fn test_invalid_schemas_are_rejected() {
+        let error = Schema::compile(&JsonNode::from_str("{\"properties\":{\"a\":{\"minLength\":-1}}}").unwrap()).unwrap_err();
+        assert_eq!(error.schema_path.to_string(), "/properties/a/minLength");
+        assert!(Schema::compile(&JsonNode::from_str("{\"$ref\":\"#/definitions/missing\"}").unwrap()).is_err());
+        assert!(Schema::compile(&JsonNode::from_str("{\"pattern\":\"(\"}").unwrap()).is_err());
+        assert!(Schema::compile(&JsonNode::from_str("{\"type\":\"text\"}").unwrap()).is_err());
+        let error = Schema::compile(&JsonNode::from_str("{\"$ref\":\"#\"}").unwrap()).unwrap_err();
+        assert_eq!(error.schema_path.to_string(), "/$ref");
+        let cycle = "{\"definitions\":{\"a\":{\"anyOf\":[{\"type\":\"string\"},{\"$ref\":\"#/definitions/b\"}]},\"b\":{\"not\":{\"$ref\":\"#/definitions/a\"}}},\"$ref\":\"#/definitions/a\"}";
+        assert!(Schema::compile(&JsonNode::from_str(cycle).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_unsupported_2020_12_keywords_are_rejected() {
+        let compile = |text : &str| Schema::compile_with(&JsonNode::from_str(text).unwrap(), Dialect::Draft202012, &mut NoResolver);
+        let error = compile("{\"properties\":{\"a\":{}},\"unevaluatedProperties\":false}").unwrap_err();
+        assert_eq!(error.schema_path.to_string(), "/unevaluatedProperties");
+        let error = compile("{\"prefixItems\":[{}],\"unevaluatedItems\":{\"type\":\"string\"}}").unwrap_err();
+        assert_eq!(error.schema_path.to_string(), "/unevaluatedItems");
+        let error = compile("{\"$dynamicAnchor\":\"node\",\"items\":{\"$dynamicRef\":\"#node\"}}").unwrap_err();
+        assert_eq!(error.schema_path.to_string(), "/items/$dynamicRef");
+        assert!(compile("{\"unevaluatedProperties\":true,\"unevaluatedItems\":{}}").is_ok());
+        assert!(Schema::compile(&JsonNode::from_str("{\"unevaluatedProperties\":false}").unwrap()).is_ok());
+
+        let s = compile("{\"$defs\":{\"node\":{\"$anchor\":\"node\",\"type\":\"array\"}},\"items\":{\"$dynamicRef\":\"#node\"}}").unwrap();
+        assert!(s.is_valid(&JsonNode::from_str("[[]]").unwrap()));
+        assert!(!s.is_valid(&JsonNode::from_str("[1]").unwrap()));
+    }
+
+    #[test]
+    fn test_2020_12_defs_anchors_and_sibling_refs() {
+        let s = schema("{\"$schema\":\"https://json-schema.org/draft/2020-12/schema\",\"$defs\":{\"name\":{\"$anchor\":\"name\",\"type\":\"string\"}},\"type\":\"array\",\"prefixItems\":[{\"$ref\":\"#name\"},{\"$ref\":\"#/$defs/name\",\"maxLength\":2}],\"items\":{\"type\":\"number\"},\"contains\":{\"const\":0},\"maxContains\":1}");
+        assert!(s.is_valid(&JsonNode::from_str("[\"a\",\"bc\",1,0]").unwrap()));
+        let errors = s.validate(&JsonNode::from_str("[1,\"abc\",\"x\",0,0]").unwrap()).unwrap_err();
+        assert_eq!(paths(&errors), vec![
+            ("".to_string(), "/contains".to_string()),
+            ("/2".to_string(), "/items/type".to_string()),
+            ("/0".to_string(), "/$defs/name/type".to_string()),
+            ("/1".to_string(), "/prefixItems/1/maxLength".to_string())
+        ]);
+    }
+
+    #[test]
+    fn test_external_documents_through_a_resolver() {
+        let mut documents = HashMap::new();
+        documents.insert("https://example.com/common/types.json".to_string(),
+            JsonNode::from_str("{\"$defs\":{\"port\":{\"type\":\"integer\",\"maximum\":65535}}}").unwrap());
+        let root = JsonNode::from_str("{\"$id\":\"https://example.com/config/server.json\",\"properties\":{\"port\":{\"$ref\":\"../common/types.json#/$defs/port\"}}}").unwrap();
+        let s = Schema::compile_with(&root, Dialect::Draft202012, &mut documents).unwrap();
+        let errors = s.validate(&JsonNode::from_str("{\"port\":1e6}").unwrap()).unwrap_err();
+        assert_eq!(errors[0].schema_uri, "https://example.com/common/types.json");
+        assert_eq!(errors[0].schema_path.to_string(), "/$defs/port/maximum");
+
+        let error = Schema::compile_with(&root, Dialect::Draft202012, &mut NoResolver).unwrap_err();
+        assert_eq!(error.schema_path.to_string(), "/properties/port/$ref");
+    }
+
+    #[test]
+    fn test_file_resolver() {
+        let directory = std::env::temp_dir().join(format!("json_schema_test_{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("name.json"), "{\"type\":\"string\"}\n").unwrap();
+        let root = JsonNode::from_str("{\"items\":{\"$ref\":\"name.json\"}}").unwrap();
+        let s = Schema::compile_with(&root, Dialect::Draft202012, &mut FileResolver::new(&directory));
+        fs::remove_dir_all(&directory).unwrap();
+        let s = s.unwrap();
+        assert!(s.is_valid(&JsonNode::from_str("[\"a\"]").unwrap()));
+        assert!(!s.is_valid(&JsonNode::from_str("[1]").unwrap()));
+    }
+
+    #[test]
+    fn test_join_uri() {
+        assert_eq!(join_uri("https://example.com/a/b.json", "c.json#/x"), "https://example.com/a/c.json#/x");
+        assert_eq!(join_uri("https://example.com/a/b.json", "../c.json"), "https://example.com/c.json");
+        assert_eq!(join_uri("https://example.com/a/b.json", "/c.json"), "https://example.com/c.json");
+        assert_eq!(join_uri("https://example.com/a/b.json", "#foo"), "https://example.com/a/b.json#foo");
+        assert_eq!(join_uri("", "schemas/c.json"), "schemas/c.json");
+        assert_eq!(join_uri("schemas/a.json", "urn:example:c"), "urn:example:c");
+    }
+}
@@ -0,0 +1,123 @@
+// `serde` support, with the `serde` feature: a `JsonNode` serializes as the
+// value it holds and deserializes from any self-describing format.
+
+use std::fmt;
+use serde::de::{Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::ser::{Serialize, Serializer};
+use crate::json::{maybe_grow, JsonNode, Number, Object};
+
+impl Serialize for Number {
+    fn serialize<S : Serializer>(&self, serializer : S) -> Result<S::Ok, S::Error> {
+        match (self.as_u64(), self.as_i64()) {
+            (Some(u), _) => serializer.serialize_u64(u),
+            (None, Some(i)) => serializer.serialize_i64(i),
+            (None, None) => serializer.serialize_f64(self.as_f64())
+        }
+    }
+}
+
+impl Serialize for JsonNode {
+    fn serialize<S : Serializer>(&self, serializer : S) -> Result<S::Ok, S::Error> {
+        maybe_grow(|| match self {
+            JsonNode::Number(n) => n.serialize(serializer),
+            JsonNode::String(s) => serializer.serialize_str(s),
+            JsonNode::Array(a) => serializer.collect_seq(a),
+            JsonNode::Object(o) => serializer.collect_map(o),
+            JsonNode::Boolean(t) => serializer.serialize_bool(*t),
+            JsonNode::Null => serializer.serialize_unit()
+        })
+    }
+}
+
+struct NodeVisitor;
+
+impl<'de> Visitor<'de> for NodeVisitor {
+    type Value = JsonNode;
+
+    fn expecting(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("any JSON value")
+    }
+
+    fn visit_bool<E>(self, t : bool) -> Result<JsonNode, E> {
+        Ok(JsonNode::Boolean(t))
+    }
+
+    fn visit_i64<E>(self, i : i64) -> Result<JsonNode, E> {
+        Ok(JsonNode::Number(i.into()))
+    }
+
+    fn visit_u64<E>(self, u : u64) -> Result<JsonNode, E> {
+        Ok(JsonNode::Number(u.into()))
+    }
+
+    fn visit_f64<E>(self, f : f64) -> Result<JsonNode, E> {
+        Ok(JsonNode::Number(f.into()))
+    }
+
+    fn visit_str<E>(self, s : &str) -> Result<JsonNode, E> {
+        Ok(JsonNode::String(s.to_string()))
+    }
+
+    fn visit_string<E>(self, s : String) -> Result<JsonNode, E> {
+        Ok(JsonNode::String(s))
+    }
+
+    fn visit_unit<E>(self) -> Result<JsonNode, E> {
+        Ok(JsonNode::Null)
+    }
+
+    fn visit_none<E>(self) -> Result<JsonNode, E> {
+        Ok(JsonNode::Null)
+    }
+
+    fn visit_some<D : Deserializer<'de>>(self, deserializer : D) -> Result<JsonNode, D::Error> {
+        JsonNode::deserialize(deserializer)
+    }
+
+    fn visit_seq<A : SeqAccess<'de>>(self, mut seq : A) -> Result<JsonNode, A::Error> {
+        let mut items = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(item) = seq.next_element()? {
+            items.push(item)
+        }
+        Ok(JsonNode::Array(items))
+    }
+
+    fn visit_map<A : MapAccess<'de>>(self, mut map : A) -> Result<JsonNode, A::Error> {
+        let mut members = Object::default();
+        // the first of duplicate keys wins, as when parsing
+        while let Some((key, value)) = map.next_entry::<String, JsonNode>()? {
+            members.entry(key).or_insert(value);
+        }
+        Ok(JsonNode::Object(members))
+    }
+}
+
+impl<'de> Deserialize<'de> for JsonNode {
+    fn deserialize<D : Deserializer<'de>>(deserializer : D) -> Result<JsonNode, D::Error> {
+        maybe_grow(|| deserializer.deserialize_any(NodeVisitor))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::de::IntoDeserializer;
+    use serde::de::value::{Error, MapDeserializer, SeqDeserializer};
+    use super::*;
+    use std::str::FromStr;
+
+    fn from<'de, D : Deserializer<'de, Error = Error>>(deserializer : D) -> JsonNode {
+        JsonNode::deserialize(deserializer).unwrap()
+    }
+
+    #[test]
+    fn test_deserialize() {
+        assert_eq!(from(7u64.into_deserializer()), JsonNode::Number(7.into()));
+        assert_eq!(from((-7i64).into_deserializer()), JsonNode::Number((-7).into()));
+        assert_eq!(from(0.5f64.into_deserializer()), JsonNode::Number(0.5.into()));
+        assert_eq!(from("x".into_deserializer()), JsonNode::String("x".to_string()));
+        assert_eq!(from(().into_deserializer()), JsonNode::Null);
+        assert_eq!(from(SeqDeserializer::new(vec![true, false].into_iter())), JsonNode::from_str("[true,false]").unwrap());
+        let map = MapDeserializer::new(vec![("a", 1u32), ("b", 2), ("a", 3)].into_iter());
+        assert_eq!(from(map), JsonNode::from_str("{\"a\":1,\"b\":2}").unwrap());
+    }
+}
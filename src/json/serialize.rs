@@ -0,0 +1,573 @@
+use std::fmt::{self, Write};
+use std::io;
+use crate::json::{maybe_grow, JsonNode, Number, NumberFormat, SerializeOptions};
+use crate::json::canonical::ecmascript;
+use crate::json::scan;
+
+/// Writes documents as JSON text. `Display` for `JsonNode` is the default
+/// serializer: compact, with only the characters JSON requires escaped.
+/// `{:#}` formats like `Serializer::new().indent(INDENT)`.
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct Serializer {
+    options: SerializeOptions
+}
+
+/// Indentation of `JsonNode::to_string_pretty` and `{:#}`.
+pub const INDENT : &str = "  ";
+
+impl Serializer {
+    pub fn new() -> Serializer {
+        Serializer::default()
+    }
+
+    pub fn with_options(options : SerializeOptions) -> Serializer {
+        Serializer { options }
+    }
+
+    pub fn options(&self) -> &SerializeOptions {
+        &self.options
+    }
+
+    /// Escapes every character outside ASCII as `\uXXXX`, using a surrogate
+    /// pair beyond the Basic Multilingual Plane, for channels that are not
+    /// 8-bit clean.
+    pub fn ascii_only(mut self) -> Serializer {
+        self.options.escape_non_ascii = true;
+        self
+    }
+
+    /// Puts every array element and object member on a line of its own,
+    /// indented by `indent` once per level of nesting, with a space after
+    /// each colon.
+    pub fn indent(mut self, indent : &str) -> Serializer {
+        self.options.indent = Some(indent.to_string());
+        self
+    }
+
+    /// Writes object members in the byte order of their keys, whatever order
+    /// the object keeps them in, so that equal documents print alike.
+    pub fn sort_keys(mut self) -> Serializer {
+        self.options.sort_keys = true;
+        self
+    }
+
+    pub fn to_string(&self, node : &JsonNode) -> String {
+        let mut out = String::new();
+        self.write(node, &mut out).expect("writing to a String does not fail");
+        out
+    }
+
+    pub fn write<W : Write + ?Sized>(&self, node : &JsonNode, out : &mut W) -> fmt::Result {
+        self.write_at(node, out, 0)?;
+        if self.options.trailing_newline {
+            out.write_char('\n')?
+        }
+        Ok(())
+    }
+
+    /// Writes `node` to `out` as it is serialized, without building it up in
+    /// a `String` first. Many small writes go to `out`, so a file or socket
+    /// is best wrapped in an `io::BufWriter`.
+    pub fn write_to<W : io::Write + ?Sized>(&self, node : &JsonNode, out : &mut W) -> io::Result<()> {
+        write!(out, "{}", Formatted(self, node, 0))?;
+        if self.options.trailing_newline {
+            out.write_all(b"\n")?
+        }
+        Ok(())
+    }
+
+    fn write_at<W : Write + ?Sized>(&self, node : &JsonNode, out : &mut W, depth : usize) -> fmt::Result {
+        maybe_grow(|| match node {
+            JsonNode::Number(n) => write_number(*n, &self.options, out),
+            JsonNode::String(s) => write_string(s, &self.options, &mut |piece| out.write_str(piece)),
+            JsonNode::Array(a) => {
+                out.write_char('[')?;
+                for (i, item) in a.iter().enumerate() {
+                    self.separate(out, i, depth + 1)?;
+                    self.write_at(item, out, depth + 1)?
+                }
+                self.close(out, a.is_empty(), depth, ']')
+            },
+            JsonNode::Object(o) => {
+                out.write_char('{')?;
+                if self.options.sort_keys {
+                    let mut members : Vec<(&String, &JsonNode)> = o.iter().collect();
+                    members.sort_unstable_by(|a, b| a.0.cmp(b.0));
+                    self.write_members(members.into_iter(), out, depth)?
+                } else {
+                    self.write_members(o.iter(), out, depth)?
+                }
+                self.close(out, o.is_empty(), depth, '}')
+            },
+            JsonNode::Boolean(t) => out.write_str(if *t { "true" } else { "false" }),
+            JsonNode::Null => out.write_str("null")
+        })
+    }
+
+    fn write_members<'a, I, W>(&self, members : I, out : &mut W, depth : usize) -> fmt::Result
+        where I : Iterator<Item = (&'a String, &'a JsonNode)>, W : Write + ?Sized
+    {
+        for (i, (key, value)) in members.enumerate() {
+            self.separate(out, i, depth + 1)?;
+            write_string(key, &self.options, &mut |piece| out.write_str(piece))?;
+            out.write_str(if self.options.indent.is_some() { ": " } else { ":" })?;
+            self.write_at(value, out, depth + 1)?
+        }
+        Ok(())
+    }
+
+    // what goes before the `i`th item of a container
+    fn separate<W : Write + ?Sized>(&self, out : &mut W, i : usize, depth : usize) -> fmt::Result {
+        if i > 0 {
+            out.write_char(',')?
+        }
+        self.newline(out, depth)
+    }
+
+    fn close<W : Write + ?Sized>(&self, out : &mut W, empty : bool, depth : usize, bracket : char) -> fmt::Result {
+        if !empty {
+            self.newline(out, depth)?
+        }
+        out.write_char(bracket)
+    }
+
+    fn newline<W : Write + ?Sized>(&self, out : &mut W, depth : usize) -> fmt::Result {
+        if let Some(indent) = &self.options.indent {
+            out.write_char('\n')?;
+            for _ in 0..depth {
+                out.write_str(indent)?
+            }
+        }
+        Ok(())
+    }
+}
+
+// `Display` through a serializer, nested `depth` levels deep, for
+// formatting straight into an `io::Write`
+struct Formatted<'a>(&'a Serializer, &'a JsonNode, usize);
+
+impl<'a> fmt::Display for Formatted<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.write_at(self.1, f, self.2)
+    }
+}
+
+#[derive(Debug)]
+pub enum WriterError {
+    Io(io::Error),
+    /// A `JsonWriter` call that would not make valid JSON where it was made.
+    Misplaced { call: &'static str, expected: &'static str }
+}
+
+impl fmt::Display for WriterError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WriterError::Io(reason) => write!(f, "writing output failed: {}", reason),
+            WriterError::Misplaced { call, expected } => write!(f, "{} called where {} was expected", call, expected)
+        }
+    }
+}
+
+// a container a `JsonWriter` has begun and not yet ended
+struct OpenContainer {
+    object: bool,
+    items: usize,
+    // whether an object has a key waiting for its value
+    keyed: bool
+}
+
+/// Writes one document piece by piece as it is produced, placing commas and
+/// indentation itself, so that no `JsonNode` tree needs to be built. Calls
+/// that would not make valid JSON fail with `WriterError::Misplaced`
+/// without writing anything.
+pub struct JsonWriter<W : io::Write> {
+    out: W,
+    serializer: Serializer,
+    open: Vec<OpenContainer>,
+    done: bool
+}
+
+impl<W : io::Write> JsonWriter<W> {
+    /// A writer of compact JSON, like `Display` for `JsonNode`.
+    pub fn new(out : W) -> JsonWriter<W> {
+        JsonWriter::with_serializer(out, Serializer::new())
+    }
+
+    /// A writer formatting as `serializer` does.
+    pub fn with_serializer(out : W, serializer : Serializer) -> JsonWriter<W> {
+        JsonWriter { out, serializer, open: Vec::new(), done: false }
+    }
+
+    pub fn begin_object(&mut self) -> Result<(), WriterError> {
+        self.begin(true, "begin_object")
+    }
+
+    pub fn end_object(&mut self) -> Result<(), WriterError> {
+        self.end(true, "end_object")
+    }
+
+    pub fn begin_array(&mut self) -> Result<(), WriterError> {
+        self.begin(false, "begin_array")
+    }
+
+    pub fn end_array(&mut self) -> Result<(), WriterError> {
+        self.end(false, "end_array")
+    }
+
+    /// Names the next member of the innermost object, whose value comes next.
+    pub fn key(&mut self, key : &str) -> Result<(), WriterError> {
+        match self.open.last() {
+            Some(top) if top.object && !top.keyed => (),
+            _ => return Err(self.misplaced("key"))
+        }
+        self.separate()?;
+        let out = &mut self.out;
+        write_string(key, &self.serializer.options, &mut |piece| out.write_all(piece.as_bytes()))?;
+        self.out.write_all(if self.serializer.options.indent.is_some() { b": " } else { b":" })?;
+        if let Some(top) = self.open.last_mut() {
+            top.keyed = true
+        }
+        Ok(())
+    }
+
+    /// Writes a whole value, which may be a container built beforehand.
+    pub fn value(&mut self, value : &JsonNode) -> Result<(), WriterError> {
+        self.before_value("value")?;
+        write!(self.out, "{}", Formatted(&self.serializer, value, self.open.len()))?;
+        self.after_value();
+        Ok(())
+    }
+
+    /// Hands back the output once the document is complete, after a line
+    /// break if the serializer ends documents with one.
+    pub fn finish(mut self) -> Result<W, WriterError> {
+        if !self.done {
+            return Err(self.misplaced("finish"))
+        }
+        if self.serializer.options.trailing_newline {
+            self.out.write_all(b"\n")?
+        }
+        self.out.flush()?;
+        Ok(self.out)
+    }
+
+    fn begin(&mut self, object : bool, call : &'static str) -> Result<(), WriterError> {
+        self.before_value(call)?;
+        self.out.write_all(if object { b"{" } else { b"[" })?;
+        self.open.push(OpenContainer { object, items: 0, keyed: false });
+        Ok(())
+    }
+
+    fn end(&mut self, object : bool, call : &'static str) -> Result<(), WriterError> {
+        match self.open.last() {
+            Some(top) if top.object == object && !top.keyed => (),
+            _ => return Err(self.misplaced(call))
+        }
+        let items = self.open.pop().map_or(0, |top| top.items);
+        if items > 0 {
+            self.newline()?
+        }
+        self.out.write_all(if object { b"}" } else { b"]" })?;
+        self.after_value();
+        Ok(())
+    }
+
+    fn before_value(&mut self, call : &'static str) -> Result<(), WriterError> {
+        match self.open.last() {
+            None if !self.done => Ok(()),
+            Some(top) if top.object && top.keyed => Ok(()),
+            Some(top) if !top.object => self.separate(),
+            _ => Err(self.misplaced(call))
+        }
+    }
+
+    fn after_value(&mut self) {
+        match self.open.last_mut() {
+            Some(top) => {
+                top.items += 1;
+                top.keyed = false
+            },
+            None => self.done = true
+        }
+    }
+
+    // what goes before the next item of the innermost container
+    fn separate(&mut self) -> Result<(), WriterError> {
+        if self.open.last().is_some_and(|top| top.items > 0) {
+            self.out.write_all(b",")?
+        }
+        self.newline()
+    }
+
+    fn newline(&mut self) -> Result<(), WriterError> {
+        if let Some(indent) = &self.serializer.options.indent {
+            self.out.write_all(b"\n")?;
+            for _ in 0..self.open.len() {
+                self.out.write_all(indent.as_bytes())?
+            }
+        }
+        Ok(())
+    }
+
+    fn misplaced(&self, call : &'static str) -> WriterError {
+        let expected = match self.open.last() {
+            None if self.done => "finish",
+            None => "a value",
+            Some(top) if top.object && top.keyed => "a value",
+            Some(top) if top.object => "a key or end_object",
+            Some(_) => "a value or end_array"
+        };
+        WriterError::Misplaced { call, expected }
+    }
+}
+
+impl From<io::Error> for WriterError {
+    fn from(reason : io::Error) -> WriterError {
+        WriterError::Io(reason)
+    }
+}
+
+// integers and decimals exactly, floats as the options say
+fn write_number<W : Write + ?Sized>(n : Number, options : &SerializeOptions, out : &mut W) -> fmt::Result {
+    let f = n.as_f64();
+    if !n.is_f64() || n.is_decimal() {
+        return write!(out, "{}", n)
+    }
+    if options.whole_floats_as_integers && f.fract() == 0.0 && f.abs() < 1e21 {
+        return write!(out, "{:.0}", f)
+    }
+    match options.number_format {
+        NumberFormat::Plain if f.is_finite() => write!(out, "{}", n),
+        NumberFormat::Shortest => match ecmascript(n) {
+            Some(shortest) => out.write_str(&shortest),
+            None => out.write_str(non_finite(f, options))
+        },
+        NumberFormat::Fixed(digits) if f.is_finite() => write!(out, "{:.*}", digits, f),
+        NumberFormat::Plain | NumberFormat::Fixed(_) => out.write_str(non_finite(f, options))
+    }
+}
+
+// NaN or an infinity, which only JSON5 can write
+fn non_finite(f : f64, options : &SerializeOptions) -> &'static str {
+    match f {
+        _ if !options.non_finite_numbers => "null",
+        f if f.is_nan() => "NaN",
+        f if f > 0.0 => "Infinity",
+        _ => "-Infinity"
+    }
+}
+
+/// Hands `s` to `emit` quoted and escaped as `options` say, in as few
+/// pieces as it can: runs that need no escaping go out whole.
+pub(crate) fn write_string<E, F>(s : &str, options : &SerializeOptions, emit : &mut F) -> Result<(), E>
+    where F : FnMut(&str) -> Result<(), E>
+{
+    let bytes = s.as_bytes();
+    emit("\"")?;
+    let mut start = 0;
+    while start < bytes.len() {
+        let end = scan::escape_position(bytes, start);
+        if options.escape_non_ascii || options.escape_forward_slash {
+            write_run(&s[start..end], options, emit)?
+        } else {
+            emit(&s[start..end])?
+        }
+        match bytes.get(end) {
+            Some(b'"') => emit("\\\"")?,
+            Some(b'\\') => emit("\\\\")?,
+            Some(b'\n') => emit("\\n")?,
+            Some(b'\r') => emit("\\r")?,
+            Some(b'\t') => emit("\\t")?,
+            Some(byte) => emit(&format!("\\u{:04x}", byte))?,
+            None => ()
+        }
+        start = end + 1
+    }
+    emit("\"")
+}
+
+// a run that JSON lets stand as it is, with the optional escapes
+fn write_run<E, F>(run : &str, options : &SerializeOptions, emit : &mut F) -> Result<(), E>
+    where F : FnMut(&str) -> Result<(), E>
+{
+    let mut rest = run;
+    let escaped = |c : char| c == '/' && options.escape_forward_slash || !c.is_ascii() && options.escape_non_ascii;
+    while let Some(i) = rest.find(escaped) {
+        emit(&rest[..i])?;
+        let c = rest[i..].chars().next().expect("found above");
+        if c == '/' {
+            emit("\\/")?
+        } else {
+            for unit in c.encode_utf16(&mut [0; 2]) {
+                emit(&format!("\\u{:04x}", unit))?
+            }
+        }
+        rest = &rest[i + c.len_utf8()..]
+    }
+    emit(rest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_escapes_round_trip() {
+        let text = "say \"hi\"\\\n\r\t\u{1}\u{1f} / é 𝄞";
+        let node = JsonNode::Array(vec![JsonNode::String(text.to_string())]);
+        assert_eq!(node.to_string(), "[\"say \\\"hi\\\"\\\\\\n\\r\\t\\u0001\\u001f / é 𝄞\"]");
+        assert_eq!(JsonNode::from_str(&node.to_string()).unwrap(), node);
+
+        let mut object = crate::json::Object::default();
+        object.insert("a\"b".to_string(), JsonNode::Null);
+        assert_eq!(JsonNode::Object(object).to_string(), "{\"a\\\"b\":null}");
+    }
+
+    #[test]
+    fn test_indent() {
+        let node = JsonNode::from_str("[1,{\"a\":[],\"b\":{}},[[\"x\"]]]").unwrap();
+        let pretty = "[\n  1,\n  {\n    \"a\": [],\n    \"b\": {}\n  },\n  [\n    [\n      \"x\"\n    ]\n  ]\n]";
+        let parse = |text : &str| JsonNode::from_str(text).unwrap();
+        // members may come out in either order
+        assert_eq!(parse(&node.to_string_pretty()), node);
+        assert_eq!(node.to_string_pretty().len(), pretty.len());
+        assert_eq!(format!("{:#}", JsonNode::from_str("[[1],{}]").unwrap()), "[\n  [\n    1\n  ],\n  {}\n]");
+        assert_eq!(Serializer::new().indent("\t").to_string(&parse("{\"k\":[true]}")), "{\n\t\"k\": [\n\t\ttrue\n\t]\n}");
+        assert_eq!(parse("[\n  1\n]").to_string_pretty(), "[\n  1\n]");
+    }
+
+    #[test]
+    #[cfg(feature = "preserve_order")]
+    fn test_preserves_member_order() {
+        let text = "{\"z\":1,\"a\":{\"y\":[],\"b\":null,\"x\":2},\"m\":true,\"z\":3,\"k1\":0,\"k2\":0,\"k3\":0,\"k4\":0,\"k5\":0,\"k6\":0}";
+        let node = JsonNode::from_str(text).unwrap();
+        assert_eq!(node.to_string(), text.replace(",\"z\":3", ""));
+    }
+
+    #[test]
+    fn test_sort_keys() {
+        let node = JsonNode::from_str("{\"b\":{\"z\":1,\"y\":[{\"d\":0,\"c\":0}]},\"a\":null,\"B\":true}").unwrap();
+        assert_eq!(Serializer::new().sort_keys().to_string(&node), "{\"B\":true,\"a\":null,\"b\":{\"y\":[{\"c\":0,\"d\":0}],\"z\":1}}");
+    }
+
+    #[test]
+    fn test_ascii_only() {
+        let node = JsonNode::String("é 𝄞 \"".to_string());
+        let ascii = Serializer::new().ascii_only().to_string(&node);
+        assert_eq!(ascii, "\"\\u00e9 \\ud834\\udd1e \\\"\"");
+        assert!(ascii.is_ascii());
+        assert_eq!(Serializer::new().to_string(&node), node.to_string());
+    }
+
+    #[test]
+    fn test_serialize_options() {
+        let node = JsonNode::from_str("{\"b\":\"</é>\",\"a\":[]}").unwrap();
+        let options = SerializeOptions::new().sort_keys(true).escape_forward_slash(true);
+        assert_eq!(node.to_string_with(&options), "{\"a\":[],\"b\":\"<\\/é>\"}");
+        let options = options.escape_non_ascii(true).trailing_newline(true).indent(" ");
+        assert_eq!(node.to_string_with(&options), "{\n \"a\": [],\n \"b\": \"<\\/\\u00e9>\"\n}\n");
+        let mut out = Vec::new();
+        Serializer::with_options(options.clone()).write_to(&node, &mut out).unwrap();
+        assert_eq!(out, node.to_string_with(&options).into_bytes());
+        let mut writer = JsonWriter::with_serializer(Vec::new(), Serializer::with_options(options));
+        writer.value(&JsonNode::from("/")).unwrap();
+        assert_eq!(writer.finish().unwrap(), b"\"\\/\"\n");
+    }
+
+    #[test]
+    fn test_number_format() {
+        let node = JsonNode::from_str("[1e300,0.30000000000000004,2.0,-7,1.5e-7,18446744073709551615]").unwrap();
+        let write = |options : SerializeOptions| node.to_string_with(&options);
+        assert_eq!(write(SerializeOptions::new()), node.to_string());
+        assert_eq!(write(SerializeOptions::new().number_format(NumberFormat::Shortest)),
+            "[1e+300,0.30000000000000004,2,-7,1.5e-7,18446744073709551615]");
+        let fixed = SerializeOptions::new().number_format(NumberFormat::Fixed(2));
+        assert_eq!(write(fixed.clone()).split(',').skip(1).collect::<Vec<_>>(), ["0.30", "2.00", "-7", "0.00", "18446744073709551615]"]);
+        assert_eq!(write(fixed.whole_floats_as_integers(true)).split(',').skip(1).collect::<Vec<_>>(), ["0.30", "2", "-7", "0.00", "18446744073709551615]"]);
+    }
+
+    #[test]
+    fn test_non_finite_numbers() {
+        let text = "[NaN,Infinity,-Infinity,1]";
+        let node = JsonNode::from_str_with(text, crate::json::ParserOptions::json5()).unwrap();
+        assert_eq!(node.to_string(), "[null,null,null,1]");
+        let json5 = node.to_string_with(&SerializeOptions::new().non_finite_numbers(true));
+        assert_eq!(json5, text);
+        assert_eq!(JsonNode::from_str_with(&json5, crate::json::ParserOptions::json5()).unwrap()[2], node[2]);
+    }
+
+    #[test]
+    fn test_non_finite_numbers_in_every_format() {
+        let node = JsonNode::Array(vec![f64::NAN.into(), f64::INFINITY.into(), f64::NEG_INFINITY.into()]);
+        for format in &[NumberFormat::Plain, NumberFormat::Shortest, NumberFormat::Fixed(2)] {
+            for whole in &[false, true] {
+                let options = SerializeOptions::new().number_format(*format).whole_floats_as_integers(*whole);
+                assert_eq!(node.to_string_with(&options), "[null,null,null]", "{:?}", format);
+                assert_eq!(node.to_string_with(&options.non_finite_numbers(true)), "[NaN,Infinity,-Infinity]", "{:?}", format);
+            }
+        }
+    }
+
+    #[test]
+    fn test_write_to() {
+        let node = JsonNode::from_str("{\"a\":[1,\"é\"]}").unwrap();
+        let mut out = Vec::new();
+        node.write_to(&mut out).unwrap();
+        assert_eq!(out, node.to_string().into_bytes());
+        let mut out = Vec::new();
+        node.write_pretty_to(&mut out).unwrap();
+        assert_eq!(out, node.to_string_pretty().into_bytes());
+        let mut out = Vec::new();
+        Serializer::new().ascii_only().write_to(&node, &mut out).unwrap();
+        assert_eq!(out, b"{\"a\":[1,\"\\u00e9\"]}");
+
+        let mut full = [0u8; 4];
+        let error = node.write_to(&mut &mut full[..]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::WriteZero);
+    }
+
+    #[test]
+    fn test_json_writer() {
+        let mut writer = JsonWriter::new(Vec::new());
+        writer.begin_object().unwrap();
+        writer.key("a").unwrap();
+        writer.begin_array().unwrap();
+        writer.value(&JsonNode::from(1)).unwrap();
+        writer.value(&JsonNode::from_str("{\"b\":null}").unwrap()).unwrap();
+        writer.begin_array().unwrap();
+        writer.end_array().unwrap();
+        writer.end_array().unwrap();
+        writer.key("s\"").unwrap();
+        writer.value(&JsonNode::from("x")).unwrap();
+        writer.end_object().unwrap();
+        assert_eq!(writer.finish().unwrap(), b"{\"a\":[1,{\"b\":null},[]],\"s\\\"\":\"x\"}");
+
+        let node = JsonNode::from_str("{\"k\":[true,{\"m\":[]}]}").unwrap();
+        let mut writer = JsonWriter::with_serializer(Vec::new(), Serializer::new().indent(INDENT));
+        writer.begin_object().unwrap();
+        writer.key("k").unwrap();
+        writer.begin_array().unwrap();
+        writer.value(&JsonNode::Boolean(true)).unwrap();
+        writer.value(&node["k"][1]).unwrap();
+        writer.end_array().unwrap();
+        writer.end_object().unwrap();
+        assert_eq!(String::from_utf8(writer.finish().unwrap()).unwrap(), node.to_string_pretty());
+    }
+
+    #[test]
+    fn test_json_writer_misuse() {
+        let mut writer = JsonWriter::new(Vec::new());
+        assert!(matches!(writer.key("a"), Err(WriterError::Misplaced { call: "key", expected: "a value" })));
+        writer.begin_object().unwrap();
+        assert_eq!(writer.value(&JsonNode::Null).unwrap_err().to_string(), "value called where a key or end_object was expected");
+        assert!(writer.end_array().is_err());
+        writer.key("a").unwrap();
+        assert!(writer.end_object().is_err());
+        writer.value(&JsonNode::Null).unwrap();
+        writer.end_object().unwrap();
+        assert!(matches!(writer.begin_array(), Err(WriterError::Misplaced { expected: "finish", .. })));
+        assert_eq!(writer.finish().unwrap(), b"{\"a\":null}");
+        assert!(JsonWriter::new(Vec::new()).finish().is_err());
+    }
+}
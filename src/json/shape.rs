@@ -0,0 +1,139 @@
+use std::fmt;
+use crate::json::{maybe_grow, JsonNode};
+use crate::json::pointer::JsonPointer;
+
+/// A pattern string matching any value.
+pub const ANY : &str = "<any>";
+/// As an object member name, allows members the pattern does not list; as
+/// the last element of an array pattern, repeats the element before it.
+pub const MORE : &str = "...";
+
+#[derive(PartialEq, Debug)]
+pub struct ShapeMismatch {
+    pub path: JsonPointer,
+    pub expected: String,
+    pub found: String
+}
+
+impl fmt::Display for ShapeMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let path = if self.path.is_root() { "(root)".to_string() } else { self.path.to_string() };
+        write!(f, "at {}: expected {}, found {}", path, self.expected, self.found)
+    }
+}
+
+/// Checks `node` against `pattern`, which is an example document where the
+/// strings `"<string>"`, `"<number>"`, `"<boolean>"`, `"<null>"`,
+/// `"<array>"`, `"<object>"` and `ANY` stand for any value of that type.
+/// Objects must have exactly the members of the pattern unless it has a
+/// `MORE` member, and arrays exactly its elements unless it ends with `MORE`.
+/// Other values must be equal. Reports every mismatch, not just the first.
+pub fn assert_shape(node : &JsonNode, pattern : &JsonNode) -> Result<(), Vec<ShapeMismatch>> {
+    let mut mismatches = Vec::new();
+    check(node, pattern, &mut JsonPointer::root(), &mut mismatches);
+    if mismatches.is_empty() { Ok(()) } else { Err(mismatches) }
+}
+
+fn type_name(node : &JsonNode) -> &'static str {
+    match node {
+        JsonNode::Null => "null",
+        JsonNode::Boolean(_) => "boolean",
+        JsonNode::Number(_) => "number",
+        JsonNode::String(_) => "string",
+        JsonNode::Array(_) => "array",
+        JsonNode::Object(_) => "object"
+    }
+}
+
+fn check(node : &JsonNode, pattern : &JsonNode, path : &mut JsonPointer, out : &mut Vec<ShapeMismatch>) {
+    maybe_grow(|| {
+        let mut mismatch = |path : &JsonPointer, expected : String, found : String| out.push(ShapeMismatch { path: path.clone(), expected, found });
+        match (pattern, node) {
+            (JsonNode::String(placeholder), _) if placeholder == ANY => (),
+            (JsonNode::String(placeholder), _) if placeholder.len() > 2 && placeholder.starts_with('<') && placeholder.ends_with('>')
+                && ["string", "number", "boolean", "null", "array", "object"].contains(&&placeholder[1..placeholder.len() - 1]) => {
+                let expected = &placeholder[1..placeholder.len() - 1];
+                if expected != type_name(node) {
+                    mismatch(path, expected.to_string(), type_name(node).to_string())
+                }
+            },
+            (JsonNode::Object(expected), JsonNode::Object(actual)) => {
+                let mut keys : Vec<&String> = expected.keys().filter(|key| *key != MORE).collect();
+                keys.sort();
+                for key in keys {
+                    path.push(key.as_str());
+                    match actual.get(key) {
+                        Some(value) => check(value, &expected[key], path, out),
+                        None => out.push(ShapeMismatch { path: path.clone(), expected: "a member".to_string(), found: "nothing".to_string() })
+                    }
+                    path.pop();
+                }
+                if !expected.contains_key(MORE) {
+                    let mut extra : Vec<&String> = actual.keys().filter(|key| !expected.contains_key(*key)).collect();
+                    extra.sort();
+                    for key in extra {
+                        out.push(ShapeMismatch { path: path.child(key.as_str()), expected: "no member".to_string(), found: type_name(&actual[key]).to_string() })
+                    }
+                }
+            },
+            (JsonNode::Array(expected), JsonNode::Array(actual)) => {
+                let repeat = expected.len() > 1 && expected.last() == Some(&JsonNode::String(MORE.to_string()));
+                let fixed = if repeat { &expected[..expected.len() - 2] } else { &expected[..] };
+                let too_short = actual.len() < fixed.len();
+                let too_long = !repeat && actual.len() > fixed.len();
+                if too_short || too_long {
+                    let expected = if repeat { format!("at least {} elements", fixed.len()) } else { format!("{} elements", fixed.len()) };
+                    mismatch(path, expected, format!("{}", actual.len()))
+                }
+                for (i, value) in actual.iter().enumerate() {
+                    let element = match fixed.get(i) {
+                        Some(element) => element,
+                        None if repeat => &expected[expected.len() - 2],
+                        None => break
+                    };
+                    path.push(i.to_string());
+                    check(value, element, path, out);
+                    path.pop();
+                }
+            },
+            (JsonNode::Object(_), _) | (JsonNode::Array(_), _) => mismatch(path, type_name(pattern).to_string(), type_name(node).to_string()),
+            _ if pattern != node => mismatch(path, pattern.to_string(), node.to_string()),
+            _ => ()
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    const PATTERN : &str = "{\"id\":\"<number>\",\"kind\":\"user\",\"tags\":[\"<string>\",\"...\"],\"meta\":{\"...\":0},\"pair\":[\"<any>\",\"<null>\"]}";
+
+    #[test]
+    fn test_matching_shapes() {
+        let pattern = JsonNode::from_str(PATTERN).unwrap();
+        let node = JsonNode::from_str("{\"id\":1,\"kind\":\"user\",\"tags\":[\"a\",\"b\"],\"meta\":{\"x\":[]},\"pair\":[{},null]}").unwrap();
+        assert_eq!(assert_shape(&node, &pattern), Ok(()));
+        let node = JsonNode::from_str("{\"id\":1,\"kind\":\"user\",\"tags\":[],\"meta\":{},\"pair\":[1,null]}").unwrap();
+        assert_eq!(assert_shape(&node, &pattern), Ok(()));
+        assert_eq!(assert_shape(&JsonNode::Boolean(true), &JsonNode::from_str("\"<any>\"").unwrap()), Ok(()));
+    }
+
+    #[test]
+    fn test_reports_every_mismatch() {
+        let pattern = JsonNode::from_str(PATTERN).unwrap();
+        let node = JsonNode::from_str("{\"id\":\"1\",\"kind\":\"admin\",\"tags\":[\"a\",2],\"meta\":[],\"pair\":[1],\"extra\":true}").unwrap();
+        let messages : Vec<String> = assert_shape(&node, &pattern).unwrap_err().iter().map(|m| m.to_string()).collect();
+        assert_eq!(messages, vec![
+            "at /id: expected number, found string",
+            "at /kind: expected \"user\", found \"admin\"",
+            "at /meta: expected object, found array",
+            "at /pair: expected 2 elements, found 1",
+            "at /tags/1: expected string, found number",
+            "at /extra: expected no member, found boolean"
+        ]);
+        let error = assert_shape(&JsonNode::Null, &JsonNode::from_str("{\"a\":1}").unwrap()).unwrap_err();
+        assert_eq!(error[0].to_string(), "at (root): expected object, found null");
+    }
+}
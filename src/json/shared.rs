@@ -0,0 +1,295 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::Read;
+use std::sync::Arc;
+use crate::json::{maybe_grow, JsonNode, Number};
+use crate::json::events::{EventError, EventReader, JsonEvent};
+
+/// Persistent variant of `JsonNode` whose strings and containers live behind `Arc`s.
+/// Cloning is O(1) and mutation copies only the containers on the path being edited,
+/// so snapshots of a large document share every untouched subtree. Object keys are
+/// interned, so tabular data repeating the same keys stores each of them once.
+/// Like `JsonNode`, it drops without recursion and compares and debug prints
+/// through `grow_stack`.
+#[derive(Clone)]
+pub enum SharedNode {
+    Number(Number),
+    String(Arc<str>),
+    Array(Arc<Vec<SharedNode>>),
+    Object(Arc<HashMap<Arc<str>, SharedNode>>),
+    Boolean(bool),
+    Null
+}
+
+impl SharedNode {
+    // moves the members of a container no other node shares, and which holds
+    // containers, into `pending`
+    fn take_nested(&mut self, pending : &mut Vec<SharedNode>) {
+        let nested = |node : &SharedNode| matches!(node, SharedNode::Array(_) | SharedNode::Object(_));
+        match self {
+            SharedNode::Array(a) => if let Some(a) = Arc::get_mut(a).filter(|a| a.iter().any(nested)) {
+                pending.append(a)
+            },
+            SharedNode::Object(o) => if let Some(o) = Arc::get_mut(o).filter(|o| o.values().any(nested)) {
+                pending.extend(o.drain().map(|(_, value)| value))
+            },
+            _ => ()
+        }
+    }
+}
+
+impl Drop for SharedNode {
+    fn drop(&mut self) {
+        let mut pending = Vec::new();
+        self.take_nested(&mut pending);
+        while let Some(mut node) = pending.pop() {
+            node.take_nested(&mut pending)
+        }
+    }
+}
+
+impl PartialEq for SharedNode {
+    fn eq(&self, other : &SharedNode) -> bool {
+        maybe_grow(|| match (self, other) {
+            (SharedNode::Number(a), SharedNode::Number(b)) => a == b,
+            (SharedNode::String(a), SharedNode::String(b)) => a == b,
+            (SharedNode::Array(a), SharedNode::Array(b)) => a == b,
+            (SharedNode::Object(a), SharedNode::Object(b)) => a == b,
+            (SharedNode::Boolean(a), SharedNode::Boolean(b)) => a == b,
+            (SharedNode::Null, SharedNode::Null) => true,
+            _ => false
+        })
+    }
+}
+
+impl fmt::Debug for SharedNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        maybe_grow(|| match self {
+            SharedNode::Number(n) => f.debug_tuple("Number").field(n).finish(),
+            SharedNode::String(s) => f.debug_tuple("String").field(s).finish(),
+            SharedNode::Array(a) => f.debug_tuple("Array").field(a).finish(),
+            SharedNode::Object(o) => f.debug_tuple("Object").field(o).finish(),
+            SharedNode::Boolean(b) => f.debug_tuple("Boolean").field(b).finish(),
+            SharedNode::Null => f.write_str("Null")
+        })
+    }
+}
+
+/// Hands out one shared allocation per distinct object key.
+#[derive(Debug, Clone, Default)]
+pub struct KeyTable {
+    keys: HashSet<Arc<str>>
+}
+
+impl KeyTable {
+    pub fn new() -> KeyTable {
+        KeyTable::default()
+    }
+
+    pub fn intern(&mut self, key : &str) -> Arc<str> {
+        match self.keys.get(key) {
+            Some(shared) => shared.clone(),
+            None => {
+                let shared : Arc<str> = Arc::from(key);
+                self.keys.insert(shared.clone());
+                shared
+            }
+        }
+    }
+
+    /// How many distinct keys have been interned.
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+}
+
+impl From<JsonNode> for SharedNode {
+    fn from(node : JsonNode) -> SharedNode {
+        SharedNode::from_json(node, &mut KeyTable::new())
+    }
+}
+
+impl From<&SharedNode> for JsonNode {
+    fn from(node : &SharedNode) -> JsonNode {
+        maybe_grow(|| match node {
+            SharedNode::Number(n) => JsonNode::Number(*n),
+            SharedNode::String(s) => JsonNode::String(s.to_string()),
+            SharedNode::Array(a) => JsonNode::Array(a.iter().map(JsonNode::from).collect()),
+            SharedNode::Object(o) => JsonNode::Object(
+                o.iter().map(|(k, v)| (k.to_string(), JsonNode::from(v))).collect()
+            ),
+            SharedNode::Boolean(t) => JsonNode::Boolean(*t),
+            SharedNode::Null => JsonNode::Null
+        })
+    }
+}
+
+impl SharedNode {
+    /// Converts a tree, sharing its keys with everything else `keys` interned.
+    pub fn from_json(mut node : JsonNode, keys : &mut KeyTable) -> SharedNode {
+        maybe_grow(|| match &mut node {
+            JsonNode::Number(n) => SharedNode::Number(*n),
+            JsonNode::String(s) => SharedNode::String(Arc::from(std::mem::take(s))),
+            JsonNode::Array(a) => SharedNode::Array(Arc::new(std::mem::take(a).into_iter().map(|v| SharedNode::from_json(v, keys)).collect())),
+            JsonNode::Object(o) => SharedNode::Object(Arc::new(
+                std::mem::take(o).into_iter().map(|(k, v)| (keys.intern(&k), SharedNode::from_json(v, keys))).collect()
+            )),
+            JsonNode::Boolean(t) => SharedNode::Boolean(*t),
+            JsonNode::Null => SharedNode::Null
+        })
+    }
+
+    /// Parses one document straight into shared form, never building a
+    /// `JsonNode`; reusing `keys` across documents shares keys between them.
+    pub fn read<R : Read>(source : R, keys : &mut KeyTable) -> Result<Option<SharedNode>, EventError> {
+        let mut reader = EventReader::new(source);
+        let mut open : Vec<(SharedNode, Option<Arc<str>>)> = Vec::new();
+        while let Some(event) = reader.next_event()? {
+            let node = match event {
+                JsonEvent::StartArray => { open.push((SharedNode::Array(Arc::new(Vec::new())), None)); None },
+                JsonEvent::StartObject => { open.push((SharedNode::Object(Arc::new(HashMap::new())), None)); None },
+                JsonEvent::Key(key) => {
+                    if let Some(top) = open.last_mut() { top.1 = Some(keys.intern(&key)) }
+                    None
+                },
+                JsonEvent::EndArray | JsonEvent::EndObject => open.pop().map(|(node, _)| node),
+                JsonEvent::String(s) => Some(SharedNode::String(Arc::from(s))),
+                JsonEvent::Number(n) => Some(SharedNode::Number(n)),
+                JsonEvent::Boolean(t) => Some(SharedNode::Boolean(t)),
+                JsonEvent::Null => Some(SharedNode::Null)
+            };
+
+            if let Some(node) = node {
+                match open.last_mut() {
+                    None => return Ok(Some(node)),
+                    Some((SharedNode::Array(a), _)) => Arc::make_mut(a).push(node),
+                    // of members with the same name the first wins, as in `JsonNode`
+                    Some((SharedNode::Object(o), key)) => { Arc::make_mut(o).entry(key.take().unwrap_or_else(|| keys.intern(""))).or_insert(node); },
+                    Some(_) => unreachable!("only containers are kept open")
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn get(&self, key : &str) -> Option<&SharedNode> {
+        match self {
+            SharedNode::Object(o) => o.get(key),
+            _ => None
+        }
+    }
+
+    pub fn get_index(&self, index : usize) -> Option<&SharedNode> {
+        match self {
+            SharedNode::Array(a) => a.get(index),
+            _ => None
+        }
+    }
+
+    /// Mutable access to an object member, unsharing this object first if needed.
+    pub fn get_mut(&mut self, key : &str) -> Option<&mut SharedNode> {
+        self.as_object_mut().and_then(|o| o.get_mut(key))
+    }
+
+    /// Mutable access to an array element, unsharing this array first if needed.
+    pub fn get_index_mut(&mut self, index : usize) -> Option<&mut SharedNode> {
+        self.as_array_mut().and_then(|a| a.get_mut(index))
+    }
+
+    /// The elements of an array, copied out of any shared storage first.
+    /// Only the element handles are copied; the elements' own subtrees stay shared.
+    pub fn as_array_mut(&mut self) -> Option<&mut Vec<SharedNode>> {
+        match self {
+            SharedNode::Array(a) => Some(Arc::make_mut(a)),
+            _ => None
+        }
+    }
+
+    /// The members of an object, copied out of any shared storage first.
+    pub fn as_object_mut(&mut self) -> Option<&mut HashMap<Arc<str>, SharedNode>> {
+        match self {
+            SharedNode::Object(o) => Some(Arc::make_mut(o)),
+            _ => None
+        }
+    }
+
+    /// True when both nodes are the same shared allocation (scalars never are).
+    pub fn ptr_eq(&self, other : &SharedNode) -> bool {
+        match (self, other) {
+            (SharedNode::String(a), SharedNode::String(b)) => Arc::ptr_eq(a, b),
+            (SharedNode::Array(a), SharedNode::Array(b)) => Arc::ptr_eq(a, b),
+            (SharedNode::Object(a), SharedNode::Object(b)) => Arc::ptr_eq(a, b),
+            _ => false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn sample() -> SharedNode {
+        SharedNode::from(JsonNode::from_str("{\"a\":[1,2],\"b\":{\"c\":\"x\"}}").unwrap())
+    }
+
+    #[test]
+    fn test_clone_shares_everything() {
+        let original = sample();
+        let copy = original.clone();
+        assert!(copy.ptr_eq(&original));
+        assert!(copy.get("b").unwrap().ptr_eq(original.get("b").unwrap()));
+    }
+
+    #[test]
+    fn test_mutation_copies_only_the_edited_path() {
+        let original = sample();
+        let mut copy = original.clone();
+        *copy.get_mut("a").unwrap().get_index_mut(0).unwrap() = SharedNode::Null;
+
+        assert_eq!(original.get("a").unwrap().get_index(0), Some(&SharedNode::Number(1.0.into())));
+        assert_eq!(copy.get("a").unwrap().get_index(0), Some(&SharedNode::Null));
+        assert!(!copy.ptr_eq(&original));
+        assert!(copy.get("b").unwrap().ptr_eq(original.get("b").unwrap()));
+    }
+
+    #[test]
+    fn test_round_trip_to_json_node() {
+        let node = JsonNode::from_str("[true,null,\"s\",{\"k\":[]}]").unwrap();
+        let shared = SharedNode::from(JsonNode::from_str("[true,null,\"s\",{\"k\":[]}]").unwrap());
+        assert_eq!(JsonNode::from(&shared), node);
+    }
+
+    #[test]
+    fn test_keys_are_interned() {
+        let mut keys = KeyTable::new();
+        let input = "[{\"id\":1,\"name\":\"a\"},{\"id\":2,\"name\":\"b\"}]";
+        let rows = SharedNode::read(input.as_bytes(), &mut keys).unwrap().unwrap();
+        assert_eq!(JsonNode::from(&rows), JsonNode::from_str(input).unwrap());
+        assert_eq!(keys.len(), 2);
+
+        let key_of = |row : &SharedNode| match row {
+            SharedNode::Object(o) => o.keys().find(|k| &***k == "id").unwrap().clone(),
+            _ => panic!("not an object")
+        };
+        let more = SharedNode::from_json(JsonNode::from_str("{\"id\":3}").unwrap(), &mut keys);
+        let first = key_of(rows.get_index(0).unwrap());
+        assert!(Arc::ptr_eq(&first, &key_of(rows.get_index(1).unwrap())));
+        assert!(Arc::ptr_eq(&first, &key_of(&more)));
+        assert_eq!(keys.len(), 2);
+        assert!(SharedNode::read("[1,".as_bytes(), &mut keys).is_err());
+        assert_eq!(SharedNode::read("".as_bytes(), &mut keys).unwrap(), None);
+    }
+
+    #[test]
+    fn test_first_duplicate_key_wins() {
+        let input = "{\"a\":1,\"b\":[2],\"a\":{\"c\":3}}";
+        let shared = SharedNode::read(input.as_bytes(), &mut KeyTable::new()).unwrap().unwrap();
+        assert_eq!(JsonNode::from(&shared), JsonNode::from_str(input).unwrap());
+        assert_eq!(shared.get("a"), Some(&SharedNode::Number(Number::from(1u64))));
+    }
+}
@@ -0,0 +1,187 @@
+use crate::json::{JsonNode, JsonParseError, ParserOptions};
+use crate::json::scan::{skip_whitespace, string_end, value_end};
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum SliceKind {
+    Number,
+    String,
+    Array,
+    Object,
+    Boolean,
+    Null
+}
+
+/// A view of one JSON value inside a byte buffer. Creating a slice or navigating
+/// to a child only scans for value boundaries; nothing is decoded until `parse`.
+#[derive(Debug, Clone, Copy)]
+pub struct JsonSlice<'a> {
+    input: &'a [u8],
+    len: usize
+}
+
+impl<'a> JsonSlice<'a> {
+    /// Locates the first value in `input`, or None if it is malformed or truncated.
+    pub fn new(input : &'a [u8]) -> Option<JsonSlice<'a>> {
+        let start = skip_whitespace(input, 0);
+        JsonSlice::at(input, start).map(|(slice, _)| slice)
+    }
+
+    /// The raw bytes of this value.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        &self.input[..self.len]
+    }
+
+    pub fn kind(&self) -> SliceKind {
+        match self.input[0] {
+            b'"' => SliceKind::String,
+            b'[' => SliceKind::Array,
+            b'{' => SliceKind::Object,
+            b't' | b'f' => SliceKind::Boolean,
+            b'n' => SliceKind::Null,
+            _ => SliceKind::Number
+        }
+    }
+
+    /// Fully parses this value, and only this value, as a complete document.
+    pub fn parse(&self) -> Result<JsonNode, JsonParseError> {
+        JsonNode::parse_complete(self.as_bytes(), ParserOptions::default())
+    }
+
+    pub fn get(&self, key : &str) -> Option<JsonSlice<'a>> {
+        self.members().find(|(k, _)| k.is_key(key)).map(|(_, v)| v)
+    }
+
+    pub fn get_index(&self, index : usize) -> Option<JsonSlice<'a>> {
+        self.elements().nth(index)
+    }
+
+    /// The elements of an array; empty for any other kind of value.
+    pub fn elements(&self) -> impl Iterator<Item = JsonSlice<'a>> {
+        self.children(b'[').map(|(_, v)| v)
+    }
+
+    /// The (key, value) members of an object; empty for any other kind of value.
+    pub fn members(&self) -> impl Iterator<Item = (JsonSlice<'a>, JsonSlice<'a>)> {
+        self.children(b'{').filter_map(|(k, v)| k.map(|k| (k, v)))
+    }
+
+    fn at(input : &'a [u8], pos : usize) -> Option<(JsonSlice<'a>, usize)> {
+        let end = value_end(input, pos)?;
+        Some((JsonSlice { input: &input[pos..], len: end - pos }, end))
+    }
+
+    fn children(&self, open : u8) -> Children<'a> {
+        Children {
+            input: &self.input[..self.len],
+            pos: 1,
+            keyed: open == b'{',
+            done: self.input[0] != open
+        }
+    }
+
+    fn is_key(&self, key : &str) -> bool {
+        let raw = &self.as_bytes()[1..self.len - 1];
+        if raw.contains(&b'\\') {
+            self.parse() == Ok(JsonNode::String(key.to_string()))
+        } else {
+            raw == key.as_bytes()
+        }
+    }
+}
+
+struct Children<'a> {
+    input: &'a [u8],
+    pos: usize,
+    keyed: bool,
+    done: bool
+}
+
+impl<'a> Children<'a> {
+    fn next_child(&mut self) -> Option<(Option<JsonSlice<'a>>, JsonSlice<'a>)> {
+        let input = self.input;
+        let mut pos = skip_whitespace(input, self.pos);
+        if pos + 1 >= input.len() {
+            return None
+        }
+
+        let key = if self.keyed {
+            let end = string_end(input, pos).filter(|_| input[pos] == b'"')?;
+            let key = JsonSlice { input: &input[pos..], len: end - pos };
+            pos = skip_whitespace(input, end);
+            if input.get(pos) != Some(&b':') {
+                return None
+            }
+            pos = skip_whitespace(input, pos + 1);
+            Some(key)
+        } else {
+            None
+        };
+
+        let (value, end) = JsonSlice::at(input, pos)?;
+        pos = skip_whitespace(input, end);
+        match input.get(pos) {
+            Some(b',') => self.pos = pos + 1,
+            _ => self.done = true
+        }
+        Some((key, value))
+    }
+}
+
+impl<'a> Iterator for Children<'a> {
+    type Item = (Option<JsonSlice<'a>>, JsonSlice<'a>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None
+        }
+        let child = self.next_child();
+        if child.is_none() {
+            self.done = true
+        }
+        child
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOCUMENT : &str = "{ \"name\": \"x\", \"tags\": [1, [2, 3], {\"a\\\"b\": null}], \"ok\": true }";
+
+    #[test]
+    fn test_navigate_without_parsing() {
+        let doc = JsonSlice::new(DOCUMENT.as_bytes()).unwrap();
+        assert_eq!(doc.kind(), SliceKind::Object);
+        assert_eq!(doc.get("name").unwrap().as_bytes(), b"\"x\"");
+        assert_eq!(doc.get("ok").unwrap().kind(), SliceKind::Boolean);
+        assert_eq!(doc.get("tags").unwrap().get_index(1).unwrap().as_bytes(), b"[2, 3]");
+        assert!(doc.get("missing").is_none());
+        assert!(doc.get("tags").unwrap().get_index(3).is_none());
+    }
+
+    #[test]
+    fn test_escaped_keys_and_counts() {
+        let doc = JsonSlice::new(DOCUMENT.as_bytes()).unwrap();
+        let tags = doc.get("tags").unwrap();
+        assert_eq!(tags.elements().count(), 3);
+        assert_eq!(doc.members().count(), 3);
+        assert_eq!(tags.get_index(2).unwrap().get("a\"b").unwrap().kind(), SliceKind::Null);
+    }
+
+    #[test]
+    fn test_parse_only_the_requested_region() {
+        let doc = JsonSlice::new(b"[1,{\"k\":\"v\"}]").unwrap();
+        assert_eq!(doc.get_index(0).unwrap().parse().unwrap(), JsonNode::Number(1.0.into()));
+        assert_eq!(doc.get_index(1).unwrap().get("k").unwrap().parse().unwrap(), JsonNode::String("v".to_string()));
+        assert_eq!(JsonSlice::new(b"42").unwrap().parse().unwrap(), JsonNode::Number(42.0.into()));
+        assert_eq!(JsonSlice::new(b" -1.5").unwrap().parse().unwrap(), JsonNode::Number((-1.5).into()));
+        assert_eq!(JsonSlice::new(b"[7,8]").unwrap().get_index(1).unwrap().parse().unwrap(), JsonNode::Number(8.0.into()));
+    }
+
+    #[test]
+    fn test_truncated_input_has_no_slice() {
+        assert!(JsonSlice::new(b"[1, 2").is_none());
+        assert!(JsonSlice::new(b"\"open").is_none());
+        assert!(JsonSlice::new(b"").is_none());
+    }
+}
@@ -0,0 +1,374 @@
+// A map for object members that keeps them in a vector in the order they
+// were added. Up to INLINE members are found by comparing keys one by one;
+// past that, a hash index of positions is built next to the vector. Most
+// objects are that small, and for them this saves hashing every key and
+// allocating a table with room to spare. There is no such thing for arrays:
+// `JsonNode::Array` stays a `Vec`, as a node cannot keep nodes inline in
+// itself, and behind a pointer they would save no allocation.
+
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{BuildHasher, Hash};
+use std::iter::FromIterator;
+use std::ops::Index;
+use std::{slice, vec};
+
+/// Members searched in order before an index is built.
+pub const INLINE : usize = 8;
+
+#[derive(Clone)]
+pub struct SmallMap<K, V, S> {
+    entries: Vec<(K, V)>,
+    // the position of every entry, once there are more than INLINE
+    index: Option<HashMap<K, usize, S>>,
+    hasher: S
+}
+
+pub type Iter<'a, K, V> = std::iter::Map<slice::Iter<'a, (K, V)>, fn(&'a (K, V)) -> (&'a K, &'a V)>;
+pub type IterMut<'a, K, V> = std::iter::Map<slice::IterMut<'a, (K, V)>, fn(&'a mut (K, V)) -> (&'a K, &'a mut V)>;
+pub type IntoIter<K, V> = vec::IntoIter<(K, V)>;
+pub type Drain<'a, K, V> = vec::Drain<'a, (K, V)>;
+
+impl<K, V, S : Default> Default for SmallMap<K, V, S> {
+    fn default() -> SmallMap<K, V, S> {
+        SmallMap { entries: Vec::new(), index: None, hasher: S::default() }
+    }
+}
+
+impl<K, V, S> SmallMap<K, V, S> {
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.entries.capacity()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.index = None
+    }
+
+    /// The members in the order they were added.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        self.entries.iter().map(|(k, v)| (k, v))
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        self.entries.iter_mut().map(|(k, v)| (&*k, v))
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.iter().map(|(k, _)| k)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.iter().map(|(_, v)| v)
+    }
+
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut V> {
+        self.iter_mut().map(|(_, v)| v)
+    }
+
+    pub fn drain(&mut self) -> Drain<'_, K, V> {
+        self.index = None;
+        self.entries.drain(..)
+    }
+}
+
+// index keys are clones of the member names, and the index gets a clone of
+// the hasher
+impl<K : Eq + Hash + Clone, V, S : BuildHasher + Clone> SmallMap<K, V, S> {
+    pub fn with_capacity_and_hasher(capacity : usize, hasher : S) -> SmallMap<K, V, S> {
+        let index = if capacity > INLINE { Some(HashMap::with_capacity_and_hasher(capacity, hasher.clone())) } else { None };
+        SmallMap { entries: Vec::with_capacity(capacity), index, hasher }
+    }
+
+    pub fn reserve(&mut self, additional : usize) {
+        self.entries.reserve(additional);
+        if let Some(index) = &mut self.index {
+            index.reserve(additional)
+        }
+    }
+
+    pub fn get<Q>(&self, key : &Q) -> Option<&V>
+        where K : Borrow<Q>, Q : Eq + Hash + ?Sized
+    {
+        self.position(key).map(|i| &self.entries[i].1)
+    }
+
+    pub fn get_mut<Q>(&mut self, key : &Q) -> Option<&mut V>
+        where K : Borrow<Q>, Q : Eq + Hash + ?Sized
+    {
+        let i = self.position(key)?;
+        Some(&mut self.entries[i].1)
+    }
+
+    pub fn contains_key<Q>(&self, key : &Q) -> bool
+        where K : Borrow<Q>, Q : Eq + Hash + ?Sized
+    {
+        self.position(key).is_some()
+    }
+
+    /// Replaces the value of a member in place, or adds a new one last.
+    pub fn insert(&mut self, key : K, value : V) -> Option<V> {
+        match self.get_mut(&key) {
+            Some(old) => Some(std::mem::replace(old, value)),
+            None => {
+                self.insert_new(key, value);
+                None
+            }
+        }
+    }
+
+    /// Removes a member, keeping the others in order.
+    pub fn remove<Q>(&mut self, key : &Q) -> Option<V>
+        where K : Borrow<Q>, Q : Eq + Hash + ?Sized
+    {
+        let i = self.position(key)?;
+        if let Some(index) = &mut self.index {
+            index.remove(key);
+            for position in index.values_mut() {
+                if *position > i {
+                    *position -= 1
+                }
+            }
+        }
+        Some(self.entries.remove(i).1)
+    }
+
+    pub fn retain<F : FnMut(&K, &mut V) -> bool>(&mut self, mut keep : F) {
+        self.entries.retain_mut(|(k, v)| keep(k, v));
+        if self.index.is_some() {
+            self.reindex()
+        }
+    }
+
+    pub fn entry(&mut self, key : K) -> Entry<'_, K, V, S> {
+        if self.contains_key(&key) {
+            Entry::Occupied(OccupiedEntry { map: self, key })
+        } else {
+            Entry::Vacant(VacantEntry { map: self, key })
+        }
+    }
+
+    fn position<Q>(&self, key : &Q) -> Option<usize>
+        where K : Borrow<Q>, Q : Eq + Hash + ?Sized
+    {
+        match &self.index {
+            Some(index) => index.get(key).cloned(),
+            None => self.entries.iter().position(|(k, _)| k.borrow() == key)
+        }
+    }
+
+    // `key` must not be in the map yet
+    fn insert_new(&mut self, key : K, value : V) -> &mut V {
+        if self.index.is_none() && self.entries.len() == INLINE {
+            self.reindex()
+        }
+        if let Some(index) = &mut self.index {
+            index.insert(key.clone(), self.entries.len());
+        }
+        self.entries.push((key, value));
+        &mut self.entries.last_mut().expect("just pushed").1
+    }
+
+    fn reindex(&mut self) {
+        let mut index = HashMap::with_capacity_and_hasher(self.entries.capacity(), self.hasher.clone());
+        index.extend(self.entries.iter().enumerate().map(|(i, (k, _))| (k.clone(), i)));
+        self.index = Some(index)
+    }
+}
+
+pub enum Entry<'a, K, V, S> {
+    Occupied(OccupiedEntry<'a, K, V, S>),
+    Vacant(VacantEntry<'a, K, V, S>)
+}
+
+pub struct OccupiedEntry<'a, K, V, S> {
+    map: &'a mut SmallMap<K, V, S>,
+    key: K
+}
+
+pub struct VacantEntry<'a, K, V, S> {
+    map: &'a mut SmallMap<K, V, S>,
+    key: K
+}
+
+impl<'a, K : Eq + Hash + Clone, V, S : BuildHasher + Clone> Entry<'a, K, V, S> {
+    pub fn or_insert(self, default : V) -> &'a mut V {
+        self.or_insert_with(|| default)
+    }
+
+    pub fn or_insert_with<F : FnOnce() -> V>(self, default : F) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default())
+        }
+    }
+
+    pub fn or_default(self) -> &'a mut V
+        where V : Default
+    {
+        self.or_insert_with(V::default)
+    }
+
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(entry) => &entry.key,
+            Entry::Vacant(entry) => &entry.key
+        }
+    }
+}
+
+impl<'a, K : Eq + Hash + Clone, V, S : BuildHasher + Clone> OccupiedEntry<'a, K, V, S> {
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    pub fn get(&self) -> &V {
+        self.map.get(&self.key).expect("occupied")
+    }
+
+    pub fn into_mut(self) -> &'a mut V {
+        self.map.get_mut(&self.key).expect("occupied")
+    }
+
+    pub fn insert(&mut self, value : V) -> V {
+        std::mem::replace(self.map.get_mut(&self.key).expect("occupied"), value)
+    }
+
+    pub fn remove(self) -> V {
+        self.map.remove(&self.key).expect("occupied")
+    }
+}
+
+impl<'a, K : Eq + Hash + Clone, V, S : BuildHasher + Clone> VacantEntry<'a, K, V, S> {
+    pub fn insert(self, value : V) -> &'a mut V {
+        self.map.insert_new(self.key, value)
+    }
+}
+
+impl<K : Eq + Hash + Clone, V, S : BuildHasher + Clone + Default> FromIterator<(K, V)> for SmallMap<K, V, S> {
+    fn from_iter<I : IntoIterator<Item = (K, V)>>(members : I) -> SmallMap<K, V, S> {
+        let mut map = SmallMap::default();
+        map.extend(members);
+        map
+    }
+}
+
+impl<K : Eq + Hash + Clone, V, S : BuildHasher + Clone> Extend<(K, V)> for SmallMap<K, V, S> {
+    fn extend<I : IntoIterator<Item = (K, V)>>(&mut self, members : I) {
+        for (key, value) in members {
+            self.insert(key, value);
+        }
+    }
+}
+
+impl<K, V, S> IntoIterator for SmallMap<K, V, S> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+
+    fn into_iter(self) -> IntoIter<K, V> {
+        self.entries.into_iter()
+    }
+}
+
+impl<'a, K, V, S> IntoIterator for &'a SmallMap<K, V, S> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Iter<'a, K, V> {
+        self.iter()
+    }
+}
+
+impl<'a, K, V, S> IntoIterator for &'a mut SmallMap<K, V, S> {
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = IterMut<'a, K, V>;
+
+    fn into_iter(self) -> IterMut<'a, K, V> {
+        self.iter_mut()
+    }
+}
+
+impl<K : Eq + Hash + Clone + Borrow<Q>, Q : Eq + Hash + ?Sized, V, S : BuildHasher + Clone> Index<&Q> for SmallMap<K, V, S> {
+    type Output = V;
+
+    fn index(&self, key : &Q) -> &V {
+        self.get(key).expect("no such key in the map")
+    }
+}
+
+// members compare regardless of order, as JSON objects do
+impl<K : Eq + Hash + Clone, V : PartialEq, S : BuildHasher + Clone> PartialEq for SmallMap<K, V, S> {
+    fn eq(&self, other : &SmallMap<K, V, S>) -> bool {
+        self.len() == other.len() && self.iter().all(|(k, v)| other.get(k) == Some(v))
+    }
+}
+
+impl<K : fmt::Debug, V : fmt::Debug, S> fmt::Debug for SmallMap<K, V, S> {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::hash_map::RandomState;
+
+    type Map = SmallMap<String, usize, RandomState>;
+
+    fn filled(count : usize) -> Map {
+        (0..count).map(|i| (i.to_string(), i)).collect()
+    }
+
+    fn keys(map : &Map) -> Vec<&str> {
+        map.keys().map(String::as_str).collect()
+    }
+
+    #[test]
+    fn test_indexes_past_inline() {
+        let mut map = filled(INLINE);
+        assert!(map.index.is_none());
+        assert_eq!(map.insert("3".to_string(), 30), Some(3));
+        *map.entry("new".to_string()).or_insert(0) += 1;
+        assert!(map.index.is_some());
+        assert_eq!((map.len(), map["3"], map["new"]), (INLINE + 1, 30, 1));
+        assert!(Map::with_capacity_and_hasher(INLINE + 1, RandomState::new()).index.is_some());
+    }
+
+    #[test]
+    fn test_keeps_insertion_order() {
+        let mut map = filled(INLINE + 4);
+        map.remove("2");
+        map.insert("first".to_string(), 0);
+        map.retain(|key, _| key != "5");
+        assert_eq!(keys(&map), vec!["0", "1", "3", "4", "6", "7", "8", "9", "10", "11", "first"]);
+        assert_eq!((map["3"], map["first"], map.get("5")), (3, 0, None));
+    }
+
+    #[test]
+    fn test_equality_ignores_order() {
+        let mut small = filled(3);
+        let mut large = filled(INLINE + 1);
+        for i in 3..=INLINE {
+            large.remove(&i.to_string());
+        }
+        assert_eq!(small, large);
+        small.retain(|key, _| key != "0");
+        assert_ne!(small, large);
+        small.insert("0".to_string(), 0);
+        assert_eq!(small, large);
+        assert_eq!(keys(&small), vec!["1", "2", "0"]);
+        assert!(matches!(small.entry("1".to_string()), Entry::Occupied(_)));
+        assert_eq!(small.drain().count(), 3);
+        assert!(small.is_empty());
+    }
+}
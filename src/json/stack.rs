@@ -0,0 +1,291 @@
+// Stack growth for everything that recurses through a document, the way the
+// stacker crate does it: when recursion gets close to the end of the stack, the
+// rest of it carries on on the same thread, on a segment mapped for it,
+// which is unmapped again once the recursion returns to where it grew.
+
+#[cfg(not(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64"))))]
+compile_error!("the grow_stack feature switches stacks only on x86_64 and aarch64 Linux");
+
+use std::any::Any;
+use std::cell::Cell;
+use std::hint::black_box;
+use std::panic::{self, AssertUnwindSafe};
+use std::{mem, ptr};
+
+/// Stack space left below which recursion moves on to a new segment.
+pub const RED_ZONE : usize = 128 * 1024;
+
+/// Stack size of each new segment.
+pub const SEGMENT : usize = 8 * 1024 * 1024;
+
+thread_local! {
+    // the lowest address the stack in use may reach, or 0 until it is looked up
+    static LIMIT : Cell<usize> = const { Cell::new(0) };
+}
+
+// stacks grow downwards on every target this builds for
+fn stack_pointer() -> usize {
+    let marker = 0u8;
+    black_box(&marker) as *const u8 as usize
+}
+
+/// How many bytes of stack are left below the caller, or None when the
+/// system does not tell where the stack of this thread ends.
+pub fn remaining_stack() -> Option<usize> {
+    let limit = match LIMIT.with(Cell::get) {
+        0 => {
+            let limit = thread_stack_limit()?;
+            LIMIT.with(|cell| cell.set(limit));
+            limit
+        },
+        limit => limit
+    };
+    Some(stack_pointer().saturating_sub(limit))
+}
+
+/// Runs `f`, first moving to a new stack segment if this one is about to run out.
+pub fn maybe_grow<R, F : FnOnce() -> R>(f : F) -> R {
+    match remaining_stack() {
+        Some(left) if left < RED_ZONE => grow(f),
+        _ => f()
+    }
+}
+
+fn grow<R, F : FnOnce() -> R>(f : F) -> R {
+    let mut f = Some(f);
+    let mut outcome : Option<Result<R, Box<dyn Any + Send>>> = None;
+    // panics are caught on the segment and resumed here, as no unwinding
+    // crosses the switch between stacks
+    let mut run = || outcome = Some(panic::catch_unwind(AssertUnwindSafe(|| (f.take().expect("a segment runs once"))())));
+    let segment = Segment::new(SEGMENT);
+    let below = LIMIT.with(|limit| limit.replace(segment.limit()));
+    unsafe { switch(segment.top(), &mut run) }
+    LIMIT.with(|limit| limit.set(below));
+    drop(segment);
+    match outcome.expect("the segment has run") {
+        Ok(result) => result,
+        Err(reason) => panic::resume_unwind(reason)
+    }
+}
+
+fn thread_stack_limit() -> Option<usize> {
+    unsafe {
+        let mut attr : libc::pthread_attr_t = mem::zeroed();
+        if libc::pthread_getattr_np(libc::pthread_self(), &mut attr) != 0 {
+            return None
+        }
+        let (mut base, mut size) = (ptr::null_mut(), 0);
+        let found = libc::pthread_attr_getstack(&attr, &mut base, &mut size) == 0;
+        libc::pthread_attr_destroy(&mut attr);
+        if found { Some(base as usize) } else { None }
+    }
+}
+
+// a stack mapped for recursion to carry on in, with an inaccessible page
+// below it so that running out of it faults instead of overwriting memory
+struct Segment {
+    base: *mut libc::c_void,
+    guard: usize,
+    size: usize
+}
+
+impl Segment {
+    fn new(size : usize) -> Segment {
+        let guard = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+        let base = unsafe {
+            libc::mmap(ptr::null_mut(), guard + size, libc::PROT_READ | libc::PROT_WRITE, libc::MAP_PRIVATE | libc::MAP_ANONYMOUS, -1, 0)
+        };
+        if base == libc::MAP_FAILED {
+            panic!("cannot map a new stack segment")
+        }
+        let segment = Segment { base, guard, size };
+        if unsafe { libc::mprotect(base, guard, libc::PROT_NONE) } != 0 {
+            panic!("cannot protect the end of a new stack segment")
+        }
+        segment
+    }
+
+    fn limit(&self) -> usize {
+        self.base as usize + self.guard
+    }
+
+    // page aligned, and so aligned as any call needs
+    fn top(&self) -> usize {
+        self.limit() + self.size
+    }
+}
+
+impl Drop for Segment {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.base, self.guard + self.size) };
+    }
+}
+
+// what the new stack starts with: `run` is a `&mut dyn FnMut()`, which
+// catches its own panics
+extern "C" fn trampoline(run : *mut &mut dyn FnMut()) {
+    unsafe { (*run)() }
+}
+
+// calls `run` with the stack pointer at `top`, and restores it afterwards
+// from a register the call preserves
+#[cfg(target_arch = "x86_64")]
+unsafe fn switch(top : usize, mut run : &mut dyn FnMut()) {
+    std::arch::asm!(
+        "mov r12, rsp",
+        "mov rsp, {top}",
+        "call {trampoline}",
+        "mov rsp, r12",
+        top = in(reg) top,
+        trampoline = sym trampoline,
+        in("rdi") &mut run as *mut &mut dyn FnMut(),
+        out("r12") _,
+        clobber_abi("C")
+    )
+}
+
+#[cfg(target_arch = "aarch64")]
+unsafe fn switch(top : usize, mut run : &mut dyn FnMut()) {
+    std::arch::asm!(
+        "mov x20, sp",
+        "mov sp, {top}",
+        "bl {trampoline}",
+        "mov sp, x20",
+        top = in(reg) top,
+        trampoline = sym trampoline,
+        in("x0") &mut run as *mut &mut dyn FnMut(),
+        out("x20") _,
+        clobber_abi("C")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+    use std::rc::Rc;
+    use std::thread;
+    use crate::json::JsonNode;
+
+    #[test]
+    fn test_deep_nesting() {
+        let depth = 50000;
+        let text = format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+        let node = JsonNode::from_str(&text).unwrap();
+        assert_eq!(node.to_string(), text);
+        let copy = node.clone();
+        assert_eq!(copy, node);
+        assert!(format!("{:?}", copy).starts_with("Array([Array(["))
+    }
+
+    fn count_down(n : usize, bottom : fn() -> usize) -> usize {
+        maybe_grow(|| if n == 0 { bottom() } else { count_down(n - 1, bottom) + 1 })
+    }
+
+    #[test]
+    fn test_results_and_panics_cross_segments() {
+        assert_eq!(count_down(200000, || 0), 200000);
+        assert!(panic::catch_unwind(|| count_down(200000, || panic!("bottom"))).is_err());
+    }
+
+    fn count_shared(n : usize, shared : &Rc<Cell<usize>>) {
+        maybe_grow(|| if n > 0 {
+            shared.set(shared.get() + 1);
+            count_shared(n - 1, shared)
+        })
+    }
+
+    #[test]
+    fn test_grows_on_the_same_thread() {
+        let remaining = thread::Builder::new().stack_size(256 * 1024).spawn(|| {
+            let left = remaining_stack().unwrap();
+            // what is neither Send nor Sync stays on this thread
+            let shared = Rc::new(Cell::new(0));
+            count_shared(100000, &shared);
+            assert_eq!(shared.get(), 100000);
+            (left, remaining_stack().unwrap())
+        }).unwrap().join().unwrap();
+        assert!(remaining.0 < 256 * 1024);
+        assert_eq!(remaining.0, remaining.1);
+    }
+
+    #[test]
+    fn test_walkers_on_deep_documents() {
+        use crate::json::{arrays, case, diff, flatten, infer, merge_patch, patch, redact, refs, template};
+        use crate::json::filter::Filter;
+        use crate::json::index::JsonIndex;
+        use crate::json::jsonpath::JsonPath;
+        use crate::json::pipeline::Pipeline;
+        use crate::json::pointer::JsonPointer;
+        use crate::json::sanitize::Sanitizer;
+        use crate::json::schema::Schema;
+        use crate::json::shape::assert_shape;
+        use crate::json::shared::SharedNode;
+        use crate::json::subscribe::Subscriptions;
+
+        // a stack only a little over the red zone, which every walker
+        // outgrows long before the bottom; some walkers are quadratic in the
+        // depth, which keeps it modest
+        thread::Builder::new().stack_size(RED_ZONE + 32 * 1024).spawn(|| {
+            let depth = 400;
+            let nested = |leaf : &str| format!("{}{}{}", "[{\"a\":".repeat(depth), leaf, "}]".repeat(depth));
+            let text = nested("1");
+            let node = JsonNode::from_str(&text).unwrap();
+            let other = JsonNode::from_str(&nested("2")).unwrap();
+            let nowhere = JsonPointer::parse("/**/b").unwrap();
+
+            let mut copy = node.clone();
+            redact::redact(&mut copy, std::slice::from_ref(&nowhere), redact::Strategy::Mask);
+            refs::resolve_refs(&mut copy).unwrap();
+            case::rename_keys_with(&mut copy, &mut |key| key.to_string());
+            assert_eq!(copy, node);
+            assert_eq!(diff::equivalent(&node, &other, &diff::DiffOptions::new()).map(|path| path.tokens().len()), Some(2 * depth));
+            assert_eq!(arrays::compare(&node, &other, arrays::Collation::Plain), std::cmp::Ordering::Less);
+
+            let pairs = flatten::flatten(&node, flatten::Separator::Pointer);
+            assert_eq!(flatten::unflatten(&pairs, flatten::FlattenOptions::new(flatten::Separator::Pointer)).unwrap(), node);
+            assert_eq!(flatten::ungron(&flatten::gron(&node)).unwrap(), node);
+
+            let mut patched = node.clone();
+            merge_patch::apply(&mut patched, &merge_patch::diff(&node, &other).unwrap());
+            assert_eq!(patched, other);
+            let mut patched = node.clone();
+            patch::apply(&mut patched, &patch::diff(&node, &other)).unwrap();
+            assert_eq!(patched, other);
+
+            assert_eq!(node.find_all(|_, value| value.as_f64() == Some(1.0)).len(), 1);
+            assert!(node.approx_heap_bytes() > 0);
+            assert_eq!(template::render(&node, &JsonNode::Null).unwrap(), node);
+            assert_eq!(Sanitizer::new().trim_strings().apply(node.clone()), node);
+            assert!(assert_shape(&node, &node).is_ok());
+            assert_eq!(infer::schema(vec![&node]).to_string().matches("items").count(), depth);
+            assert_eq!(JsonIndex::build(&node).len(), 2 * depth + 1);
+            assert_eq!(Pipeline::new().apply(node.clone()), node);
+            assert!(nowhere.select_all(&node).is_empty());
+            assert!(Schema::compile(&JsonNode::from_str("{\"items\":{\"properties\":{\"a\":{\"$ref\":\"#\"}}}}").unwrap()).unwrap().is_valid(&node));
+            let mut found = 0;
+            Subscriptions::new().subscribe(&JsonPointer::parse("/**/a").unwrap(), |_, _| found += 1).run(text.as_bytes()).unwrap();
+            assert_eq!(found, depth);
+            assert_eq!(Filter::parse("[..] | length").unwrap().run(&node).unwrap(), vec![JsonNode::from(2 * depth + 1)]);
+            assert_eq!(JsonPath::parse("$..a").unwrap().select(&node).len(), depth);
+
+            let shared = SharedNode::from(node.clone());
+            assert_eq!(shared, SharedNode::from(node.clone()));
+            assert_eq!(JsonNode::from(&shared), node);
+            assert!(format!("{:?}", shared).starts_with("Array([Object("));
+
+            #[cfg(feature = "yaml")]
+            assert_eq!(JsonNode::from_yaml_str(&node.to_yaml_string()).unwrap(), node);
+            #[cfg(feature = "toml")]
+            {
+                let tables = JsonNode::from_str(&format!("{}1{}", "{\"a\":".repeat(depth), "}".repeat(depth))).unwrap();
+                assert_eq!(JsonNode::from_toml_str(&tables.to_toml_string().unwrap()).unwrap(), tables);
+            }
+            #[cfg(feature = "serde")]
+            {
+                assert_eq!(crate::json::typed::from_str::<JsonNode>(&text).unwrap(), node);
+                assert_eq!(crate::json::typed::to_string(&node).unwrap(), text);
+            }
+        }).unwrap().join().unwrap()
+    }
+}
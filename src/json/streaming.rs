@@ -0,0 +1,327 @@
+use std::fmt;
+use std::io::{self, Read};
+use circular::Buffer;
+use crate::json::{JsonNode, JsonParseError};
+use crate::json::scan::is_whitespace;
+
+const INITIAL_BUFFER : usize = 4096;
+
+/// Default for `StreamingParser::max_buffer`.
+pub const MAX_BUFFER : usize = 1 << 30;
+
+/// What the data fed so far holds next.
+#[derive(PartialEq, Debug)]
+pub enum Parsed {
+    Value(JsonNode),
+    /// The next document is not complete yet.
+    NeedMoreData,
+    /// The input has been finished and every document in it handed out.
+    End
+}
+
+#[derive(Debug)]
+pub enum StreamError {
+    Io(io::Error),
+    /// Positions count from the end of the previous document.
+    Syntax(JsonParseError),
+    /// A single document would not fit in this many bytes.
+    TooLarge(usize),
+    UnexpectedEnd
+}
+
+impl fmt::Display for StreamError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StreamError::Io(reason) => write!(f, "reading input failed: {}", reason),
+            StreamError::Syntax(reason) => write!(f, "{}", reason),
+            StreamError::TooLarge(max) => write!(f, "a document exceeds the maximum of {} bytes", max),
+            StreamError::UnexpectedEnd => write!(f, "input ended in the middle of a document")
+        }
+    }
+}
+
+/// Parses a stream of concatenated documents as it arrives in chunks of any
+/// size, keeping incomplete input buffered until the rest of it is fed.
+pub struct StreamingParser {
+    buffer: Buffer,
+    max_buffer: usize,
+    consumed: usize,
+    finished: bool,
+    scan: Scan
+}
+
+// how far the document at the start of the buffer has been scanned, so that
+// each fed byte is looked at once rather than on every call to `next_value`
+#[derive(Default)]
+struct Scan {
+    pos: usize,
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+    ready: bool
+}
+
+impl Scan {
+    // whether the document at the start of `data` may be complete: a string
+    // or container that has been closed, or anything else, which the parser
+    // then decides on
+    fn ready(&mut self, data : &[u8]) -> bool {
+        while !self.ready && self.pos < data.len() {
+            let byte = data[self.pos];
+            self.pos += 1;
+            if self.in_string {
+                match byte {
+                    _ if self.escaped => self.escaped = false,
+                    b'\\' => self.escaped = true,
+                    b'"' => {
+                        self.in_string = false;
+                        self.ready = self.depth == 0
+                    },
+                    _ => ()
+                }
+                continue
+            }
+            match byte {
+                b'"' => self.in_string = true,
+                b'[' | b'{' => self.depth += 1,
+                b']' | b'}' if self.depth > 1 => self.depth -= 1,
+                b']' | b'}' => self.ready = true,
+                _ if self.depth > 0 || is_whitespace(byte) => (),
+                _ => self.ready = true
+            }
+        }
+        self.ready
+    }
+}
+
+impl Default for StreamingParser {
+    fn default() -> StreamingParser {
+        StreamingParser::new()
+    }
+}
+
+impl StreamingParser {
+    pub fn new() -> StreamingParser {
+        StreamingParser {
+            buffer: Buffer::with_capacity(INITIAL_BUFFER),
+            max_buffer: MAX_BUFFER,
+            consumed: 0,
+            finished: false,
+            scan: Scan::default()
+        }
+    }
+
+    /// Refuses documents larger than `max` bytes instead of buffering them.
+    pub fn max_buffer(mut self, max : usize) -> StreamingParser {
+        self.max_buffer = max;
+        self
+    }
+
+    /// Bytes of input taken by the documents handed out so far.
+    pub fn offset(&self) -> usize {
+        self.consumed
+    }
+
+    pub fn feed(&mut self, chunk : &[u8]) -> Result<(), StreamError> {
+        self.make_room(chunk.len())?;
+        self.buffer.space()[..chunk.len()].copy_from_slice(chunk);
+        self.buffer.fill(chunk.len());
+        Ok(())
+    }
+
+    /// Feeds one read from `source` straight into the buffer, returning the
+    /// number of bytes read, which is 0 at the end of `source`.
+    pub fn read_from<R : Read>(&mut self, source : &mut R) -> Result<usize, StreamError> {
+        self.make_room(1)?;
+        let read = source.read(self.buffer.space()).map_err(StreamError::Io)?;
+        self.buffer.fill(read);
+        Ok(read)
+    }
+
+    /// Marks the end of the input, after which whatever remains buffered
+    /// must be whole documents.
+    pub fn finish(&mut self) -> Result<(), StreamError> {
+        // a number at the very end only completes with a terminator
+        self.feed(b"\n")?;
+        self.finished = true;
+        Ok(())
+    }
+
+    /// Takes the next complete document out of the buffer.
+    pub fn next_value(&mut self) -> Result<Parsed, StreamError> {
+        if !self.finished && !self.scan.ready(self.buffer.data()) {
+            return Ok(Parsed::NeedMoreData)
+        }
+        match JsonNode::parse_prefix(self.buffer.data()) {
+            Ok(Some((json, length))) => {
+                self.buffer.consume(length);
+                self.consumed += length;
+                self.scan = Scan::default();
+                Ok(Parsed::Value(json))
+            },
+            Ok(None) if !self.finished => Ok(Parsed::NeedMoreData),
+            Ok(None) if self.buffer.data().iter().all(|b| is_whitespace(*b)) => Ok(Parsed::End),
+            Ok(None) => Err(StreamError::UnexpectedEnd),
+            Err(reason) => Err(StreamError::Syntax(reason))
+        }
+    }
+
+    fn make_room(&mut self, needed : usize) -> Result<(), StreamError> {
+        let wanted = self.buffer.available_data() + needed;
+        if wanted > self.max_buffer {
+            return Err(StreamError::TooLarge(self.max_buffer))
+        }
+        if self.buffer.available_space() < needed {
+            self.buffer.shift()
+        }
+        if self.buffer.available_space() < needed {
+            self.buffer.grow((self.buffer.capacity() * 2).max(wanted).min(self.max_buffer));
+        }
+        Ok(())
+    }
+}
+
+/// Reads whitespace-separated documents, such as `{"a":1}{"b":2}` or one per
+/// line, from `source` one after another, reading only as much as each needs.
+/// Iterating stops after the last document or the first error.
+pub struct JsonStream<R : Read> {
+    source: R,
+    parser: StreamingParser,
+    done: bool
+}
+
+impl<R : Read> JsonStream<R> {
+    pub fn new(source : R) -> JsonStream<R> {
+        JsonStream { source, parser: StreamingParser::new(), done: false }
+    }
+
+    /// Refuses documents larger than `max` bytes instead of buffering them.
+    pub fn max_buffer(mut self, max : usize) -> JsonStream<R> {
+        self.parser = self.parser.max_buffer(max);
+        self
+    }
+
+    /// Bytes of input taken by the documents handed out so far.
+    pub fn offset(&self) -> usize {
+        self.parser.offset()
+    }
+
+    fn next_value(&mut self) -> Result<Option<JsonNode>, StreamError> {
+        loop {
+            match self.parser.next_value()? {
+                Parsed::Value(json) => return Ok(Some(json)),
+                Parsed::End => return Ok(None),
+                Parsed::NeedMoreData => match self.parser.read_from(&mut self.source) {
+                    Ok(0) => self.parser.finish()?,
+                    Ok(_) => (),
+                    Err(StreamError::Io(ref reason)) if reason.kind() == io::ErrorKind::Interrupted => (),
+                    Err(reason) => return Err(reason)
+                }
+            }
+        }
+    }
+}
+
+impl<R : Read> Iterator for JsonStream<R> {
+    type Item = Result<JsonNode, StreamError>;
+
+    fn next(&mut self) -> Option<Result<JsonNode, StreamError>> {
+        if self.done {
+            return None
+        }
+        let next = self.next_value();
+        self.done = !matches!(next, Ok(Some(_)));
+        next.transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn drain(parser : &mut StreamingParser) -> Vec<String> {
+        let mut values = Vec::new();
+        while let Parsed::Value(json) = parser.next_value().unwrap() {
+            values.push(json.to_string())
+        }
+        values
+    }
+
+    #[test]
+    fn test_documents_split_anywhere() {
+        let text = b"{\"a\":[1,\"x y\"]} 12 [true] \"s\" -3.5";
+        for chunk in 1..text.len() {
+            let mut parser = StreamingParser::new();
+            let mut values = Vec::new();
+            for piece in text.chunks(chunk) {
+                parser.feed(piece).unwrap();
+                values.extend(drain(&mut parser))
+            }
+            parser.finish().unwrap();
+            values.extend(drain(&mut parser));
+            assert_eq!(values, vec!["{\"a\":[1,\"x y\"]}", "12", "[true]", "\"s\"", "-3.5"]);
+            assert_eq!(parser.next_value().unwrap(), Parsed::End);
+        }
+    }
+
+    #[test]
+    fn test_large_document_in_small_chunks() {
+        let item = "{\"k\":\"v]}\\\"[\",\"n\":[1,{}]}";
+        let text = format!("[{}] 7", vec![item; 20_000].join(","));
+        let mut parser = StreamingParser::new();
+        let mut values = Vec::new();
+        for piece in text.as_bytes().chunks(3) {
+            parser.feed(piece).unwrap();
+            values.extend(drain(&mut parser))
+        }
+        parser.finish().unwrap();
+        values.extend(drain(&mut parser));
+        assert_eq!(values.len(), 2);
+        assert_eq!(values[0].len(), text.len() - 2);
+        assert_eq!(values[1], "7");
+    }
+
+    #[test]
+    fn test_read_from() {
+        let mut source = &b"[1] [2"[..];
+        let mut parser = StreamingParser::new();
+        assert_eq!(parser.next_value().unwrap(), Parsed::NeedMoreData);
+        assert_eq!(parser.read_from(&mut source).unwrap(), 6);
+        assert_eq!(drain(&mut parser), vec!["[1]"]);
+        assert_eq!(parser.offset(), 3);
+        assert_eq!(parser.read_from(&mut source).unwrap(), 0);
+        parser.finish().unwrap();
+        assert!(matches!(parser.next_value(), Err(StreamError::UnexpectedEnd)));
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut parser = StreamingParser::new();
+        parser.feed(b"[1] [1 2]").unwrap();
+        assert_eq!(parser.next_value().unwrap(), Parsed::Value(JsonNode::from_str("[1]").unwrap()));
+        match parser.next_value() {
+            Err(StreamError::Syntax(reason)) => assert_eq!(reason.offset, 4),
+            other => panic!("expected a syntax error, got {:?}", other)
+        }
+
+        let mut parser = StreamingParser::new().max_buffer(8);
+        parser.feed(b"[1,2,").unwrap();
+        assert!(matches!(parser.feed(b"3,4,5]"), Err(StreamError::TooLarge(8))));
+    }
+
+    #[test]
+    fn test_json_stream() {
+        let mut stream = JsonStream::new(&b"{\"a\":1}{\"b\":2}\n3"[..]);
+        assert_eq!(stream.next().unwrap().unwrap(), json!({"a": 1}));
+        assert_eq!(stream.offset(), 7);
+        let rest : Vec<JsonNode> = stream.by_ref().map(Result::unwrap).collect();
+        assert_eq!(rest, vec![json!({"b": 2}), json!(3)]);
+        assert!(stream.next().is_none());
+
+        let mut stream = JsonStream::new(&b"[1] [1 2] [3]"[..]);
+        assert_eq!(stream.next().unwrap().unwrap(), json!([1]));
+        assert!(matches!(stream.next(), Some(Err(StreamError::Syntax(_)))));
+        assert!(stream.next().is_none());
+    }
+}
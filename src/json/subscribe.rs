@@ -0,0 +1,173 @@
+use std::io::Read;
+use crate::json::{maybe_grow, JsonNode};
+use crate::json::events::{EventError, EventReader, JsonEvent};
+use crate::json::pointer::JsonPointer;
+
+type Callback<'f> = Box<dyn FnMut(&str, JsonNode) + 'f>;
+
+struct Route<'f> {
+    pattern: JsonPointer,
+    callback: Callback<'f>
+}
+
+/// Extracts selected values from a document while it streams in.
+/// Patterns are glob-style JSON Pointers as in `JsonPointer::select_all`;
+/// only matching values are materialized, everything else is skipped.
+#[derive(Default)]
+pub struct Subscriptions<'f> {
+    routes: Vec<Route<'f>>
+}
+
+impl<'f> Subscriptions<'f> {
+    pub fn new() -> Subscriptions<'f> {
+        Subscriptions { routes: Vec::new() }
+    }
+
+    /// Calls `callback` with the concrete pointer and value of every match of `pattern`.
+    pub fn subscribe<F>(&mut self, pattern : &JsonPointer, callback : F) -> &mut Subscriptions<'f>
+        where F : FnMut(&str, JsonNode) + 'f
+    {
+        self.routes.push(Route { pattern: pattern.clone(), callback: Box::new(callback) });
+        self
+    }
+
+    /// Streams one document from `source`, returning the number of values delivered.
+    pub fn run<R : Read>(&mut self, source : R) -> Result<usize, EventError> {
+        let mut reader = EventReader::new(source);
+        match reader.next_event()? {
+            Some(first) => self.visit(&mut reader, &mut JsonPointer::root(), first),
+            None => Ok(0)
+        }
+    }
+
+    fn visit<R : Read>(&mut self, reader : &mut EventReader<R>, path : &mut JsonPointer, event : JsonEvent) -> Result<usize, EventError> {
+        maybe_grow(|| {
+            if self.routes.iter().any(|route| route.pattern.glob_matches(path)) {
+                let value = reader.read_value(event)?;
+                return Ok(self.deliver(path, &value))
+            }
+
+            let wanted = self.wants_below(path);
+            let mut delivered = 0;
+            match event {
+                JsonEvent::StartArray if wanted => {
+                    let mut index = 0;
+                    loop {
+                        match next(reader)? {
+                            JsonEvent::EndArray => break,
+                            element => {
+                                path.push(index.to_string());
+                                delivered += self.visit(reader, path, element)?;
+                                path.pop();
+                                index += 1
+                            }
+                        }
+                    }
+                },
+                JsonEvent::StartObject if wanted => {
+                    while let JsonEvent::Key(key) = next(reader)? {
+                        let member = next(reader)?;
+                        path.push(key);
+                        delivered += self.visit(reader, path, member)?;
+                        path.pop();
+                    }
+                },
+                other => reader.skip_value(other)?
+            }
+            Ok(delivered)
+        })
+    }
+
+    // Delivers matches inside an already materialized value, innermost first,
+    // in the same order a streaming pass would complete them.
+    fn deliver(&mut self, path : &mut JsonPointer, value : &JsonNode) -> usize {
+        maybe_grow(|| {
+            let mut delivered = 0;
+            if self.wants_below(path) {
+                match value {
+                    JsonNode::Array(a) => for (i, child) in a.iter().enumerate() {
+                        path.push(i.to_string());
+                        delivered += self.deliver(path, child);
+                        path.pop();
+                    },
+                    JsonNode::Object(o) => for (key, child) in o {
+                        path.push(key.as_str());
+                        delivered += self.deliver(path, child);
+                        path.pop();
+                    },
+                    _ => ()
+                }
+            }
+
+            let pointer = path.to_string();
+            for route in self.routes.iter_mut().filter(|route| route.pattern.glob_matches(path)) {
+                (route.callback)(&pointer, value.clone());
+                delivered += 1
+            }
+            delivered
+        })
+    }
+
+    fn wants_below(&self, path : &JsonPointer) -> bool {
+        self.routes.iter().any(|route| route.pattern.glob_matches_below(path))
+    }
+}
+
+fn next<R : Read>(reader : &mut EventReader<R>) -> Result<JsonEvent, EventError> {
+    reader.next_event()?.ok_or_else(|| EventError::UnexpectedEnd(reader.offset()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn pointer(text : &str) -> JsonPointer {
+        JsonPointer::parse(text).unwrap()
+    }
+
+    const FEED : &str = "{\"users\":[{\"email\":\"a@x\",\"bio\":[1,2,3]},{\"email\":\"b@x\"}],\"count\":2}";
+
+    #[test]
+    fn test_wildcard_subscription() {
+        let mut emails = Vec::new();
+        let delivered = Subscriptions::new()
+            .subscribe(&pointer("/users/*/email"), |path, value| emails.push((path.to_string(), value)))
+            .run(FEED.as_bytes())
+            .unwrap();
+        assert_eq!(delivered, 2);
+        assert_eq!(emails, vec![
+            ("/users/0/email".to_string(), JsonNode::String("a@x".to_string())),
+            ("/users/1/email".to_string(), JsonNode::String("b@x".to_string()))
+        ]);
+    }
+
+    #[test]
+    fn test_any_depth_subscription() {
+        let mut paths = Vec::new();
+        Subscriptions::new()
+            .subscribe(&pointer("/**/b?o"), |path, _| paths.push(path.to_string()))
+            .run(FEED.as_bytes())
+            .unwrap();
+        assert_eq!(paths, vec!["/users/0/bio"]);
+    }
+
+    #[test]
+    fn test_several_subscriptions() {
+        let mut count = None;
+        let mut whole = None;
+        Subscriptions::new()
+            .subscribe(&pointer("/count"), |_, value| count = Some(value))
+            .subscribe(&pointer(""), |_, value| whole = Some(value))
+            .run(FEED.as_bytes())
+            .unwrap();
+        assert_eq!(count, Some(JsonNode::Number(2.0.into())));
+        assert_eq!(whole, Some(JsonNode::from_str(FEED).unwrap()));
+    }
+
+    #[test]
+    fn test_errors_are_reported() {
+        let result = Subscriptions::new().subscribe(&pointer("/a"), |_, _| ()).run("{\"a\":".as_bytes());
+        assert!(matches!(result, Err(EventError::UnexpectedEnd(5))));
+    }
+}
@@ -0,0 +1,113 @@
+use std::fmt;
+use crate::json::{maybe_grow, JsonNode, Object};
+use crate::json::pointer::array_index;
+
+#[derive(PartialEq, Debug)]
+pub struct TemplateError {
+    pub placeholder: String,
+    pub message: &'static str
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "cannot render \"{}\": {}", self.placeholder, self.message)
+    }
+}
+
+/// Fills the placeholders of `template` from `context`. A string that is a
+/// single placeholder such as `"${user.name}"`, and an object whose only
+/// member is `"$var": "user.name"`, become the value at that dotted path,
+/// whatever its type. Placeholders inside longer strings and object keys are
+/// replaced by text: strings as they are, other values as JSON. `$${` writes
+/// a literal `${`, and the empty path stands for the whole context.
+pub fn render(template : &JsonNode, context : &JsonNode) -> Result<JsonNode, TemplateError> {
+    maybe_grow(|| match template {
+        JsonNode::String(s) => match whole_placeholder(s) {
+            Some(path) => lookup(context, path).cloned(),
+            None => interpolate(s, context).map(JsonNode::String)
+        },
+        JsonNode::Array(a) => a.iter().map(|item| render(item, context)).collect::<Result<_, _>>().map(JsonNode::Array),
+        JsonNode::Object(o) => {
+            if let (1, Some(JsonNode::String(path))) = (o.len(), o.get("$var")) {
+                return lookup(context, path).cloned()
+            }
+            let mut out = Object::with_capacity_and_hasher(o.len(), Default::default());
+            for (key, value) in o {
+                out.insert(interpolate(key, context)?, render(value, context)?);
+            }
+            Ok(JsonNode::Object(out))
+        },
+        other => Ok(other.clone())
+    })
+}
+
+fn whole_placeholder(s : &str) -> Option<&str> {
+    let path = s.strip_prefix("${")?.strip_suffix('}')?;
+    if path.contains('}') { None } else { Some(path) }
+}
+
+fn interpolate(s : &str, context : &JsonNode) -> Result<String, TemplateError> {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(start) = rest.find("${") {
+        if rest[..start].ends_with('$') {
+            out.push_str(&rest[..start - 1]);
+            out.push_str("${");
+            rest = &rest[start + 2..];
+            continue
+        }
+        out.push_str(&rest[..start]);
+        let end = rest[start..].find('}').ok_or_else(|| TemplateError { placeholder: rest[start..].to_string(), message: "unterminated placeholder" })?;
+        match lookup(context, &rest[start + 2..start + end])? {
+            JsonNode::String(value) => out.push_str(value),
+            value => out.push_str(&value.to_string())
+        }
+        rest = &rest[start + end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+fn lookup<'a>(context : &'a JsonNode, path : &str) -> Result<&'a JsonNode, TemplateError> {
+    let error = |message| TemplateError { placeholder: format!("${{{}}}", path), message };
+    if path.is_empty() {
+        return Ok(context)
+    }
+    let mut node = context;
+    for token in path.split('.') {
+        node = match node {
+            JsonNode::Object(o) => o.get(token).ok_or_else(|| error("no such member in the context"))?,
+            JsonNode::Array(a) => array_index(token).and_then(|i| a.get(i)).ok_or_else(|| error("no such element in the context"))?,
+            _ => return Err(error("the context has a scalar on the way"))
+        }
+    }
+    Ok(node)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    const CONTEXT : &str = "{\"user\":{\"name\":\"ann\",\"id\":7,\"roles\":[\"admin\",\"dev\"]},\"host\":\"example.org\"}";
+
+    #[test]
+    fn test_render() {
+        let template = JsonNode::from_str("{\"url\":\"https://${host}/users/${user.id}\",\"id\":\"${user.id}\",\
+            \"roles\":{\"$var\":\"user.roles\"},\"first\":\"${user.roles.0}\",\"${user.name}_seen\":true,\"raw\":\"$${host}\",\"n\":[1,null]}").unwrap();
+        let expected = JsonNode::from_str("{\"url\":\"https://example.org/users/7\",\"id\":7,\
+            \"roles\":[\"admin\",\"dev\"],\"first\":\"admin\",\"ann_seen\":true,\"raw\":\"${host}\",\"n\":[1,null]}").unwrap();
+        assert_eq!(render(&template, &JsonNode::from_str(CONTEXT).unwrap()).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_render_errors() {
+        let context = JsonNode::from_str(CONTEXT).unwrap();
+        let error = render(&JsonNode::from_str("[\"hi ${user.email}\"]").unwrap(), &context).unwrap_err();
+        assert_eq!(error.placeholder, "${user.email}");
+        assert!(render(&JsonNode::from_str("\"${user.roles.9}\"").unwrap(), &context).is_err());
+        assert!(render(&JsonNode::from_str("\"${host.x}\"").unwrap(), &context).is_err());
+        assert!(render(&JsonNode::from_str("\"a ${host\"").unwrap(), &context).is_err());
+        assert_eq!(render(&JsonNode::from_str("\"${}\"").unwrap(), &context).unwrap(), context);
+    }
+}
@@ -0,0 +1,617 @@
+// TOML, with the `toml` feature: reading TOML 1.0 documents into `JsonNode`
+// and writing objects back, for configuration such as Cargo.toml.
+
+use std::fmt;
+use crate::json::{maybe_grow, JsonNode, Number, Object};
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum TomlError {
+    /// The text is not TOML: on which line, counting from 1, and why.
+    Syntax { line: usize, message: String },
+    /// A value has no TOML form: the dotted key holding it, and why.
+    Unrepresentable { path: String, message: &'static str }
+}
+
+impl fmt::Display for TomlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TomlError::Syntax { line, message } => write!(f, "line {}: {}", line, message),
+            TomlError::Unrepresentable { path, message } => write!(f, "cannot write {} as TOML: {}", path, message)
+        }
+    }
+}
+
+impl JsonNode {
+    /// Reads a TOML document into an object: tables and inline tables become
+    /// objects, arrays of tables arrays of them, and dates and times their
+    /// text as strings. Keys and tables defined twice are errors.
+    pub fn from_toml_str(toml : &str) -> Result<JsonNode, TomlError> {
+        Reader { text: toml.trim_start_matches('\u{feff}'), position: 0 }.document()
+    }
+
+    /// This object as a TOML document: the plain values of each table come
+    /// first, then its objects as `[tables]` and its arrays of objects as
+    /// `[[arrays of tables]]`, members in key order unless the
+    /// `small_objects` or `preserve_order` feature keeps their order. Fails
+    /// on anything but an object, on null and on integers beyond an i64.
+    pub fn to_toml_string(&self) -> Result<String, TomlError> {
+        let mut out = String::new();
+        match self {
+            JsonNode::Object(members) => write_table(members, &mut Vec::new(), &mut out)?,
+            _ => return Err(TomlError::Unrepresentable { path: "the document".to_string(), message: "a TOML document is a table" })
+        }
+        Ok(out)
+    }
+}
+
+struct Reader<'a> {
+    text: &'a str,
+    position: usize
+}
+
+impl<'a> Reader<'a> {
+    fn rest(&self) -> &'a str {
+        &self.text[self.position..]
+    }
+
+    fn error<T>(&self, position : usize, message : &str) -> Result<T, TomlError> {
+        let line = self.text[..position].matches('\n').count() + 1;
+        Err(TomlError::Syntax { line, message: message.to_string() })
+    }
+
+    fn eat(&mut self, token : &str) -> bool {
+        let found = self.rest().starts_with(token);
+        if found {
+            self.position += token.len()
+        }
+        found
+    }
+
+    fn skip_spaces(&mut self) {
+        let rest = self.rest();
+        self.position += rest.len() - rest.trim_start_matches([' ', '\t']).len()
+    }
+
+    // past whitespace, line breaks and comments
+    fn skip_blank(&mut self) {
+        loop {
+            let rest = self.rest();
+            self.position += rest.len() - rest.trim_start_matches([' ', '\t', '\r', '\n']).len();
+            if !self.rest().starts_with('#') {
+                return
+            }
+            self.position += self.rest().find('\n').unwrap_or(self.rest().len())
+        }
+    }
+
+    fn end_of_line(&mut self) -> Result<(), TomlError> {
+        self.skip_spaces();
+        if self.rest().starts_with('#') {
+            self.position += self.rest().find('\n').unwrap_or(self.rest().len())
+        }
+        if self.rest().is_empty() || self.eat("\n") || self.eat("\r\n") {
+            return Ok(())
+        }
+        self.error(self.position, "expected the end of the line")
+    }
+
+    fn document(&mut self) -> Result<JsonNode, TomlError> {
+        let mut root = Object::default();
+        // the table that keys go into, and the headers seen so far
+        let mut current = Vec::new();
+        let mut defined : Vec<Vec<String>> = Vec::new();
+        loop {
+            self.skip_blank();
+            let start = self.position;
+            if self.rest().is_empty() {
+                return Ok(JsonNode::Object(root))
+            } else if self.eat("[[") {
+                let path = self.keys()?;
+                if !self.eat("]]") {
+                    return self.error(self.position, "expected ]] after the name of an array of tables")
+                }
+                let (last, parents) = path.split_last().expect("keys gives at least one key");
+                let parent = descend(&mut root, parents).or_else(|message| self.error(start, message))?;
+                match parent.entry(last.clone()).or_insert_with(|| JsonNode::Array(Vec::new())) {
+                    JsonNode::Array(tables) if tables.iter().all(|table| matches!(table, JsonNode::Object(_))) =>
+                        tables.push(JsonNode::Object(Object::default())),
+                    _ => return self.error(start, "the key already holds a value")
+                }
+                // tables within the previous element may be defined again in this one
+                defined.retain(|table| !table.starts_with(&path));
+                current = path
+            } else if self.eat("[") {
+                let path = self.keys()?;
+                if !self.eat("]") {
+                    return self.error(self.position, "expected ] after the name of a table")
+                }
+                if defined.contains(&path) {
+                    return self.error(start, "the table is already defined")
+                }
+                descend(&mut root, &path).or_else(|message| self.error(start, message))?;
+                defined.push(path.clone());
+                current = path
+            } else {
+                let table = descend(&mut root, &current).or_else(|message| self.error(start, message))?;
+                self.key_value(table)?
+            }
+            self.end_of_line()?
+        }
+    }
+
+    // `key = value`, put into `table`
+    fn key_value(&mut self, table : &mut Object) -> Result<(), TomlError> {
+        let start = self.position;
+        let keys = self.keys()?;
+        if !self.eat("=") {
+            return self.error(self.position, "expected = after a key")
+        }
+        self.skip_spaces();
+        let value = self.value()?;
+        let (last, parents) = keys.split_last().expect("keys gives at least one key");
+        let table = descend(table, parents).or_else(|message| self.error(start, message))?;
+        if table.contains_key(last) {
+            return self.error(start, &format!("duplicate key {:?}", last))
+        }
+        table.insert(last.clone(), value);
+        Ok(())
+    }
+
+    // a dotted key, and the spaces after it
+    fn keys(&mut self) -> Result<Vec<String>, TomlError> {
+        let mut keys = Vec::new();
+        loop {
+            self.skip_spaces();
+            let key = match self.rest().chars().next() {
+                Some('"') => self.basic_string()?,
+                Some('\'') => self.literal_string()?,
+                _ => {
+                    let rest = self.rest();
+                    let length = rest.find(|c : char| !(c.is_ascii_alphanumeric() || c == '_' || c == '-')).unwrap_or(rest.len());
+                    if length == 0 {
+                        return self.error(self.position, "expected a key")
+                    }
+                    self.position += length;
+                    rest[..length].to_string()
+                }
+            };
+            keys.push(key);
+            self.skip_spaces();
+            if !self.eat(".") {
+                return Ok(keys)
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<JsonNode, TomlError> {
+        maybe_grow(|| {
+            let start = self.position;
+            let rest = self.rest();
+            if rest.starts_with("\"\"\"") {
+                self.multiline_string("\"\"\"").map(JsonNode::String)
+            } else if rest.starts_with("'''") {
+                self.multiline_string("'''").map(JsonNode::String)
+            } else if rest.starts_with('"') {
+                self.basic_string().map(JsonNode::String)
+            } else if rest.starts_with('\'') {
+                self.literal_string().map(JsonNode::String)
+            } else if self.eat("[") {
+                let mut items = Vec::new();
+                loop {
+                    self.skip_blank();
+                    if self.eat("]") {
+                        return Ok(JsonNode::Array(items))
+                    }
+                    items.push(self.value()?);
+                    self.skip_blank();
+                    if !self.eat(",") && !self.rest().starts_with(']') {
+                        return self.error(self.position, "expected , or ] in an array")
+                    }
+                }
+            } else if self.eat("{") {
+                let mut object = Object::default();
+                self.skip_spaces();
+                if !self.eat("}") {
+                    loop {
+                        self.key_value(&mut object)?;
+                        self.skip_spaces();
+                        if self.eat("}") {
+                            break
+                        }
+                        if !self.eat(",") {
+                            return self.error(self.position, "expected , or } in an inline table")
+                        }
+                    }
+                }
+                Ok(JsonNode::Object(object))
+            } else {
+                let mut length = rest.find([' ', '\t', '\r', '\n', ',', ']', '}', '#']).unwrap_or(rest.len());
+                // a date and a time may stand apart
+                if shape(&rest[..length], "9999-99-99") && rest[length..].starts_with(' ') && rest[length + 1..].get(..3).is_some_and(|time| shape(time, "99:")) {
+                    length += 1 + rest[length + 1..].find([' ', '\t', '\r', '\n', ',', ']', '}', '#']).unwrap_or(rest.len() - length - 1)
+                }
+                let token = &rest[..length];
+                self.position += length;
+                match token {
+                    "true" => Ok(JsonNode::Boolean(true)),
+                    "false" => Ok(JsonNode::Boolean(false)),
+                    _ if is_datetime(token) => Ok(JsonNode::String(token.to_string())),
+                    _ => match number(token) {
+                        Some(n) => Ok(JsonNode::Number(n)),
+                        None => self.error(start, "expected a value")
+                    }
+                }
+            }
+        })
+    }
+
+    fn basic_string(&mut self) -> Result<String, TomlError> {
+        let start = self.position;
+        self.position += 1;
+        let mut out = String::new();
+        while let Some(c) = self.rest().chars().next() {
+            self.position += c.len_utf8();
+            match c {
+                '"' => return Ok(out),
+                '\\' => out.push(self.escape()?),
+                '\n' => break,
+                c if c.is_control() && c != '\t' => return self.error(self.position, "control characters must be escaped"),
+                c => out.push(c)
+            }
+        }
+        self.error(start, "the string does not end on its line")
+    }
+
+    fn literal_string(&mut self) -> Result<String, TomlError> {
+        let start = self.position;
+        let rest = &self.rest()[1..];
+        match rest.find(['\'', '\n']) {
+            Some(end) if rest[end..].starts_with('\'') => {
+                self.position += end + 2;
+                Ok(rest[..end].to_string())
+            },
+            _ => self.error(start, "the string does not end on its line")
+        }
+    }
+
+    // a `"""` or `'''` string, in which a line break right after the opening
+    // quotes is left out and, in the first, a `\` at the end of a line takes
+    // the whitespace after it out
+    fn multiline_string(&mut self, quotes : &str) -> Result<String, TomlError> {
+        let start = self.position;
+        self.position += 3;
+        let _ = self.eat("\n") || self.eat("\r\n");
+        let mut out = String::new();
+        while let Some(c) = self.rest().chars().next() {
+            if self.rest().starts_with(quotes) {
+                // up to two quotes may come right before the closing ones
+                let run = self.rest().len() - self.rest().trim_start_matches(c).len();
+                if run > 5 {
+                    return self.error(self.position, "too many quotes in a row")
+                }
+                out.push_str(&quotes[..run - 3]);
+                self.position += run;
+                return Ok(out)
+            }
+            self.position += c.len_utf8();
+            match c {
+                '\\' if quotes == "\"\"\"" => {
+                    let rest = self.rest();
+                    let trimmed = rest.trim_start_matches([' ', '\t']);
+                    if trimmed.starts_with('\n') || trimmed.starts_with("\r\n") {
+                        self.position += rest.len() - rest.trim_start().len()
+                    } else {
+                        out.push(self.escape()?)
+                    }
+                },
+                '\r' if self.rest().starts_with('\n') => (),
+                c if c.is_control() && c != '\t' && c != '\n' => return self.error(self.position, "control characters must be escaped"),
+                c => out.push(c)
+            }
+        }
+        self.error(start, "the string does not end")
+    }
+
+    // the character an escape stands for, after its backslash
+    fn escape(&mut self) -> Result<char, TomlError> {
+        let start = self.position - 1;
+        let c = match self.rest().chars().next() {
+            Some('b') => '\x08',
+            Some('t') => '\t',
+            Some('n') => '\n',
+            Some('f') => '\x0c',
+            Some('r') => '\r',
+            Some('"') => '"',
+            Some('\\') => '\\',
+            Some(u @ 'u') | Some(u @ 'U') => {
+                let length = if u == 'u' { 4 } else { 8 };
+                let hex = self.rest().get(1..=length).filter(|hex| hex.chars().all(|c| c.is_ascii_hexdigit()));
+                let c = hex.and_then(|hex| u32::from_str_radix(hex, 16).ok()).and_then(char::from_u32);
+                match c {
+                    Some(c) => {
+                        self.position += length;
+                        c
+                    },
+                    None => return self.error(start, "invalid unicode escape")
+                }
+            },
+            _ => return self.error(start, "unknown escape")
+        };
+        self.position += 1;
+        Ok(c)
+    }
+}
+
+// the table at `keys` under `table`, made as needed; an array of tables
+// stands for its last element
+fn descend<'t>(mut table : &'t mut Object, keys : &[String]) -> Result<&'t mut Object, &'static str> {
+    for key in keys {
+        table = match table.entry(key.clone()).or_insert_with(|| JsonNode::Object(Object::default())) {
+            JsonNode::Object(members) => members,
+            JsonNode::Array(items) => match items.last_mut() {
+                Some(JsonNode::Object(members)) => members,
+                _ => return Err("the key already holds a value")
+            },
+            _ => return Err("the key already holds a value")
+        }
+    }
+    Ok(table)
+}
+
+// whether `s` matches `pattern`, in which 9 stands for any digit
+fn shape(s : &str, pattern : &str) -> bool {
+    s.len() == pattern.len() && s.bytes().zip(pattern.bytes()).all(|(c, p)| if p == b'9' { c.is_ascii_digit() } else { c == p })
+}
+
+// whether `token` is an offset or local date-time, a local date or a local time
+fn is_datetime(token : &str) -> bool {
+    let date = token.get(..10).is_some_and(|date| shape(date, "9999-99-99"));
+    let time = match (date, token.get(10..)) {
+        (true, Some("")) => return true,
+        (true, Some(time)) if time.starts_with(['T', 't', ' ']) => &time[1..],
+        (true, _) => return false,
+        (false, _) => token
+    };
+    if !time.get(..8).is_some_and(|time| shape(time, "99:99:99")) {
+        return false
+    }
+    let mut rest = &time[8..];
+    if let Some(fraction) = rest.strip_prefix('.') {
+        let digits = fraction.find(|c : char| !c.is_ascii_digit()).unwrap_or(fraction.len());
+        if digits == 0 {
+            return false
+        }
+        rest = &fraction[digits..]
+    }
+    rest.is_empty() || date && (rest == "Z" || rest == "z" || rest.starts_with(['+', '-']) && shape(&rest[1..], "99:99"))
+}
+
+// an integer, which fits in an i64, or a float
+fn number(token : &str) -> Option<Number> {
+    // underscores only between digits
+    let bytes = token.as_bytes();
+    let separated = bytes.iter().enumerate().all(|(i, &b)| b != b'_'
+        || i > 0 && bytes[i - 1].is_ascii_hexdigit() && bytes.get(i + 1).is_some_and(u8::is_ascii_hexdigit));
+    if !separated {
+        return None
+    }
+    let token = token.replace('_', "");
+    let unsigned = token.strip_prefix(['+', '-']).unwrap_or(&token);
+    match unsigned {
+        "inf" => return Some(Number::from(if token.starts_with('-') { f64::NEG_INFINITY } else { f64::INFINITY })),
+        "nan" => return Some(Number::from(f64::NAN)),
+        _ => ()
+    }
+    for (prefix, radix) in [("0x", 16), ("0o", 8), ("0b", 2)] {
+        if let Some(digits) = token.strip_prefix(prefix) {
+            return i64::from_str_radix(digits, radix).ok().filter(|_| !digits.starts_with(['+', '-'])).map(Number::from)
+        }
+    }
+    let (mantissa, exponent) = match unsigned.find(['e', 'E']) {
+        Some(e) => (&unsigned[..e], Some(&unsigned[e + 1..])),
+        None => (unsigned, None)
+    };
+    let (whole, fraction) = match mantissa.split_once('.') {
+        Some((whole, fraction)) => (whole, Some(fraction)),
+        None => (mantissa, None)
+    };
+    let digits = |s : &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    let valid = digits(whole) && (whole == "0" || !whole.starts_with('0'))
+        && fraction.is_none_or(digits)
+        && exponent.is_none_or(|e| digits(e.strip_prefix(['+', '-']).unwrap_or(e)));
+    match (valid, fraction.is_none() && exponent.is_none()) {
+        (false, _) => None,
+        (true, true) => token.parse::<i64>().ok().map(Number::from),
+        (true, false) => token.parse::<f64>().ok().map(Number::from)
+    }
+}
+
+fn is_table_array(node : &JsonNode) -> bool {
+    match node {
+        JsonNode::Array(items) => !items.is_empty() && items.iter().all(|item| matches!(item, JsonNode::Object(_))),
+        _ => false
+    }
+}
+
+fn sorted(members : &Object) -> Vec<(&String, &JsonNode)> {
+    let mut members : Vec<(&String, &JsonNode)> = members.iter().collect();
+    if !cfg!(feature = "small_objects") {
+        members.sort_by_key(|&(key, _)| key)
+    }
+    members
+}
+
+// the members of the table at `path`: plain values, then tables and arrays
+// of tables under headers of their own
+fn write_table(members : &Object, path : &mut Vec<String>, out : &mut String) -> Result<(), TomlError> {
+    maybe_grow(|| {
+        let members = sorted(members);
+        for &(key, value) in &members {
+            if !matches!(value, JsonNode::Object(_)) && !is_table_array(value) {
+                path.push(key_text(key));
+                out.push_str(&key_text(key));
+                out.push_str(" = ");
+                write_value(value, path, out)?;
+                out.push('\n');
+                path.pop();
+            }
+        }
+        for &(key, value) in &members {
+            path.push(key_text(key));
+            match value {
+                JsonNode::Object(table) => {
+                    header(path, "[", "]", out);
+                    write_table(table, path, out)?
+                },
+                JsonNode::Array(tables) if is_table_array(value) => for table in tables {
+                    header(path, "[[", "]]", out);
+                    if let JsonNode::Object(table) = table {
+                        write_table(table, path, out)?
+                    }
+                },
+                _ => ()
+            }
+            path.pop();
+        }
+        Ok(())
+    })
+}
+
+fn header(path : &[String], open : &str, close : &str, out : &mut String) {
+    if !out.is_empty() {
+        out.push('\n')
+    }
+    out.push_str(open);
+    out.push_str(&path.join("."));
+    out.push_str(close);
+    out.push('\n')
+}
+
+// `key` bare if it can be, and quoted otherwise
+fn key_text(key : &str) -> String {
+    if !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+        key.to_string()
+    } else {
+        string(key)
+    }
+}
+
+fn string(s : &str) -> String {
+    JsonNode::String(s.to_string()).to_string().replace('\u{7f}', "\\u007F")
+}
+
+// a value on the rest of a line
+fn write_value(node : &JsonNode, path : &[String], out : &mut String) -> Result<(), TomlError> {
+    maybe_grow(|| {
+        let unrepresentable = |message| TomlError::Unrepresentable { path: path.join("."), message };
+        match node {
+            JsonNode::Null => return Err(unrepresentable("TOML has no null")),
+            JsonNode::Boolean(b) => out.push_str(if *b { "true" } else { "false" }),
+            JsonNode::String(s) => out.push_str(&string(s)),
+            JsonNode::Number(n) if !n.is_f64() => match n.as_i64() {
+                Some(i) => out.push_str(&i.to_string()),
+                None => return Err(unrepresentable("TOML integers fit in an i64"))
+            },
+            JsonNode::Number(n) => {
+                let f = n.as_f64();
+                if f.is_nan() {
+                    out.push_str("nan")
+                } else if f.is_infinite() {
+                    out.push_str(if f > 0.0 { "inf" } else { "-inf" })
+                } else {
+                    let text = f.to_string();
+                    out.push_str(&text);
+                    // a float without a point would read back as an integer
+                    if !text.contains('.') {
+                        out.push_str(".0")
+                    }
+                }
+            },
+            JsonNode::Array(items) => {
+                out.push('[');
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        out.push_str(", ")
+                    }
+                    write_value(item, path, out)?
+                }
+                out.push(']')
+            },
+            JsonNode::Object(members) if members.is_empty() => out.push_str("{}"),
+            JsonNode::Object(members) => {
+                out.push_str("{ ");
+                for (i, (key, value)) in sorted(members).into_iter().enumerate() {
+                    if i > 0 {
+                        out.push_str(", ")
+                    }
+                    out.push_str(&key_text(key));
+                    out.push_str(" = ");
+                    write_value(value, &[path, &[key_text(key)]].concat(), out)?
+                }
+                out.push_str(" }")
+            }
+        }
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn json(text : &str) -> JsonNode {
+        JsonNode::from_str(text).unwrap()
+    }
+
+    #[test]
+    fn test_reads_toml() {
+        let toml = "# a manifest\n[package]\nname = \"demo\"   # trailing comment\nversion = '0.1.0'\nauthors = [\n  \"a\",\n  \"b\",  # the second\n]\n\n\
+            [dependencies]\nserde = { version = \"1\", features = [\"derive\"] }\nsite.\"google.com\" = true\n\n\
+            [[bin]]\nname = \"one\"\n[bin.extra]\nx = 1_000\n\n[[bin]]\nname = \"two\"\n[bin.extra]\nx = 0xff\n\n\
+            [numbers]\nfloat = -1.5e3\nzero = +0.0\noctal = 0o17\nbinary = 0b101\ninfinite = -inf\n\
+            [dates]\nodt = 1979-05-27T07:32:00Z\nspaced = 1979-05-27 07:32:00.999-07:00\nday = 1979-05-27\ntime = 07:32:00\n\
+            [strings]\nbasic = \"tab\\t\\u00e9\\\"\"\nraw = 'C:\\path'\nlines = \"\"\"\nfirst\n  second \\\n   joined\"\"\"\nliteral = '''\n\\n stays'''\nquoted = \"\"\"a \"\"quote\"\"\"\"\"\n";
+        assert_eq!(JsonNode::from_toml_str(toml).unwrap(), json("{\"package\":{\"name\":\"demo\",\"version\":\"0.1.0\",\"authors\":[\"a\",\"b\"]},\
+            \"dependencies\":{\"serde\":{\"version\":\"1\",\"features\":[\"derive\"]},\"site\":{\"google.com\":true}},\
+            \"bin\":[{\"name\":\"one\",\"extra\":{\"x\":1000}},{\"name\":\"two\",\"extra\":{\"x\":255}}],\
+            \"numbers\":{\"float\":-1500.0,\"zero\":0.0,\"octal\":15,\"binary\":5,\"infinite\":-1e999},\
+            \"dates\":{\"odt\":\"1979-05-27T07:32:00Z\",\"spaced\":\"1979-05-27 07:32:00.999-07:00\",\"day\":\"1979-05-27\",\"time\":\"07:32:00\"},\
+            \"strings\":{\"basic\":\"tab\\t\\u00e9\\\"\",\"raw\":\"C:\\\\path\",\"lines\":\"first\\n  second joined\",\"literal\":\"\\\\n stays\",\"quoted\":\"a \\\"\\\"quote\\\"\\\"\"}}"));
+        assert_eq!(JsonNode::from_toml_str("").unwrap(), json("{}"));
+    }
+
+    #[test]
+    fn test_reports_errors() {
+        let line = |toml : &str| match JsonNode::from_toml_str(toml).unwrap_err() {
+            TomlError::Syntax { line, .. } => line,
+            error => panic!("not a syntax error: {}", error)
+        };
+        assert_eq!(line("a = 1\na = 2"), 2);
+        assert_eq!(line("[t]\n[t]"), 2);
+        assert_eq!(line("a = 1\n[a]"), 2);
+        assert_eq!(line("a = 1\n\nb = 01"), 3);
+        assert_eq!(line("a = \"open\nb = 1"), 1);
+        assert_eq!(line("a = 1 b = 2"), 1);
+        assert_eq!(line("a = 1__0"), 1);
+        assert_eq!(line("a = { b = 1,\n}"), 1);
+        assert_eq!(line("a = \"\\q\""), 1);
+    }
+
+    #[test]
+    fn test_writes_and_reads_back() {
+        let node = json("{\"title\":\"x\",\"owner\":{\"name\":\"t\",\"dob\":\"1979-05-27\"},\"ports\":[8000,8001],\"ratio\":2.0,\
+            \"points\":[{\"x\":1,\"y\":{}},{\"x\":2,\"y\":{\"z\":[1,{\"w\":true}]}}],\"a b\":{\"c.d\":[]},\"mixed\":[1,\"a\",{\"k\":-0.5}]}");
+        let toml = node.to_toml_string().unwrap();
+        assert_eq!(JsonNode::from_toml_str(&toml).unwrap(), node);
+        if !cfg!(feature = "small_objects") {
+            assert_eq!(toml, "mixed = [1, \"a\", { k = -0.5 }]\nports = [8000, 8001]\nratio = 2.0\ntitle = \"x\"\n\n\
+                [\"a b\"]\n\"c.d\" = []\n\n[owner]\ndob = \"1979-05-27\"\nname = \"t\"\n\n\
+                [[points]]\nx = 1\n\n[points.y]\n\n[[points]]\nx = 2\n\n[points.y]\nz = [1, { w = true }]\n");
+        }
+        let unrepresentable = |text : &str| json(text).to_toml_string().unwrap_err();
+        assert_eq!(unrepresentable("{\"a\":{\"b\":[1,null]}}"), TomlError::Unrepresentable { path: "a.b".to_string(), message: "TOML has no null" });
+        assert_eq!(unrepresentable("{\"big\":18446744073709551615}").to_string(), "cannot write big as TOML: TOML integers fit in an i64");
+        assert!(matches!(unrepresentable("[]"), TomlError::Unrepresentable { .. }));
+    }
+}
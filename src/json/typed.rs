@@ -0,0 +1,710 @@
+// JSON as a serde data format, with the `serde` feature: `from_str` decodes
+// any `Deserialize` type straight from the input with this crate's parser,
+// and `to_string` writes any `Serialize` type the way `Display` writes a
+// `JsonNode`.
+
+use std::error;
+use std::fmt;
+use serde::de::{self, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess, SeqAccess, VariantAccess, Visitor};
+use serde::ser::{self, Serialize};
+use crate::json::{maybe_grow, JsonNode, JsonParseError, Number, Object};
+use crate::json::parser::{parse_json_escaped_string, parse_json_number};
+use crate::json::scan::{self, is_whitespace};
+use crate::json::serialize::{self as text, Serializer as TextSerializer};
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum Error {
+    /// The input is not JSON.
+    Syntax(JsonParseError),
+    /// Something other than whitespace follows the document, at this offset.
+    TrailingData(usize),
+    /// The input does not have the shape the type expects, or a value
+    /// cannot be written as JSON.
+    Custom(String)
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Syntax(reason) => write!(f, "{}", reason),
+            Error::TrailingData(offset) => write!(f, "unexpected data after the document at byte {}", offset),
+            Error::Custom(message) => f.write_str(message)
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+impl de::Error for Error {
+    fn custom<T : fmt::Display>(message : T) -> Error {
+        Error::Custom(message.to_string())
+    }
+}
+
+impl ser::Error for Error {
+    fn custom<T : fmt::Display>(message : T) -> Error {
+        Error::Custom(message.to_string())
+    }
+}
+
+/// Decodes a `T` from the document in `json`, which may have nothing but
+/// whitespace after it.
+pub fn from_str<'de, T : de::Deserialize<'de>>(json : &'de str) -> Result<T, Error> {
+    from_slice(json.as_bytes())
+}
+
+pub fn from_slice<'de, T : de::Deserialize<'de>>(json : &'de [u8]) -> Result<T, Error> {
+    let mut deserializer = Deserializer::new(json);
+    let value = T::deserialize(&mut deserializer)?;
+    deserializer.end()?;
+    Ok(value)
+}
+
+/// Encodes `value` as compact JSON.
+pub fn to_string<T : Serialize + ?Sized>(value : &T) -> Result<String, Error> {
+    Ok(to_node(value)?.to_string())
+}
+
+/// Encodes `value` like `JsonNode::to_string_pretty`.
+pub fn to_string_pretty<T : Serialize + ?Sized>(value : &T) -> Result<String, Error> {
+    Ok(TextSerializer::new().indent(text::INDENT).to_string(&to_node(value)?))
+}
+
+/// Converts `value` to the document it would be written as.
+pub fn to_node<T : Serialize + ?Sized>(value : &T) -> Result<JsonNode, Error> {
+    value.serialize(NodeSerializer)
+}
+
+// strings without escapes are lent straight out of the input
+enum Text<'de> {
+    Borrowed(&'de str),
+    Owned(String)
+}
+
+/// Reads serde data model values out of JSON text as the type being
+/// decoded asks for them.
+pub struct Deserializer<'de> {
+    whole: &'de [u8],
+    input: &'de [u8]
+}
+
+impl<'de> Deserializer<'de> {
+    pub fn new(input : &'de [u8]) -> Deserializer<'de> {
+        Deserializer { whole: input, input }
+    }
+
+    /// Checks that only whitespace is left after the document.
+    pub fn end(&mut self) -> Result<(), Error> {
+        match self.peek() {
+            None => Ok(()),
+            Some(_) => Err(Error::TrailingData(self.offset()))
+        }
+    }
+
+    fn offset(&self) -> usize {
+        self.whole.len() - self.input.len()
+    }
+
+    fn syntax_error(&self) -> Error {
+        Error::Syntax(JsonParseError::locate(self.whole, self.offset()))
+    }
+
+    // the next byte that is not whitespace, which is left unread
+    fn peek(&mut self) -> Option<u8> {
+        let blank = self.input.iter().take_while(|b| is_whitespace(**b)).count();
+        self.input = &self.input[blank..];
+        self.input.first().copied()
+    }
+
+    fn eat(&mut self, byte : u8) -> Result<(), Error> {
+        if self.peek() == Some(byte) {
+            self.input = &self.input[1..];
+            Ok(())
+        } else {
+            Err(self.syntax_error())
+        }
+    }
+
+    fn literal(&mut self, word : &[u8]) -> Result<(), Error> {
+        if self.input.starts_with(word) {
+            self.input = &self.input[word.len()..];
+            Ok(())
+        } else {
+            Err(self.syntax_error())
+        }
+    }
+
+    fn number(&mut self) -> Result<Number, Error> {
+        let parsed = match parse_json_number(self.input) {
+            Err(nom::Err::Incomplete(_)) => {
+                // a number at the very end only completes with a terminator
+                let mut terminated = self.input.to_vec();
+                terminated.push(b' ');
+                parse_json_number(&terminated).map(|(rest, node)| (rest.len() - 1, node))
+                    .map_err(|_| self.syntax_error())?
+            },
+            parsed => parsed.map(|(rest, node)| (rest.len(), node)).map_err(|_| self.syntax_error())?
+        };
+        match parsed {
+            (left, JsonNode::Number(n)) => {
+                self.input = &self.input[self.input.len() - left..];
+                Ok(n)
+            },
+            _ => unreachable!("the number parser makes numbers")
+        }
+    }
+
+    fn string(&mut self) -> Result<Text<'de>, Error> {
+        let end = match scan::string_end(self.input, 0) {
+            Some(end) => end,
+            None => {
+                self.input = &self.input[self.input.len()..];
+                return Err(self.syntax_error())
+            }
+        };
+        let body = &self.input[1..end - 1];
+        // escapes, and control characters that should have been escaped, are left to the full parser
+        if !body.iter().any(|b| *b == b'\\' || *b < 0x20) {
+            if let Ok(s) = std::str::from_utf8(body) {
+                self.input = &self.input[end..];
+                return Ok(Text::Borrowed(s))
+            }
+        }
+        match parse_json_escaped_string(self.input) {
+            Ok((rest, s)) => {
+                self.input = rest;
+                Ok(Text::Owned(s))
+            },
+            Err(_) => Err(self.syntax_error())
+        }
+    }
+}
+
+impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V : Visitor<'de>>(self, visitor : V) -> Result<V::Value, Error> {
+        maybe_grow(|| match self.peek() {
+            Some(b'n') => {
+                self.literal(b"null")?;
+                visitor.visit_unit()
+            },
+            Some(b't') => {
+                self.literal(b"true")?;
+                visitor.visit_bool(true)
+            },
+            Some(b'f') => {
+                self.literal(b"false")?;
+                visitor.visit_bool(false)
+            },
+            Some(b'"') => match self.string()? {
+                Text::Borrowed(s) => visitor.visit_borrowed_str(s),
+                Text::Owned(s) => visitor.visit_string(s)
+            },
+            Some(b'[') => {
+                self.input = &self.input[1..];
+                let value = visitor.visit_seq(Elements { de: self, first: true })?;
+                self.eat(b']')?;
+                Ok(value)
+            },
+            Some(b'{') => {
+                self.input = &self.input[1..];
+                let value = visitor.visit_map(Members { de: self, first: true })?;
+                self.eat(b'}')?;
+                Ok(value)
+            },
+            Some(_) => {
+                let n = self.number()?;
+                match (n.as_u64(), n.as_i64()) {
+                    (Some(u), _) => visitor.visit_u64(u),
+                    (None, Some(i)) => visitor.visit_i64(i),
+                    (None, None) => visitor.visit_f64(n.as_f64())
+                }
+            },
+            None => Err(self.syntax_error())
+        })
+    }
+
+    fn deserialize_option<V : Visitor<'de>>(self, visitor : V) -> Result<V::Value, Error> {
+        if self.peek() == Some(b'n') {
+            self.literal(b"null")?;
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_newtype_struct<V : Visitor<'de>>(self, _ : &'static str, visitor : V) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    /// Unit variants are strings; the others are objects of one member,
+    /// named after the variant.
+    fn deserialize_enum<V : Visitor<'de>>(self, _ : &'static str, _ : &'static [&'static str], visitor : V) -> Result<V::Value, Error> {
+        match self.peek() {
+            Some(b'"') => match self.string()? {
+                Text::Borrowed(s) => visitor.visit_enum(s.into_deserializer()),
+                Text::Owned(s) => visitor.visit_enum(s.into_deserializer())
+            },
+            Some(b'{') => {
+                self.input = &self.input[1..];
+                let value = visitor.visit_enum(Variant { de: self })?;
+                self.eat(b'}')?;
+                Ok(value)
+            },
+            _ => Err(de::Error::custom("expected a string or an object for an enum"))
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
+
+struct Elements<'a, 'de> {
+    de: &'a mut Deserializer<'de>,
+    first: bool
+}
+
+impl<'de> SeqAccess<'de> for Elements<'_, 'de> {
+    type Error = Error;
+
+    fn next_element_seed<T : DeserializeSeed<'de>>(&mut self, seed : T) -> Result<Option<T::Value>, Error> {
+        if self.de.peek() == Some(b']') {
+            return Ok(None)
+        }
+        if !self.first {
+            self.de.eat(b',')?
+        }
+        self.first = false;
+        seed.deserialize(&mut *self.de).map(Some)
+    }
+}
+
+struct Members<'a, 'de> {
+    de: &'a mut Deserializer<'de>,
+    first: bool
+}
+
+impl<'de> MapAccess<'de> for Members<'_, 'de> {
+    type Error = Error;
+
+    fn next_key_seed<K : DeserializeSeed<'de>>(&mut self, seed : K) -> Result<Option<K::Value>, Error> {
+        if self.de.peek() == Some(b'}') {
+            return Ok(None)
+        }
+        if !self.first {
+            self.de.eat(b',')?
+        }
+        self.first = false;
+        if self.de.peek() != Some(b'"') {
+            return Err(self.de.syntax_error())
+        }
+        seed.deserialize(&mut *self.de).map(Some)
+    }
+
+    fn next_value_seed<V : DeserializeSeed<'de>>(&mut self, seed : V) -> Result<V::Value, Error> {
+        self.de.eat(b':')?;
+        seed.deserialize(&mut *self.de)
+    }
+}
+
+struct Variant<'a, 'de> {
+    de: &'a mut Deserializer<'de>
+}
+
+impl<'de> EnumAccess<'de> for Variant<'_, 'de> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V : DeserializeSeed<'de>>(self, seed : V) -> Result<(V::Value, Self), Error> {
+        if self.de.peek() != Some(b'"') {
+            return Err(self.de.syntax_error())
+        }
+        let variant = seed.deserialize(&mut *self.de)?;
+        self.de.eat(b':')?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de> VariantAccess<'de> for Variant<'_, 'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        de::Deserialize::deserialize(self.de)
+    }
+
+    fn newtype_variant_seed<T : DeserializeSeed<'de>>(self, seed : T) -> Result<T::Value, Error> {
+        seed.deserialize(self.de)
+    }
+
+    fn tuple_variant<V : Visitor<'de>>(self, _ : usize, visitor : V) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_seq(self.de, visitor)
+    }
+
+    fn struct_variant<V : Visitor<'de>>(self, _ : &'static [&'static str], visitor : V) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_map(self.de, visitor)
+    }
+}
+
+/// Turns serde data model values into a `JsonNode`. Non-finite floats,
+/// which JSON cannot hold, become null.
+pub struct NodeSerializer;
+
+fn variant(name : &str, value : JsonNode) -> JsonNode {
+    let mut members = Object::default();
+    members.insert(name.to_string(), value);
+    JsonNode::Object(members)
+}
+
+impl ser::Serializer for NodeSerializer {
+    type Ok = JsonNode;
+    type Error = Error;
+    type SerializeSeq = SerializeArray;
+    type SerializeTuple = SerializeArray;
+    type SerializeTupleStruct = SerializeArray;
+    type SerializeTupleVariant = SerializeArray;
+    type SerializeMap = SerializeObject;
+    type SerializeStruct = SerializeObject;
+    type SerializeStructVariant = SerializeObject;
+
+    fn serialize_bool(self, v : bool) -> Result<JsonNode, Error> {
+        Ok(JsonNode::Boolean(v))
+    }
+
+    fn serialize_i8(self, v : i8) -> Result<JsonNode, Error> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i16(self, v : i16) -> Result<JsonNode, Error> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i32(self, v : i32) -> Result<JsonNode, Error> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i64(self, v : i64) -> Result<JsonNode, Error> {
+        Ok(JsonNode::Number(v.into()))
+    }
+
+    fn serialize_u8(self, v : u8) -> Result<JsonNode, Error> {
+        self.serialize_u64(v as u64)
+    }
+
+    fn serialize_u16(self, v : u16) -> Result<JsonNode, Error> {
+        self.serialize_u64(v as u64)
+    }
+
+    fn serialize_u32(self, v : u32) -> Result<JsonNode, Error> {
+        self.serialize_u64(v as u64)
+    }
+
+    fn serialize_u64(self, v : u64) -> Result<JsonNode, Error> {
+        Ok(JsonNode::Number(v.into()))
+    }
+
+    fn serialize_f32(self, v : f32) -> Result<JsonNode, Error> {
+        self.serialize_f64(v as f64)
+    }
+
+    fn serialize_f64(self, v : f64) -> Result<JsonNode, Error> {
+        Ok(if v.is_finite() { JsonNode::Number(v.into()) } else { JsonNode::Null })
+    }
+
+    fn serialize_char(self, v : char) -> Result<JsonNode, Error> {
+        Ok(JsonNode::String(v.to_string()))
+    }
+
+    fn serialize_str(self, v : &str) -> Result<JsonNode, Error> {
+        Ok(JsonNode::String(v.to_string()))
+    }
+
+    /// Bytes become an array of numbers.
+    fn serialize_bytes(self, v : &[u8]) -> Result<JsonNode, Error> {
+        Ok(JsonNode::Array(v.iter().map(|b| JsonNode::Number((*b as u64).into())).collect()))
+    }
+
+    fn serialize_none(self) -> Result<JsonNode, Error> {
+        Ok(JsonNode::Null)
+    }
+
+    fn serialize_some<T : Serialize + ?Sized>(self, value : &T) -> Result<JsonNode, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<JsonNode, Error> {
+        Ok(JsonNode::Null)
+    }
+
+    fn serialize_unit_struct(self, _ : &'static str) -> Result<JsonNode, Error> {
+        Ok(JsonNode::Null)
+    }
+
+    fn serialize_unit_variant(self, _ : &'static str, _ : u32, name : &'static str) -> Result<JsonNode, Error> {
+        Ok(JsonNode::String(name.to_string()))
+    }
+
+    fn serialize_newtype_struct<T : Serialize + ?Sized>(self, _ : &'static str, value : &T) -> Result<JsonNode, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T : Serialize + ?Sized>(self, _ : &'static str, _ : u32, name : &'static str, value : &T) -> Result<JsonNode, Error> {
+        Ok(variant(name, value.serialize(self)?))
+    }
+
+    fn serialize_seq(self, len : Option<usize>) -> Result<SerializeArray, Error> {
+        Ok(SerializeArray { items: Vec::with_capacity(len.unwrap_or(0)), variant: None })
+    }
+
+    fn serialize_tuple(self, len : usize) -> Result<SerializeArray, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(self, _ : &'static str, len : usize) -> Result<SerializeArray, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(self, _ : &'static str, _ : u32, name : &'static str, len : usize) -> Result<SerializeArray, Error> {
+        Ok(SerializeArray { items: Vec::with_capacity(len), variant: Some(name) })
+    }
+
+    fn serialize_map(self, _ : Option<usize>) -> Result<SerializeObject, Error> {
+        Ok(SerializeObject { members: Object::default(), key: None, variant: None })
+    }
+
+    fn serialize_struct(self, _ : &'static str, _ : usize) -> Result<SerializeObject, Error> {
+        self.serialize_map(None)
+    }
+
+    fn serialize_struct_variant(self, _ : &'static str, _ : u32, name : &'static str, _ : usize) -> Result<SerializeObject, Error> {
+        Ok(SerializeObject { members: Object::default(), key: None, variant: Some(name) })
+    }
+}
+
+/// Collects the elements of a sequence, tuple or tuple variant.
+pub struct SerializeArray {
+    items: Vec<JsonNode>,
+    variant: Option<&'static str>
+}
+
+impl SerializeArray {
+    fn push<T : Serialize + ?Sized>(&mut self, value : &T) -> Result<(), Error> {
+        self.items.push(value.serialize(NodeSerializer)?);
+        Ok(())
+    }
+
+    fn finish(self) -> JsonNode {
+        let array = JsonNode::Array(self.items);
+        match self.variant {
+            Some(name) => variant(name, array),
+            None => array
+        }
+    }
+}
+
+impl ser::SerializeSeq for SerializeArray {
+    type Ok = JsonNode;
+    type Error = Error;
+
+    fn serialize_element<T : Serialize + ?Sized>(&mut self, value : &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<JsonNode, Error> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeTuple for SerializeArray {
+    type Ok = JsonNode;
+    type Error = Error;
+
+    fn serialize_element<T : Serialize + ?Sized>(&mut self, value : &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<JsonNode, Error> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeArray {
+    type Ok = JsonNode;
+    type Error = Error;
+
+    fn serialize_field<T : Serialize + ?Sized>(&mut self, value : &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<JsonNode, Error> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeTupleVariant for SerializeArray {
+    type Ok = JsonNode;
+    type Error = Error;
+
+    fn serialize_field<T : Serialize + ?Sized>(&mut self, value : &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<JsonNode, Error> {
+        Ok(self.finish())
+    }
+}
+
+/// Collects the members of a map, struct or struct variant.
+pub struct SerializeObject {
+    members: Object,
+    key: Option<String>,
+    variant: Option<&'static str>
+}
+
+impl SerializeObject {
+    fn insert<T : Serialize + ?Sized>(&mut self, key : String, value : &T) -> Result<(), Error> {
+        self.members.insert(key, value.serialize(NodeSerializer)?);
+        Ok(())
+    }
+
+    fn finish(self) -> JsonNode {
+        let object = JsonNode::Object(self.members);
+        match self.variant {
+            Some(name) => variant(name, object),
+            None => object
+        }
+    }
+}
+
+impl ser::SerializeMap for SerializeObject {
+    type Ok = JsonNode;
+    type Error = Error;
+
+    /// Keys must come out as strings, numbers or booleans, which are
+    /// written as strings.
+    fn serialize_key<T : Serialize + ?Sized>(&mut self, key : &T) -> Result<(), Error> {
+        self.key = Some(match key.serialize(NodeSerializer)? {
+            JsonNode::String(ref mut s) => std::mem::take(s),
+            key @ JsonNode::Number(_) | key @ JsonNode::Boolean(_) => key.to_string(),
+            _ => return Err(ser::Error::custom("object keys must be strings"))
+        });
+        Ok(())
+    }
+
+    fn serialize_value<T : Serialize + ?Sized>(&mut self, value : &T) -> Result<(), Error> {
+        let key = self.key.take().expect("serde gives a key before each value");
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<JsonNode, Error> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeStruct for SerializeObject {
+    type Ok = JsonNode;
+    type Error = Error;
+
+    fn serialize_field<T : Serialize + ?Sized>(&mut self, key : &'static str, value : &T) -> Result<(), Error> {
+        self.insert(key.to_string(), value)
+    }
+
+    fn end(self) -> Result<JsonNode, Error> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeStructVariant for SerializeObject {
+    type Ok = JsonNode;
+    type Error = Error;
+
+    fn serialize_field<T : Serialize + ?Sized>(&mut self, key : &'static str, value : &T) -> Result<(), Error> {
+        self.insert(key.to_string(), value)
+    }
+
+    fn end(self) -> Result<JsonNode, Error> {
+        Ok(self.finish())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use serde::{Deserialize, Serialize};
+    use super::*;
+    use std::str::FromStr;
+
+    #[derive(PartialEq, Debug, Serialize, Deserialize)]
+    enum Shape {
+        Empty,
+        Circle(f64),
+        Line(i32, i32),
+        Box { width: u32, label: Option<String> }
+    }
+
+    #[derive(PartialEq, Debug, Serialize, Deserialize)]
+    struct Drawing<'a> {
+        title: &'a str,
+        shapes: Vec<Shape>,
+        #[serde(default)]
+        layers: u8
+    }
+
+    #[test]
+    fn test_derived_types_round_trip() {
+        let text = "{\"title\":\"t\",\"shapes\":[\"Empty\",{\"Circle\":1.5},{\"Line\":[-1,2]},{\"Box\":{\"width\":3,\"label\":null}}],\"ignored\":[{}]}";
+        let drawing : Drawing = from_str(text).unwrap();
+        assert_eq!(drawing, Drawing {
+            title: "t",
+            shapes: vec![Shape::Empty, Shape::Circle(1.5), Shape::Line(-1, 2), Shape::Box { width: 3, label: None }],
+            layers: 0
+        });
+        let written = to_string(&drawing).unwrap();
+        assert_eq!(from_str::<Drawing>(&written).unwrap(), drawing);
+        assert_eq!(JsonNode::from_str(&written).unwrap()["shapes"][2].to_string(), "{\"Line\":[-1,2]}");
+        assert!(matches!(from_str::<Shape>("{\"Circle\":\"x\"}"), Err(Error::Custom(_))));
+    }
+
+    #[test]
+    fn test_decodes_standard_types() {
+        assert_eq!(from_str::<Vec<u32>>(" [1, 2 ,3] "), Ok(vec![1, 2, 3]));
+        assert_eq!(from_str::<(i8, String, bool)>("[-4,\"a\\nb\",true]"), Ok((-4, "a\nb".to_string(), true)));
+        assert_eq!(from_str::<Option<f64>>("null"), Ok(None));
+        assert_eq!(from_str::<Option<f64>>("2.5"), Ok(Some(2.5)));
+        assert_eq!(from_str::<u64>("18446744073709551615"), Ok(u64::MAX));
+        assert_eq!(from_str::<&str>("\"borrowed\""), Ok("borrowed"));
+        let map : BTreeMap<String, Vec<()>> = from_str("{\"a\":[null],\"b\":[]}").unwrap();
+        assert_eq!(map.len(), 2);
+        assert_eq!(from_str::<JsonNode>("{\"k\":[1,{}]}"), Ok(JsonNode::from_str("{\"k\":[1,{}]}").unwrap()));
+    }
+
+    #[test]
+    fn test_errors() {
+        match from_str::<Vec<u32>>("[1,\n2 3]") {
+            Err(Error::Syntax(reason)) => assert_eq!((reason.line, reason.column), (2, 3)),
+            other => panic!("expected a syntax error, got {:?}", other)
+        }
+        assert_eq!(from_str::<u8>("1 2"), Err(Error::TrailingData(2)));
+        assert!(matches!(from_str::<u8>("300"), Err(Error::Custom(_))));
+        assert!(matches!(from_str::<(u8, u8)>("[1,2,3]"), Err(Error::Syntax(_))));
+        assert!(matches!(from_str::<String>("\"open"), Err(Error::Syntax(_))));
+        assert!(matches!(from_str::<&str>("\"a\tb\""), Err(Error::Syntax(_))));
+    }
+
+    #[test]
+    fn test_encodes_standard_types() {
+        let mut map = BTreeMap::new();
+        map.insert(2, vec![Some(1.5), None, Some(f64::NAN)]);
+        assert_eq!(to_string(&map), Ok("{\"2\":[1.5,null,null]}".to_string()));
+        assert_eq!(to_string(&("tab\t", 'c', -1i64, u64::MAX)), Ok("[\"tab\\t\",\"c\",-1,18446744073709551615]".to_string()));
+        assert_eq!(to_string_pretty(&vec![(); 2]), Ok("[\n  null,\n  null\n]".to_string()));
+        assert_eq!(to_node(&JsonNode::from_str("[1,\"x\"]").unwrap()), Ok(JsonNode::from_str("[1,\"x\"]").unwrap()));
+
+        let mut bad = BTreeMap::new();
+        bad.insert(vec![1], 1);
+        assert!(matches!(to_string(&bad), Err(Error::Custom(_))));
+    }
+}
@@ -0,0 +1,695 @@
+// YAML, with the `yaml` feature: reading the block and flow styles that
+// configuration files are written in, and writing block style.
+
+use std::fmt;
+use crate::json::{maybe_grow, JsonNode, Number, Object};
+
+/// Why YAML text could not be read.
+#[derive(PartialEq, Debug, Clone)]
+pub struct YamlError {
+    /// Counting from 1.
+    pub line: usize,
+    pub message: String
+}
+
+impl fmt::Display for YamlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl JsonNode {
+    /// Reads a YAML document: block mappings and sequences, flow `[...]` and
+    /// `{...}` collections, plain, quoted and `|` or `>` block scalars, and
+    /// comments. Plain scalars resolve as in the YAML 1.2 core schema, so
+    /// `~`, `true` and `0x1F` are null, a boolean and a number, and `.inf`
+    /// and `.nan` are the doubles. Anchors, aliases, tags, complex keys and
+    /// streams of several documents are refused.
+    pub fn from_yaml_str(yaml : &str) -> Result<JsonNode, YamlError> {
+        Reader::new(yaml.trim_start_matches('\u{feff}')).document()
+    }
+
+    /// This as a YAML document in block style, members in key order unless
+    /// the `small_objects` or `preserve_order` feature keeps their order.
+    /// Strings are double-quoted where a plain scalar would read back as
+    /// something else.
+    pub fn to_yaml_string(&self) -> String {
+        let mut out = String::new();
+        if is_block(self) {
+            write_block(self, 0, &mut out)
+        } else {
+            out.push_str(&inline(self));
+            out.push('\n')
+        }
+        out
+    }
+}
+
+fn error(line : usize, message : &str) -> YamlError {
+    YamlError { line: line + 1, message: message.to_string() }
+}
+
+struct Reader<'a> {
+    // the indentation and the rest of each line
+    lines: Vec<(usize, &'a str)>,
+    next: usize
+}
+
+impl<'a> Reader<'a> {
+    fn new(yaml : &'a str) -> Reader<'a> {
+        let lines = yaml.lines().map(|line| {
+            let content = line.trim_start_matches(' ');
+            (line.len() - content.len(), content)
+        }).collect();
+        Reader { lines, next: 0 }
+    }
+
+    // the next line with something on it other than a comment
+    fn peek_line(&mut self) -> Option<(usize, &'a str)> {
+        while let Some(&(indent, content)) = self.lines.get(self.next) {
+            if !content.trim().is_empty() && !content.starts_with('#') {
+                return Some((indent, content))
+            }
+            self.next += 1
+        }
+        None
+    }
+
+    // the same, up to a marker ending the document
+    fn peek(&mut self) -> Option<(usize, &'a str)> {
+        self.peek_line().filter(|&(indent, content)| !(indent == 0 && (is_start(content) || content == "...")))
+    }
+
+    fn document(&mut self) -> Result<JsonNode, YamlError> {
+        while let Some((0, content)) = self.peek_line() {
+            if !content.starts_with('%') {
+                break
+            }
+            self.next += 1
+        }
+        if let Some((0, content)) = self.peek_line() {
+            if is_start(content) {
+                let rest = strip_comment(&content[3..]).trim_start();
+                if rest.is_empty() {
+                    self.next += 1
+                } else {
+                    self.lines[self.next] = (4, rest)
+                }
+            }
+        }
+        let node = self.node(0)?;
+        if let Some((0, "...")) = self.peek_line() {
+            self.next += 1
+        }
+        match self.peek_line() {
+            None => Ok(node),
+            Some((0, content)) if is_start(content) => Err(error(self.next, "only one document can be read")),
+            Some(_) => Err(error(self.next, "unexpected text after the document"))
+        }
+    }
+
+    // the node on the next lines indented by at least `min`, null if there is none
+    fn node(&mut self, min : usize) -> Result<JsonNode, YamlError> {
+        maybe_grow(|| {
+            let (indent, content) = match self.peek() {
+                Some((indent, content)) if indent >= min => (indent, content),
+                _ => return Ok(JsonNode::Null)
+            };
+            if content.starts_with('\t') {
+                Err(error(self.next, "tabs cannot indent"))
+            } else if is_item(content) {
+                self.sequence(indent)
+            } else if key_end(content).is_some() {
+                self.mapping(indent)
+            } else {
+                self.value(strip_comment(content), min)
+            }
+        })
+    }
+
+    fn sequence(&mut self, indent : usize) -> Result<JsonNode, YamlError> {
+        let mut items = Vec::new();
+        while let Some((at, content)) = self.peek() {
+            if at < indent || at == indent && !is_item(content) {
+                break
+            }
+            if at > indent {
+                return Err(error(self.next, "unexpected indentation"))
+            }
+            let rest = content[1..].trim_start();
+            if rest.is_empty() || rest.starts_with('#') {
+                self.next += 1
+            } else {
+                // what follows the dash reads as if on a line of its own
+                self.lines[self.next] = (indent + content.len() - rest.len(), rest)
+            }
+            items.push(self.node(indent + 1)?)
+        }
+        Ok(JsonNode::Array(items))
+    }
+
+    fn mapping(&mut self, indent : usize) -> Result<JsonNode, YamlError> {
+        let mut object = Object::default();
+        while let Some((at, content)) = self.peek() {
+            if at < indent {
+                break
+            }
+            let line = self.next;
+            if at > indent {
+                return Err(error(line, "unexpected indentation"))
+            }
+            let text = strip_comment(content);
+            let colon = key_end(text).ok_or_else(|| error(line, "expected a key and a :"))?;
+            let mut key = scalar(text[..colon].trim_end()).map_err(|message| error(line, message))?;
+            let key = match &mut key {
+                JsonNode::String(key) => std::mem::take(key),
+                _ => key.to_string()
+            };
+            if object.contains_key(&key) {
+                return Err(YamlError { line: line + 1, message: format!("duplicate key {:?}", key) })
+            }
+            let rest = text[colon + 1..].trim_start();
+            let value = if rest.is_empty() {
+                self.next += 1;
+                match self.peek() {
+                    // a sequence may sit at the indentation of its key
+                    Some((at, next)) if at == indent && is_item(next) => self.sequence(indent)?,
+                    _ => self.node(indent + 1)?
+                }
+            } else {
+                self.value(rest, indent + 1)?
+            };
+            object.insert(key, value);
+        }
+        Ok(JsonNode::Object(object))
+    }
+
+    // the value that `text`, the rest of the next line, starts; a block scalar
+    // continues on lines indented by at least `min`, a flow collection on any
+    fn value(&mut self, text : &'a str, min : usize) -> Result<JsonNode, YamlError> {
+        let line = self.next;
+        self.next += 1;
+        if text.starts_with('|') || text.starts_with('>') {
+            self.block_scalar(text, min).map_err(|message| error(line, message))
+        } else if text.starts_with('[') || text.starts_with('{') {
+            self.flow(text, line)
+        } else {
+            scalar(text).map_err(|message| error(line, message))
+        }
+    }
+
+    fn block_scalar(&mut self, header : &str, min : usize) -> Result<JsonNode, &'static str> {
+        let folded = header.starts_with('>');
+        let chomping = match &header[1..] {
+            "" => None,
+            "-" => Some(false),
+            "+" => Some(true),
+            _ => return Err("expected | or > alone or followed by - or +")
+        };
+        let mut lines : Vec<String> = Vec::new();
+        let mut width = None;
+        while let Some(&(at, content)) = self.lines.get(self.next) {
+            if content.trim().is_empty() {
+                lines.push(String::new())
+            } else {
+                if at < min || width.is_some_and(|width| at < width) {
+                    break
+                }
+                let width = *width.get_or_insert(at);
+                lines.push(" ".repeat(at - width) + content)
+            }
+            self.next += 1
+        }
+        let trailing = lines.iter().rev().take_while(|line| line.is_empty()).count();
+        let body = &lines[..lines.len() - trailing];
+        let mut text = if folded { fold(body) } else { body.join("\n") };
+        match chomping {
+            Some(false) => (),
+            Some(true) => text.push_str(&"\n".repeat(trailing + !body.is_empty() as usize)),
+            None if !body.is_empty() => text.push('\n'),
+            None => ()
+        }
+        Ok(JsonNode::String(text))
+    }
+
+    // a flow collection starting with `text` on `line`, taking in lines
+    // until it ends
+    fn flow(&mut self, text : &str, line : usize) -> Result<JsonNode, YamlError> {
+        let mut text = text.to_string();
+        loop {
+            let mut flow = Flow { text: &text, position: 0 };
+            let parsed = flow.value().and_then(|node| {
+                flow.skip();
+                if flow.position < text.len() {
+                    return Err(Some("unexpected text after a flow collection"))
+                }
+                Ok(node)
+            });
+            match parsed {
+                Ok(node) => return Ok(node),
+                Err(None) if self.next < self.lines.len() => {
+                    text.push('\n');
+                    text.push_str(self.lines[self.next].1);
+                    self.next += 1
+                },
+                Err(None) => return Err(error(line, "the flow collection does not end")),
+                Err(Some(message)) => return Err(error(line, message))
+            }
+        }
+    }
+}
+
+// whether `content` is the marker starting a document
+fn is_start(content : &str) -> bool {
+    content == "---" || content.starts_with("--- ")
+}
+
+// whether `content` is an item of a block sequence
+fn is_item(content : &str) -> bool {
+    content == "-" || content.starts_with("- ") || content.starts_with("-\t")
+}
+
+// where the `:` after a mapping key is in `content`, if it starts with one
+fn key_end(content : &str) -> Option<usize> {
+    let text = strip_comment(content);
+    let start = match text.chars().next()? {
+        '"' => double_quoted(text).ok()?.1,
+        '\'' => single_quoted(text).ok()?.1,
+        '[' | '{' => return None,
+        _ => 0
+    };
+    let colon = match text[start..].find(": ") {
+        Some(i) => start + i,
+        None if text.ends_with(':') && text.len() > start => text.len() - 1,
+        None => return None
+    };
+    if start > 0 && !text[start..colon].trim().is_empty() {
+        return None
+    }
+    Some(colon)
+}
+
+// `text` without a comment at the end and the whitespace before it
+fn strip_comment(text : &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    let mut previous = ' ';
+    for (i, c) in text.char_indices() {
+        match quote {
+            Some('"') if escaped => escaped = false,
+            Some('"') if c == '\\' => escaped = true,
+            Some(q) if c == q => quote = None,
+            Some(_) => (),
+            None if c == '#' && previous.is_whitespace() => return text[..i].trim_end(),
+            None if (c == '"' || c == '\'') && (previous.is_whitespace() || "[{,:".contains(previous)) => quote = Some(c),
+            None => ()
+        }
+        previous = c
+    }
+    text.trim_end()
+}
+
+// a scalar taking up all of `text`
+fn scalar(text : &str) -> Result<JsonNode, &'static str> {
+    let (node, length) = match text.chars().next() {
+        Some('"') => double_quoted(text).map(|(s, length)| (JsonNode::String(s), length))?,
+        Some('\'') => single_quoted(text).map(|(s, length)| (JsonNode::String(s), length))?,
+        Some('&') | Some('*') => return Err("anchors and aliases are not supported"),
+        Some('!') => return Err("tags are not supported"),
+        Some('?') if text == "?" || text.starts_with("? ") => return Err("complex keys are not supported"),
+        Some('@') | Some('`') => return Err("@ and ` cannot start a plain scalar"),
+        _ => return Ok(plain(text))
+    };
+    if length < text.len() {
+        return Err("unexpected text after a quoted scalar")
+    }
+    Ok(node)
+}
+
+// what a plain scalar means in the core schema
+fn plain(text : &str) -> JsonNode {
+    match text {
+        "" | "~" | "null" | "Null" | "NULL" => JsonNode::Null,
+        "true" | "True" | "TRUE" => JsonNode::Boolean(true),
+        "false" | "False" | "FALSE" => JsonNode::Boolean(false),
+        ".inf" | ".Inf" | ".INF" | "+.inf" | "+.Inf" | "+.INF" => JsonNode::from(f64::INFINITY),
+        "-.inf" | "-.Inf" | "-.INF" => JsonNode::from(f64::NEG_INFINITY),
+        ".nan" | ".NaN" | ".NAN" => JsonNode::from(f64::NAN),
+        _ => number(text).map_or_else(|| JsonNode::String(text.to_string()), JsonNode::Number)
+    }
+}
+
+// a decimal, `0o` octal or `0x` hexadecimal number
+fn number(text : &str) -> Option<Number> {
+    let digits = |s : &str, radix| !s.is_empty() && s.chars().all(|c| c.is_digit(radix));
+    if let Some(octal) = text.strip_prefix("0o").filter(|octal| digits(octal, 8)) {
+        return u64::from_str_radix(octal, 8).ok().map(Number::from)
+    }
+    if let Some(hex) = text.strip_prefix("0x").filter(|hex| digits(hex, 16)) {
+        return u64::from_str_radix(hex, 16).ok().map(Number::from)
+    }
+    let unsigned = text.strip_prefix(['-', '+']).unwrap_or(text);
+    let (mantissa, exponent) = match unsigned.find(['e', 'E']) {
+        Some(e) => (&unsigned[..e], Some(&unsigned[e + 1..])),
+        None => (unsigned, None)
+    };
+    let (whole, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let decimal = (digits(whole, 10) || digits(fraction, 10))
+        && (whole.is_empty() || digits(whole, 10))
+        && (fraction.is_empty() || digits(fraction, 10))
+        && exponent.is_none_or(|e| digits(e.strip_prefix(['-', '+']).unwrap_or(e), 10));
+    if !decimal {
+        return None
+    }
+    text.strip_prefix('+').unwrap_or(text).parse().ok()
+}
+
+// the string a double-quoted scalar at the start of `text` stands for, and its length
+fn double_quoted(text : &str) -> Result<(String, usize), &'static str> {
+    let mut out = String::new();
+    let mut chars = text.char_indices().skip(1);
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Ok((out, i + 1)),
+            '\\' => {
+                let c = match chars.next() {
+                    Some((_, 'x')) => hex_escape(&mut chars, 2)?,
+                    Some((_, 'u')) => hex_escape(&mut chars, 4)?,
+                    Some((_, 'U')) => hex_escape(&mut chars, 8)?,
+                    Some((_, escape)) => match escape {
+                        '0' => '\0',
+                        'a' => '\x07',
+                        'b' => '\x08',
+                        't' | '\t' => '\t',
+                        'n' => '\n',
+                        'v' => '\x0b',
+                        'f' => '\x0c',
+                        'r' => '\r',
+                        'e' => '\x1b',
+                        ' ' | '"' | '/' | '\\' => escape,
+                        'N' => '\u{85}',
+                        '_' => '\u{a0}',
+                        'L' => '\u{2028}',
+                        'P' => '\u{2029}',
+                        _ => return Err("unknown escape in a double-quoted scalar")
+                    },
+                    None => break
+                };
+                out.push(c)
+            },
+            c => out.push(c)
+        }
+    }
+    Err("the double-quoted scalar does not end")
+}
+
+// the character `length` hex digits stand for, reading a second `\u` escape
+// after a high surrogate as JSON writes characters beyond the BMP
+fn hex_escape<I : Iterator<Item = (usize, char)>>(chars : &mut I, length : usize) -> Result<char, &'static str> {
+    fn read<I : Iterator<Item = (usize, char)>>(chars : &mut I, length : usize) -> Result<u32, &'static str> {
+        let hex : String = chars.take(length).map(|(_, c)| c).collect();
+        if hex.len() != length || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err("invalid hex escape")
+        }
+        u32::from_str_radix(&hex, 16).map_err(|_| "invalid hex escape")
+    }
+    let code = read(chars, length)?;
+    let code = match code {
+        0xD800..=0xDBFF if length == 4 => {
+            let low = match (chars.next(), chars.next()) {
+                (Some((_, '\\')), Some((_, 'u'))) => read(chars, 4)?,
+                _ => return Err("unpaired surrogate in a \\u escape")
+            };
+            if !(0xDC00..=0xDFFF).contains(&low) {
+                return Err("unpaired surrogate in a \\u escape")
+            }
+            0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00)
+        },
+        code => code
+    };
+    char::from_u32(code).ok_or("invalid character in an escape")
+}
+
+// the string a single-quoted scalar at the start of `text` stands for, and its length
+fn single_quoted(text : &str) -> Result<(String, usize), &'static str> {
+    let mut out = String::new();
+    let mut position = 1;
+    while let Some(end) = text[position..].find('\'') {
+        out.push_str(&text[position..position + end]);
+        position += end + 1;
+        if !text[position..].starts_with('\'') {
+            return Ok((out, position))
+        }
+        out.push('\'');
+        position += 1
+    }
+    Err("the single-quoted scalar does not end")
+}
+
+// a flow collection being read; errors are None where the text ends too early
+struct Flow<'t> {
+    text: &'t str,
+    position: usize
+}
+
+impl<'t> Flow<'t> {
+    // past whitespace, line breaks and comments
+    fn skip(&mut self) {
+        loop {
+            let rest = &self.text[self.position..];
+            let trimmed = rest.trim_start();
+            self.position += rest.len() - trimmed.len();
+            if !trimmed.starts_with('#') {
+                return
+            }
+            self.position += trimmed.find('\n').unwrap_or(trimmed.len())
+        }
+    }
+
+    fn next_is(&mut self, c : char) -> bool {
+        self.skip();
+        let is = self.text[self.position..].starts_with(c);
+        if is {
+            self.position += 1
+        }
+        is
+    }
+
+    fn value(&mut self) -> Result<JsonNode, Option<&'static str>> {
+        maybe_grow(|| {
+            if self.next_is('[') {
+                let mut items = Vec::new();
+                while !self.next_is(']') {
+                    items.push(self.value()?);
+                    if !self.next_is(',') && !self.text[self.position..].starts_with(']') {
+                        return Err(self.expected("expected , or ] in a flow sequence"))
+                    }
+                }
+                Ok(JsonNode::Array(items))
+            } else if self.next_is('{') {
+                let mut object = Object::default();
+                while !self.next_is('}') {
+                    let mut key = self.scalar()?;
+                    let key = match &mut key {
+                        JsonNode::String(key) => std::mem::take(key),
+                        _ => key.to_string()
+                    };
+                    let value = if self.next_is(':') { self.value()? } else { JsonNode::Null };
+                    if object.contains_key(&key) {
+                        return Err(Some("duplicate key in a flow mapping"))
+                    }
+                    object.insert(key, value);
+                    if !self.next_is(',') && !self.text[self.position..].starts_with('}') {
+                        return Err(self.expected("expected , or } in a flow mapping"))
+                    }
+                }
+                Ok(JsonNode::Object(object))
+            } else {
+                self.scalar()
+            }
+        })
+    }
+
+    fn scalar(&mut self) -> Result<JsonNode, Option<&'static str>> {
+        self.skip();
+        let rest = &self.text[self.position..];
+        let length = match rest.chars().next() {
+            None => return Err(None),
+            Some('"') => double_quoted(rest).map_err(|_| None)?.1,
+            Some('\'') => single_quoted(rest).map_err(|_| None)?.1,
+            Some(_) => plain_length(rest)
+        };
+        self.position += length;
+        scalar(rest[..length].trim_end()).map_err(Some)
+    }
+
+    // `message`, or None at the end of the text
+    fn expected(&self, message : &'static str) -> Option<&'static str> {
+        Some(message).filter(|_| self.position < self.text.len())
+    }
+}
+
+// how much of `text` a plain scalar in a flow collection takes up
+fn plain_length(text : &str) -> usize {
+    let bytes = text.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b',' | b'[' | b']' | b'{' | b'}' | b'\n' => break,
+            b':' if bytes.get(i + 1).is_none_or(|b| b" \n,[]{}".contains(b)) => break,
+            b'#' if i > 0 && bytes[i - 1] == b' ' => break,
+            _ => i += 1
+        }
+    }
+    i
+}
+
+// folds the lines of a `>` scalar: lines next to each other are joined by a
+// space, an empty line stands for a line break, and more indented lines keep theirs
+fn fold(lines : &[String]) -> String {
+    let mut text = String::new();
+    for (i, line) in lines.iter().enumerate() {
+        if i > 0 {
+            let previous = &lines[i - 1];
+            if line.is_empty() || previous.starts_with(' ') || line.starts_with(' ') {
+                text.push('\n')
+            } else if !previous.is_empty() {
+                text.push(' ')
+            }
+        }
+        text.push_str(line)
+    }
+    text
+}
+
+// whether `node` takes lines of its own rather than the rest of one
+fn is_block(node : &JsonNode) -> bool {
+    match node {
+        JsonNode::Array(items) => !items.is_empty(),
+        JsonNode::Object(members) => !members.is_empty(),
+        _ => false
+    }
+}
+
+fn write_block(node : &JsonNode, indent : usize, out : &mut String) {
+    maybe_grow(|| match node {
+        JsonNode::Array(items) => for item in items {
+            out.push_str(&" ".repeat(indent));
+            out.push('-');
+            if is_block(item) {
+                // the first line of the item goes after the dash
+                let start = out.len();
+                write_block(item, indent + 2, out);
+                out.replace_range(start..start + indent + 2, " ")
+            } else {
+                write_value(item, indent + 2, out)
+            }
+        },
+        JsonNode::Object(members) => {
+            let mut members : Vec<(&String, &JsonNode)> = members.iter().collect();
+            if !cfg!(feature = "small_objects") {
+                members.sort_by_key(|&(key, _)| key)
+            }
+            for (key, value) in members {
+                out.push_str(&" ".repeat(indent));
+                out.push_str(&string(key));
+                out.push(':');
+                write_value(value, indent + 2, out)
+            }
+        },
+        _ => unreachable!("only containers take lines of their own")
+    })
+}
+
+// what follows a key or a dash
+fn write_value(node : &JsonNode, indent : usize, out : &mut String) {
+    if is_block(node) {
+        out.push('\n');
+        write_block(node, indent, out)
+    } else {
+        out.push(' ');
+        out.push_str(&inline(node));
+        out.push('\n')
+    }
+}
+
+fn inline(node : &JsonNode) -> String {
+    match node {
+        JsonNode::Number(n) if n.as_f64().is_nan() => ".nan".to_string(),
+        JsonNode::Number(n) if n.as_f64() == f64::INFINITY => ".inf".to_string(),
+        JsonNode::Number(n) if n.as_f64() == f64::NEG_INFINITY => "-.inf".to_string(),
+        JsonNode::String(s) => string(s),
+        JsonNode::Array(_) => "[]".to_string(),
+        JsonNode::Object(_) => "{}".to_string(),
+        _ => node.to_string()
+    }
+}
+
+// `s` plain where that reads back as the same string, and double-quoted elsewhere
+fn string(s : &str) -> String {
+    let plain = s.trim() == s
+        && !s.starts_with(['-', '?', ':', ',', '[', ']', '{', '}', '#', '&', '*', '!', '|', '>', '\'', '"', '%', '@', '`'])
+        && !s.contains(": ")
+        && !s.contains(" #")
+        && !s.ends_with(':')
+        && !s.chars().any(char::is_control)
+        && plain(s) == JsonNode::String(s.to_string());
+    if plain {
+        s.to_string()
+    } else {
+        JsonNode::String(s.to_string()).to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn json(text : &str) -> JsonNode {
+        JsonNode::from_str(text).unwrap()
+    }
+
+    #[test]
+    fn test_reads_block_style() {
+        let yaml = "%YAML 1.2\n---\n# a service\nname: web  # inline comment\nport: 8080\nratio: .5\nmask: 0x1F\ndebug: False\nnothing: ~\n\
+            \"quoted: key\": 'it''s'\nescaped: \"tab\\there \\u00e9\\U0001F600\"\nhosts:\n- a.example\n-   b.example\nservers:\n  - name: one\n    tags: [x, 'y', {z: 1}]\n  - - nested\n    -\n\
+            empty:\nscript: |\n  echo a\n\n    indented\nfolded: >-\n  one\n  two\n\n  three\n...\n";
+        assert_eq!(JsonNode::from_yaml_str(yaml).unwrap(), json("{\"name\":\"web\",\"port\":8080,\"ratio\":0.5,\"mask\":31,\"debug\":false,\"nothing\":null,\
+            \"quoted: key\":\"it's\",\"escaped\":\"tab\\there \\u00e9\\ud83d\\ude00\",\"hosts\":[\"a.example\",\"b.example\"],\
+            \"servers\":[{\"name\":\"one\",\"tags\":[\"x\",\"y\",{\"z\":1}]},[\"nested\",null]],\"empty\":null,\
+            \"script\":\"echo a\\n\\n  indented\\n\",\"folded\":\"one two\\nthree\"}"));
+        assert_eq!(JsonNode::from_yaml_str("matrix: [\n  [1, 2],  # first\n  [3, 4],\n]\nlist: {a: [], \"b\": \"c, d\"}").unwrap(),
+            json("{\"matrix\":[[1,2],[3,4]],\"list\":{\"a\":[],\"b\":\"c, d\"}}"));
+        assert_eq!(JsonNode::from_yaml_str("- 1e3\n- -2\n- 12:30\n- http://x#y\n- 1.2.3").unwrap(), json("[1000,-2,\"12:30\",\"http://x#y\",\"1.2.3\"]"));
+        assert_eq!(JsonNode::from_yaml_str("--- \"top\"").unwrap(), json("\"top\""));
+        assert_eq!(JsonNode::from_yaml_str("# nothing\n").unwrap(), JsonNode::Null);
+    }
+
+    #[test]
+    fn test_reports_errors() {
+        let line = |yaml : &str| JsonNode::from_yaml_str(yaml).unwrap_err().line;
+        assert_eq!(line("a: 1\n  b: 2"), 2);
+        assert_eq!(line("a: 1\na: 2"), 2);
+        assert_eq!(line("a: &anchor 1"), 1);
+        assert_eq!(line("a: [1, 2"), 1);
+        assert_eq!(line("a: 1\n---\nb: 2"), 2);
+        assert_eq!(line("- a\nb: c"), 2);
+        assert_eq!(JsonNode::from_yaml_str("a: \"\\q\"").unwrap_err().message, "unknown escape in a double-quoted scalar");
+    }
+
+    #[test]
+    fn test_writes_and_reads_back() {
+        let node = json("{\"name\":\"web\",\"list\":[1,[2,[]],{\"a\":{},\"b\":[true,null]}],\"strings\":[\"true\",\"1.5\",\"\",\" pad\",\"a: b\",\
+            \"- x\",\"line\\nbreak\",\"plain text\",\"~\"],\"weird key: \\\"\":-0.25}");
+        let yaml = node.to_yaml_string();
+        assert_eq!(JsonNode::from_yaml_str(&yaml).unwrap(), node);
+        if !cfg!(feature = "small_objects") {
+            assert_eq!(yaml, "list:\n  - 1\n  - - 2\n    - []\n  - a: {}\n    b:\n      - true\n      - null\nname: web\nstrings:\n  - \"true\"\n  - \"1.5\"\n\
+                \x20 - \"\"\n  - \" pad\"\n  - \"a: b\"\n  - \"- x\"\n  - \"line\\nbreak\"\n  - plain text\n  - \"~\"\n\"weird key: \\\"\": -0.25\n");
+        }
+        assert_eq!(json("[]").to_yaml_string(), "[]\n");
+        assert_eq!(json("\"s\"").to_yaml_string(), "s\n");
+        assert_eq!(JsonNode::from(f64::NEG_INFINITY).to_yaml_string(), "-.inf\n");
+    }
+}
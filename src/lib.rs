@@ -0,0 +1,23 @@
+//! A JSON parser built on `nom`, with a document model and tools around it.
+//!
+//! `json::JsonNode` is the parsed document: `JsonNode::from_str` and
+//! `JsonNode::from_bytes` parse one, and its `Display` prints it back as
+//! compact JSON. The submodules of `json` query, transform, validate and
+//! stream documents.
+//!
+//! ```
+//! use std::str::FromStr;
+//! use json_parser::json::JsonNode;
+//!
+//! let doc = JsonNode::from_str("{\"users\": [{\"name\": \"ann\"}]}").unwrap();
+//! let names = doc.get_all("/users/*/name").unwrap();
+//! assert_eq!(names[0].1, &JsonNode::String("ann".to_string()));
+//! ```
+
+#[macro_use]
+extern crate nom;
+
+#[macro_use]
+mod macros;
+
+pub mod json;
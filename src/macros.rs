@@ -0,0 +1,100 @@
+/// Builds a `JsonNode` from JSON written as Rust tokens. Any other Rust
+/// expression in value position is converted with `JsonNode::from`, and an
+/// object key may be an expression in parentheses.
+///
+/// ```
+/// use json_parser::json;
+///
+/// let port = 8080;
+/// let config = json!({
+///     "name": "svc",
+///     "ports": [80, port, null],
+///     ("debug".to_uppercase()): false
+/// });
+/// assert_eq!(config["ports"][1], json!(8080));
+/// assert_eq!(config["DEBUG"], json!(false));
+/// ```
+#[macro_export]
+macro_rules! json {
+    // array elements so far, the tokens of the current one, then the rest
+    (@array [$($elements:expr,)*] ()) => {
+        vec![$($elements,)*]
+    };
+    (@array [$($elements:expr,)*] ($($current:tt)+) , $($rest:tt)*) => {
+        $crate::json!(@array [$($elements,)* $crate::json!($($current)+),] () $($rest)*)
+    };
+    (@array [$($elements:expr,)*] ($($current:tt)+)) => {
+        $crate::json!(@array [$($elements,)* $crate::json!($($current)+),] ())
+    };
+    (@array [$($elements:expr,)*] ($($current:tt)*) $next:tt $($rest:tt)*) => {
+        $crate::json!(@array [$($elements,)*] ($($current)* $next) $($rest)*)
+    };
+
+    // members go into `$object` one at a time
+    (@object $object:ident) => {};
+    (@object $object:ident $key:tt : $($rest:tt)*) => {
+        $crate::json!(@member $object [$key] () $($rest)*)
+    };
+    (@member $object:ident [$key:tt] ($($value:tt)+) , $($rest:tt)*) => {
+        $object.insert($crate::json!(@key $key), $crate::json!($($value)+));
+        $crate::json!(@object $object $($rest)*)
+    };
+    (@member $object:ident [$key:tt] ($($value:tt)+)) => {
+        $object.insert($crate::json!(@key $key), $crate::json!($($value)+));
+    };
+    (@member $object:ident [$key:tt] ($($value:tt)*) $next:tt $($rest:tt)*) => {
+        $crate::json!(@member $object [$key] ($($value)* $next) $($rest)*)
+    };
+    (@key ($key:expr)) => {
+        ::std::string::ToString::to_string(&$key)
+    };
+    (@key $key:literal) => {
+        ::std::string::ToString::to_string(&$key)
+    };
+
+    (null) => {
+        $crate::json::JsonNode::Null
+    };
+    (true) => {
+        $crate::json::JsonNode::Boolean(true)
+    };
+    (false) => {
+        $crate::json::JsonNode::Boolean(false)
+    };
+    ([ $($elements:tt)* ]) => {
+        $crate::json::JsonNode::Array($crate::json!(@array [] () $($elements)*))
+    };
+    ({ $($members:tt)* }) => {{
+        #[allow(unused_mut)]
+        let mut object = $crate::json::Object::default();
+        $crate::json!(@object object $($members)*);
+        $crate::json::JsonNode::Object(object)
+    }};
+    ($other:expr) => {
+        $crate::json::JsonNode::from($other)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::json::JsonNode;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_literals() {
+        assert_eq!(json!(null), JsonNode::Null);
+        assert_eq!(json!([]), JsonNode::Array(Vec::new()));
+        assert_eq!(json!({}), JsonNode::from_str("{}").unwrap());
+        let node = json!({"a": [1, -2.5, null, "x", true], "b": {"c": [[], {}]}, "d": false,});
+        assert_eq!(node, JsonNode::from_str("{\"a\":[1,-2.5,null,\"x\",true],\"b\":{\"c\":[[],{}]},\"d\":false}").unwrap());
+    }
+
+    #[test]
+    fn test_interpolation() {
+        let name = "ann".to_string();
+        let ids = json!([1, 2]);
+        let key = "k";
+        let node = json!({"name": name, "ids": ids.clone(), "count": 1 + 2, (key): [key, ids]});
+        assert_eq!(node, JsonNode::from_str("{\"name\":\"ann\",\"ids\":[1,2],\"count\":3,\"k\":[\"k\",[1,2]]}").unwrap());
+    }
+}
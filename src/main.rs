@@ -1,28 +1,445 @@
-#[macro_use]
-extern crate nom;
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, stdin, stdout, BufWriter, Read, Write};
+use std::process;
 
-use std::io::{stdin, Read};
-use circular::Buffer;
+use json_parser::json::{JsonNode, ParserOptions, SerializeOptions};
+use json_parser::json::{cbor, csv, diff, flatten, patch};
+use json_parser::json::aggregate::{aggregate, Agg};
+use json_parser::json::csv::CsvOptions;
+#[cfg(feature = "msgpack")]
+use json_parser::json::msgpack;
+use json_parser::json::filter::Filter;
+use json_parser::json::jsonpath::JsonPath;
+use json_parser::json::ndjson::NdjsonReader;
+use json_parser::json::pointer::JsonPointer;
+use json_parser::json::serialize::Serializer;
+use json_parser::json::streaming::JsonStream;
 
-mod json;
-use self::json::JsonNode;
+const USAGE : &str = "Usage: json_parser COMMAND [OPTIONS] [FILE...]
+
+Commands:
+  validate [--quiet] [FILE...]        check that each input holds one strict
+                                      document, printing FILE:LINE:COLUMN:
+                                      what is wrong for each that does not
+  format [--indent N] [--sort-keys] [--ascii] [--escape-slashes] [FILE]
+                                      print each document indented by N
+                                      spaces, 2 by default, members sorted,
+                                      escaping non-ASCII characters and `/`
+                                      if asked
+  minify [--sort-keys] [--ascii] [--escape-slashes] [FILE]
+                                      print each document without whitespace
+  get [--raw] PATH [FILE]             print the value at a JSON Pointer like
+                                      /a/0/b or a dotted path like a[0].b,
+                                      strings unquoted if raw
+  aggregate sum|min|max|avg|count PATTERN [FILE]
+                                      summarize the numbers at the paths a
+                                      glob pointer like /items/*/price
+                                      matches, skipping other values
+  diff [--json | --patch] A B         print how document B differs from A
+  convert [--from F] [--to F] [FILE]  translate between json, json5, jsonc
+                                      (read only), ndjson, cbor and, if built
+                                      with their features, msgpack, toml and
+                                      yaml
+  filter [--ndjson] [--raw] EXPRESSION [FILE]
+                                      print what a jq-like expression, such
+                                      as `.users[] | select(.age > 30).name`,
+                                      makes of each document
+  filter --jsonpath [--paths] EXPRESSION [FILE]
+                                      print the matches of a JSONPath query,
+                                      such as `$..book[?@.price < 10].title`,
+                                      after their normalized paths if asked
+  flatten [FILE]                      print each value as a greppable
+                                      statement like `json.users[0].name = \"a\";`
+  unflatten [FILE]                    rebuild a document from such statements,
+                                      even after some were filtered out
+  to-csv [--tsv] [--delimiter C] [--null TEXT] [FILE]
+                                      print an array of objects as a table
+                                      with a column for each key
+
+A FILE of `-`, or none, is stdin. `--output FILE` (`-o FILE`) writes to FILE
+instead of stdout. Exits with 0 on success, 1 when the answer is no (invalid
+input, a difference, nothing at the pointer) and 2 on errors.";
 
 fn main() {
-    let stdin = stdin();
-    let mut stdin = stdin.lock();
-    let mut buffer = Buffer::with_capacity(1000);
-
-    loop {
-        let read_result = stdin.read(buffer.space());
-        match read_result {
-            Ok(read_length) =>  if read_length > 0 {
-                buffer.fill(read_length);
-                println!("{}", JsonNode::from_bytes(buffer.data()));
+    let args : Vec<String> = env::args().skip(1).collect();
+    let rest = args.get(1..).unwrap_or_default();
+    match args.first().map(String::as_str) {
+        Some("validate") => validate(rest),
+        Some("format") => format(rest, true),
+        Some("minify") => format(rest, false),
+        Some("get") => get(rest),
+        Some("aggregate") => summarize(rest),
+        Some("diff") => compare(rest),
+        Some("convert") => convert(rest),
+        Some("filter") => filter(rest),
+        Some("flatten") => gron(rest, true),
+        Some("unflatten") => gron(rest, false),
+        Some("to-csv") => to_csv(rest),
+        Some("help") | Some("--help") | Some("-h") => println!("{}", USAGE),
+        Some(command) => fail(format!("unknown command {}; `json_parser help` lists them", command)),
+        None => {
+            eprintln!("{}", USAGE);
+            process::exit(2)
+        }
+    }
+}
+
+// prints `message` and exits with 2, for errors that stop a command
+fn fail(message : String) -> ! {
+    eprintln!("json_parser: {}", message);
+    process::exit(2)
+}
+
+// the options and other arguments given to a command
+struct Arguments {
+    options: Vec<(String, Option<String>)>,
+    operands: Vec<String>
+}
+
+impl Arguments {
+    // `valued` options take the argument after them as their value; `switches` take none
+    fn parse(args : &[String], valued : &[&str], switches : &[&str]) -> Arguments {
+        let mut parsed = Arguments { options: Vec::new(), operands: Vec::new() };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let name = match arg.as_str() {
+                "-o" => "--output",
+                name => name
+            };
+            if valued.contains(&name) || name == "--output" {
+                let value = args.next().unwrap_or_else(|| fail(format!("{} needs a value (see `json_parser help`)", name)));
+                parsed.options.push((name.to_string(), Some(value.clone())))
+            } else if switches.contains(&name) {
+                parsed.options.push((name.to_string(), None))
+            } else if name.starts_with('-') && name != "-" {
+                fail(format!("unknown option {} (see `json_parser help`)", name))
             } else {
-                println!("Completed.");
-                break;
+                parsed.operands.push(arg.clone())
+            }
+        }
+        parsed
+    }
+
+    fn flag(&self, name : &str) -> bool {
+        self.options.iter().any(|(option, _)| option == name)
+    }
+
+    // the last value given to `name`
+    fn value(&self, name : &str) -> Option<&str> {
+        self.options.iter().rev().find(|(option, _)| option == name).and_then(|(_, value)| value.as_deref())
+    }
+
+    // the operands, requiring between `min` and `max` of them
+    fn operands(&self, min : usize, max : usize) -> &[String] {
+        if self.operands.len() < min || self.operands.len() > max {
+            fail("wrong number of arguments (see `json_parser help`)".to_string())
+        }
+        &self.operands
+    }
+
+    // where the output of the command goes, buffered
+    fn output(&self) -> Box<dyn Write> {
+        match self.value("--output") {
+            None | Some("-") => Box::new(BufWriter::new(stdout())),
+            Some(path) => match File::create(path) {
+                Ok(file) => Box::new(BufWriter::new(file)),
+                Err(reason) => fail(format!("writing {} failed: {}", path, reason))
+            }
+        }
+    }
+}
+
+fn read_input(path : &str) -> Vec<u8> {
+    let bytes = if path == "-" {
+        let mut bytes = Vec::new();
+        stdin().read_to_end(&mut bytes).map(|_| bytes)
+    } else {
+        fs::read(path)
+    };
+    bytes.unwrap_or_else(|reason| fail(format!("reading {} failed: {}", path, reason)))
+}
+
+fn read_text(path : &str) -> String {
+    String::from_utf8(read_input(path)).unwrap_or_else(|_| fail(format!("{}: not UTF-8", path)))
+}
+
+fn parse_input(path : &str, options : ParserOptions) -> JsonNode {
+    JsonNode::parse_complete(&read_input(path), options).unwrap_or_else(|reason| fail(format!("{}: {}", path, reason)))
+}
+
+fn finish(written : io::Result<()>, out : &mut dyn Write) {
+    if let Err(reason) = written.and_then(|_| out.flush()) {
+        fail(format!("writing output failed: {}", reason))
+    }
+}
+
+// the one input file among `operands`, stdin if there is none
+fn input(operands : &[String]) -> &str {
+    operands.first().map_or("-", String::as_str)
+}
+
+// `validate [--quiet] [FILE...]` checks that each file holds exactly one
+// strict JSON document and nothing after it, printing `file:line:column:
+// message` for each that does not, unless quiet, and then exiting with 1
+fn validate(args : &[String]) {
+    let args = Arguments::parse(args, &[], &["--quiet", "-q"]);
+    let quiet = args.flag("--quiet") || args.flag("-q");
+    let stdin = ["-".to_string()];
+    let files = if args.operands.is_empty() { &stdin[..] } else { &args.operands };
+    let mut out = args.output();
+    let mut valid = true;
+    for path in files {
+        if let Err(reason) = JsonNode::parse_complete(&read_input(path), ParserOptions::default()) {
+            valid = false;
+            if !quiet {
+                let name = if path == "-" { "<stdin>" } else { path };
+                finish(writeln!(out, "{}:{}:{}: {}", name, reason.line, reason.column, reason.message()), &mut out)
+            }
+        }
+    }
+    if !valid {
+        process::exit(1)
+    }
+}
+
+// `format [--indent N] [--sort-keys] [--ascii] [--escape-slashes] [FILE]`
+// and `minify [--sort-keys] [--ascii] [--escape-slashes] [FILE]` print each
+// document in FILE indented, by two spaces unless told otherwise, or compact
+fn format(args : &[String], pretty : bool) {
+    let switches = ["--sort-keys", "--ascii", "--escape-slashes"];
+    let args = if pretty {
+        Arguments::parse(args, &["--indent"], &switches)
+    } else {
+        Arguments::parse(args, &[], &switches)
+    };
+    let path = input(args.operands(0, 1));
+    let bytes = read_input(path);
+    let mut options = SerializeOptions::new()
+        .sort_keys(args.flag("--sort-keys"))
+        .escape_non_ascii(args.flag("--ascii"))
+        .escape_forward_slash(args.flag("--escape-slashes"))
+        .trailing_newline(true);
+    if pretty {
+        let indent = match args.value("--indent").map(str::parse) {
+            None => 2,
+            Some(Ok(indent)) => indent,
+            Some(Err(_)) => fail("--indent takes a number of spaces".to_string())
+        };
+        if indent > 0 {
+            options = options.indent(&" ".repeat(indent))
+        }
+    }
+    let serializer = Serializer::with_options(options);
+    // like `jq .`, one after another if there are several
+    let mut out = args.output();
+    for json in JsonStream::new(&bytes[..]) {
+        let json = json.unwrap_or_else(|reason| fail(format!("{}: {}", path, reason)));
+        finish(serializer.write_to(&json, &mut out), &mut out)
+    }
+}
+
+// `get [--raw] PATH [FILE]` prints the value at PATH, a JSON Pointer or a
+// dotted path, exiting with 1 if there is none; `--raw` prints strings
+// unquoted, as they are
+fn get(args : &[String]) {
+    let args = Arguments::parse(args, &[], &["--raw", "-r"]);
+    let operands = args.operands(1, 2);
+    let path = &operands[0];
+    let pointer = if path.is_empty() || path.starts_with('/') {
+        JsonPointer::parse(path).map_err(|reason| reason.to_string())
+    } else {
+        flatten::dotted_pointer(path).map_err(|reason| reason.to_string())
+    };
+    let pointer = pointer.unwrap_or_else(|reason| fail(format!("{}: {}", path, reason)));
+    let json = parse_input(input(&operands[1..]), ParserOptions::default());
+    let mut out = args.output();
+    let written = match pointer.resolve(&json) {
+        Some(JsonNode::String(s)) if args.flag("--raw") || args.flag("-r") => writeln!(out, "{}", s),
+        Some(value) => writeln!(out, "{}", value),
+        None => process::exit(1)
+    };
+    finish(written, &mut out);
+}
+
+// `aggregate FUNCTION PATTERN [FILE]` prints the sum, minimum, maximum,
+// average or count of the numbers at the paths PATTERN matches
+fn summarize(args : &[String]) {
+    let args = Arguments::parse(args, &[], &[]);
+    let operands = args.operands(2, 3);
+    let agg = match operands[0].as_str() {
+        "sum" => Agg::Sum,
+        "min" => Agg::Min,
+        "max" => Agg::Max,
+        "avg" => Agg::Avg,
+        "count" => Agg::Count,
+        other => fail(format!("unknown aggregate {}; use sum, min, max, avg or count", other))
+    };
+    let json = parse_input(input(&operands[2..]), ParserOptions::default());
+    let result = aggregate(&json, &operands[1], agg).unwrap_or_else(|reason| fail(format!("{}: {}", operands[1], reason)));
+    let mut out = args.output();
+    let written = writeln!(out, "{}", result);
+    finish(written, &mut out);
+}
+
+// `diff [--json | --patch] A B` prints how document B differs from A as a
+// tree, as a JSON list of changes or as a JSON Patch, exiting with 1 if it does
+fn compare(args : &[String]) {
+    let args = Arguments::parse(args, &[], &["--tree", "--json", "--patch"]);
+    let (a, b) = match args.operands(2, 2) {
+        [a, b] => (parse_input(a, ParserOptions::default()), parse_input(b, ParserOptions::default())),
+        _ => unreachable!("two operands were required")
+    };
+    let explanation = diff::explain(&a, &b);
+    let mut out = args.output();
+    let written = if args.flag("--patch") {
+        writeln!(out, "{:#}", patch::diff(&a, &b).to_node())
+    } else if args.flag("--json") {
+        writeln!(out, "{:#}", explanation.to_node())
+    } else {
+        write!(out, "{}", explanation)
+    };
+    finish(written, &mut out);
+    if !explanation.is_empty() {
+        process::exit(1)
+    }
+}
+
+// `convert [--from FORMAT] [--to FORMAT] [FILE]` reads json, json5, jsonc,
+// ndjson, whose records become an array, cbor, msgpack, toml or yaml, and
+// writes json, ndjson, which puts each element of an array on a line of its
+// own, cbor, msgpack, toml or yaml
+fn convert(args : &[String]) {
+    let args = Arguments::parse(args, &["--from", "--to"], &[]);
+    let path = input(args.operands(0, 1));
+    let json = match args.value("--from").unwrap_or("json") {
+        "json" => parse_input(path, ParserOptions::default()),
+        "json5" => parse_input(path, ParserOptions::json5()),
+        "jsonc" => parse_input(path, ParserOptions::jsonc()),
+        "ndjson" => JsonNode::Array(NdjsonReader::new(&read_input(path)[..])
+            .map(|record| record.unwrap_or_else(|reason| fail(format!("{}: {}", path, reason))))
+            .collect()),
+        "cbor" => cbor::decode(&read_input(path)).unwrap_or_else(|reason| fail(format!("{}: {}", path, reason))),
+        #[cfg(feature = "msgpack")]
+        "msgpack" => msgpack::decode(&read_input(path)).unwrap_or_else(|reason| fail(format!("{}: {}", path, reason))),
+        #[cfg(feature = "toml")]
+        "toml" => JsonNode::from_toml_str(&read_text(path)).unwrap_or_else(|reason| fail(format!("{}: {}", path, reason))),
+        #[cfg(feature = "yaml")]
+        "yaml" => JsonNode::from_yaml_str(&read_text(path)).unwrap_or_else(|reason| fail(format!("{}: {}", path, reason))),
+        format => fail(format!("cannot convert from {}", format))
+    };
+    let mut out = args.output();
+    let written = match (args.value("--to").unwrap_or("json"), json) {
+        ("json", json) => json.write_pretty_to(&mut out).and_then(|_| writeln!(out)),
+        ("ndjson", JsonNode::Array(ref records)) => records.iter().try_for_each(|record| writeln!(out, "{}", record)),
+        ("ndjson", json) => writeln!(out, "{}", json),
+        ("cbor", json) => out.write_all(&cbor::encode(&json)),
+        #[cfg(feature = "msgpack")]
+        ("msgpack", json) => out.write_all(&msgpack::encode(&json)),
+        #[cfg(feature = "toml")]
+        ("toml", json) => write!(out, "{}", json.to_toml_string().unwrap_or_else(|reason| fail(reason.to_string()))),
+        #[cfg(feature = "yaml")]
+        ("yaml", json) => write!(out, "{}", json.to_yaml_string()),
+        (format, _) => fail(format!("cannot convert to {}", format))
+    };
+    finish(written, &mut out);
+}
+
+// `flatten [FILE]` prints the document in FILE as gron statements, one per
+// value, and `unflatten [FILE]` reads them back into a document
+fn gron(args : &[String], flatten : bool) {
+    let args = Arguments::parse(args, &[], &[]);
+    let path = input(args.operands(0, 1));
+    let mut out = args.output();
+    let written = if flatten {
+        write!(out, "{}", flatten::gron(&parse_input(path, ParserOptions::default())))
+    } else {
+        let json = flatten::ungron(&read_text(path)).unwrap_or_else(|reason| fail(format!("{}: {}", path, reason)));
+        json.write_pretty_to(&mut out).and_then(|_| writeln!(out))
+    };
+    finish(written, &mut out);
+}
+
+// `to-csv [--tsv] [--delimiter C] [--null TEXT] [FILE]` prints the array of
+// objects in FILE as CSV, or TSV, with a header row naming the keys
+fn to_csv(args : &[String]) {
+    let args = Arguments::parse(args, &["--delimiter", "--null"], &["--tsv"]);
+    let mut options = if args.flag("--tsv") { CsvOptions::tsv() } else { CsvOptions::csv() };
+    if let Some(delimiter) = args.value("--delimiter") {
+        let mut chars = delimiter.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => options = options.delimiter(c),
+            _ => fail(format!("the delimiter must be one character, not {:?}", delimiter))
+        }
+    }
+    if let Some(null) = args.value("--null") {
+        options = options.null(null)
+    }
+    let path = input(args.operands(0, 1));
+    let table = csv::to_csv(&parse_input(path, ParserOptions::default()), &options)
+        .unwrap_or_else(|reason| fail(format!("{}: {}", path, reason)));
+    let mut out = args.output();
+    finish(write!(out, "{}", table), &mut out);
+}
+
+enum Query {
+    Filter(Filter),
+    JsonPath(JsonPath)
+}
+
+// `filter [--ndjson] [--raw] [--jsonpath [--paths]] EXPRESSION [FILE]`
+// prints what the jq-like EXPRESSION, or the JSONPath one, makes of each
+// document in FILE; `--raw` prints strings unquoted and `--paths` puts the
+// normalized path of each JSONPath match and a tab before it
+fn filter(args : &[String]) {
+    let args = Arguments::parse(args, &[], &["--ndjson", "--raw", "-r", "--jsonpath", "--paths"]);
+    let operands = args.operands(1, 2);
+    let expression = &operands[0];
+    let query = if args.flag("--jsonpath") {
+        Query::JsonPath(JsonPath::parse(expression).unwrap_or_else(|reason| fail(reason.to_string())))
+    } else {
+        Query::Filter(Filter::parse(expression).unwrap_or_else(|reason| fail(reason.to_string())))
+    };
+    let path = input(&operands[1..]);
+    let source : Box<dyn Read> = if path == "-" {
+        Box::new(stdin())
+    } else {
+        Box::new(File::open(path).unwrap_or_else(|reason| fail(format!("reading {} failed: {}", path, reason))))
+    };
+    let raw = args.flag("--raw") || args.flag("-r");
+    let mut out = args.output();
+    let mut print = |location : Option<&str>, result : &JsonNode| {
+        let written = location.map_or(Ok(()), |location| write!(out, "{}\t", location)).and_then(|_| match result {
+            JsonNode::String(s) if raw => writeln!(out, "{}", s),
+            result => writeln!(out, "{}", result)
+        });
+        finish(written, &mut out)
+    };
+    each_document(source, args.flag("--ndjson"), |json| match &query {
+        Query::Filter(filter) => match filter.run(&json) {
+            Ok(results) => for result in results {
+                print(None, &result)
             },
-            Err(reason) => panic!("Reading input failed: {}", reason)
+            Err(reason) => fail(format!("filter failed: {}", reason))
+        },
+        Query::JsonPath(jsonpath) if args.flag("--paths") => for (location, result) in jsonpath.select_located(&json) {
+            print(Some(&location), result)
+        },
+        Query::JsonPath(jsonpath) => for result in jsonpath.select(&json) {
+            print(None, result)
+        }
+    });
+}
+
+// hands each document in `source`, or each line of it with `ndjson`, to `process`
+fn each_document<R : Read, F : FnMut(JsonNode)>(source : R, ndjson : bool, mut process : F) {
+    if ndjson {
+        for record in NdjsonReader::new(source) {
+            process(record.unwrap_or_else(|reason| fail(format!("JSON parsing failed: {}", reason))))
+        }
+    } else {
+        for json in JsonStream::new(source) {
+            process(json.unwrap_or_else(|reason| fail(format!("JSON parsing failed: {}", reason))))
         }
     }
 }
@@ -0,0 +1,46 @@
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+// runs the json_parser binary with `args`, feeding it `input` on stdin
+fn run(args : &[&str], input : &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_json_parser"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("the binary runs");
+    child.stdin.take().expect("stdin is piped").write_all(input.as_bytes()).expect("stdin takes the input");
+    child.wait_with_output().expect("the binary exits")
+}
+
+#[test]
+fn test_validate_accepts_strict_json() {
+    for valid in &["1", "-0.5e+3", "[0, 1.25, -7]", "{\"a\": [true, null]}\n"] {
+        let output = run(&["validate"], valid);
+        assert_eq!(output.status.code(), Some(0), "{}", valid);
+        assert!(output.stdout.is_empty(), "{}", valid);
+    }
+}
+
+#[test]
+fn test_validate_rejects_lenient_numbers() {
+    for invalid in &["+1", "01", ".5", "[1.]", "-01", "1.e5", "[+1]", "{\"a\": .5}"] {
+        let output = run(&["validate"], invalid);
+        assert_eq!(output.status.code(), Some(1), "{}", invalid);
+        assert!(String::from_utf8_lossy(&output.stdout).starts_with("<stdin>:1:"), "{}", invalid);
+        assert!(run(&["validate", "--quiet"], invalid).stdout.is_empty(), "{}", invalid);
+    }
+}
+
+#[test]
+fn test_aggregate() {
+    let order = "{\"items\":[{\"price\":9007199254740993},{\"price\":\"free\"},{\"price\":2}]}";
+    let stdout = |args : &[&str]| String::from_utf8(run(args, order).stdout).unwrap();
+    assert_eq!(stdout(&["aggregate", "sum", "/items/*/price"]), "9007199254740995\n");
+    assert_eq!(stdout(&["aggregate", "count", "/items/*/price"]), "2\n");
+    assert_eq!(stdout(&["aggregate", "min", "/items/*/price"]), "2\n");
+    assert_eq!(stdout(&["aggregate", "avg", "/missing"]), "null\n");
+    assert_eq!(run(&["aggregate", "median", "/items/*/price"], order).status.code(), Some(2));
+    assert_eq!(run(&["aggregate", "sum", "items"], order).status.code(), Some(2));
+}